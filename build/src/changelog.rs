@@ -1,5 +1,16 @@
+//! Parsing the `CHANGELOG.md` files of the Enso repository.
+//!
+//! Each release has its own level one section, with the header containing the version, like
+//! `# Enso 2022.1.1 (2022-01-01)`. The changes not released yet are gathered in the topmost
+//! section, titled `# Next Release`. The entries link to their pull requests, usually through the
+//! link reference definitions, like `[3500]: https://github.com/enso-org/enso/pull/3500`.
+
 use crate::prelude::*;
 use anyhow::Context;
+use ide_ci::github::event::PullRequest;
+use regex::Regex;
+use std::collections::BTreeSet;
+use std::lazy::SyncLazy;
 // use ouroboros::self_referencing;
 use pulldown_cmark::Event;
 use pulldown_cmark::HeadingLevel;
@@ -69,6 +80,68 @@ impl<'a> Changelog<'a> {
             Entry { header: first_header.text.to_string(), contents: contents.to_string() };
         Ok(entry)
     }
+
+    /// All the release sections, from the topmost one.
+    pub fn entries(&self) -> Vec<Entry> {
+        let headers = self.iterate_headers().collect_vec();
+        let ends = headers.iter().skip(1).map(|header| header.pos.start).chain(once(self.0.len()));
+        headers
+            .iter()
+            .zip(ends)
+            .map(|(header, end)| Entry {
+                header:   header.text.trim_start_matches('#').trim().to_string(),
+                contents: self.0[header.pos.end..end].trim().to_string(),
+            })
+            .collect()
+    }
+
+    /// The section of the changes that are not released yet.
+    pub fn unreleased(&self) -> Option<Entry> {
+        self.entries().into_iter().next().filter(|entry| entry.is_unreleased())
+    }
+
+    /// The section describing the given version.
+    ///
+    /// Only the release number is compared, so the prereleases (like the nightly builds) of
+    /// a version already in the changelog get its section. Otherwise, they get the section of the
+    /// unreleased changes.
+    pub fn entry_for(&self, version: &Version) -> Result<Entry> {
+        let same_release = |other: &Version| {
+            (other.major, other.minor, other.patch) == (version.major, version.minor, version.patch)
+        };
+        let entries = self.entries();
+        let released =
+            entries.iter().find(|entry| entry.version().map_or(false, |v| same_release(&v)));
+        match released {
+            Some(entry) => Ok(entry.clone()),
+            None => entries
+                .into_iter()
+                .next()
+                .filter(|entry| entry.is_unreleased())
+                .with_context(|| format!("No changelog section for the version {version}.")),
+        }
+    }
+}
+
+/// The label of the pull requests that do not need a changelog entry, like the CI tweaks.
+pub const NO_CHANGELOG_LABEL: &str = "CI: No changelog needed";
+
+/// Check that the pull request is described in the unreleased changes, unless it is
+/// [labeled](NO_CHANGELOG_LABEL) as not needing that.
+pub fn check_pull_request(changelog: &Changelog, pull_request: &PullRequest) -> Result {
+    if pull_request.has_label(NO_CHANGELOG_LABEL) {
+        info!("The pull request is labeled as not needing a changelog entry.");
+        return Ok(());
+    }
+    let unreleased = changelog.unreleased().context("No section for the unreleased changes.")?;
+    ensure!(
+        unreleased.pull_requests().contains(&pull_request.number),
+        "The changelog does not mention the pull request #{} in the `{}` section. Please add an \
+        entry linking to it or, if no entry is needed, the `{NO_CHANGELOG_LABEL}` label.",
+        pull_request.number,
+        unreleased.header
+    );
+    Ok(())
 }
 
 #[derive(Clone, Debug)]
@@ -77,6 +150,31 @@ pub struct Entry {
     pub contents: String,
 }
 
+impl Entry {
+    /// The released version, if the header has one.
+    pub fn version(&self) -> Option<Version> {
+        ide_ci::program::version::find_in_text(&self.header).ok()
+    }
+
+    pub fn is_unreleased(&self) -> bool {
+        self.version().is_none()
+    }
+
+    /// Numbers of the pull requests linked from the entry.
+    pub fn pull_requests(&self) -> BTreeSet<u64> {
+        static PULL_LINK: SyncLazy<Regex> = SyncLazy::new(|| Regex::new(r"/pull/(\d+)").unwrap());
+        PULL_LINK
+            .captures_iter(&self.contents)
+            .filter_map(|captures| captures[1].parse().ok())
+            .collect()
+    }
+
+    /// Body of the GitHub release.
+    pub fn release_notes(&self) -> String {
+        format!("{}\n", self.contents)
+    }
+}
+
 pub struct Header<'a> {
     /// Text of the header.
     pub text: &'a str,
@@ -105,3 +203,61 @@ pub fn is_release_notes_header(event: &Event) -> bool {
 //     dbg!(entry);
 //     Ok(())
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHANGELOG: &str = r#"# Next Release
+
+#### Visual Environment
+
+- [Added the component browser][3500]
+
+[3500]: https://github.com/enso-org/enso/pull/3500
+
+# Enso 2022.1.1 (2022-01-01)
+
+- [Fixed the crash on startup][3400]
+
+[3400]: https://github.com/enso-org/enso/pull/3400
+"#;
+
+    #[test]
+    fn entries() -> Result {
+        let changelog = Changelog(CHANGELOG);
+        let entries = changelog.entries();
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].is_unreleased());
+        assert_eq!(entries[1].version(), Some(Version::new(2022, 1, 1)));
+        assert_eq!(entries[0].pull_requests(), BTreeSet::from([3500]));
+
+        let released = changelog.entry_for(&Version::parse("2022.1.1-nightly.2022-01-02")?)?;
+        assert!(released.release_notes().starts_with("- [Fixed the crash on startup][3400]"));
+        let unreleased = changelog.entry_for(&Version::new(2022, 1, 2))?;
+        assert_eq!(unreleased.header, "Next Release");
+        assert!(Changelog("# Enso 2022.1.1").entry_for(&Version::new(2022, 1, 2)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn checking_pull_requests() -> Result {
+        let pull_request = |number: u64, labels: &[&str]| -> Result<PullRequest> {
+            let labels = labels.iter().map(|name| serde_json::json!({ "name": name }));
+            let branch = serde_json::json!({"ref": "develop", "sha": "abc"});
+            Ok(serde_json::from_value(serde_json::json!({
+                "number": number,
+                "title": "Change",
+                "body": null,
+                "head": branch,
+                "base": branch,
+                "labels": labels.collect_vec(),
+            }))?)
+        };
+        let changelog = Changelog(CHANGELOG);
+        check_pull_request(&changelog, &pull_request(3500, &[])?)?;
+        assert!(check_pull_request(&changelog, &pull_request(3400, &[])?).is_err());
+        check_pull_request(&changelog, &pull_request(3600, &[NO_CHANGELOG_LABEL])?)?;
+        Ok(())
+    }
+}
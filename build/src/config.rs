@@ -1,13 +1,122 @@
+//! Build configuration, like the `build-config.yaml` file in the Enso repository.
+//!
+//! The configuration can be written in YAML or TOML. Any value can be overridden by an environment
+//! variable named after its key path, prefixed with [`ENV_OVERRIDE_PREFIX`]. The path segments are
+//! separated by double underscores, e.g. `ENSO_BUILD_CONFIG_REQUIRED_VERSIONS__WASM_PACK=^0.10.2`
+//! overrides the `wasm-pack` entry of the `required-versions` map.
+
 use crate::prelude::*;
 use byte_unit::Byte;
 use ide_ci::program;
+use ide_ci::programs::signing::codesign;
+use ide_ci::programs::signing::signtool;
 use semver::VersionReq;
+use serde_yaml::Value;
 
-pub fn load_yaml(yaml_text: &str) -> Result<Config> {
-    let raw = serde_yaml::from_str::<ConfigRaw>(yaml_text)?;
+/// Prefix of the environment variables overriding the configuration values.
+pub const ENV_OVERRIDE_PREFIX: &str = "ENSO_BUILD_CONFIG_";
+
+/// Syntax of the configuration file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    /// Deduce the format from the file extension.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("yaml" | "yml") => Ok(Format::Yaml),
+            Some("toml") => Ok(Format::Toml),
+            _ => bail!("Unknown configuration format of {}.", path.display()),
+        }
+    }
+
+    /// Parse the text into a document tree, common to all the formats.
+    pub fn parse(self, text: &str) -> Result<Value> {
+        match self {
+            Format::Yaml => Ok(serde_yaml::from_str(text)?),
+            Format::Toml => Ok(serde_yaml::to_value(toml::from_str::<toml::Value>(text)?)?),
+        }
+    }
+}
+
+/// Apply the overrides from the given environment variables to the document tree.
+///
+/// Boolean values are parsed as such, everything else is kept as a string. If the overridden value
+/// is a sequence in the `schema` document (or in the overridden document), the override is a
+/// comma-separated list. The schema is needed for keys that are missing from the document.
+pub fn apply_env_overrides(
+    document: &mut Value,
+    schema: &Value,
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> Result {
+    for (name, value) in vars {
+        let path = match name.strip_prefix(ENV_OVERRIDE_PREFIX) {
+            Some(path) => path,
+            None => continue,
+        };
+        let keys = path.split("__").map(|key| key.to_lowercase().replace('_', "-")).collect_vec();
+        ensure!(keys.iter().all(|key| !key.is_empty()), "Invalid override variable {name}.");
+        let mut node = &mut *document;
+        let mut schema_node = Some(schema);
+        for key in &keys {
+            schema_node = schema_node.and_then(|schema_node| schema_node.get(key.as_str()));
+            if node.is_null() {
+                *node = Value::Mapping(default());
+            }
+            let mapping = node
+                .as_mapping_mut()
+                .with_context(|| format!("Cannot apply {name}: `{key}` is not in a mapping."))?;
+            let key = Value::from(key.as_str());
+            if !mapping.contains_key(&key) {
+                mapping.insert(key.clone(), Value::Null);
+            }
+            node = mapping.get_mut(&key).unwrap();
+        }
+        debug!("Overriding configuration value {} from {name}.", keys.join("."));
+        let is_sequence = node.is_sequence() || schema_node.map_or(false, Value::is_sequence);
+        *node = match value.as_str() {
+            _ if is_sequence =>
+                Value::Sequence(value.split(',').map(|item| item.trim().into()).collect()),
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            _ => value.into(),
+        };
+    }
+    Ok(())
+}
+
+/// Document tree of the default configuration, describing which values are sequences.
+pub fn schema() -> Result<Value> {
+    Ok(serde_yaml::to_value(ConfigRaw::default())?)
+}
+
+/// Load the configuration, applying the overrides from the process environment.
+///
+/// The environment is not under our control, so the variables that are not valid Unicode are
+/// converted lossily rather than rejected.
+pub fn load(text: &str, format: Format) -> Result<Config> {
+    let mut document = format.parse(text)?;
+    let vars = std::env::vars_os().map(|(name, value)| {
+        (name.to_string_lossy().into_owned(), value.to_string_lossy().into_owned())
+    });
+    apply_env_overrides(&mut document, &schema()?, vars)?;
+    let raw = serde_yaml::from_value::<ConfigRaw>(document)?;
     raw.try_into()
 }
 
+pub fn load_yaml(yaml_text: &str) -> Result<Config> {
+    load(yaml_text, Format::Yaml)
+}
+
+#[context("Failed to load the build configuration from {}.", path.as_ref().display())]
+pub fn load_file(path: impl AsRef<Path>) -> Result<Config> {
+    load(&ide_ci::fs::read_to_string(&path)?, Format::from_path(&path)?)
+}
+
 #[derive(Clone, Debug, Display, PartialEq, Eq, Hash, Serialize, Deserialize, strum::EnumString)]
 pub enum RecognizedProgram {
     #[strum(default)]
@@ -22,20 +131,83 @@ impl RecognizedProgram {
     }
 }
 
+/// Code signing settings. The secrets, like the certificate passwords, are not part of the
+/// configuration, they are passed through the environment.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Signing {
+    /// macOS signing identity, like `Developer ID Application: New Byte Order`.
+    pub macos_identity: Option<String>,
+    pub macos_entitlements: Option<PathBuf>,
+    /// Whether the signed macOS packages should be notarized.
+    pub notarize: bool,
+    /// Thumbprint of the signing certificate in the Windows certificate store.
+    pub windows_certificate_thumbprint: Option<String>,
+    /// Overrides the [default](signtool::DEFAULT_TIMESTAMP_URL) timestamping server.
+    pub windows_timestamp_url: Option<String>,
+}
+
+impl Signing {
+    /// The `codesign` options, if the macOS signing is configured.
+    pub fn codesign_options(&self) -> Option<codesign::SignOptions> {
+        self.macos_identity.as_ref().map(|identity| codesign::SignOptions {
+            entitlements: self.macos_entitlements.clone(),
+            ..codesign::SignOptions::new(identity)
+        })
+    }
+
+    /// The `signtool` options, if the Windows signing is configured.
+    pub fn signtool_options(&self) -> Option<signtool::SignOptions> {
+        self.windows_certificate_thumbprint.as_ref().map(|thumbprint| {
+            let certificate = signtool::Certificate::Store { thumbprint: thumbprint.clone() };
+            let mut options = signtool::SignOptions::new(certificate);
+            if let Some(url) = &self.windows_timestamp_url {
+                options.timestamp_url = Some(url.clone());
+            }
+            options
+        })
+    }
+}
+
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct ConfigRaw {
     pub wasm_size_limit:   Option<String>,
     pub required_versions: HashMap<String, String>,
+    #[serde(default)]
+    pub target_platforms:  Vec<String>,
+    #[serde(default)]
+    pub features:          BTreeMap<String, bool>,
+    #[serde(default)]
+    pub signing:           Signing,
 }
 
 #[derive(Clone, Debug, Default)]
 pub struct Config {
     pub wasm_size_limit:   Option<Byte>,
     pub required_versions: HashMap<RecognizedProgram, VersionReq>,
+    /// Platforms for which the packages are built. If empty, only the host platform.
+    pub target_platforms:  Vec<OS>,
+    /// Optional parts of the build, by their names.
+    pub features:          BTreeMap<String, bool>,
+    pub signing:           Signing,
 }
 
 impl Config {
+    /// Whether the feature is enabled. Features not mentioned in the configuration are disabled.
+    pub fn is_enabled(&self, feature: &str) -> bool {
+        self.features.get(feature).copied().unwrap_or(false)
+    }
+
+    /// Whether the packages for the given platform should be built.
+    pub fn targets(&self, os: OS) -> bool {
+        if self.target_platforms.is_empty() {
+            os == TARGET_OS
+        } else {
+            self.target_platforms.contains(&os)
+        }
+    }
+
     pub async fn check_programs(&self) -> Result {
         for (program, version_req) in &self.required_versions {
             let found = program.version().await?;
@@ -69,12 +241,21 @@ impl TryFrom<ConfigRaw> for Config {
             );
         }
 
+        let target_platforms = value
+            .target_platforms
+            .iter()
+            .map(|os| <OS as FromString>::from_str(os))
+            .collect_result()?;
+
         Ok(Self {
             wasm_size_limit: value
                 .wasm_size_limit
                 .map(|limit_text| <Byte as FromString>::from_str(&limit_text))
                 .transpose()?,
             required_versions,
+            target_platforms,
+            features: value.features,
+            signing: value.signing,
         })
     }
 }
@@ -102,4 +283,55 @@ required-versions:
 
         Ok(())
     }
+
+    #[test]
+    fn overrides() -> Result {
+        let toml = r#"
+target-platforms = ["windows", "linux"]
+
+[required-versions]
+wasm-pack = "^0.10.2"
+
+[features]
+docs = false
+
+[signing]
+macos-identity = "Developer ID Application: New Byte Order"
+"#;
+        let mut document = Format::Toml.parse(toml)?;
+        let vars = [
+            ("ENSO_BUILD_CONFIG_REQUIRED_VERSIONS__WASM_PACK", "^0.10.3"),
+            ("ENSO_BUILD_CONFIG_TARGET_PLATFORMS", "linux, macos"),
+            ("ENSO_BUILD_CONFIG_FEATURES__DOCS", "true"),
+            ("ENSO_BUILD_CONFIG_SIGNING__NOTARIZE", "true"),
+            ("ENSO_BUILD_CONFIG_WASM_SIZE_LIMIT", "5MB"),
+            ("UNRELATED", "value"),
+        ];
+        apply_env_overrides(
+            &mut document,
+            &schema()?,
+            vars.map(|(name, value)| (name.to_string(), value.to_string())),
+        )?;
+        let config = Config::try_from(serde_yaml::from_value::<ConfigRaw>(document)?)?;
+        let wasm_pack = RecognizedProgram::Other("wasm-pack".into());
+        assert_eq!(config.required_versions[&wasm_pack], VersionReq::parse("^0.10.3")?);
+        assert_eq!(config.target_platforms, vec![OS::Linux, OS::MacOS]);
+        assert!(config.is_enabled("docs"));
+        assert!(!config.is_enabled("unknown"));
+        assert!(config.signing.notarize);
+        assert!(config.signing.codesign_options().is_some());
+        assert!(config.signing.signtool_options().is_none());
+        assert_eq!(config.wasm_size_limit, Some(Byte::from_bytes(5_000_000)));
+        Ok(())
+    }
+
+    #[test]
+    fn override_missing_list() -> Result {
+        let mut document = Format::Yaml.parse("required-versions: {}")?;
+        let vars = [("ENSO_BUILD_CONFIG_TARGET_PLATFORMS".to_string(), "macos".to_string())];
+        apply_env_overrides(&mut document, &schema()?, vars)?;
+        let config = Config::try_from(serde_yaml::from_value::<ConfigRaw>(document)?)?;
+        assert_eq!(config.target_platforms, vec![OS::MacOS]);
+        Ok(())
+    }
 }
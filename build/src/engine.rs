@@ -4,6 +4,7 @@ use std::collections::BTreeSet;
 use crate::paths::ComponentPaths;
 use crate::paths::Paths;
 
+use ide_ci::archive::CompressionOptions;
 use ide_ci::future::AsyncPolicy;
 use ide_ci::models::config::RepoContext;
 
@@ -274,7 +275,8 @@ trait ComponentPathExt {
 #[async_trait]
 impl ComponentPathExt for ComponentPaths {
     async fn pack(&self) -> Result {
-        ide_ci::archive::create(&self.artifact_archive, [&self.dir]).await
+        ide_ci::archive::create(&self.artifact_archive, [&self.dir], CompressionOptions::best())
+            .await
     }
     fn clear(&self) -> Result {
         ide_ci::fs::remove_dir_if_exists(&self.root)?;
@@ -296,6 +298,7 @@ pub async fn package_component(paths: &ComponentPaths) -> Result<PathBuf> {
         }
     }
 
-    ide_ci::archive::create(&paths.artifact_archive, [&paths.root]).await?;
+    ide_ci::archive::create(&paths.artifact_archive, [&paths.root], CompressionOptions::best())
+        .await?;
     Ok(paths.artifact_archive.clone())
 }
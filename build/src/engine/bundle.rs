@@ -4,6 +4,7 @@ use crate::engine::ComponentPathExt;
 use crate::paths::ComponentPaths;
 use crate::paths::Paths;
 use anyhow::Context;
+use ide_ci::programs::java;
 
 #[async_trait]
 pub trait Bundle {
@@ -60,11 +61,12 @@ impl Bundle for ProjectManager {
 
 #[context("Placing a GraalVM package under {}", target_directory.as_ref().display())]
 pub async fn place_graal_under(target_directory: impl AsRef<Path>) -> Result {
-    let graal_path = PathBuf::from(ide_ci::env::expect_var_os("JAVA_HOME")?);
+    let graal_path = java::JAVA_HOME.get()?;
     let graal_dirname = graal_path
         .file_name()
         .context(anyhow!("Invalid Graal Path deduced from JAVA_HOME: {}", graal_path.display()))?;
-    ide_ci::fs::mirror_directory(&graal_path, target_directory.as_ref().join(graal_dirname)).await
+    let target = target_directory.as_ref().join(graal_dirname);
+    ide_ci::fs::mirror_directory(&graal_path, target, default()).await
 }
 
 #[context("Placing a Enso Engine package in {}", target_engine_dir.as_ref().display())]
@@ -72,5 +74,5 @@ pub async fn place_component_at(
     engine_paths: &ComponentPaths,
     target_engine_dir: impl AsRef<Path>,
 ) -> Result {
-    ide_ci::fs::mirror_directory(&engine_paths.dir, &target_engine_dir).await
+    ide_ci::fs::mirror_directory(&engine_paths.dir, &target_engine_dir, default()).await
 }
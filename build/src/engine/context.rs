@@ -1,6 +1,7 @@
 use crate::prelude::*;
 
 use ide_ci::actions::workflow::is_in_env;
+use ide_ci::env::EnvironmentOverlay;
 use ide_ci::env::Variable;
 use sysinfo::SystemExt;
 
@@ -20,7 +21,6 @@ use crate::get_java_major_version;
 use crate::paths::cache_directory;
 use crate::paths::Paths;
 use crate::project::ProcessWrapper;
-use crate::retrieve_github_access_token;
 
 use crate::engine::bundle::Bundle;
 use crate::engine::sbt::verify_generated_package;
@@ -33,6 +33,8 @@ use ide_ci::goodies::graalvm;
 use ide_ci::platform::DEFAULT_SHELL;
 use ide_ci::program::with_cwd::WithCwd;
 use ide_ci::programs::graal;
+use ide_ci::programs::sbt::run_reporting_errors;
+use ide_ci::programs::sbt::LauncherOption;
 use ide_ci::programs::Flatc;
 use ide_ci::programs::Git;
 use ide_ci::programs::Sbt;
@@ -96,12 +98,15 @@ impl RunContext {
             // GitHub-hosted runner has `conda` on PATH but not things installed by it.
             // It provides `CONDA` variable pointing to the relevant location.
             if let Some(conda_path) = std::env::var_os("CONDA").map(PathBuf::from) {
-                ide_ci::env::prepend_to_path(conda_path.join("bin"))?;
+                let mut conda_environment =
+                    EnvironmentOverlay::new().prepend_to_path(conda_path.join("bin"));
                 if TARGET_OS == OS::Windows {
                     // Not sure if it documented anywhere, but this is where installed `flatc`
                     // appears on Windows.
-                    ide_ci::env::prepend_to_path(conda_path.join("Library").join("bin"))?;
+                    conda_environment =
+                        conda_environment.prepend_to_path(conda_path.join("Library").join("bin"));
                 }
+                conda_environment.apply_to_process()?;
             }
 
             ide_ci::programs::Conda
@@ -132,14 +137,12 @@ impl RunContext {
         let build_sbt_content = ide_ci::fs::read_to_string(self.paths.build_sbt())?;
         let graalvm = graalvm::GraalVM {
             client:        &self.octocrab,
+            edition:       default(),
             graal_version: get_graal_version(&build_sbt_content)?,
             java_version:  get_java_major_version(&build_sbt_content)?,
             os:            TARGET_OS,
             arch:          TARGET_ARCH,
         };
-        self.goodies.require(&graalvm).await?;
-        graal::Gu.require_present().await?;
-
         // Make sure that Graal has installed the optional components that we need.
         // Some are not supported on Windows, in part because their runtime (Sulong) is not.
         // See e.g. https://github.com/oracle/graalpython/issues/156
@@ -151,7 +154,8 @@ impl RunContext {
 
         let required_components =
             once(graal::Component::NativeImage).chain(conditional_components.into_iter().copied());
-        graal::install_missing_components(required_components).await?;
+        let java = graalvm.provision(&self.goodies, required_components).await?;
+        debug!("Using Java from {}.", java.executable_path.display());
         prepare_simple_library_server.await??;
         Ok(())
     }
@@ -165,12 +169,12 @@ impl RunContext {
             // engine version as part of the key. As such, any change made to engine that does not
             // change its version might break the caches.
             // See (private): https://discord.com/channels/401396655599124480/407883082310352928/939618590158630922
-            ide_ci::fs::remove_dir_if_exists(cache_directory())?;
+            ide_ci::fs::tokio::remove_dir_robust(cache_directory()).await?;
         }
 
         if self.config.test_standard_library {
             // If we run tests, make sure that old and new results won't end up mixed together.
-            ide_ci::fs::reset_dir(&self.paths.test_results)?;
+            ide_ci::fs::tokio::reset_dir(&self.paths.test_results).await?;
         }
 
         let git = Git::new(&self.paths.repo_root);
@@ -187,7 +191,7 @@ impl RunContext {
             });
 
         // Download Project Template Files
-        let client = reqwest::Client::new();
+        let client = ide_ci::net::client()?;
         download_project_templates(client.clone(), self.paths.repo_root.path.clone()).await?;
 
         let sbt = WithCwd::new(Sbt, &self.paths.repo_root);
@@ -201,7 +205,7 @@ impl RunContext {
 
         // Build packages.
         debug!("Bootstrapping Enso project.");
-        sbt.call_arg("bootstrap").await?;
+        run_reporting_errors(sbt.cmd()?.arg("bootstrap")).await?;
 
         perhaps_generate_java_from_rust_job.await.transpose()?;
         let perhaps_test_java_generated_from_rust_job =
@@ -255,51 +259,54 @@ impl RunContext {
 
             if !tasks.is_empty() {
                 let build_stuff = Sbt::concurrent_tasks(tasks);
-                sbt.call_arg(build_stuff).await?;
+                run_reporting_errors(sbt.cmd()?.arg(build_stuff)).await?;
             }
         } else {
-            // Compile
-            sbt.call_arg("compile").await?;
-
-            // Build the Runner & Runtime Uberjars
-            sbt.call_arg("engine-runner/assembly").await?;
+            // Tasks are batched to reduce the number of sbt invocations. The native images are
+            // built separately, as they need different memory settings.
+            let native_image_sbt = || -> Result<Command> {
+                let mut cmd = sbt.cmd()?;
+                cmd.apply(&LauncherOption::Memory(1536));
+                Ok(cmd)
+            };
+
+            // Compile and build the Runner & Runtime Uberjars.
+            let tasks = ["compile", "engine-runner/assembly", "launcher/assembly"];
+            run_reporting_errors(sbt.cmd()?.arg(Sbt::sequential_tasks(tasks))).await?;
 
             // Build the Launcher Native Image
-            sbt.call_arg("launcher/assembly").await?;
-            sbt.call_args(["--mem", "1536", "launcher/buildNativeImage"]).await?;
+            run_reporting_errors(native_image_sbt()?.arg("launcher/buildNativeImage")).await?;
 
             // Build the PM Native Image
-            sbt.call_arg("project-manager/assembly").await?;
-            sbt.call_args(["--mem", "1536", "project-manager/buildNativeImage"]).await?;
-
-            // Prepare Launcher Distribution
-            //create_launcher_package(&paths)?;
-            sbt.call_arg("buildLauncherDistribution").await?;
-
-            // Prepare Engine Distribution
-            sbt.call_arg("buildEngineDistribution").await?;
+            run_reporting_errors(sbt.cmd()?.arg("project-manager/assembly")).await?;
+            run_reporting_errors(native_image_sbt()?.arg("project-manager/buildNativeImage"))
+                .await?;
 
-            // Prepare Project Manager Distribution
-            sbt.call_arg("buildProjectManagerDistribution").await?;
+            // Prepare the Launcher, Engine and Project Manager Distributions.
+            let mut tasks = vec![
+                "buildLauncherDistribution",
+                "buildEngineDistribution",
+                "buildProjectManagerDistribution",
+            ];
 
             if self.config.build_benchmarks {
-                // Check Runtime Benchmark Compilation
-                sbt.call_arg("runtime/Benchmark/compile").await?;
-
-                // Check Language Server Benchmark Compilation
-                sbt.call_arg("language-server/Benchmark/compile").await?;
-
-                // Check Searcher Benchmark Compilation
-                sbt.call_arg("searcher/Benchmark/compile").await?;
+                // Check Runtime, Language Server and Searcher Benchmark Compilation
+                tasks.extend([
+                    "runtime/Benchmark/compile",
+                    "language-server/Benchmark/compile",
+                    "searcher/Benchmark/compile",
+                ]);
             }
 
             for benchmark in &self.config.execute_benchmarks {
-                sbt.call_arg(benchmark.sbt_task()).await?;
+                tasks.push(benchmark.sbt_task());
             }
+            run_reporting_errors(sbt.cmd()?.arg(Sbt::sequential_tasks(tasks))).await?;
         }
         if self.config.test_scala {
             // Test Enso
-            sbt.call_arg("set Global / parallelExecution := false; test").await?;
+            run_reporting_errors(sbt.cmd()?.arg("set Global / parallelExecution := false; test"))
+                .await?;
         }
 
         perhaps_test_java_generated_from_rust_job.await.transpose()?;
@@ -310,13 +317,13 @@ impl RunContext {
             //  docs-generator fails on Windows because it can't understand non-Unix-style paths.
             if TARGET_OS != OS::Windows {
                 // Build the docs from standard library sources.
-                sbt.call_arg("docs-generator/run").await?;
+                run_reporting_errors(sbt.cmd()?.arg("docs-generator/run")).await?;
             }
         }
 
         if self.config.build_js_parser {
             // Build the Parser JS Bundle
-            sbt.call_arg("syntaxJS/fullOptJS").await?;
+            run_reporting_errors(sbt.cmd()?.arg("syntaxJS/fullOptJS")).await?;
             ide_ci::fs::copy_to(
                 self.paths.target.join("scala-parser.js"),
                 self.paths.target.join("parser-upload"),
@@ -427,7 +434,8 @@ impl RunContext {
 
                     // Make packages.
                     let release_id = crate::env::ReleaseId.fetch()?;
-                    let client = ide_ci::github::create_client(retrieve_github_access_token()?)?;
+                    let client =
+                        ide_ci::github::create_client(ide_ci::github::client::token_from_env()?)?;
                     let upload_asset = |asset: PathBuf| {
                         ide_ci::github::release::upload_asset(repo, &client, release_id, asset)
                     };
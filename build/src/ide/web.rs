@@ -11,6 +11,7 @@ use futures_util::future::try_join;
 use futures_util::future::try_join3;
 use ide_ci::io::download_all;
 use ide_ci::program::command;
+use ide_ci::program::watchdog::Watchdog;
 use ide_ci::program::EMPTY_ARGS;
 use ide_ci::programs::node::NpmCommand;
 use ide_ci::programs::Npm;
@@ -157,6 +158,8 @@ impl IdeDesktop {
         command.arg("--color").arg("always");
         command.arg("--yes");
         command.current_dir(&self.package_dir);
+        // Applies only to the builds, the watch processes are not run to completion.
+        command.set_watchdog(Watchdog::from_env());
         command.stdin(Stdio::null()); // nothing in that process subtree should require input
         Ok(command)
     }
@@ -167,7 +170,7 @@ impl IdeDesktop {
     }
 
     pub async fn install(&self) -> Result {
-        self.npm()?.install().arg("--workspaces").arg("--verbose").run_ok().await?;
+        self.npm()?.install().workspaces().arg("--verbose").run_ok().await?;
         Ok(())
     }
 
@@ -92,24 +92,17 @@ pub fn get_java_major_version(build_sbt_contents: &str) -> Result<java::Language
     get_string_assignment_value(build_sbt_contents, "javaVersion")?.parse2()
 }
 
+/// Get the GitHub access token.
+#[deprecated(note = "Use `ide_ci::github::client::token_from_env` instead.")]
 pub fn retrieve_github_access_token() -> Result<String> {
-    fn get_token_from_file() -> Result<String> {
-        let path =
-            dirs::home_dir().context("Failed to locate home directory.")?.join("GITHUB_TOKEN");
-        let content = ide_ci::fs::read_to_string(path)?;
-        Ok(content.trim().into())
-    }
-
-    ide_ci::env::expect_var("GITHUB_TOKEN")
-        .inspect(|_| debug!("Will use GITHUB_TOKEN environment variable."))
-        .or_else(|_| get_token_from_file())
+    ide_ci::github::client::token_from_env()
 }
 
+/// Create the [GitHub client](ide_ci::github::Client). The token from the environment, if any, is
+/// checked to be valid.
 pub async fn setup_octocrab() -> Result<Octocrab> {
-    let mut builder = octocrab::OctocrabBuilder::new();
-    if let Ok(access_token) = retrieve_github_access_token() {
-        builder = builder.personal_token(access_token);
-        let octocrab = builder.build()?;
+    if let Ok(access_token) = ide_ci::github::client::token_from_env() {
+        let octocrab = ide_ci::github::Client::with_token(access_token)?.octocrab;
         match octocrab.ratelimit().get().await {
             Ok(rate) => info!(
                 "GitHub API rate limit: {}/{}.",
@@ -121,7 +114,7 @@ pub async fn setup_octocrab() -> Result<Octocrab> {
         }
         Ok(octocrab)
     } else {
-        builder.build().anyhow_err()
+        Ok(ide_ci::github::Client::anonymous()?.octocrab)
     }
 }
 
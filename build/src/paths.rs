@@ -19,10 +19,7 @@ pub const EDITION_FILE_ARTIFACT_NAME: &str = "Edition File";
 pub const LIBRARIES_TO_TEST: [&str; 6] =
     ["Tests", "Table_Tests", "Geo_Tests", "Visualization_Tests", "Image_Tests", "Examples_Tests"];
 
-pub const ARCHIVE_EXTENSION: &str = match TARGET_OS {
-    OS::Windows => "zip",
-    _ => "tar.gz",
-};
+pub const ARCHIVE_EXTENSION: &str = ide_ci::platform::archive_extension(TARGET_OS);
 
 pub fn new_repo_root(repo_root: impl Into<PathBuf>, triple: &TargetTriple) -> generated::RepoRoot {
     generated::RepoRoot::new_root(repo_root, triple.to_string(), triple.versions.edition_name())
@@ -142,7 +142,7 @@ pub trait IsTarget: Clone + Debug + Sized + Send + Sync + 'static {
             .boxed(),
             ExternalSource::CiRun(ci_run) => self.download_artifact(context, ci_run, destination),
             ExternalSource::LocalFile(source_path) => async move {
-                ide_ci::fs::mirror_directory(source_path, &destination).await?;
+                ide_ci::fs::mirror_directory(source_path, &destination, default()).await?;
                 this.adapt_artifact(destination).await
             }
             .boxed(),
@@ -147,7 +147,7 @@ impl IsTarget for Backend {
             let artifacts = context.build().await?;
             let project_manager =
                 artifacts.bundles.project_manager.context("Missing project manager bundle!")?;
-            ide_ci::fs::mirror_directory(&project_manager.dir, &destination).await?;
+            ide_ci::fs::mirror_directory(&project_manager.dir, &destination, default()).await?;
             this.adapt_artifact(destination).await
         }
         .boxed()
@@ -5,6 +5,7 @@ use crate::prelude::*;
 use crate::paths::generated::RepoRoot;
 use crate::paths::generated::RepoRootDistWasm;
 use crate::project::wasm::js_patcher::patch_js_glue_in_place;
+use crate::project::wasm::post_process::PostProcessing;
 use crate::project::Context;
 use crate::project::IsArtifact;
 use crate::project::IsTarget;
@@ -16,7 +17,6 @@ use derivative::Derivative;
 use ide_ci::cache;
 use ide_ci::env::Variable;
 use ide_ci::fs::compressed_size;
-use ide_ci::fs::copy_file_if_different;
 use ide_ci::programs::cargo;
 use ide_ci::programs::wasm_opt;
 use ide_ci::programs::wasm_opt::WasmOpt;
@@ -25,11 +25,11 @@ use ide_ci::programs::Cargo;
 use ide_ci::programs::WasmPack;
 use semver::VersionReq;
 use std::time::Duration;
-use tempfile::tempdir;
 use tokio::process::Child;
 
 pub mod env;
 pub mod js_patcher;
+pub mod post_process;
 pub mod test;
 
 pub const BINARYEN_VERSION_TO_INSTALL: usize = 108;
@@ -58,12 +58,26 @@ pub enum ProfilingLevel {
     Debug,
 }
 
-#[derive(clap::ArgEnum, Clone, Copy, Debug, PartialEq, strum::Display, strum::AsRefStr)]
+#[derive(
+    clap::ArgEnum,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    strum::Display,
+    strum::AsRefStr,
+)]
 #[strum(serialize_all = "kebab-case")]
 pub enum Profile {
     Dev,
     Profile,
     Release,
+    /// Release build optimized for size rather than speed. Used to track the WASM size.
+    Size,
     // Production,
 }
 
@@ -72,7 +86,7 @@ impl From<Profile> for wasm_pack::Profile {
         match profile {
             Profile::Dev => Self::Dev,
             Profile::Profile => Self::Profile,
-            Profile::Release => Self::Release,
+            Profile::Release | Profile::Size => Self::Release,
             // Profile::Production => Self::Release,
         }
     }
@@ -84,6 +98,7 @@ impl Profile {
             Profile::Dev => false,
             Profile::Profile => false,
             Profile::Release => true,
+            Profile::Size => true,
             // Profile::Production => true,
         }
     }
@@ -94,7 +109,7 @@ impl Profile {
             //     .into_iter()
             //     .map(ToString::to_string)
             //     .collect(),
-            Profile::Dev | Profile::Profile | Profile::Release => vec![],
+            Profile::Dev | Profile::Profile | Profile::Release | Profile::Size => vec![],
         }
     }
 
@@ -103,6 +118,7 @@ impl Profile {
             Profile::Dev => wasm_opt::OptimizationLevel::O0,
             Profile::Profile => wasm_opt::OptimizationLevel::O,
             Profile::Release => wasm_opt::OptimizationLevel::O3,
+            Profile::Size => wasm_opt::OptimizationLevel::Oz,
         }
     }
 }
@@ -121,7 +137,43 @@ pub struct BuildInput {
     pub wasm_size_limit:     Option<byte_unit::Byte>,
 }
 
+/// Description of everything that affects the WASM build output, besides the sources.
+///
+/// Stored next to the built artifacts, so they can be told apart by the settings they were built
+/// with.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BuildKey {
+    pub crate_path:          PathBuf,
+    pub profile:             Profile,
+    pub profiling_level:     Option<String>,
+    pub extra_cargo_options: Vec<String>,
+    pub post_processing:     PostProcessing,
+}
+
+impl BuildKey {
+    /// Short textual identifier of the key, suitable for file and artifact names.
+    pub fn digest(&self) -> Result<String> {
+        ide_ci::cache::key_digest(self)
+    }
+}
+
 impl BuildInput {
+    /// The wasm-opt post-processing settings for this build.
+    pub fn post_processing(&self) -> PostProcessing {
+        PostProcessing::for_profile(self.profile)
+            .with_extra_options(self.wasm_opt_options.iter().cloned())
+    }
+
+    pub fn key(&self) -> BuildKey {
+        BuildKey {
+            crate_path:          self.crate_path.clone(),
+            profile:             self.profile,
+            profiling_level:     self.profiling_level.map(|level| level.to_string()),
+            extra_cargo_options: self.extra_cargo_options.clone(),
+            post_processing:     self.post_processing(),
+        }
+    }
+
     pub async fn perhaps_check_size(&self, wasm_path: impl AsRef<Path>) -> Result {
         let compressed_size = compressed_size(&wasm_path).await?.get_appropriate_unit(true);
         info!("Compressed size of {} is {}.", wasm_path.as_ref().display(), compressed_size);
@@ -180,24 +232,29 @@ impl IsTarget for Wasm {
         async move {
             // Old wasm-pack does not pass trailing `build` command arguments to the Cargo.
             // We want to be able to pass --profile this way.
-            WasmPack.require_present_that(&VersionReq::parse(">=0.10.1")?).await?;
+            WasmPack
+                .require_version_for(&VersionReq::parse(">=0.10.1")?, "passing `--profile`")
+                .await?;
 
             let BuildInput {
                 repo_root,
                 crate_path,
-                wasm_opt_options,
+                wasm_opt_options: _,
                 extra_cargo_options,
                 profile,
                 profiling_level,
                 wasm_size_limit: _wasm_size_limit,
             } = &inner;
+            let post_processing = inner.post_processing();
+            let key = inner.key();
+            info!("Build key {}: {:?}", key.digest()?, key);
 
             cache::goodie::binaryen::Binaryen { version: BINARYEN_VERSION_TO_INSTALL }
                 .install_if_missing(&cache, WasmOpt)
                 .await?;
 
             info!("Building wasm.");
-            let temp_dir = tempdir()?;
+            let temp_dir = ide_ci::fs::temp::dir("wasm-build")?;
             let temp_dist = RepoRootDistWasm::new_root(temp_dir.path());
             let mut command = ide_ci::programs::WasmPack.cmd()?;
             command
@@ -220,31 +277,26 @@ impl IsTarget for Wasm {
             }
             command.run_ok().await?;
 
-            if *profile != Profile::Dev {
-                let mut wasm_opt_command = WasmOpt.cmd()?;
-                let has_custom_opt_level = wasm_opt_options.iter().any(|opt| {
-                    wasm_opt::OptimizationLevel::from_str(opt.trim_start_matches('-')).is_ok()
-                });
-                if !has_custom_opt_level {
-                    wasm_opt_command.apply(&profile.optimization_level());
+            // Besides the main module, there might be additional ones (e.g. for workers). They
+            // are post-processed in place.
+            let mut post_processing_jobs =
+                vec![(temp_dist.wasm_main_raw.to_path_buf(), temp_dist.wasm_main.to_path_buf())];
+            let wasm_pattern = temp_dist.path.join_iter(["**", "*.wasm"]).display().to_string();
+            for wasm in glob::glob(&wasm_pattern)? {
+                let wasm = wasm?;
+                if wasm != *temp_dist.wasm_main_raw && wasm != *temp_dist.wasm_main {
+                    post_processing_jobs.push((wasm.clone(), wasm));
                 }
-                wasm_opt_command
-                    .args(wasm_opt_options)
-                    .arg(&temp_dist.wasm_main_raw)
-                    .apply(&wasm_opt::Output(&temp_dist.wasm_main))
-                    .run_ok()
-                    .await?;
-            } else {
-                debug!("Skipping wasm-opt invocation, as it is not part of profile {profile}.");
-                copy_file_if_different(&temp_dist.wasm_main_raw, &temp_dist.wasm_main)?;
             }
+            post_processing.run_all(post_processing_jobs).await?;
 
             // ide_ci::fs::rename(&temp_dist.wasm_main_raw, &temp_dist.wasm_main)?;
             patch_js_glue_in_place(&temp_dist.wasm_glue)?;
 
-            ide_ci::fs::create_dir_if_missing(&destination)?;
             let ret = RepoRootDistWasm::new_root(&destination);
+            ret.ensure_dir()?;
             ide_ci::fs::copy(&temp_dist, &ret)?;
+            ide_ci::fs::write_json(&ret.build_key, &key)?;
             // copy_if_different(&temp_dist, &ret).await?;
             // copy_if_different(&temp_dist.wasm_main_raw, &ret.wasm_main)?;
             inner.perhaps_check_size(&ret.wasm_main).await?;
@@ -339,6 +391,13 @@ impl Artifact {
     pub fn new(path: impl Into<PathBuf>) -> Self {
         Self(RepoRootDistWasm::new_root(path))
     }
+
+    /// The settings this artifact was built with.
+    #[context("Failed to read the build key of the WASM artifact at {}.", self.as_ref().display())]
+    pub fn build_key(&self) -> Result<BuildKey> {
+        let contents = ide_ci::fs::read_to_string(&self.0.build_key)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
     pub fn wasm(&self) -> &Path {
         &self.0.wasm_main
     }
@@ -460,6 +519,29 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn build_key_depends_on_profile() -> Result {
+        let input = |profile| BuildInput {
+            repo_root: RepoRoot::new_root(".", "x86_64-unknown-linux-gnu", "2022.1.1"),
+            crate_path: PathBuf::from("app/gui"),
+            wasm_opt_options: vec![],
+            extra_cargo_options: vec![],
+            profile,
+            profiling_level: None,
+            wasm_size_limit: None,
+        };
+        let dev = input(Profile::Dev).key();
+        let release = input(Profile::Release).key();
+        assert_ne!(dev, release);
+        assert_ne!(dev.digest()?, release.digest()?);
+        assert_eq!(dev.digest()?, input(Profile::Dev).key().digest()?);
+
+        let mut with_options = input(Profile::Release);
+        with_options.wasm_opt_options.push("--debuginfo".into());
+        assert_ne!(release.digest()?, with_options.key().digest()?);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn watch_by_cargo_watch() -> Result {
         pretty_env_logger::init();
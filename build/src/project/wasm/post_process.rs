@@ -0,0 +1,128 @@
+//! Post-processing of the compiled WASM modules with `wasm-opt`.
+
+use crate::prelude::*;
+
+use crate::project::wasm::Profile;
+use ide_ci::fs::copy_file_if_different;
+use ide_ci::programs::wasm_opt;
+use ide_ci::programs::wasm_opt::WasmOpt;
+
+
+/// Settings of the `wasm-opt` pass over the WASM modules generated by `wasm-pack`.
+///
+/// The settings are serializable, so they can be made part of cache keys: the same sources
+/// post-processed differently yield different artifacts.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PostProcessing {
+    /// Whether `wasm-opt` is run at all. If not, the modules are copied unchanged.
+    pub enabled:            bool,
+    pub optimization_level: wasm_opt::OptimizationLevel,
+    /// Remove the DWARF debug information.
+    pub strip_debug:        bool,
+    /// Remove the "producers" section, which describes the toolchain used.
+    pub strip_producers:    bool,
+    /// Additional options for `wasm-opt`. If they contain an optimization level, it overrides
+    /// the `optimization_level`.
+    pub extra_options:      Vec<String>,
+}
+
+impl PostProcessing {
+    /// The default settings for a given build profile.
+    pub fn for_profile(profile: Profile) -> Self {
+        let (enabled, strip) = match profile {
+            Profile::Dev => (false, false),
+            // Debug information is kept, as it makes the profiles readable.
+            Profile::Profile => (true, false),
+            Profile::Release | Profile::Size => (true, true),
+        };
+        Self {
+            enabled,
+            optimization_level: profile.optimization_level(),
+            strip_debug: strip,
+            strip_producers: strip,
+            extra_options: default(),
+        }
+    }
+
+    pub fn with_extra_options(mut self, options: impl IntoIterator<Item = String>) -> Self {
+        self.extra_options.extend(options);
+        self
+    }
+
+    /// Whether the extra options set the optimization level.
+    pub fn has_custom_optimization_level(&self) -> bool {
+        self.extra_options.iter().any(|option| {
+            wasm_opt::OptimizationLevel::from_str(option.trim_start_matches('-')).is_ok()
+        })
+    }
+
+    /// Arguments for `wasm-opt`, not including the input and output paths.
+    pub fn args(&self) -> Vec<String> {
+        let mut ret = vec![];
+        if !self.has_custom_optimization_level() {
+            ret.push(format!("-{}", self.optimization_level));
+        }
+        if self.strip_debug {
+            ret.push("--strip-debug".into());
+        }
+        if self.strip_producers {
+            ret.push("--strip-producers".into());
+        }
+        ret.extend(self.extra_options.iter().cloned());
+        ret
+    }
+
+    /// Post-process a single module. The output may be the same file as the input.
+    pub async fn run(&self, input: impl AsRef<Path>, output: impl AsRef<Path>) -> Result {
+        let (input, output) = (input.as_ref(), output.as_ref());
+        if self.enabled {
+            WasmOpt
+                .cmd()?
+                .args(self.args())
+                .arg(input)
+                .apply(&wasm_opt::Output(output))
+                .run_ok()
+                .await
+        } else {
+            debug!("Skipping wasm-opt invocation for {}.", input.display());
+            if input != output {
+                copy_file_if_different(input, output)?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Post-process many modules in parallel. Each job is a pair of input and output paths.
+    pub async fn run_all(&self, jobs: impl IntoIterator<Item = (PathBuf, PathBuf)>) -> Result {
+        let jobs = jobs.into_iter().map(|(input, output)| async move {
+            self.run(&input, &output)
+                .await
+                .with_context(|| format!("Failed to post-process {}.", input.display()))
+        });
+        futures::future::try_join_all(jobs).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_settings() {
+        assert!(!PostProcessing::for_profile(Profile::Dev).enabled);
+        assert_eq!(PostProcessing::for_profile(Profile::Release).args(), vec![
+            "-O3",
+            "--strip-debug",
+            "--strip-producers"
+        ]);
+        assert_eq!(PostProcessing::for_profile(Profile::Size).args(), vec![
+            "-Oz",
+            "--strip-debug",
+            "--strip-producers"
+        ]);
+        let custom = PostProcessing::for_profile(Profile::Profile)
+            .with_extra_options(["-O1".to_string(), "--debuginfo".to_string()]);
+        assert_eq!(custom.args(), vec!["-O1", "--debuginfo"]);
+    }
+}
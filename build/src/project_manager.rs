@@ -1,5 +1,6 @@
 use crate::prelude::*;
 
+use crate::paths::generated::RepoRootBuiltDistributionProjectManagerBundleTriple as Bundle;
 use crate::paths::TargetTriple;
 use crate::paths::ARCHIVE_EXTENSION;
 
@@ -8,7 +9,7 @@ pub fn url(target: &TargetTriple) -> Result<Url> {
         "https://github.com/enso-org/{repo}/releases/download/{tag}/{asset}.{ext}",
         repo = "ci-build",
         tag = target.versions.tag(),
-        asset = format!("project-manager-bundle-{target}"),
+        asset = Bundle::segment_name(target.to_string()),
         ext = ARCHIVE_EXTENSION,
     );
     Url::parse(&url_text).anyhow_err()
@@ -3,8 +3,10 @@ use crate::prelude::*;
 use crate::context::BuildContext;
 use crate::paths::EDITION_FILE_ARTIFACT_NAME;
 use crate::project;
+use ide_ci::actions::step_summary;
+use ide_ci::actions::step_summary::Markdown;
+use ide_ci::github::release::ReleaseSpec;
 use octocrab::models::repos::Release;
-use tempfile::tempdir;
 
 pub async fn create_release(context: &BuildContext) -> Result<Release> {
     let versions = &context.triple.versions;
@@ -12,22 +14,20 @@ pub async fn create_release(context: &BuildContext) -> Result<Release> {
 
     let paths = context.repo_root();
     let changelog_contents = ide_ci::fs::read_to_string(&paths.changelog_md)?;
-    let latest_changelog_body =
-        crate::changelog::Changelog(&changelog_contents).top_release_notes()?;
+    let changelog_entry =
+        crate::changelog::Changelog(&changelog_contents).entry_for(&versions.version)?;
 
     debug!("Preparing release {} for commit {}", versions.version, commit);
-    let release = context
-        .remote_repo
-        .repos(&context.octocrab)
-        .releases()
-        .create(&versions.tag())
-        .target_commitish(&commit)
-        .name(&versions.pretty_name())
-        .body(&latest_changelog_body.contents)
-        .prerelease(true)
-        .draft(true)
-        .send()
-        .await?;
+    let spec = ReleaseSpec {
+        tag:              versions.tag(),
+        name:             versions.release_name(),
+        body:             changelog_entry.release_notes(),
+        target_commitish: Some(commit),
+        prerelease:       true,
+    };
+    let release =
+        ide_ci::github::release::create_draft(&context.remote_repo, &context.octocrab, &spec)
+            .await?;
 
     crate::env::ReleaseId.emit(&release.id)?;
     Ok(release)
@@ -39,15 +39,18 @@ pub async fn publish_release(context: &BuildContext) -> Result {
 
     let release_id = crate::env::ReleaseId.fetch()?;
 
-    debug!("Looking for release with id {release_id} on github.");
-    let release = remote_repo.repos(octocrab).releases().get_by_id(release_id).await?;
-    ensure!(release.draft, "Release has been already published!");
+    let release = ide_ci::github::release::publish(remote_repo, octocrab, release_id).await?;
+    let assets = release.assets.iter().map(|asset| {
+        let megabytes = asset.size as f64 / (1024.0 * 1024.0);
+        [asset.name.clone(), format!("{megabytes:.1} MiB")]
+    });
+    let summary = Markdown::new()
+        .header(2, format!("Published release {}", release.tag_name))
+        .paragraph(release.html_url.as_str())
+        .table(["Asset", "Size"], assets);
+    step_summary::append(&summary)?;
 
-    debug!("Found the target release, will publish it.");
-    remote_repo.repos(octocrab).releases().update(release.id.0).draft(false).send().await?;
-    debug!("Done. Release URL: {}", release.url);
-
-    let temp = tempdir()?;
+    let temp = ide_ci::fs::temp::dir("edition")?;
     let edition_file_path = crate::paths::generated::RepoRootDistributionEditions::new_root(
         temp.path(),
         triple.versions.edition_name(),
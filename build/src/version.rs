@@ -3,9 +3,12 @@ use crate::prelude::*;
 use anyhow::Context;
 use chrono::Datelike;
 use derivative::Derivative;
+use ide_ci::actions::context::RunnerContext;
 use ide_ci::define_env_var;
 use ide_ci::env::new::TypedVariable;
 use ide_ci::models::config::RepoContext;
+use ide_ci::programs::git::Describe;
+use ide_ci::programs::Git;
 use octocrab::models::repos::Release;
 use semver::Prerelease;
 use std::collections::BTreeSet;
@@ -22,6 +25,9 @@ define_env_var!(ENSO_RELEASE_MODE, bool);
 pub const LOCAL_BUILD_PREFIX: &str = "dev";
 pub const NIGHTLY_BUILD_PREFIX: &str = "nightly";
 
+/// Prefix of the prerelease identifier marking the dev builds of a pull request, like `pr-3500`.
+pub const PULL_REQUEST_PREFIX: &str = "pr-";
+
 pub fn default_dev_version() -> Version {
     let mut ret = Version::new(0, 0, 0);
     ret.pre = Prerelease::new(LOCAL_BUILD_PREFIX).unwrap();
@@ -76,10 +82,30 @@ impl Versions {
         format!("Enso {}", self.version)
     }
 
+    /// The name of the GitHub release, like `Enso 2022.1.1`.
+    pub fn release_name(&self) -> String {
+        self.pretty_name()
+    }
+
     pub fn local_prerelease() -> Result<Prerelease> {
         Prerelease::new(LOCAL_BUILD_PREFIX).anyhow_err()
     }
 
+    /// Prerelease of the dev builds of the pull request, like `dev.pr-3500`. They still count as
+    /// the [dev](BuildKind::Dev) builds, but can be told apart from the local ones.
+    pub fn pull_request_prerelease(number: u64) -> Result<Prerelease> {
+        Prerelease::new(&format!("{LOCAL_BUILD_PREFIX}.{PULL_REQUEST_PREFIX}{number}")).anyhow_err()
+    }
+
+    /// Prerelease of the dev builds, depending on the CI context.
+    pub fn dev_prerelease() -> Result<Prerelease> {
+        let pull_request = RunnerContext::from_env().ok().and_then(|ctx| ctx.pull_request_number());
+        match pull_request {
+            Some(number) => Self::pull_request_prerelease(number),
+            None => Self::local_prerelease(),
+        }
+    }
+
     pub async fn nightly_prerelease(octocrab: &Octocrab, repo: &RepoContext) -> Result<Prerelease> {
         let date = chrono::Utc::now();
         let date = date.format("%F").to_string();
@@ -121,6 +147,16 @@ impl Versions {
         self.version.to_string()
     }
 
+    /// The environment variables describing the versions, as read by [`versions_from_env`].
+    pub fn variables(&self) -> [(String, String); 3] {
+        [
+            (ENSO_VERSION.name().into(), self.version.to_string()),
+            (ENSO_EDITION.name().into(), self.edition_name()),
+            (ENSO_RELEASE_MODE.name().into(), self.release_mode.to_string()),
+        ]
+    }
+
+    /// Set the [variables](Self::variables) for the subsequent steps of the workflow.
     pub fn publish(&self) -> Result {
         let edition = self.edition_name();
         ENSO_VERSION.emit_to_workflow(&self.version)?;
@@ -128,6 +164,25 @@ impl Versions {
         ENSO_RELEASE_MODE.emit_to_workflow(&self.release_mode)?;
         Ok(())
     }
+
+    /// Write the [variables](Self::variables) as a `NAME=value` file, e.g. for the tools that
+    /// embed the version at build time.
+    pub fn write_env_file(&self, path: impl AsRef<Path>) -> Result {
+        let contents =
+            self.variables().iter().map(|(name, value)| format!("{name}={value}\n")).join("");
+        ide_ci::fs::write_atomic(path, contents)
+    }
+}
+
+/// The version of the tag that `HEAD` is exactly at, if the working tree is clean.
+///
+/// Allows rebuilding the released commits with their original versions.
+pub fn version_from_describe(describe: &Describe) -> Option<Version> {
+    if describe.is_exact() && !describe.dirty {
+        Version::parse(describe.tag.trim_start_matches('v')).ok()
+    } else {
+        None
+    }
 }
 
 #[context("Deducing version using changelog file: {}", changelog_path.as_ref().display())]
@@ -208,17 +263,32 @@ pub async fn deduce_versions(
 ) -> Result<Versions> {
     debug!("Deciding on version to target.");
     if let Some(versions) = versions_from_env(Some(build_kind))? {
-        Ok(versions)
-    } else {
-        let changelog_path = crate::paths::root_to_changelog(&root_path);
-        let version = Version {
-            pre: match build_kind {
-                BuildKind::Dev => Versions::local_prerelease()?,
-                BuildKind::Nightly => Versions::nightly_prerelease(octocrab, target_repo?).await?,
-            },
-            ..crate::version::base_version(&changelog_path)?
-        };
-        Ok(Versions::new(version))
+        return Ok(versions);
+    }
+    let tagged = match Git::new(root_path.as_ref()).describe().await {
+        Ok(describe) => version_from_describe(&describe),
+        Err(e) => {
+            debug!("Cannot describe the repository: {e:#}");
+            None
+        }
+    };
+    match tagged {
+        Some(version) if BuildKind::deduce(&version).ok() == Some(build_kind) => {
+            info!("Using the version {version} of the tag at HEAD.");
+            Ok(Versions::new(version))
+        }
+        _ => {
+            let changelog_path = crate::paths::root_to_changelog(&root_path);
+            let version = Version {
+                pre: match build_kind {
+                    BuildKind::Dev => Versions::dev_prerelease()?,
+                    BuildKind::Nightly =>
+                        Versions::nightly_prerelease(octocrab, target_repo?).await?,
+                },
+                ..crate::version::base_version(&changelog_path)?
+            };
+            Ok(Versions::new(version))
+        }
     }
 }
 
@@ -238,6 +308,29 @@ mod tests {
         assert!(BuildKind::deduce(&version).contains(&BuildKind::Nightly));
     }
 
+    #[test]
+    fn ci_derived_versions() -> Result {
+        let pre = Versions::pull_request_prerelease(3500)?;
+        assert_eq!(pre.as_str(), "dev.pr-3500");
+        let version = Version { pre, ..Version::new(2022, 1, 1) };
+        assert_eq!(BuildKind::deduce(&version)?, BuildKind::Dev);
+
+        let describe = Describe::from_str("2022.1.1-nightly.2022-04-01-0-g1a2b3c4")?;
+        let tagged = version_from_describe(&describe).context("No version from the tag.")?;
+        assert_eq!(BuildKind::deduce(&tagged)?, BuildKind::Nightly);
+        let describe = Describe { commits_since_tag: 3, ..describe };
+        assert_eq!(version_from_describe(&describe), None);
+
+        let versions = Versions::new(tagged);
+        let temp = tempfile::tempdir()?;
+        let env_file = temp.path().join("version.env");
+        versions.write_env_file(&env_file)?;
+        let contents = ide_ci::fs::read_to_string(&env_file)?;
+        assert!(contents.contains("ENSO_VERSION=2022.1.1-nightly.2022-04-01\n"));
+        assert!(contents.contains("ENSO_RELEASE_MODE=true\n"));
+        Ok(())
+    }
+
     #[test]
     #[ignore]
     fn iii() -> Result {
@@ -0,0 +1,34 @@
+//! Extract an archive, optionally only the entries matching given glob patterns.
+//!
+//! Usage: `cargo run --example extract -- <archive> <output-directory> [pattern...]`
+//!
+//! Without patterns, the whole archive is extracted using the same routine as the build script.
+
+use ide_ci::prelude::*;
+
+use ide_ci::archive;
+use ide_ci::log::setup_logging;
+
+
+#[tokio::main]
+async fn main() -> Result {
+    setup_logging()?;
+    let mut args = std::env::args().skip(1);
+    let usage = "Usage: extract <archive> <output-directory> [pattern...]";
+    let archive_path = PathBuf::from(args.next().context(usage)?);
+    let output_path = PathBuf::from(args.next().context(usage)?);
+    let patterns = args.collect_vec();
+
+    let format = archive::Format::from_filename(&archive_path)?;
+    info!("Archive {} is in {format:?} format.", archive_path.display());
+    if patterns.is_empty() {
+        archive::extract_to(&archive_path, &output_path).await?;
+    } else {
+        let selection = archive::Selection::matching(&patterns)?;
+        archive::extract_item(&archive_path, selection, &output_path).await?;
+    }
+
+    let extracted = walkdir::WalkDir::new(&output_path).into_iter().collect_result()?;
+    info!("Output directory {} has {} entries.", output_path.display(), extracted.len());
+    Ok(())
+}
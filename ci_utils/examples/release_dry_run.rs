@@ -0,0 +1,58 @@
+//! Plan a release asset upload without touching the release.
+//!
+//! Usage: `cargo run --example release_dry_run -- <owner/repo> <asset...>`
+//!
+//! Prints the requests that would be issued to upload the given files. If a GitHub token is
+//! available, it is also checked whether the token is allowed to manage releases in the repository.
+
+use ide_ci::prelude::*;
+
+use ide_ci::github::permissions::Permission;
+use ide_ci::github::permissions::Requirement;
+use ide_ci::github::permissions::TokenPermissions;
+use ide_ci::log::setup_logging;
+use ide_ci::models::config::RepoContext;
+
+
+#[tokio::main]
+async fn main() -> Result {
+    setup_logging()?;
+    let mut args = std::env::args().skip(1);
+    let usage = "Usage: release_dry_run <owner/repo> <asset...>";
+    let repo: RepoContext = args.next().context(usage)?.parse()?;
+    let assets = args.map(PathBuf::from).collect_vec();
+    ensure!(!assets.is_empty(), "{usage}");
+
+    let token = ide_ci::github::client::token_from_env().ok();
+    let octocrab = match &token {
+        Some(token) => ide_ci::github::Client::with_token(token)?.octocrab,
+        None => ide_ci::github::Client::anonymous()?.octocrab,
+    };
+    match repo.latest_release(&octocrab).await {
+        Ok(release) => info!("Latest release in {repo} is {}.", release.tag_name),
+        Err(e) => warn!("Failed to get the latest release in {repo}: {e}"),
+    }
+
+    for asset in &assets {
+        let name = asset.file_name().context("Asset path has no file name.")?.to_string_lossy();
+        let size = ide_ci::fs::metadata(asset)?.len();
+        let mime = new_mime_guess::from_path(asset).first_or_octet_stream();
+        info!(
+            "Would upload {} as `{name}` ({size} bytes, {mime}) to \
+            https://uploads.github.com/repos/{}/{}/releases/<id>/assets?name={name}",
+            asset.display(),
+            repo.owner(),
+            repo.name(),
+        );
+    }
+
+    if let Some(token) = token {
+        let client = ide_ci::github::create_client(&token)?;
+        let permissions = TokenPermissions::query_with(&client, &token).await?;
+        permissions.check(&[Requirement::write(Permission::Contents)])?;
+        info!("The token is allowed to manage releases.");
+    } else {
+        info!("GITHUB_TOKEN is not set, skipping the permission check.");
+    }
+    Ok(())
+}
@@ -0,0 +1,107 @@
+//! Measure the artifact upload throughput with different connection settings.
+//!
+//! Usage: `cargo run --example upload_benchmark -- [size-in-MiB] [file-count]`
+//!
+//! Random files of the given total size are uploaded once for each of the configurations, each
+//! time as a separate artifact. When run outside of GitHub Actions (i.e. `ACTIONS_RUNTIME_URL` is
+//! not set), a local mock of the artifact service is used, which only checks the client overhead.
+//! To assess the settings for a self-hosted runner, run it as a step of the workflow there.
+
+use ide_ci::prelude::*;
+
+use ide_ci::actions::artifacts;
+use ide_ci::actions::artifacts::context::Context;
+use ide_ci::actions::artifacts::context::RunContext;
+use ide_ci::actions::artifacts::upload::UploadOptions;
+use ide_ci::log::setup_logging;
+use rand::RngCore;
+use std::time::Duration;
+use std::time::Instant;
+use wiremock::matchers::method;
+use wiremock::Mock;
+use wiremock::MockServer;
+use wiremock::ResponseTemplate;
+
+
+/// Start a server that accepts all the requests issued during the artifact uploads.
+async fn start_mock_artifact_service() -> Result<MockServer> {
+    let server = MockServer::start().await;
+    let artifact = serde_json::json!({
+        "containerId": 1,
+        "size": -1,
+        "signedContent": null,
+        "fileContainerResourceUrl": format!("{}/_apis/resources/Containers/1", server.uri()),
+        "type": "actions_storage",
+        "name": "benchmark",
+        "url": format!("{}/_apis/pipelines/1/runs/1/artifacts", server.uri()),
+        "expiresOn": "2100-01-01T00:00:00Z",
+    });
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(&artifact))
+        .mount(&server)
+        .await;
+    Mock::given(method("PUT")).respond_with(ResponseTemplate::new(201)).mount(&server).await;
+    Mock::given(method("PATCH"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&artifact))
+        .mount(&server)
+        .await;
+    Ok(server)
+}
+
+/// The benchmarked configurations, with their names.
+fn configurations() -> Vec<(&'static str, UploadOptions)> {
+    let default = UploadOptions::default();
+    vec![
+        ("default", default.clone()),
+        ("http1", UploadOptions { http2: false, ..default.clone() }),
+        ("small-chunks", UploadOptions { chunk_size: 1024 * 1024, ..default.clone() }),
+        ("large-chunks", UploadOptions { chunk_size: 32 * 1024 * 1024, ..default.clone() }),
+        ("keepalive", UploadOptions {
+            pool_idle_timeout: Some(Duration::from_secs(30)),
+            tcp_keepalive: Some(Duration::from_secs(15)),
+            ..default
+        }),
+    ]
+}
+
+#[tokio::main]
+async fn main() -> Result {
+    setup_logging()?;
+    let mut args = std::env::args().skip(1);
+    let size_mib: usize = args.next().map_or(Ok(64), |arg| arg.parse())?;
+    let file_count: usize = args.next().map_or(Ok(8), |arg| arg.parse())?;
+
+    let input = tempfile::tempdir()?;
+    let file_size = size_mib * 1024 * 1024 / file_count.max(1);
+    for index in 0..file_count {
+        let mut contents = vec![0; file_size];
+        rand::thread_rng().fill_bytes(&mut contents);
+        ide_ci::fs::write(input.path().join(format!("file{index}.bin")), contents)?;
+    }
+
+    let (run, _mock) = if std::env::var_os("ACTIONS_RUNTIME_URL").is_none() {
+        info!("Not running in GitHub Actions, using a mock artifact service.");
+        let mock = start_mock_artifact_service().await?;
+        (RunContext::test_context(&mock), Some(mock))
+    } else {
+        (RunContext::from_env()?, None)
+    };
+    let context = Context::from(run);
+
+    let mut results = Vec::new();
+    for (name, options) in configurations() {
+        let files = artifacts::single_dir_provider(input.path())?;
+        let started = Instant::now();
+        artifacts::upload_with(&context, files, format!("upload-benchmark-{name}"), options)
+            .await?;
+        let elapsed = started.elapsed();
+        results.push((name, elapsed));
+    }
+
+    let total_mib = (file_size * file_count) as f64 / (1024.0 * 1024.0);
+    for (name, elapsed) in results {
+        let throughput = total_mib / elapsed.as_secs_f64();
+        println!("{name:<16}{:>10.2} s{throughput:>10.2} MiB/s", elapsed.as_secs_f64());
+    }
+    Ok(())
+}
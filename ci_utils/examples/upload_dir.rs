@@ -0,0 +1,74 @@
+//! Upload a directory as a GitHub Actions run artifact.
+//!
+//! Usage: `cargo run --example upload_dir -- [directory] [artifact-name]`
+//!
+//! When run outside of GitHub Actions (i.e. `ACTIONS_RUNTIME_URL` is not set), a local mock of the
+//! artifact service is started, so the whole upload pipeline can be exercised offline.
+
+use ide_ci::prelude::*;
+
+use ide_ci::actions::artifacts;
+use ide_ci::actions::artifacts::context::Context;
+use ide_ci::actions::artifacts::context::RunContext;
+use ide_ci::log::setup_logging;
+use wiremock::matchers::method;
+use wiremock::Mock;
+use wiremock::MockServer;
+use wiremock::ResponseTemplate;
+
+
+/// Start a server that accepts all the requests issued during the artifact upload.
+async fn start_mock_artifact_service(artifact_name: &str) -> Result<MockServer> {
+    let server = MockServer::start().await;
+    let container_url = format!("{}/_apis/resources/Containers/1", server.uri());
+    let artifact_url =
+        format!("{}/_apis/pipelines/1/runs/1/artifacts?artifactName={artifact_name}", server.uri());
+    let artifact = serde_json::json!({
+        "containerId": 1,
+        "size": -1,
+        "signedContent": null,
+        "fileContainerResourceUrl": container_url,
+        "type": "actions_storage",
+        "name": artifact_name,
+        "url": artifact_url,
+        "expiresOn": "2100-01-01T00:00:00Z",
+    });
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(&artifact))
+        .mount(&server)
+        .await;
+    Mock::given(method("PUT")).respond_with(ResponseTemplate::new(201)).mount(&server).await;
+    Mock::given(method("PATCH"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&artifact))
+        .mount(&server)
+        .await;
+    Ok(server)
+}
+
+#[tokio::main]
+async fn main() -> Result {
+    setup_logging()?;
+    let mut args = std::env::args().skip(1);
+    let directory = match args.next() {
+        Some(directory) => PathBuf::from(directory),
+        None => std::env::current_dir()?,
+    };
+    let artifact_name = args.next().unwrap_or_else(|| "upload-dir-example".into());
+
+    let (run, mock) = if std::env::var_os("ACTIONS_RUNTIME_URL").is_none() {
+        info!("Not running in GitHub Actions, using a mock artifact service.");
+        let mock = start_mock_artifact_service(&artifact_name).await?;
+        (RunContext::test_context(&mock), Some(mock))
+    } else {
+        (RunContext::from_env()?, None)
+    };
+
+    let files = artifacts::single_dir_provider(&directory)?;
+    artifacts::upload_with(&Context::from(run), files, &artifact_name, default()).await?;
+
+    if let Some(mock) = mock {
+        let requests = mock.received_requests().await.unwrap_or_default();
+        info!("Mock artifact service received {} requests.", requests.len());
+    }
+    Ok(())
+}
@@ -1,4 +1,7 @@
+pub mod annotations;
 pub mod artifacts;
 pub mod context;
 pub mod env;
+pub mod oidc;
+pub mod step_summary;
 pub mod workflow;
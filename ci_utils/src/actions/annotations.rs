@@ -0,0 +1,211 @@
+//! Annotations of the source code, displayed inline on the pull requests.
+//!
+//! The diagnostics parsed by the program wrappers are [reported](report) in two ways:
+//! * immediately, as workflow commands like `::error file=src/lib.rs,line=10::text`. GitHub
+//!   displays only the first 10 errors and 10 warnings of each step reported this way;
+//! * [at the end of the build](publish_reported), as a check run with structured annotations. This
+//!   requires a token with the `checks: write` permission, but the annotations are not limited.
+
+use crate::prelude::*;
+
+use crate::actions::env;
+use crate::actions::workflow;
+use crate::actions::workflow::Message;
+use crate::actions::workflow::MessageLevel;
+use std::lazy::SyncLazy;
+use std::sync::Mutex;
+
+
+/// Maximum number of annotations that can be sent in a single Checks API request.
+pub const MAX_ANNOTATIONS_PER_REQUEST: usize = 50;
+
+/// Messages reported during this process run.
+static REPORTED: SyncLazy<Mutex<Vec<Message>>> = SyncLazy::new(default);
+
+/// Report the annotation.
+///
+/// When running on GitHub Actions, it is sent as a workflow command and collected for
+/// [`publish_reported`]. Otherwise, this does nothing.
+pub fn report(mut message: Message) {
+    if !workflow::is_in_env() {
+        return;
+    }
+    if let Some(location) = &mut message.location {
+        // Annotations require paths relative to the repository root, while tools like sbt report
+        // the absolute ones.
+        if let Ok(workspace) = env::GITHUB_WORKSPACE.get() {
+            if let Ok(relative) = location.file.strip_prefix(&workspace) {
+                location.file = relative.to_owned();
+            }
+        }
+    }
+    message.send();
+    REPORTED.lock().unwrap().push(message);
+}
+
+/// Take the messages reported so far.
+pub fn take_reported() -> Vec<Message> {
+    std::mem::take(&mut *REPORTED.lock().unwrap())
+}
+
+/// Annotation level, as understood by the Checks API.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AnnotationLevel {
+    Notice,
+    Warning,
+    Failure,
+}
+
+impl From<MessageLevel> for AnnotationLevel {
+    fn from(level: MessageLevel) -> Self {
+        match level {
+            MessageLevel::Debug | MessageLevel::Notice => AnnotationLevel::Notice,
+            MessageLevel::Warning => AnnotationLevel::Warning,
+            MessageLevel::Error => AnnotationLevel::Failure,
+        }
+    }
+}
+
+/// Annotation of a check run.
+///
+/// See: <https://docs.github.com/en/rest/checks/runs#create-a-check-run>
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CheckAnnotation {
+    pub path:             String,
+    pub start_line:       usize,
+    pub end_line:         usize,
+    /// Columns are allowed only if the annotation does not span multiple lines.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_column:     Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_column:       Option<usize>,
+    pub annotation_level: AnnotationLevel,
+    pub message:          String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title:            Option<String>,
+}
+
+impl CheckAnnotation {
+    /// Convert the message. Returns `None` if it does not refer to a source line.
+    pub fn from_message(message: &Message) -> Option<Self> {
+        let location = message.location.as_ref()?;
+        let start_line = location.line?;
+        let end_line = location.end_line.unwrap_or(start_line);
+        let is_single_line = start_line == end_line;
+        Some(Self {
+            path: location.file.to_string_lossy().replace('\\', "/"),
+            start_line,
+            end_line,
+            start_column: location.column.filter(|_| is_single_line),
+            end_column: location.end_column.filter(|_| is_single_line),
+            annotation_level: message.level.into(),
+            message: message.text.clone(),
+            title: message.title.clone(),
+        })
+    }
+}
+
+/// Check run, as returned by the Checks API.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CheckRun {
+    pub id:       u64,
+    pub html_url: Option<Url>,
+}
+
+/// Create a completed check run with the annotations of the given messages.
+///
+/// The check run fails if any of the messages is an error. The annotations are sent in batches,
+/// as the API accepts only [`MAX_ANNOTATIONS_PER_REQUEST`] of them per request.
+#[context("Failed to create the check run {name} in {repo}.")]
+pub async fn create_check_run(
+    octocrab: &Octocrab,
+    repo: &(impl RepoPointer + ?Sized),
+    head_sha: &str,
+    name: &str,
+    messages: &[Message],
+) -> Result<CheckRun> {
+    let annotations = messages.iter().filter_map(CheckAnnotation::from_message).collect_vec();
+    let failures = annotations
+        .iter()
+        .filter(|annotation| annotation.annotation_level == AnnotationLevel::Failure)
+        .count();
+    let conclusion = if failures > 0 { "failure" } else { "success" };
+    let title = format!("{failures} errors, {} other annotations", annotations.len() - failures);
+    let mut batches = annotations.chunks(MAX_ANNOTATIONS_PER_REQUEST);
+    let output = |annotations: &[CheckAnnotation]| {
+        serde_json::json!({
+            "title": title,
+            "summary": title,
+            "annotations": annotations,
+        })
+    };
+
+    let path = format!("/repos/{}/{}/check-runs", repo.owner(), repo.name());
+    let body = serde_json::json!({
+        "name": name,
+        "head_sha": head_sha,
+        "status": "completed",
+        "conclusion": conclusion,
+        "output": output(batches.next().unwrap_or_default()),
+    });
+    let check_run: CheckRun = octocrab.post(&path, Some(&body)).await?;
+    for batch in batches {
+        let path = format!("{path}/{}", check_run.id);
+        let body = serde_json::json!({ "output": output(batch) });
+        let _: CheckRun = octocrab.patch(&path, Some(&body)).await?;
+    }
+    Ok(check_run)
+}
+
+/// Publish the [reported](report) annotations as a check run of the current commit.
+///
+/// Does nothing if there are no annotations or if no GitHub token is available.
+pub async fn publish_reported(name: &str) -> Result {
+    let messages = take_reported();
+    if messages.is_empty() {
+        return Ok(());
+    }
+    let token = match crate::github::client::token_from_env() {
+        Ok(token) => token,
+        Err(e) => {
+            info!("Not publishing {} annotations as a check run: {e:#}", messages.len());
+            return Ok(());
+        }
+    };
+    let octocrab = crate::github::Client::with_token(token)?;
+    let repo = env::GITHUB_REPOSITORY.get()?;
+    let head_sha = env::GITHUB_SHA.get()?;
+    let check_run = create_check_run(&octocrab, &repo, &head_sha, name, &messages).await?;
+    info!("Published {} annotations in the check run {:?}.", messages.len(), check_run.html_url);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::workflow::Location;
+
+    #[test]
+    fn check_annotation() {
+        let mut message = Message::new(MessageLevel::Error, "mismatched types");
+        assert_eq!(CheckAnnotation::from_message(&message), None);
+
+        message.location = Some(Location {
+            file:       r"lib\src\lib.rs".into(),
+            line:       Some(10),
+            end_line:   Some(12),
+            column:     Some(5),
+            end_column: Some(8),
+        });
+        let annotation = CheckAnnotation::from_message(&message).unwrap();
+        assert_eq!(annotation.path, "lib/src/lib.rs");
+        assert_eq!((annotation.start_line, annotation.end_line), (10, 12));
+        // The columns are not allowed for multi-line annotations.
+        assert_eq!((annotation.start_column, annotation.end_column), (None, None));
+
+        let json = serde_json::to_value(&annotation).unwrap();
+        assert_eq!(json["annotation_level"], "failure");
+        assert!(json.get("start_column").is_none());
+    }
+}
@@ -1,5 +1,6 @@
 use crate::prelude::*;
 
+use crate::actions::artifacts::context::Context;
 use crate::actions::artifacts::run_session::SessionClient;
 
 use crate::actions::artifacts::download::FileToDownload;
@@ -9,13 +10,15 @@ use crate::actions::artifacts::upload::UploadOptions;
 use anyhow::Context as Trait_anyhow_Context;
 use flume::Sender;
 use serde::de::DeserializeOwned;
-use tempfile::tempdir;
 
 pub mod artifact;
 pub mod context;
+pub mod dedup;
 pub mod download;
+pub mod gc;
 pub mod models;
 pub mod raw;
+pub mod retention;
 pub mod run_session;
 pub mod upload;
 
@@ -65,11 +68,25 @@ pub async fn upload(
     artifact_name: impl AsRef<str>,
     options: UploadOptions,
 ) -> Result {
-    let handler =
-        ArtifactUploader::new(SessionClient::new_from_env()?, artifact_name.as_ref()).await?;
+    upload_with(&Context::new_from_env()?, file_provider, artifact_name, options).await
+}
+
+/// Like [`upload`], but in the explicitly given context rather than the one from the environment.
+pub async fn upload_with(
+    context: &Context,
+    file_provider: impl futures_util::Stream<Item = FileToUpload> + Send + 'static,
+    artifact_name: impl AsRef<str>,
+    options: UploadOptions,
+) -> Result {
+    options.validate()?;
+    let mut client = SessionClient::new(context)?;
+    client.upload_client = context.upload_client_with(&options)?;
+    let handler = ArtifactUploader::new(client, artifact_name.as_ref()).await?;
     let result = handler.upload_artifact_to_file_container(file_provider, &options).await;
     // We want to patch size even if there were some failures.
     handler.patch_artifact_size().await?;
+    let uploaded_size = handler.total_size.load(std::sync::atomic::Ordering::SeqCst);
+    crate::metrics::record_artifact_size(artifact_name.as_ref(), uploaded_size as u64);
     result
 }
 
@@ -82,6 +99,14 @@ pub fn upload_single_file(
     (async move || -> Result { upload(files?, artifact_name, default()).await })()
 }
 
+pub async fn upload_single_file_with(
+    context: &Context,
+    file: impl Into<PathBuf>,
+    artifact_name: impl AsRef<str>,
+) -> Result {
+    upload_with(context, single_file_provider(file)?, artifact_name, default()).await
+}
+
 pub fn upload_directory(
     dir: impl Into<PathBuf>,
     artifact_name: impl AsRef<str>,
@@ -92,13 +117,21 @@ pub fn upload_directory(
     (async move || -> Result { upload(files?, artifact_name, default()).await })()
 }
 
-#[tracing::instrument(skip_all , fields(artifact_name = %artifact_name.as_ref(), target = %target.as_ref().display()), err)]
 pub async fn download_single_file_artifact(
     artifact_name: impl AsRef<str>,
     target: impl AsRef<Path>,
+) -> Result {
+    download_single_file_artifact_with(&Context::new_from_env()?, artifact_name, target).await
+}
+
+#[tracing::instrument(skip_all , fields(artifact_name = %artifact_name.as_ref(), target = %target.as_ref().display()), err)]
+pub async fn download_single_file_artifact_with(
+    context: &Context,
+    artifact_name: impl AsRef<str>,
+    target: impl AsRef<Path>,
 ) -> Result {
     let downloader =
-        download::ArtifactDownloader::new(SessionClient::new_from_env()?, artifact_name.as_ref())
+        download::ArtifactDownloader::new(SessionClient::new(context)?, artifact_name.as_ref())
             .await?;
     match downloader.file_items().collect_vec().as_slice() {
         [item] => {
@@ -120,6 +153,58 @@ pub fn single_file_provider(
     Ok(futures::stream::iter([file]))
 }
 
+/// Upload the given `(local, remote)` pairs, so the files can be placed anywhere in the artifact,
+/// e.g. `target/release/enso.exe` as `enso-win.exe`, without copying them first.
+///
+/// Fails if two files would end up at the same remote path.
+pub fn mapped_files_provider(
+    files: impl IntoIterator<Item = (impl Into<PathBuf>, impl Into<PathBuf>)>,
+) -> Result<impl Stream<Item = FileToUpload> + 'static> {
+    let files = files
+        .into_iter()
+        .map(|(local, remote)| FileToUpload::new(local, remote))
+        .collect_result()?;
+    let mut remote_paths = HashSet::new();
+    for file in &files {
+        ensure!(
+            remote_paths.insert(&file.remote_path),
+            "Multiple files would be uploaded as {}.",
+            file.remote_path.display()
+        );
+    }
+    Ok(futures::stream::iter(files))
+}
+
+/// Upload the files from the directory, placed in the artifact by the mapping function.
+///
+/// The function gets the path relative to the directory and returns the path within the
+/// artifact, or `None` if the file should be skipped.
+pub fn mapped_dir_provider(
+    path: &Path,
+    mut mapping: impl FnMut(&Path) -> Option<PathBuf>,
+) -> Result<impl Stream<Item = FileToUpload> + 'static> {
+    let mut files = Vec::new();
+    for entry in walkdir::WalkDir::new(path) {
+        let entry = entry?;
+        if !entry.file_type().is_dir() {
+            let relative = entry.path().strip_prefix(path)?;
+            if let Some(remote) = mapping(relative) {
+                files.push((entry.path().to_path_buf(), remote));
+            }
+        }
+    }
+    info!("Discovered {} files to upload under the {}.", files.len(), path.display());
+    mapped_files_provider(files)
+}
+
+/// Upload the given `(local, remote)` pairs as an artifact. See [`mapped_files_provider`].
+pub async fn upload_mapped_files(
+    files: impl IntoIterator<Item = (impl Into<PathBuf>, impl Into<PathBuf>)>,
+    artifact_name: impl AsRef<str>,
+) -> Result {
+    upload(mapped_files_provider(files)?, artifact_name, default()).await
+}
+
 pub fn single_dir_provider(path: &Path) -> Result<impl Stream<Item = FileToUpload> + 'static> {
     // TODO not optimal, could discover files at the same time as handling them.
     let files = walkdir::WalkDir::new(path)
@@ -140,11 +225,11 @@ pub async fn upload_compressed_directory(
     artifact_name: impl AsRef<str> + Send,
 ) -> Result {
     let artifact_name = artifact_name.as_ref();
-    let tempdir = tempdir()?;
+    let tempdir = crate::fs::temp::dir("artifact-upload")?;
     let archive_path = tempdir.path().join(format!("{artifact_name}.tar.gz"));
 
     info!("Packing {} to {}", path_to_upload.as_ref().display(), archive_path.display());
-    crate::archive::pack_directory_contents(&archive_path, path_to_upload).await?;
+    crate::archive::pack_directory_contents(&archive_path, path_to_upload, default()).await?;
 
     info!("Starting upload of {artifact_name}.");
     upload_single_file(&archive_path, artifact_name).await?;
@@ -158,7 +243,7 @@ pub async fn retrieve_compressed_directory(
     path_to_extract: impl AsRef<Path> + Send,
 ) -> Result {
     let artifact_name = artifact_name.as_ref();
-    let tempdir = tempdir()?;
+    let tempdir = crate::fs::temp::dir("artifact-download")?;
     let archive_path = tempdir.path().join(format!("{artifact_name}.tar.gz"));
 
     download_single_file_artifact(&artifact_name, &archive_path).await?;
@@ -170,6 +255,7 @@ pub async fn retrieve_compressed_directory(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::actions::artifacts::context::RunContext;
     use crate::actions::artifacts::models::CreateArtifactResponse;
     use reqwest::StatusCode;
     use tempfile::TempDir;
@@ -197,9 +283,7 @@ mod tests {
             )
             .await;
 
-        std::env::set_var("ACTIONS_RUNTIME_URL", mock_server.uri());
-        std::env::set_var("ACTIONS_RUNTIME_TOKEN", "password123");
-        std::env::set_var("GITHUB_RUN_ID", "12");
+        let context = Context::from(RunContext::test_context(&mock_server));
 
         let path_to_upload = "Cargo.toml";
 
@@ -208,7 +292,8 @@ mod tests {
             remote_path: PathBuf::from(path_to_upload),
         };
 
-        upload(futures::stream::once(ready(file_to_upload)), "MyCargoArtifact", default()).await?;
+        let files = futures::stream::once(ready(file_to_upload));
+        upload_with(&context, files, "MyCargoArtifact", default()).await?;
         // artifacts::upload_path(path_to_upload).await?;
         Ok(())
         //let client = reqwest::Client::builder().default_headers().
@@ -225,6 +310,28 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn mapping_files() -> Result {
+        let dir = TempDir::new()?;
+        let exe = dir.path().join_iter(["target", "release", "enso.exe"]);
+        crate::fs::create(&exe)?;
+        crate::fs::create(dir.path().join_iter(["target", "release", "enso.pdb"]))?;
+
+        let flatten = |relative: &Path| {
+            let is_exe = relative.extension() == Some(OsStr::new("exe"));
+            is_exe.then(|| PathBuf::from("enso-win.exe"))
+        };
+        let files = mapped_dir_provider(dir.path(), flatten)?.collect::<Vec<_>>().await;
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].local_path, exe);
+        assert_eq!(files[0].remote_path, PathBuf::from("enso-win.exe"));
+
+        assert!(mapped_files_provider([(&exe, "a"), (&exe, "a")]).is_err());
+        assert!(mapped_files_provider([(&exe, "../a")]).is_err());
+        assert!(mapped_files_provider([(&exe, "/a")]).is_err());
+        Ok(())
+    }
+
     #[test]
     fn deserialize_response() -> Result {
         let text = r#"{"containerId":11099678,"size":-1,"signedContent":null,"fileContainerResourceUrl":"https://pipelines.actions.githubusercontent.com/VYS7uSE1JB12MkavBOHvD6nounefzg1s5vHmQvfbiLmuvFuM6c/_apis/resources/Containers/11099678","type":"actions_storage","name":"SomeFile","url":"https://pipelines.actions.githubusercontent.com/VYS7uSE1JB12MkavBOHvD6nounefzg1s5vHmQvfbiLmuvFuM6c/_apis/pipelines/1/runs/75/artifacts?artifactName=SomeFile","expiresOn":"2022-01-29T04:07:24.5807079Z","items":null}"#;
@@ -238,7 +345,7 @@ mod tests {
         let path = PathBuf::from("Cargo.toml");
         let artifact_path = path.file_name().unwrap(); // FIXME
 
-        let client = reqwest::ClientBuilder::new().build()?;
+        let client = crate::net::client()?;
         dbg!(artifact_path);
         client
             .patch(response.url)
@@ -7,10 +7,81 @@ use reqwest::header::ACCEPT_ENCODING;
 use reqwest::Client;
 use reqwest::ClientBuilder;
 
+use crate::actions::artifacts::upload::UploadOptions;
 use crate::actions::artifacts::API_VERSION;
-use crate::env::expect_var;
 use crate::extensions::reqwest::ClientBuilderExt;
 
+
+/// The workflow run whose artifacts are accessed, and the credentials for the artifact service.
+///
+/// On GitHub Actions it is provided by the runner through the environment, see
+/// [`RunContext::from_env`]. Elsewhere (like in tests or on other CI services) it can be put
+/// together with [`RunContext::builder`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RunContext {
+    /// Base URL of the runtime service. Always ends with a slash.
+    pub runtime_url: Url,
+    pub token:       String,
+    pub run_id:      String,
+}
+
+impl RunContext {
+    pub fn builder() -> RunContextBuilder {
+        default()
+    }
+
+    /// Context of the current workflow run, as provided by the runner to the actions.
+    pub fn from_env() -> Result<Self> {
+        RunContext::builder()
+            .runtime_url(crate::actions::env::ACTIONS_RUNTIME_URL.get()?)
+            .token(crate::actions::env::ACTIONS_RUNTIME_TOKEN.get()?)
+            .run_id(crate::actions::env::GITHUB_RUN_ID.get()?)
+            .build()
+    }
+
+    /// Context for accessing the artifact service mocked by the given server.
+    pub fn test_context(mock_server: &wiremock::MockServer) -> Self {
+        let runtime_url = mock_server.uri().parse().expect("Mock server URI must be a valid URL.");
+        RunContext { runtime_url, token: "mock-token".into(), run_id: "1".into() }
+    }
+}
+
+/// Builder of the [`RunContext`]. All the fields are required.
+#[derive(Clone, Debug, Default)]
+pub struct RunContextBuilder {
+    pub runtime_url: Option<Url>,
+    pub token:       Option<String>,
+    pub run_id:      Option<String>,
+}
+
+impl RunContextBuilder {
+    pub fn runtime_url(&mut self, runtime_url: Url) -> &mut Self {
+        self.runtime_url = Some(runtime_url);
+        self
+    }
+
+    pub fn token(&mut self, token: impl Into<String>) -> &mut Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn run_id(&mut self, run_id: impl ToString) -> &mut Self {
+        self.run_id = Some(run_id.to_string());
+        self
+    }
+
+    pub fn build(&self) -> Result<RunContext> {
+        let mut runtime_url = self.runtime_url.clone().context("Missing the runtime URL.")?;
+        // The service endpoints are resolved relative to the runtime URL.
+        if !runtime_url.path().ends_with('/') {
+            runtime_url.set_path(&format!("{}/", runtime_url.path()));
+        }
+        let token = self.token.clone().context("Missing the runtime token.")?;
+        let run_id = self.run_id.clone().context("Missing the run ID.")?;
+        Ok(RunContext { runtime_url, token, run_id })
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Context {
     pub runtime_url:   Url,
@@ -20,12 +91,16 @@ pub struct Context {
 }
 
 impl Context {
-    pub fn new_from_env() -> Result<Self> {
-        let runtime_url = expect_var("ACTIONS_RUNTIME_URL")?.parse()?;
-        let runtime_token = expect_var("ACTIONS_RUNTIME_TOKEN")?;
-        let run_id = expect_var("GITHUB_RUN_ID")?;
+    pub fn new(runtime_url: Url, runtime_token: impl Into<String>, run_id: impl ToString) -> Self {
+        let runtime_token = runtime_token.into();
+        let run_id = run_id.to_string();
         let api_version = API_VERSION.to_string();
-        Ok(Context { runtime_url, runtime_token, run_id, api_version })
+        Context { runtime_url, runtime_token, run_id, api_version }
+    }
+
+    /// Context of the current workflow run, as provided by the runner to the actions.
+    pub fn new_from_env() -> Result<Self> {
+        Ok(RunContext::from_env()?.into())
     }
 
     pub fn artifact_url(&self) -> Result<Url> {
@@ -47,7 +122,7 @@ impl Context {
             format!("Bearer {}", self.runtime_token).parse()?,
         );
 
-        Ok(ClientBuilder::new().default_headers(headers).user_agent(crate::USER_AGENT))
+        Ok(crate::net::client_builder()?.default_headers(headers))
     }
 
     pub fn json_client(&self) -> Result<Client> {
@@ -58,16 +133,21 @@ impl Context {
     }
 
     pub fn upload_client(&self) -> Result<Client> {
+        self.upload_client_with(&default())
+    }
+
+    /// Upload client with the connection settings from the options.
+    pub fn upload_client_with(&self, options: &UploadOptions) -> Result<Client> {
         let keep_alive_seconds = 3;
 
         let mut headers = HeaderMap::new();
         headers.insert(reqwest::header::CONNECTION, HeaderValue::from_static("Keep-Alive"));
         headers.insert("Keep-Alive", keep_alive_seconds.into());
-        self.prepare_client(mime::APPLICATION_OCTET_STREAM)?
+        let builder = self
+            .prepare_client(mime::APPLICATION_OCTET_STREAM)?
             .default_content_type(mime::APPLICATION_JSON)
-            .default_headers(headers)
-            .build()
-            .anyhow_err()
+            .default_headers(headers);
+        options.tune_client(builder).build().anyhow_err()
     }
 
     pub fn download_client(&self) -> Result<Client> {
@@ -79,3 +159,29 @@ impl Context {
             .anyhow_err()
     }
 }
+
+impl From<RunContext> for Context {
+    fn from(run: RunContext) -> Self {
+        Context::new(run.runtime_url, run.token, run.run_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn building_run_context() -> Result {
+        assert!(RunContext::builder().token("token").run_id(1).build().is_err());
+        let run = RunContext::builder()
+            .runtime_url("https://pipelines.example.com/abc".parse()?)
+            .token("token")
+            .run_id(75)
+            .build()?;
+        assert_eq!(run.runtime_url.as_str(), "https://pipelines.example.com/abc/");
+        let context = Context::from(run);
+        let artifact_url = context.artifact_url()?;
+        assert_eq!(artifact_url.path(), "/abc/_apis/pipelines/workflows/75/artifacts");
+        Ok(())
+    }
+}
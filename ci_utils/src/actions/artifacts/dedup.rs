@@ -0,0 +1,237 @@
+//! Content-addressed artifacts, sharing the identical files between the jobs of the run.
+//!
+//! In this mode the files are uploaded to the shared [store](STORE_ARTIFACT_NAME) artifact, named
+//! by the SHA-256 digests of their contents. The artifact itself contains only the
+//! [index](INDEX_FILENAME), mapping the paths to the digests. The files that are already in the
+//! store, e.g. uploaded by another job of the matrix, are not uploaded again.
+//!
+//! Jobs running at the same time may still upload the same file, which is harmless, as the
+//! contents are identical.
+
+use crate::prelude::*;
+
+use crate::actions::artifacts::context::Context;
+use crate::actions::artifacts::models::ItemType;
+use crate::actions::artifacts::run_session::SessionClient;
+use crate::actions::artifacts::upload::UploadOptions;
+use crate::hash::Algorithm;
+
+
+/// Name of the artifact with the files of all the content-addressed artifacts of the run.
+pub const STORE_ARTIFACT_NAME: &str = "content-store";
+
+/// Name of the file with the [`Index`], which is the only file of the content-addressed artifact.
+pub const INDEX_FILENAME: &str = "index.json";
+
+/// File of the content-addressed artifact.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// Lowercase hex SHA-256 digest of the contents, the name of the file in the store.
+    pub digest:     String,
+    pub size:       u64,
+    #[serde(default)]
+    pub executable: bool,
+}
+
+/// Contents of the content-addressed artifact.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Index {
+    /// The files, by their paths relative to the artifact root, with forward slashes.
+    pub files: BTreeMap<String, IndexEntry>,
+}
+
+impl Index {
+    /// Hash all the files in the directory.
+    #[context("Failed to index the directory {}.", root.display())]
+    pub async fn of_directory(root: &Path) -> Result<Self> {
+        let mut files = BTreeMap::new();
+        for entry in walkdir::WalkDir::new(root).min_depth(1) {
+            let entry = entry?;
+            if entry.file_type().is_dir() {
+                continue;
+            }
+            let relative = entry.path().strip_prefix(root)?;
+            let key = relative.iter().map(|part| part.to_string_lossy()).join("/");
+            let metadata = entry.metadata()?;
+            #[cfg(unix)]
+            let executable = {
+                use std::os::unix::fs::PermissionsExt;
+                metadata.permissions().mode() & 0o111 != 0
+            };
+            #[cfg(not(unix))]
+            let executable = false;
+            let digest = crate::hash::file(entry.path(), Algorithm::Sha256).await?;
+            files.insert(key, IndexEntry { digest, size: metadata.len(), executable });
+        }
+        Ok(Self { files })
+    }
+
+    /// Paths of the files, grouped by the digests of their contents.
+    pub fn paths_by_digest(&self) -> BTreeMap<&str, Vec<&str>> {
+        let mut ret = BTreeMap::<_, Vec<_>>::new();
+        for (path, entry) in &self.files {
+            ret.entry(entry.digest.as_str()).or_default().push(path.as_str());
+        }
+        ret
+    }
+
+    /// Total size of the files, counting the duplicates once.
+    pub fn unique_size(&self) -> u64 {
+        let sizes = self.files.values().map(|entry| (&entry.digest, entry.size));
+        sizes.collect::<HashMap<_, _>>().values().sum()
+    }
+}
+
+/// File in the store.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoredFile {
+    pub location: Url,
+    /// Size reported by the artifact service, if known.
+    pub size:     Option<u64>,
+}
+
+impl StoredFile {
+    /// Whether the stored file can be reused for the entry.
+    ///
+    /// A file with the wrong size is a leftover of an interrupted upload, so it must be uploaded
+    /// again.
+    pub fn is_complete(&self, entry: &IndexEntry) -> bool {
+        self.size == Some(entry.size)
+    }
+}
+
+/// The files in the store, by their digests.
+pub async fn stored_files(client: &SessionClient) -> Result<HashMap<String, StoredFile>> {
+    let artifacts = client.list_artifacts().await?;
+    let store = match artifacts.iter().find(|artifact| artifact.name == STORE_ARTIFACT_NAME) {
+        Some(store) => store,
+        None => return Ok(default()),
+    };
+    let items = client.get_container_items(store).await?;
+    Ok(items
+        .into_iter()
+        .filter(|item| item.item_type == ItemType::File)
+        .map(|item| {
+            let size = item.file_length.and_then(|length| u64::try_from(length).ok());
+            let file = StoredFile { location: item.content_location.clone(), size };
+            (item.relative_path().as_str().to_owned(), file)
+        })
+        .collect())
+}
+
+/// Upload the directory as a content-addressed artifact.
+#[tracing::instrument(skip(context, options), err)]
+pub async fn upload_directory(
+    context: &Context,
+    root: &Path,
+    artifact_name: &str,
+    options: UploadOptions,
+) -> Result<Index> {
+    let index = Index::of_directory(root).await?;
+    let stored = stored_files(&SessionClient::new(context)?).await?;
+    let missing = index
+        .paths_by_digest()
+        .into_iter()
+        .filter(|(digest, paths)| {
+            let entry = &index.files[paths[0]];
+            !stored.get(*digest).map_or(false, |file| file.is_complete(entry))
+        })
+        .map(|(digest, paths)| (root.join(paths[0]), digest.to_owned()))
+        .collect_vec();
+    let unique_count = index.paths_by_digest().len();
+    info!(
+        "{} of {unique_count} unique files are already stored, uploading {}.",
+        unique_count - missing.len(),
+        missing.len()
+    );
+    if !missing.is_empty() {
+        let files = crate::actions::artifacts::mapped_files_provider(missing)?;
+        crate::actions::artifacts::upload_with(context, files, STORE_ARTIFACT_NAME, options)
+            .await?;
+    }
+
+    let temp = crate::fs::temp::dir("artifact-index")?;
+    let index_path = temp.path().join(INDEX_FILENAME);
+    index_path.write_as_json(&index)?;
+    crate::actions::artifacts::upload_single_file_with(context, &index_path, artifact_name).await?;
+    Ok(index)
+}
+
+/// Download the content-addressed artifact, reconstructing the directory tree.
+#[tracing::instrument(skip(context), err)]
+pub async fn download_directory(
+    context: &Context,
+    artifact_name: &str,
+    output: &Path,
+) -> Result<Index> {
+    let temp = crate::fs::temp::dir("artifact-index")?;
+    let index_path = temp.path().join(INDEX_FILENAME);
+    crate::actions::artifacts::download_single_file_artifact_with(
+        context,
+        artifact_name,
+        &index_path,
+    )
+    .await?;
+    let index: Index = index_path.read_to_json()?;
+
+    let client = SessionClient::new(context)?;
+    let stored = stored_files(&client).await?;
+    for (digest, paths) in index.paths_by_digest() {
+        let file = stored
+            .get(digest)
+            .with_context(|| format!("The file {digest} is missing from the store."))?;
+        let first = output.join(paths[0]);
+        let stream = client.download_container_item(file.location.clone()).await?;
+        crate::fs::tokio::copy_to_file(stream, &first).await?;
+        let actual = crate::hash::file(&first, Algorithm::Sha256).await?;
+        ensure!(actual == digest, "The stored file {digest} has the digest {actual}.");
+        for path in &paths[1..] {
+            crate::fs::copy(&first, output.join(path))?;
+        }
+    }
+    for (path, entry) in &index.files {
+        if entry.executable {
+            crate::fs::allow_owner_execute(output.join(path))?;
+        }
+    }
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn indexing_directory() -> Result {
+        let temp = tempfile::tempdir()?;
+        crate::fs::write(temp.path().join_iter(["linux", "lib.so"]), "shared")?;
+        crate::fs::write(temp.path().join_iter(["windows", "lib.so"]), "shared")?;
+        crate::fs::write(temp.path().join_iter(["windows", "enso.exe"]), "exe")?;
+
+        let index = Index::of_directory(temp.path()).await?;
+        assert_eq!(index.files.keys().collect_vec(), [
+            "linux/lib.so",
+            "windows/enso.exe",
+            "windows/lib.so"
+        ]);
+        let by_digest = index.paths_by_digest();
+        assert_eq!(by_digest.len(), 2);
+        assert_eq!(by_digest[index.files["linux/lib.so"].digest.as_str()], [
+            "linux/lib.so",
+            "windows/lib.so"
+        ]);
+        assert_eq!(index.unique_size(), 9);
+        Ok(())
+    }
+
+    #[test]
+    fn incomplete_stored_files() -> Result {
+        let entry = IndexEntry { digest: "abc".into(), size: 9, executable: false };
+        let location = Url::parse("https://example.com/content-store/abc")?;
+        let stored = |size| StoredFile { location: location.clone(), size };
+        assert!(stored(Some(9)).is_complete(&entry));
+        assert!(!stored(Some(4)).is_complete(&entry));
+        assert!(!stored(None).is_complete(&entry));
+        Ok(())
+    }
+}
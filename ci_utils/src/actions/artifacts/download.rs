@@ -56,6 +56,9 @@ impl ArtifactDownloader {
                 ItemType::Folder => {
                     create_dir_all(root_path.join(item.relative_path())).await?;
                 }
+                ItemType::Unknown => {
+                    warn!("Skipping entry {} of unknown type.", item.path.display());
+                }
             }
         }
         Ok(())
@@ -0,0 +1,219 @@
+//! Removal of the artifacts left behind by interrupted uploads.
+//!
+//! If an upload is interrupted before the artifact size is patched, the artifact container is
+//! never finalized. Such containers are useless, but they still count against the storage quota
+//! until they expire.
+//!
+//! The artifact is unfinalized if it is missing from the artifacts listed for its run, which
+//! include only the finalized uploads. Artifacts are considered orphaned only after they have been
+//! inactive for a given time, so uploads that are still in progress are not affected.
+
+use crate::prelude::*;
+
+use crate::actions::artifacts::models::ArtifactResponse;
+use crate::actions::artifacts::run_session::SessionClient;
+use crate::github::Client;
+use crate::serde::null_as_default;
+use chrono::DateTime;
+use chrono::Utc;
+
+
+/// Default time after which an unfinalized artifact is considered orphaned.
+pub fn default_threshold() -> chrono::Duration {
+    chrono::Duration::hours(12)
+}
+
+/// Workflow run that uploaded the artifact.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkflowRunReference {
+    pub id: u64,
+}
+
+/// Artifact as described by the GitHub REST API.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RestArtifact {
+    pub id:            u64,
+    pub name:          String,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub size_in_bytes: u64,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub expired:       bool,
+    #[serde(default)]
+    pub created_at:    Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub expires_at:    Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub workflow_run:  Option<WorkflowRunReference>,
+}
+
+impl RestArtifact {
+    /// Whether the artifact is not expired and older than the threshold.
+    ///
+    /// Artifacts of unknown age are never considered stale.
+    pub fn is_stale(&self, now: DateTime<Utc>, threshold: chrono::Duration) -> bool {
+        let is_old = self.created_at.map_or(false, |created_at| now - created_at >= threshold);
+        !self.expired && is_old
+    }
+
+    /// Whether the artifact is stale and its upload was never finalized, i.e. it is not among
+    /// the `finalized` artifacts (given by their IDs) listed for its run.
+    ///
+    /// Note that the finalized artifacts might be empty, so the size cannot be used instead.
+    pub fn is_orphaned(
+        &self,
+        finalized: &HashSet<u64>,
+        now: DateTime<Utc>,
+        threshold: chrono::Duration,
+    ) -> bool {
+        self.is_stale(now, threshold) && !finalized.contains(&self.id)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ListRestArtifactsResponse {
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub total_count: u64,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub artifacts:   Vec<RestArtifact>,
+}
+
+/// List the most recent artifacts in the repository, newest first.
+///
+/// At most `pages` pages of 100 artifacts each are retrieved.
+#[context("Failed to list the artifacts in {repo}.")]
+pub async fn list_recent_artifacts(
+    octocrab: &Octocrab,
+    repo: &(impl RepoPointer + Sync),
+    pages: u32,
+) -> Result<Vec<RestArtifact>> {
+    let path = format!("/repos/{}/{}/actions/artifacts", repo.owner(), repo.name());
+    let limit = usize::try_from(pages)? * 100;
+    Client::new(octocrab.clone()).paginate(path, Some("artifacts")).take(limit).try_collect().await
+}
+
+/// List the artifacts uploaded by the workflow run. Only the finalized uploads are listed.
+#[context("Failed to list the artifacts of the run {run_id} in {repo}.")]
+pub async fn list_run_artifacts(
+    octocrab: &Octocrab,
+    repo: &(impl RepoPointer + Sync),
+    run_id: u64,
+) -> Result<Vec<RestArtifact>> {
+    let path = format!("/repos/{}/{}/actions/runs/{run_id}/artifacts", repo.owner(), repo.name());
+    Client::new(octocrab.clone()).paginate(path, Some("artifacts")).try_collect().await
+}
+
+/// Delete the artifact using the REST API.
+#[context("Failed to delete the artifact {} (id {}) in {repo}.", artifact.name, artifact.id)]
+pub async fn delete_artifact(
+    octocrab: &Octocrab,
+    repo: &(impl RepoPointer + Sync),
+    artifact: &RestArtifact,
+) -> Result {
+    let path = format!("/repos/{}/{}/actions/artifacts/{}", repo.owner(), repo.name(), artifact.id);
+    Client::new(octocrab.clone()).delete(path).await?;
+    Ok(())
+}
+
+/// Delete orphaned artifacts among the recent artifacts of the repository.
+///
+/// If `dry_run` is set, the orphaned artifacts are only reported. Returns the orphaned artifacts.
+pub async fn collect_orphaned_artifacts(
+    octocrab: &Octocrab,
+    repo: &(impl RepoPointer + Sync),
+    pages: u32,
+    threshold: chrono::Duration,
+    dry_run: bool,
+) -> Result<Vec<RestArtifact>> {
+    let now = Utc::now();
+    let stale = list_recent_artifacts(octocrab, repo, pages)
+        .await?
+        .into_iter()
+        .filter(|artifact| artifact.is_stale(now, threshold))
+        .collect_vec();
+    let mut orphaned = Vec::new();
+    // Artifacts of unknown run cannot be checked, so they are skipped.
+    let by_run =
+        stale.into_iter().filter_map(|artifact| Some((artifact.workflow_run?.id, artifact)));
+    for (run_id, artifacts) in &by_run.into_group_map() {
+        let finalized = list_run_artifacts(octocrab, repo, *run_id)
+            .await?
+            .into_iter()
+            .map(|artifact| artifact.id)
+            .collect::<HashSet<_>>();
+        orphaned.extend(
+            artifacts
+                .iter()
+                .filter(|artifact| artifact.is_orphaned(&finalized, now, threshold))
+                .cloned(),
+        );
+    }
+    info!("Found {} orphaned artifacts in {repo}.", orphaned.len());
+    for artifact in &orphaned {
+        info!("Removing orphaned artifact {} created at {:?}.", artifact.name, artifact.created_at);
+        if !dry_run {
+            delete_artifact(octocrab, repo, artifact).await?;
+        }
+    }
+    Ok(orphaned)
+}
+
+/// Delete orphaned artifacts of the current run using the artifact service session.
+///
+/// This is useful for jobs that retry an upload, as the artifacts from the previous attempts are
+/// not visible through the REST API until the run completes. The artifact is inactive since the
+/// last modification of any of its items. Empty containers are skipped, as their age is unknown.
+pub async fn collect_orphaned_in_current_run(
+    session: &SessionClient,
+    threshold: chrono::Duration,
+    dry_run: bool,
+) -> Result<Vec<ArtifactResponse>> {
+    let now = Utc::now();
+    let mut ret = Vec::new();
+    for artifact in session.list_artifacts().await? {
+        // Finalized artifacts have their size patched.
+        if artifact.size >= 0 {
+            continue;
+        }
+        let items = session.get_container_items(&artifact).await?;
+        let last_activity = items.iter().map(|item| item.date_last_modified).max();
+        match last_activity {
+            Some(last_activity) if now - last_activity >= threshold => {
+                info!("Removing orphaned artifact {} of the current run.", artifact.name);
+                if !dry_run {
+                    session.delete_artifact(&artifact).await?;
+                }
+                ret.push(artifact);
+            }
+            _ => debug!("Artifact {} might be still uploaded, skipping.", artifact.name),
+        }
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_orphaned() -> Result {
+        let text = r#"{"total_count":4,"artifacts":[
+            {"id":1,"name":"ide-linux","size_in_bytes":0,"expired":false,"created_at":"2022-03-01T10:00:00Z","workflow_run":{"id":7}},
+            {"id":2,"name":"ide-macos","size_in_bytes":1024,"expired":false,"created_at":"2022-03-01T10:00:00Z","workflow_run":{"id":7}},
+            {"id":3,"name":"ide-windows","size_in_bytes":0,"expired":false,"created_at":"2022-03-02T09:00:00Z","workflow_run":{"id":7}},
+            {"id":4,"name":"empty-logs","size_in_bytes":0,"expired":false,"created_at":"2022-03-01T10:00:00Z","workflow_run":{"id":7}}
+        ]}"#;
+        let response = serde_json::from_str::<ListRestArtifactsResponse>(text)?;
+        assert_eq!(response.artifacts[0].workflow_run, Some(WorkflowRunReference { id: 7 }));
+        let now = DateTime::parse_from_rfc3339("2022-03-02T10:00:00Z")?.with_timezone(&Utc);
+        // The empty artifact was finalized, so it is kept.
+        let finalized = HashSet::from([2, 4]);
+        let orphaned = response
+            .artifacts
+            .iter()
+            .filter(|artifact| artifact.is_orphaned(&finalized, now, default_threshold()))
+            .map(|artifact| artifact.id)
+            .collect_vec();
+        assert_eq!(orphaned, vec![1]);
+        Ok(())
+    }
+}
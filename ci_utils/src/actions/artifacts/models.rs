@@ -1,7 +1,31 @@
+//! Models of the payloads exchanged with the GitHub Actions artifact service.
+//!
+//! The service is not publicly documented and its responses change without notice. Therefore,
+//! the response models are deliberately lenient: unknown fields are ignored, non-essential fields
+//! are defaulted when missing or `null`, and enumerations have a catch-all variant.
+//!
+//! The models in this module describe the legacy "preview" service (see
+//! [`API_VERSION`](crate::actions::artifacts::API_VERSION)). The models of the newer Twirp-based
+//! service are in the [`v4`] submodule.
+
 use crate::prelude::*;
+
+use crate::serde::null_as_default;
 use chrono::DateTime;
 use chrono::Utc;
 
+pub mod v4;
+
+
+/// Version of the artifact service protocol.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ServiceVersion {
+    /// The legacy service, used by `actions/upload-artifact@v3` and older.
+    Preview,
+    /// The Twirp-based service, used by `actions/upload-artifact@v4` and newer.
+    V4,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")] // Sic!
 pub struct CreateArtifactRequest {
@@ -27,15 +51,27 @@ impl CreateArtifactRequest {
 #[serde(rename_all = "camelCase")] // Sic!
 pub struct CreateArtifactResponse {
     pub container_id: u64,
+    #[serde(default = "unknown_size", deserialize_with = "size_or_unknown")]
     pub size: i64, // must be signed, as -1 is used as a placeholder
+    #[serde(default)]
     pub signed_content: Option<String>,
     pub file_container_resource_url: Url,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub r#type: String,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub name: String,
     pub url: Url,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub expires_on: String,
 }
 
+impl CreateArtifactResponse {
+    /// When the artifact expires, if the service reported it.
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        crate::actions::artifacts::retention::parse_expiry(&self.expires_on)
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")] // Sic!
 pub struct UploadFileQuery {
@@ -55,9 +91,13 @@ pub struct PatchArtifactSize {
 #[serde(rename_all = "camelCase")] // Sic!
 pub struct PatchArtifactSizeResponse {
     pub container_id:   u64,
+    #[serde(default = "unknown_size", deserialize_with = "size_or_unknown")]
     pub size:           i64,
+    #[serde(default)]
     pub signed_content: Option<String>,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub r#type:         String,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub name:           String,
     pub url:            Url,
     // This is not actually present, despite what GH sources say.
@@ -66,7 +106,9 @@ pub struct PatchArtifactSizeResponse {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ListArtifactsResponse {
+    #[serde(default, deserialize_with = "null_as_default")]
     pub count: i64,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub value: Vec<ArtifactResponse>,
 }
 
@@ -74,17 +116,31 @@ pub struct ListArtifactsResponse {
 #[serde(rename_all = "camelCase")]
 pub struct ArtifactResponse {
     pub container_id: u64,
+    #[serde(default = "unknown_size", deserialize_with = "size_or_unknown")]
     pub size: i64,
+    #[serde(default)]
     pub signed_content: Option<String>,
     pub file_container_resource_url: Url,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub r#type: String,
     pub name: String,
     pub url: Url,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub expires_on: String,
+}
+
+impl ArtifactResponse {
+    /// When the artifact expires, if the service reported it.
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        crate::actions::artifacts::retention::parse_expiry(&self.expires_on)
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct QueryArtifactResponse {
+    #[serde(default, deserialize_with = "null_as_default")]
     pub count: i64,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub value: Vec<ContainerEntry>,
 }
 
@@ -96,8 +152,11 @@ pub struct ContainerEntry {
     pub path:               PathBuf,
     pub item_type:          ItemType,
     pub status:             EntryStatus,
+    #[serde(default)]
     pub file_length:        Option<i64>,
+    #[serde(default)]
     pub file_encoding:      Option<i64>,
+    #[serde(default)]
     pub file_type:          Option<i64>,
     pub date_created:       DateTime<Utc>,
     pub date_last_modified: DateTime<Utc>,
@@ -105,7 +164,9 @@ pub struct ContainerEntry {
     pub last_modified_by:   Uuid,
     pub item_location:      Url,
     pub content_location:   Url,
+    #[serde(default)]
     pub file_id:            Option<usize>,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub content_id:         String,
 }
 
@@ -127,7 +188,9 @@ impl ContainerEntry {
 pub enum EntryStatus {
     Created,
     PendingUpload,
-    // No other values known at this point.
+    /// Any status not known at the time of writing.
+    #[serde(other)]
+    Unknown,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
@@ -135,4 +198,77 @@ pub enum EntryStatus {
 pub enum ItemType {
     File,
     Folder,
+    /// Any item type not known at the time of writing.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Placeholder value used by the service when the size is not known yet.
+fn unknown_size() -> i64 {
+    -1
+}
+
+fn size_or_unknown<'de, D: serde::Deserializer<'de>>(de: D) -> std::result::Result<i64, D::Error> {
+    Option::<i64>::deserialize(de).map(|size| size.unwrap_or_else(unknown_size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Response recorded from the preview service on a `POST` to the artifacts endpoint.
+    const CREATE_ARTIFACT_RESPONSE: &str = r#"{"containerId":11099678,"size":-1,"signedContent":null,"fileContainerResourceUrl":"https://pipelines.actions.githubusercontent.com/VYS7uSE1JB12MkavBOHvD6nounefzg1s5vHmQvfbiLmuvFuM6c/_apis/resources/Containers/11099678","type":"actions_storage","name":"SomeFile","url":"https://pipelines.actions.githubusercontent.com/VYS7uSE1JB12MkavBOHvD6nounefzg1s5vHmQvfbiLmuvFuM6c/_apis/pipelines/1/runs/75/artifacts?artifactName=SomeFile","expiresOn":"2022-01-29T04:07:24.5807079Z","items":null}"#;
+
+    const LIST_ARTIFACTS_RESPONSE: &str = r#"{"count":1,"value":[{"containerId":11099678,"size":1042,"signedContent":null,"fileContainerResourceUrl":"https://pipelines.actions.githubusercontent.com/VYS7uSE1JB12MkavBOHvD6nounefzg1s5vHmQvfbiLmuvFuM6c/_apis/resources/Containers/11099678","type":"actions_storage","name":"SomeFile","url":"https://pipelines.actions.githubusercontent.com/VYS7uSE1JB12MkavBOHvD6nounefzg1s5vHmQvfbiLmuvFuM6c/_apis/pipelines/1/runs/75/artifacts?artifactName=SomeFile","expiresOn":"2022-01-29T04:07:24.5807079Z","items":null}]}"#;
+
+    const CONTAINER_ITEMS_RESPONSE: &str = r#"{"count":2,"value":[{"containerId":11099678,"scopeIdentifier":"00000000-0000-0000-0000-000000000000","path":"SomeFile","itemType":"folder","status":"created","dateCreated":"2022-01-14T04:07:25.303Z","dateLastModified":"2022-01-14T04:07:25.303Z","createdBy":"2d5c4e8e-5d64-4b6a-b4a0-6a1b9a5e0b6b","lastModifiedBy":"2d5c4e8e-5d64-4b6a-b4a0-6a1b9a5e0b6b","itemLocation":"https://pipelines.actions.githubusercontent.com/VYS7uSE1JB12MkavBOHvD6nounefzg1s5vHmQvfbiLmuvFuM6c/_apis/resources/Containers/11099678?itemPath=SomeFile&metadata=True","contentLocation":"https://pipelines.actions.githubusercontent.com/VYS7uSE1JB12MkavBOHvD6nounefzg1s5vHmQvfbiLmuvFuM6c/_apis/resources/Containers/11099678?itemPath=SomeFile","contentId":""},{"containerId":11099678,"scopeIdentifier":"00000000-0000-0000-0000-000000000000","path":"SomeFile/Cargo.toml","itemType":"file","status":"created","fileLength":1042,"fileEncoding":1,"fileType":1,"dateCreated":"2022-01-14T04:07:25.303Z","dateLastModified":"2022-01-14T04:07:25.397Z","createdBy":"2d5c4e8e-5d64-4b6a-b4a0-6a1b9a5e0b6b","lastModifiedBy":"2d5c4e8e-5d64-4b6a-b4a0-6a1b9a5e0b6b","itemLocation":"https://pipelines.actions.githubusercontent.com/VYS7uSE1JB12MkavBOHvD6nounefzg1s5vHmQvfbiLmuvFuM6c/_apis/resources/Containers/11099678?itemPath=SomeFile%2FCargo.toml&metadata=True","contentLocation":"https://pipelines.actions.githubusercontent.com/VYS7uSE1JB12MkavBOHvD6nounefzg1s5vHmQvfbiLmuvFuM6c/_apis/resources/Containers/11099678?itemPath=SomeFile%2FCargo.toml","fileId":1234,"contentId":""}]}"#;
+
+    #[test]
+    fn deserialize_create_artifact_response() -> Result {
+        let response = serde_json::from_str::<CreateArtifactResponse>(CREATE_ARTIFACT_RESPONSE)?;
+        assert_eq!(response.container_id, 11099678);
+        assert_eq!(response.size, -1);
+        assert_eq!(response.name, "SomeFile");
+        Ok(())
+    }
+
+    #[test]
+    fn tolerate_nulls_and_unknown_fields() -> Result {
+        let text = r#"{"containerId":1,"size":null,"signedContent":null,"fileContainerResourceUrl":"https://example.com/container","type":null,"name":"SomeFile","url":"https://example.com/artifact","expiresOn":null,"someFutureField":{"nested":true}}"#;
+        let response = serde_json::from_str::<CreateArtifactResponse>(text)?;
+        assert_eq!(response.size, -1);
+        assert_eq!(response.r#type, "");
+        assert_eq!(response.expires_on, "");
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_list_artifacts_response() -> Result {
+        let response = serde_json::from_str::<ListArtifactsResponse>(LIST_ARTIFACTS_RESPONSE)?;
+        assert_eq!(response.count, 1);
+        assert_eq!(response.value[0].size, 1042);
+
+        let empty = serde_json::from_str::<ListArtifactsResponse>(r#"{"count":0,"value":null}"#)?;
+        assert!(empty.value.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_container_items() -> Result {
+        let response = serde_json::from_str::<QueryArtifactResponse>(CONTAINER_ITEMS_RESPONSE)?;
+        assert_eq!(response.value.len(), 2);
+        let (folder, file) = (&response.value[0], &response.value[1]);
+        assert_eq!(folder.item_type, ItemType::Folder);
+        assert_eq!(folder.file_length, None);
+        assert_eq!(file.item_type, ItemType::File);
+        assert_eq!(file.relative_path(), PathBuf::from("Cargo.toml"));
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_enum_values() -> Result {
+        assert_eq!(serde_json::from_str::<ItemType>(r#""symlink""#)?, ItemType::Unknown);
+        assert_eq!(serde_json::from_str::<EntryStatus>(r#""deleted""#)?, EntryStatus::Unknown);
+        Ok(())
+    }
 }
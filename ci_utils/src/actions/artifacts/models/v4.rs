@@ -0,0 +1,144 @@
+//! Models of the Twirp-based artifact service (`github.actions.results.api.v1.ArtifactService`).
+//!
+//! This service is used by the `v4` generation of the artifact actions. Its JSON encoding follows
+//! the protobuf conventions, so fields are expected in `camelCase`, but the original `snake_case`
+//! names are accepted as well. Missing or `null` values deserialize as protobuf defaults.
+
+use crate::prelude::*;
+
+use crate::serde::null_as_default;
+use chrono::DateTime;
+use chrono::Utc;
+
+
+/// Identifiers of the workflow run and job that own the artifact.
+///
+/// These are not the same as the numeric run ids exposed through the REST API. They are obtained
+/// from the claims of the `ACTIONS_RUNTIME_TOKEN` token.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BackendIds {
+    pub workflow_run_backend_id:     String,
+    pub workflow_job_run_backend_id: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateArtifactRequest {
+    #[serde(flatten)]
+    pub backend_ids: BackendIds,
+    pub name:        String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at:  Option<DateTime<Utc>>,
+    pub version:     i32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateArtifactResponse {
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub ok:                bool,
+    #[serde(default, alias = "signed_upload_url")]
+    pub signed_upload_url: Option<Url>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FinalizeArtifactRequest {
+    #[serde(flatten)]
+    pub backend_ids: BackendIds,
+    pub name:        String,
+    /// Encoded as a string, as protobuf JSON mapping does for 64-bit integers.
+    pub size:        String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash:        Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FinalizeArtifactResponse {
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub ok:          bool,
+    #[serde(default, alias = "artifact_id", deserialize_with = "null_as_default")]
+    pub artifact_id: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListArtifactsResponse {
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub artifacts: Vec<Artifact>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Artifact {
+    #[serde(default, alias = "workflow_run_backend_id", deserialize_with = "null_as_default")]
+    pub workflow_run_backend_id: String,
+    #[serde(default, alias = "workflow_job_run_backend_id", deserialize_with = "null_as_default")]
+    pub workflow_job_run_backend_id: String,
+    #[serde(default, alias = "database_id", deserialize_with = "null_as_default")]
+    pub database_id: String,
+    pub name: String,
+    /// Encoded as a string, as protobuf JSON mapping does for 64-bit integers.
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub size: String,
+    #[serde(default, alias = "created_at")]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+impl Artifact {
+    /// Artifact size in bytes, if the service reported a valid one.
+    pub fn size(&self) -> Option<u64> {
+        self.size.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_create_response() -> Result {
+        let text = r#"{"ok":true,"signedUploadUrl":"https://productionresultssa0.blob.core.windows.net/actions-results/artifact.zip?sig=secret"}"#;
+        let response = serde_json::from_str::<CreateArtifactResponse>(text)?;
+        assert!(response.ok);
+        assert!(response.signed_upload_url.is_some());
+
+        let text = r#"{"ok":true,"signed_upload_url":"https://example.com/upload","extra":1}"#;
+        let response = serde_json::from_str::<CreateArtifactResponse>(text)?;
+        assert!(response.signed_upload_url.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_list_response() -> Result {
+        let text = r#"{"artifacts":[{"workflowRunBackendId":"a1b2","workflowJobRunBackendId":"c3d4","databaseId":"1234","name":"ide","size":"4096","createdAt":"2024-01-01T12:00:00Z"},{"name":"partial","size":null}]}"#;
+        let response = serde_json::from_str::<ListArtifactsResponse>(text)?;
+        assert_eq!(response.artifacts.len(), 2);
+        assert_eq!(response.artifacts[0].size(), Some(4096));
+        assert_eq!(response.artifacts[1].size(), None);
+
+        let empty = serde_json::from_str::<ListArtifactsResponse>("{}")?;
+        assert!(empty.artifacts.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn serialize_create_request() -> Result {
+        let request = CreateArtifactRequest {
+            backend_ids: BackendIds {
+                workflow_run_backend_id:     "run".into(),
+                workflow_job_run_backend_id: "job".into(),
+            },
+            name:        "ide".into(),
+            expires_at:  None,
+            version:     4,
+        };
+        let json = serde_json::to_value(&request)?;
+        assert_eq!(json["workflowRunBackendId"], "run");
+        assert_eq!(json["version"], 4);
+        assert!(json.get("expiresAt").is_none());
+        Ok(())
+    }
+}
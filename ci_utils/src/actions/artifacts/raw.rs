@@ -126,6 +126,16 @@ pub mod endpoints {
         Ok(response.json().await?)
     }
 
+    /// Delete the artifact (along with its file container) from the current run.
+    ///
+    /// The `artifact_url` is the [`url`](ArtifactResponse::url) of the artifact description.
+    #[context("Failed to delete the artifact at {artifact_url}.")]
+    pub async fn delete_artifact(json_client: &reqwest::Client, artifact_url: Url) -> Result {
+        let response = json_client.delete(artifact_url.clone()).send().await?;
+        check_response(response, |_, e| e).await?;
+        Ok(())
+    }
+
     pub async fn download_item(
         bin_client: &reqwest::Client,
         artifact_location: Url,
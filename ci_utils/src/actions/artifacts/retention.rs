@@ -0,0 +1,150 @@
+//! Keeping the important artifacts past their expiry.
+//!
+//! GitHub removes the artifacts once their retention period is over. A nightly workflow can use
+//! [`republish_expiring`] to copy the critical ones to a long-term storage, i.e. a GitHub release
+//! or an S3 bucket, shortly before they expire.
+
+use crate::prelude::*;
+
+use crate::actions::artifacts::gc::list_recent_artifacts;
+use crate::actions::artifacts::gc::RestArtifact;
+use crate::models::config::RepoContext;
+use chrono::DateTime;
+use chrono::Utc;
+use octocrab::models::ReleaseId;
+
+
+/// Default time before the expiry at which the artifacts are republished.
+pub fn default_margin() -> chrono::Duration {
+    chrono::Duration::days(2)
+}
+
+/// Parse the expiry time reported by the artifact service, like `2022-01-29T04:07:24.5807079Z`.
+pub fn parse_expiry(text: &str) -> Option<DateTime<Utc>> {
+    let time = DateTime::parse_from_rfc3339(text)
+        .inspect_err(|e| {
+            if !text.is_empty() {
+                warn!("Failed to parse the artifact expiry time `{text}`: {e}")
+            }
+        })
+        .ok()?;
+    Some(time.with_timezone(&Utc))
+}
+
+/// Whether the time of expiry is not farther than the margin from now.
+///
+/// Already expired artifacts and the ones with unknown expiry do not qualify.
+pub fn expires_within(
+    expires_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+    margin: chrono::Duration,
+) -> bool {
+    expires_at.map_or(false, |expires_at| now < expires_at && expires_at - now <= margin)
+}
+
+impl RestArtifact {
+    /// See [`expires_within`].
+    pub fn expires_within(&self, now: DateTime<Utc>, margin: chrono::Duration) -> bool {
+        !self.expired && expires_within(self.expires_at, now, margin)
+    }
+}
+
+/// Where the artifacts are kept after they expire.
+#[derive(Clone, Debug)]
+pub enum LongTermStorage {
+    /// Assets of the GitHub release.
+    Release { repo: RepoContext, release: ReleaseId, client: reqwest::Client },
+    #[cfg(feature = "aws")]
+    S3(crate::cloud::s3::Uploader),
+}
+
+impl LongTermStorage {
+    /// Store the file under its filename.
+    pub async fn store(&self, file: &Path) -> Result {
+        match self {
+            LongTermStorage::Release { repo, release, client } =>
+                crate::github::release::upload_asset(repo, client, *release, file).await,
+            #[cfg(feature = "aws")]
+            LongTermStorage::S3(uploader) => {
+                let filename = file.file_name().context("The file has no name.")?;
+                uploader.upload_file(file, &filename.to_string_lossy()).await?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Download the artifact as a zip archive, using the REST API.
+#[context("Failed to download the artifact {} (id {}) from {repo}.", artifact.name, artifact.id)]
+pub async fn download_artifact_zip(
+    octocrab: &Octocrab,
+    repo: &(impl RepoPointer + Sync),
+    artifact: &RestArtifact,
+    output: &Path,
+) -> Result {
+    let path =
+        format!("/repos/{}/{}/actions/artifacts/{}/zip", repo.owner(), repo.name(), artifact.id);
+    // The response redirects to the storage URL, which does not need the authorization.
+    let response = crate::github::Client::new(octocrab.clone()).get_response(path).await?;
+    crate::io::web::stream_response_to_file(response, output).await
+}
+
+/// Copy the recent artifacts with the given names to the long-term storage, if they expire within
+/// the margin.
+///
+/// Only the newest artifact with each name is considered. They are stored as
+/// `<name>-<artifact id>.zip`. If `dry_run` is set, the expiring artifacts are only reported.
+/// Returns the expiring artifacts.
+pub async fn republish_expiring(
+    octocrab: &Octocrab,
+    repo: &(impl RepoPointer + Sync),
+    names: &[String],
+    margin: chrono::Duration,
+    storage: &LongTermStorage,
+    dry_run: bool,
+) -> Result<Vec<RestArtifact>> {
+    let now = Utc::now();
+    let expiring = list_recent_artifacts(octocrab, repo, 10)
+        .await?
+        .into_iter()
+        .filter(|artifact| names.contains(&artifact.name))
+        // The list is sorted from the newest.
+        .unique_by(|artifact| artifact.name.clone())
+        .filter(|artifact| artifact.expires_within(now, margin))
+        .collect_vec();
+    info!("Found {} critical artifacts expiring within {margin} in {repo}.", expiring.len());
+    let temp = crate::fs::temp::dir("artifact-republish")?;
+    for artifact in &expiring {
+        info!("Republishing artifact {} expiring at {:?}.", artifact.name, artifact.expires_at);
+        if !dry_run {
+            let path = temp.path().join(format!("{}-{}.zip", artifact.name, artifact.id));
+            download_artifact_zip(octocrab, repo, artifact, &path).await?;
+            storage.store(&path).await?;
+            crate::fs::remove_file_if_exists(&path)?;
+        }
+    }
+    Ok(expiring)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::artifacts::models::CreateArtifactResponse;
+
+    #[test]
+    fn expiry() -> Result {
+        let text = r#"{"containerId":1,"size":-1,"fileContainerResourceUrl":"https://example.com/1","type":"actions_storage","name":"ide-linux","url":"https://example.com/a","expiresOn":"2022-01-29T04:07:24.5807079Z"}"#;
+        let response = serde_json::from_str::<CreateArtifactResponse>(text)?;
+        let expires_at = response.expires_at().context("Missing expiry.")?;
+        assert_eq!(expires_at.to_rfc3339(), "2022-01-29T04:07:24.580707900+00:00");
+        assert_eq!(parse_expiry(""), None);
+
+        let margin = default_margin();
+        let now = |text| DateTime::parse_from_rfc3339(text).map(|time| time.with_timezone(&Utc));
+        assert!(expires_within(Some(expires_at), now("2022-01-28T00:00:00Z")?, margin));
+        assert!(!expires_within(Some(expires_at), now("2022-01-20T00:00:00Z")?, margin));
+        assert!(!expires_within(Some(expires_at), now("2022-01-30T00:00:00Z")?, margin));
+        assert!(!expires_within(None, now("2022-01-28T00:00:00Z")?, margin));
+        Ok(())
+    }
+}
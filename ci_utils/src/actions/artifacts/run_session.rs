@@ -1,6 +1,7 @@
 use crate::prelude::*;
 
 use crate::actions::artifacts::context::Context;
+use crate::actions::artifacts::context::RunContext;
 use crate::actions::artifacts::models::ArtifactResponse;
 use crate::actions::artifacts::models::ContainerEntry;
 use crate::actions::artifacts::models::CreateArtifactResponse;
@@ -44,8 +45,13 @@ impl SessionClient {
         })
     }
 
+    /// Client of the artifact service for the given run.
+    pub fn new_for_run(run: RunContext) -> Result<Self> {
+        Self::new(&run.into())
+    }
+
     pub fn new_from_env() -> Result<Self> {
-        Self::new(&Context::new_from_env()?)
+        Self::new_for_run(RunContext::from_env()?)
     }
 
     pub async fn patch_artifact_size(
@@ -75,6 +81,10 @@ impl SessionClient {
         .value)
     }
 
+    pub async fn delete_artifact(&self, artifact: &ArtifactResponse) -> Result {
+        raw::endpoints::delete_artifact(&self.json_client, artifact.url.clone()).await
+    }
+
     pub async fn download_container_item(
         &self,
         content_location: Url,
@@ -2,20 +2,37 @@ use crate::actions::artifacts::models::PatchArtifactSizeResponse;
 use crate::prelude::*;
 use anyhow::Context;
 use reqwest::Client;
+use reqwest::ClientBuilder;
 use std::sync::atomic::Ordering;
+use std::time::Duration;
 
 use crate::actions::artifacts::raw;
 use crate::actions::artifacts::run_session::SessionClient;
 use crate::global;
 
 
+/// Largest chunk that is sent in a single request.
+///
+/// The whole chunk is kept in memory by each of the upload workers.
+pub const MAX_CHUNK_SIZE: usize = 64 * 1024 * 1024;
+
 #[derive(Clone, Debug)]
 pub struct UploadOptions {
     pub file_concurrency:  usize,
+    /// Size of the file parts sent in separate requests, at most [`MAX_CHUNK_SIZE`].
     pub chunk_size:        usize,
     // by default, file uploads will continue if there is an error unless specified differently in
     // the options
     pub continue_on_error: bool,
+    /// How long the idle connections are kept in the pool. If not set, the `reqwest` default is
+    /// used.
+    pub pool_idle_timeout: Option<Duration>,
+    /// Whether HTTP/2 may be used. Some proxies handle it poorly, so it can be disabled to force
+    /// HTTP/1.1.
+    pub http2:             bool,
+    /// Interval of the TCP keep-alive probes. Keeps the idle connections from being dropped by
+    /// the proxies.
+    pub tcp_keepalive:     Option<Duration>,
 }
 
 impl Default for UploadOptions {
@@ -24,10 +41,36 @@ impl Default for UploadOptions {
             chunk_size:        8 * 1024 * 1024,
             file_concurrency:  10,
             continue_on_error: true,
+            pool_idle_timeout: None,
+            http2:             true,
+            tcp_keepalive:     None,
         }
     }
 }
 
+impl UploadOptions {
+    pub fn validate(&self) -> Result {
+        ensure!(self.file_concurrency > 0, "File concurrency must be positive.");
+        ensure!(
+            (1..=MAX_CHUNK_SIZE).contains(&self.chunk_size),
+            "Chunk size must be between 1 and {MAX_CHUNK_SIZE} bytes, got {}.",
+            self.chunk_size
+        );
+        Ok(())
+    }
+
+    /// Apply the connection settings to the upload client.
+    pub fn tune_client(&self, mut builder: ClientBuilder) -> ClientBuilder {
+        if let Some(timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+        if !self.http2 {
+            builder = builder.http1_only();
+        }
+        builder.tcp_keepalive(self.tcp_keepalive)
+    }
+}
+
 #[derive(Debug)]
 pub struct ArtifactUploader {
     pub client:        SessionClient,
@@ -208,6 +251,23 @@ pub struct FileToUpload {
 }
 
 impl FileToUpload {
+    /// Upload the local file to the given path within the artifact.
+    ///
+    /// The remote path must be relative and stay within the artifact, like `bin/enso-win.exe`.
+    pub fn new(local_path: impl Into<PathBuf>, remote_path: impl Into<PathBuf>) -> Result<Self> {
+        let local_path = local_path.into();
+        let remote_path = remote_path.into();
+        let is_normal =
+            |component: std::path::Component| matches!(component, std::path::Component::Normal(_));
+        ensure!(
+            remote_path.components().next().is_some() && remote_path.components().all(is_normal),
+            "Invalid path {} within the artifact for {}.",
+            remote_path.display(),
+            local_path.display()
+        );
+        Ok(Self { local_path, remote_path })
+    }
+
     pub fn new_in_root(path: impl Into<PathBuf>) -> Result<Self> {
         let local_path = path.into();
         let remote_path = local_path.file_name().map(into).ok_or_else(|| {
@@ -249,6 +309,14 @@ mod tests {
     use crate::actions::artifacts::models::CreateArtifactResponse;
     use crate::log::setup_logging;
 
+    #[test]
+    fn validating_options() {
+        assert!(UploadOptions::default().validate().is_ok());
+        let too_large = UploadOptions { chunk_size: MAX_CHUNK_SIZE + 1, ..default() };
+        assert!(too_large.validate().is_err());
+        assert!(UploadOptions { file_concurrency: 0, ..default() }.validate().is_err());
+    }
+
     #[tokio::test]
     async fn test_upload() -> Result {
         use warp::Filter;
@@ -270,10 +338,9 @@ mod tests {
         tokio::spawn(warp::serve(routes).run(([127, 0, 0, 1], 8080)));
 
         debug!("Hello!");
-        std::env::set_var("ACTIONS_RUNTIME_URL", "http://localhost:8080");
-        std::env::set_var("ACTIONS_RUNTIME_TOKEN", "test-token");
-        std::env::set_var("GITHUB_RUN_ID", "123");
-        let result = artifacts::upload_single_file("file", "name").await;
+        let url = "http://localhost:8080".parse()?;
+        let context = artifacts::context::Context::new(url, "test-token", 123);
+        let result = artifacts::upload_single_file_with(&context, "file", "name").await;
         dbg!(result)?;
         Ok(())
     }
@@ -1,4 +1,143 @@
-#[allow(unused_imports)]
 use crate::prelude::*;
 
+use crate::actions::env;
+use crate::models::config::RepoContext;
+use octocrab::models::RunId;
+
+
 pub struct Inputs {}
+
+/// Information about the workflow run and the runner executing it.
+///
+/// Gathers the default environment variables set by GitHub Actions, so they are read and parsed
+/// in one place.
+///
+/// See: <https://docs.github.com/en/actions/learn-github-actions/environment-variables#default-environment-variables>
+#[derive(Clone, Debug)]
+pub struct RunnerContext {
+    /// The name of the event that triggered the workflow, like `push` or `pull_request`.
+    pub event_name:  String,
+    /// The file with the full event webhook payload.
+    pub event_path:  PathBuf,
+    pub repository:  RepoContext,
+    /// The fully-formed ref that triggered the workflow, like `refs/heads/develop`.
+    pub git_ref:     String,
+    pub sha:         String,
+    /// The source branch of the pull request. Set only for the pull request events.
+    pub head_ref:    Option<String>,
+    /// The target branch of the pull request. Set only for the pull request events.
+    pub base_ref:    Option<String>,
+    pub workflow:    String,
+    pub job:         String,
+    pub run_id:      RunId,
+    pub run_attempt: u64,
+    pub run_number:  u64,
+    pub runner_name: String,
+    pub runner_os:   OS,
+    pub runner_temp: PathBuf,
+    pub workspace:   PathBuf,
+}
+
+impl RunnerContext {
+    /// Read the context from the environment. Fails outside of GitHub Actions.
+    pub fn from_env() -> Result<Self> {
+        let non_empty = |value: Result<String>| value.ok().filter(|value| !value.is_empty());
+        Ok(Self {
+            event_name:  env::GITHUB_EVENT_NAME.get()?,
+            event_path:  env::GITHUB_EVENT_PATH.get()?,
+            repository:  env::GITHUB_REPOSITORY.get()?,
+            git_ref:     env::GITHUB_REF.get()?,
+            sha:         env::GITHUB_SHA.get()?,
+            head_ref:    non_empty(env::GITHUB_HEAD_REF.get()),
+            base_ref:    non_empty(env::GITHUB_BASE_REF.get()),
+            workflow:    env::GITHUB_WORKFLOW.get()?,
+            job:         env::GITHUB_JOB.get()?,
+            run_id:      env::GITHUB_RUN_ID.get()?,
+            run_attempt: env::GITHUB_RUN_ATTEMPT.get()?,
+            run_number:  env::GITHUB_RUN_NUMBER.get()?,
+            runner_name: env::RunnerName.fetch()?,
+            runner_os:   parse_runner_os(&env::RUNNER_OS.get()?)?,
+            runner_temp: env::RUNNER_TEMP.get()?,
+            workspace:   env::GITHUB_WORKSPACE.get()?,
+        })
+    }
+
+    /// Whether the job runs on a self-hosted runner, rather than on a GitHub-hosted one.
+    pub fn is_self_hosted(&self) -> bool {
+        !self.runner_name.starts_with("GitHub Actions")
+    }
+
+    pub fn is_pull_request(&self) -> bool {
+        matches!(self.event_name.as_str(), "pull_request" | "pull_request_target")
+    }
+
+    /// The number of the pull request that triggered the workflow, parsed from the
+    /// `refs/pull/<number>/merge` ref.
+    pub fn pull_request_number(&self) -> Option<u64> {
+        let rest = self.git_ref.strip_prefix("refs/pull/")?;
+        rest.split('/').next()?.parse().ok()
+    }
+
+    /// The branch that triggered the workflow, if it was triggered by a branch.
+    pub fn branch(&self) -> Option<&str> {
+        self.git_ref.strip_prefix("refs/heads/")
+    }
+
+    /// The tag that triggered the workflow, if it was triggered by a tag.
+    pub fn tag(&self) -> Option<&str> {
+        self.git_ref.strip_prefix("refs/tags/")
+    }
+
+    /// The URL of the workflow run page.
+    pub fn run_url(&self) -> Result<Url> {
+        let url = format!("https://github.com/{}/actions/runs/{}", self.repository, self.run_id);
+        Ok(Url::parse(&url)?)
+    }
+}
+
+/// Parse the value of `RUNNER_OS`.
+pub fn parse_runner_os(value: &str) -> Result<OS> {
+    match value {
+        "Linux" => Ok(OS::Linux),
+        "Windows" => Ok(OS::Windows),
+        "macOS" => Ok(OS::MacOS),
+        other => bail!("Unknown runner operating system: {other}."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn context_accessors() -> Result {
+        let context = RunnerContext {
+            event_name:  "pull_request".into(),
+            event_path:  "/home/runner/work/_temp/_github_workflow/event.json".into(),
+            repository:  "enso-org/enso".parse()?,
+            git_ref:     "refs/pull/3500/merge".into(),
+            sha:         "ffac537e6cbbf934b08745a378932722df287a53".into(),
+            head_ref:    Some("wip/feature".into()),
+            base_ref:    Some("develop".into()),
+            workflow:    "GUI CI".into(),
+            job:         "build".into(),
+            run_id:      RunId(1658821493),
+            run_attempt: 1,
+            run_number:  42,
+            runner_name: "GitHub Actions 2".into(),
+            runner_os:   parse_runner_os("macOS")?,
+            runner_temp: "/home/runner/work/_temp".into(),
+            workspace:   "/home/runner/work/enso/enso".into(),
+        };
+        assert!(context.is_pull_request());
+        assert_eq!(context.pull_request_number(), Some(3500));
+        assert!(!context.is_self_hosted());
+        assert_eq!(context.runner_os, OS::MacOS);
+        assert_eq!((context.branch(), context.tag()), (None, None));
+        assert_eq!(
+            context.run_url()?.as_str(),
+            "https://github.com/enso-org/enso/actions/runs/1658821493"
+        );
+        Ok(())
+    }
+}
@@ -36,6 +36,36 @@ pub fn is_self_hosted() -> Result<bool> {
     Ok(!name.starts_with("GitHub Actions"))
 }
 
+crate::define_env_var! {
+    /// The name of the event that triggered the workflow. For example, `workflow_dispatch`.
+    GITHUB_EVENT_NAME, String
+}
+crate::define_env_var! {
+    /// The path to the file on the runner that contains the full event webhook payload.
+    GITHUB_EVENT_PATH, PathBuf
+}
+crate::define_env_var! {
+    /// The fully-formed ref of the branch or tag that triggered the workflow run. For example,
+    /// `refs/heads/develop` or `refs/pull/123/merge`.
+    GITHUB_REF, String
+}
+crate::define_env_var! {
+    /// The head ref or source branch of the pull request. Empty for other events.
+    GITHUB_HEAD_REF, String
+}
+crate::define_env_var! {
+    /// The name of the base ref or target branch of the pull request. Empty for other events.
+    GITHUB_BASE_REF, String
+}
+crate::define_env_var! {
+    /// The name of the workflow. If the workflow file doesn't specify a name, the full path of
+    /// the workflow file.
+    GITHUB_WORKFLOW, String
+}
+crate::define_env_var! {
+    /// The `job_id` of the current job. For example, `build`.
+    GITHUB_JOB, String
+}
 crate::define_env_var! {
     /// The owner and repository name. For example, `octocat/Hello-World`.
     GITHUB_REPOSITORY, RepoContext
@@ -45,7 +75,61 @@ crate::define_env_var! {
     /// `"ffac537e6cbbf934b08745a378932722df287a53"`.
     GITHUB_SHA, String
 }
+crate::define_env_var! {
+    /// The default working directory on the runner for steps, and the default location of the
+    /// repository when using the `checkout` action.
+    GITHUB_WORKSPACE, PathBuf
+}
+crate::define_env_var! {
+    /// Path to the file that sets the outputs of the current step.
+    GITHUB_OUTPUT, PathBuf
+}
+crate::define_env_var! {
+    /// Path to the file that prepends directories to `PATH` for the subsequent steps.
+    GITHUB_PATH, PathBuf
+}
+crate::define_env_var! {
+    /// Path to the file with the Markdown summary of the current step. Its contents are shown on
+    /// the summary page of the workflow run.
+    GITHUB_STEP_SUMMARY, PathBuf
+}
 crate::define_env_var! {
     /// A unique number for each workflow run within a repository. This number does not change if you re-run the workflow run. For example, `1658821493`.
     GITHUB_RUN_ID, octocrab::models::RunId
 }
+crate::define_env_var! {
+    /// A unique number for each attempt of a particular workflow run. It begins with 1 for the
+    /// first attempt and increments with each re-run.
+    GITHUB_RUN_ATTEMPT, u64
+}
+crate::define_env_var! {
+    /// A unique number for each run of a particular workflow, beginning with 1.
+    GITHUB_RUN_NUMBER, u64
+}
+crate::define_env_var! {
+    /// The operating system of the runner executing the job: `Linux`, `Windows`, or `macOS`.
+    RUNNER_OS, String
+}
+crate::define_env_var! {
+    /// The path to a temporary directory on the runner. It is emptied at the beginning and end
+    /// of each job.
+    RUNNER_TEMP, PathBuf
+}
+crate::define_env_var! {
+    /// The URL for requesting the OIDC token. Set only if the workflow has the `id-token: write`
+    /// permission.
+    ACTIONS_ID_TOKEN_REQUEST_URL, Url, optional
+}
+crate::define_env_var! {
+    /// The bearer token for the request to `ACTIONS_ID_TOKEN_REQUEST_URL`.
+    ACTIONS_ID_TOKEN_REQUEST_TOKEN, String, optional
+}
+crate::define_env_var! {
+    /// The URL of the Actions service, used e.g. by the artifacts API. Set only for the actions,
+    /// not for the `run` steps.
+    ACTIONS_RUNTIME_URL, Url
+}
+crate::define_env_var! {
+    /// The bearer token for the Actions service.
+    ACTIONS_RUNTIME_TOKEN, String
+}
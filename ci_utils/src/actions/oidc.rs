@@ -0,0 +1,96 @@
+//! OpenID Connect tokens issued by GitHub Actions.
+//!
+//! The cloud providers can be configured to trust these tokens, so the publishing jobs can
+//! authenticate without any long-lived secrets. The workflow needs the `id-token: write`
+//! permission for the token to be available.
+//!
+//! See: <https://docs.github.com/en/actions/deployment/security-hardening-your-deployments/about-security-hardening-with-openid-connect>
+
+use crate::prelude::*;
+
+use crate::actions::env;
+use crate::actions::workflow;
+use crate::io::web::handle_error_response;
+
+
+/// JSON Web Token identifying the workflow run.
+///
+/// The token is never printed by `Debug` and is masked in the workflow log as soon as it is
+/// fetched.
+#[derive(Clone, Derivative)]
+#[derivative(Debug)]
+pub struct IdToken {
+    pub audience: Option<String>,
+    #[derivative(Debug = "ignore")]
+    value:        String,
+}
+
+impl IdToken {
+    pub fn new(value: impl Into<String>, audience: Option<String>) -> Self {
+        Self { audience, value: value.into() }
+    }
+
+    /// The encoded token. Take care not to log it.
+    pub fn as_str(&self) -> &str {
+        &self.value
+    }
+
+    /// Make sure that the token is masked in the workflow log.
+    pub fn mask(&self) {
+        workflow::mask_text(&self.value);
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct TokenResponse {
+    value: String,
+}
+
+/// URL of the token request for the given audience.
+pub fn request_url(base: &Url, audience: Option<&str>) -> Url {
+    let mut url = base.clone();
+    if let Some(audience) = audience {
+        url.query_pairs_mut().append_pair("audience", audience);
+    }
+    url
+}
+
+/// Fetch the ID token for the given audience, like `sts.amazonaws.com`.
+///
+/// Without the audience, the token is issued for the URL of the repository owner.
+#[context(
+    "Failed to fetch the OIDC token. Does the workflow have the `id-token: write` permission?"
+)]
+pub async fn fetch_id_token(audience: Option<&str>) -> Result<IdToken> {
+    let base = env::ACTIONS_ID_TOKEN_REQUEST_URL.get()?;
+    let request_token = env::ACTIONS_ID_TOKEN_REQUEST_TOKEN.get()?;
+    let response = crate::net::client()?
+        .get(request_url(&base, audience))
+        .bearer_auth(request_token)
+        .header(reqwest::header::ACCEPT, mime::APPLICATION_JSON.as_ref())
+        .send()
+        .await?;
+    let response: TokenResponse = handle_error_response(response).await?.json().await?;
+    let token = IdToken::new(response.value, audience.map(Into::into));
+    token.mask();
+    Ok(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_requests() -> Result {
+        let base = Url::parse(
+            "https://pipelines.actions.githubusercontent.com/abc/idtoken?api-version=2.0",
+        )?;
+        let url = request_url(&base, Some("sts.amazonaws.com"));
+        assert_eq!(url.query(), Some("api-version=2.0&audience=sts.amazonaws.com"));
+        assert_eq!(request_url(&base, None), base);
+
+        let token = IdToken::new("secret.jwt.value", Some("sts.amazonaws.com".into()));
+        assert!(!format!("{token:?}").contains("secret"));
+        Ok(())
+    }
+}
@@ -0,0 +1,147 @@
+//! Publishing Markdown content to the summary of the workflow run.
+//!
+//! See: <https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions#adding-a-job-summary>
+
+use crate::prelude::*;
+
+use crate::actions::env;
+use std::io::Write;
+use std::lazy::SyncLazy;
+use std::sync::Mutex;
+
+
+/// Serializes the appends from this process, so the concurrent tasks don't interleave them.
+static SUMMARY_LOCK: SyncLazy<Mutex<()>> = SyncLazy::new(default);
+
+/// Builder of the Markdown document to be [appended](append) to the step summary.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Markdown {
+    text: String,
+}
+
+impl Markdown {
+    pub fn new() -> Self {
+        default()
+    }
+
+    /// Header of the given level, from 1 to 6.
+    pub fn header(self, level: usize, text: impl AsRef<str>) -> Self {
+        let level = level.clamp(1, 6);
+        self.block(format!("{} {}", "#".repeat(level), text.as_ref()))
+    }
+
+    pub fn paragraph(self, text: impl AsRef<str>) -> Self {
+        self.block(text.as_ref())
+    }
+
+    /// Bulleted list with an item per line.
+    pub fn list(self, items: impl IntoIterator<Item: Display>) -> Self {
+        let items = items.into_iter().map(|item| format!("* {item}")).join("\n");
+        self.block(items)
+    }
+
+    pub fn code_block(self, language: &str, code: impl AsRef<str>) -> Self {
+        self.block(format!("```{language}\n{}\n```", code.as_ref().trim_end()))
+    }
+
+    /// Table with the given header row. The cells are escaped, so they can contain pipes and
+    /// line breaks.
+    pub fn table(
+        self,
+        headers: impl IntoIterator<Item: Display>,
+        rows: impl IntoIterator<Item: IntoIterator<Item: Display>>,
+    ) -> Self {
+        let row = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+        let headers = headers.into_iter().map(|cell| escape_cell(&cell.to_string())).collect_vec();
+        let separator = row(headers.iter().map(|_| "---".to_string()).collect());
+        let mut lines = vec![row(headers), separator];
+        for cells in rows {
+            lines.push(row(cells.into_iter().map(|cell| escape_cell(&cell.to_string())).collect()));
+        }
+        self.block(lines.join("\n"))
+    }
+
+    /// Collapsible section, initially collapsed.
+    pub fn details(self, summary: impl AsRef<str>, content: Markdown) -> Self {
+        let summary = summary.as_ref();
+        self.block(format!("<details><summary>{summary}</summary>\n\n{content}</details>"))
+    }
+
+    /// Append raw Markdown text as a separate block.
+    pub fn block(mut self, text: impl AsRef<str>) -> Self {
+        self.text.push_str(text.as_ref());
+        self.text.push_str("\n\n");
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+}
+
+impl Display for Markdown {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// Escape the text, so it can be placed in a single table cell.
+pub fn escape_cell(text: &str) -> String {
+    text.replace('|', r"\|").replace("\r\n", "<br>").replace('\n', "<br>")
+}
+
+/// Append the content to the summary of the current step.
+///
+/// The content is written with a single append, so the summaries written by the concurrently
+/// running processes are not interleaved. Outside of GitHub Actions the content is just logged.
+pub fn append(content: &Markdown) -> Result {
+    match env::GITHUB_STEP_SUMMARY.get() {
+        Ok(path) => append_to_file(&path, content),
+        Err(_) => {
+            info!("Step summary:\n{content}");
+            Ok(())
+        }
+    }
+}
+
+/// Append the content to the given summary file with a single write.
+#[context("Failed to write the step summary to {}.", path.display())]
+pub fn append_to_file(path: &Path, content: &Markdown) -> Result {
+    let _guard = SUMMARY_LOCK.lock().unwrap();
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(content.as_str().as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_markdown() {
+        let sizes = Markdown::new().table(["Asset", "Size"], [["enso.zip", "12 MB"]]);
+        let markdown = Markdown::new()
+            .header(2, "Build")
+            .table(["Test", "Result"], [["a|b", "line\nbreak"]])
+            .details("Sizes", sizes);
+        let expected = "## Build\n\n\
+            | Test | Result |\n| --- | --- |\n| a\\|b | line<br>break |\n\n\
+            <details><summary>Sizes</summary>\n\n\
+            | Asset | Size |\n| --- | --- |\n| enso.zip | 12 MB |\n\n</details>\n\n";
+        assert_eq!(markdown.as_str(), expected);
+    }
+
+    #[test]
+    fn appending() -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("summary.md");
+        append_to_file(&path, &Markdown::new().paragraph("first"))?;
+        append_to_file(&path, &Markdown::new().paragraph("second"))?;
+        assert_eq!(crate::fs::read_to_string(&path)?, "first\n\nsecond\n\n");
+        Ok(())
+    }
+}
@@ -2,6 +2,8 @@ use crate::prelude::*;
 
 use crate::actions::env;
 use std::io::Write;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 
 pub mod definition;
 
@@ -11,13 +13,52 @@ pub fn is_in_env() -> bool {
     env::Actions.fetch().contains(&true)
 }
 
+/// Format the `name=value` entry of an environment or output file.
+///
+/// Multiline values are written as heredocs, with a random delimiter that cannot be injected by
+/// the value.
+///
+/// See: <https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions#multiline-strings>
+pub fn file_command_entry(name: &str, value: &str) -> Result<String> {
+    ensure!(
+        !name.contains(['=', '\n', '\r']),
+        "Invalid name `{name}`: it cannot contain `=` or line breaks."
+    );
+    if value.contains(['\n', '\r']) {
+        let delimiter = format!("ghadelimiter_{}", uuid::Uuid::new_v4());
+        ensure!(!value.contains(&delimiter), "The value of {name} contains its delimiter.");
+        Ok(format!("{name}<<{delimiter}\n{value}\n{delimiter}\n"))
+    } else {
+        Ok(format!("{name}={value}\n"))
+    }
+}
+
+/// Append the text to a file command (like `GITHUB_ENV`) with a single write.
+#[context("Failed to append to the file command {}.", path.display())]
+pub fn append_to_file_command(path: &Path, text: &str) -> Result {
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(text.as_bytes())?;
+    Ok(())
+}
+
 /// Sets an action's output parameter.
 ///
-/// See: <https://docs.github.com/en/actions/learn-github-actions/workflow-commands-for-github-actions#setting-an-output-parameter>
-pub fn set_output(name: &str, value: &impl ToString) {
+/// The output is written to the `GITHUB_OUTPUT` file, so it can span multiple lines and is never
+/// echoed to the log. Just logs when used under non-GH CI. The deprecated `::set-output` command
+/// is used only on the runners that do not provide the file.
+///
+/// See: <https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions#setting-an-output-parameter>
+pub fn set_output(name: &str, value: &impl ToString) -> Result {
     let value = value.to_string();
-    debug!("Setting GitHub Actions step output {name} to {value}");
-    println!("::set-output name={name}::{value}");
+    debug!("Setting GitHub Actions step output {name}.");
+    if is_in_env() {
+        match env::GITHUB_OUTPUT.get() {
+            Ok(output_file) =>
+                append_to_file_command(&output_file, &file_command_entry(name, &value)?)?,
+            Err(_) => println!("::set-output name={name}::{}", escape_data(&value)),
+        }
+    }
+    Ok(())
 }
 
 /// Prints a debug message to the log.
@@ -35,36 +76,99 @@ pub fn debug(message: &str) {
 /// This step and all subsequent steps in a job will have access to the variable. Environment
 /// variables are case-sensitive and you can include punctuation.
 ///
-/// Just logs and sets variable locally if used under non-GH CI.
+/// Just logs and sets variable locally if used under non-GH CI. The value can span multiple lines.
 pub fn set_env(name: &str, value: &impl ToString) -> Result {
     let value_string = value.to_string();
-    debug!("Will try writing Github Actions environment variable: {name}={value_string}");
-    std::env::set_var(name, value.to_string());
+    debug!("Will try writing Github Actions environment variable: {name}.");
+    std::env::set_var(name, &value_string);
     if is_in_env() {
         let env_file = env::EnvFile.fetch()?;
-        let mut file = std::fs::OpenOptions::new().create_new(false).append(true).open(env_file)?;
-        writeln!(file, "{name}={value_string}")?;
+        append_to_file_command(&env_file, &file_command_entry(name, &value_string)?)?;
     }
     Ok(())
 }
 
-pub fn mask_text(text: impl AsRef<str>) {
+/// Prepends the directory to `PATH`, both for this process and for the subsequent steps in the
+/// job.
+///
+/// See: <https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions#adding-a-system-path>
+pub fn add_path(directory: impl AsRef<Path>) -> Result {
+    let directory = directory.as_ref();
+    crate::env::prepend_to_path(directory)?;
     if is_in_env() {
-        println!("::add-mask::{}", text.as_ref())
+        let path_file = env::GITHUB_PATH.get()?;
+        let line = directory.to_str().context("The path is not a valid string.")?;
+        ensure!(!line.contains(['\n', '\r']), "The path cannot contain line breaks.");
+        append_to_file_command(&path_file, &format!("{line}\n"))?;
     }
+    Ok(())
 }
 
-pub fn mask_value(value: impl Display) {
+/// Make sure that the text is masked with asterisks in the log.
+///
+/// Each line is masked separately, as the runner matches the masks against the individual log
+/// lines. The text should be masked before it is used in any way that might print it.
+pub fn mask_text(text: impl AsRef<str>) {
     if is_in_env() {
-        println!("::add-mask::{value}")
+        for line in mask_lines(text.as_ref()) {
+            println!("::add-mask::{}", escape_data(line))
+        }
     }
 }
 
+pub fn mask_value(value: impl Display) {
+    mask_text(value.to_string())
+}
+
+/// The lines of the secret that need to be masked.
+fn mask_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.lines().map(str::trim).filter(|line| !line.is_empty())
+}
+
 pub fn mask_environment_variable(variable_name: impl AsRef<OsStr>) -> Result {
     mask_value(std::env::var(variable_name)?);
     Ok(())
 }
 
+/// Number of the currently open [`LogGroup`]s.
+static OPEN_GROUPS: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether any [`LogGroup`] is currently open.
+pub fn is_group_open() -> bool {
+    OPEN_GROUPS.load(Ordering::SeqCst) > 0
+}
+
+/// Collapsible group of log lines, ended when the guard is dropped.
+///
+/// Outside of GitHub Actions this does nothing.
+///
+/// See: <https://docs.github.com/en/actions/learn-github-actions/workflow-commands-for-github-actions#grouping-log-lines>
+#[derive(Debug)]
+#[must_use = "The group ends when the guard is dropped."]
+pub struct LogGroup {
+    active: bool,
+}
+
+impl LogGroup {
+    pub fn new(title: impl AsRef<str>) -> Self {
+        let active = is_in_env();
+        if active {
+            OPEN_GROUPS.fetch_add(1, Ordering::SeqCst);
+            println!("::group::{}", escape_data(title.as_ref()));
+        }
+        Self { active }
+    }
+}
+
+impl Drop for LogGroup {
+    fn drop(&mut self) {
+        if self.active {
+            println!("::endgroup::");
+            OPEN_GROUPS.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, strum::Display)]
 #[strum(serialize_all = "snake_case")]
 pub enum MessageLevel {
@@ -74,22 +178,99 @@ pub enum MessageLevel {
     Error,
 }
 
+/// Place in the source code that an annotation refers to.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Location {
+    /// Path relative to the repository root.
+    pub file:       PathBuf,
+    pub line:       Option<usize>,
+    pub end_line:   Option<usize>,
+    pub column:     Option<usize>,
+    pub end_column: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
 pub struct Message {
-    pub level: MessageLevel,
-    pub text:  String,
-    // TODO title, line, column
+    pub level:    MessageLevel,
+    pub text:     String,
+    pub title:    Option<String>,
+    /// If set, the message is displayed as an annotation of the given source file.
+    pub location: Option<Location>,
 }
 
 impl Message {
+    pub fn new(level: MessageLevel, text: impl AsRef<str>) -> Self {
+        Message { level, text: text.as_ref().into(), title: None, location: None }
+    }
+
     pub fn notice(text: impl AsRef<str>) {
-        Message { level: MessageLevel::Notice, text: text.as_ref().into() }.send()
+        Message::new(MessageLevel::Notice, text).send()
+    }
+
+    /// Parameters of the workflow command, like `file=src/lib.rs,line=10`.
+    pub fn parameters(&self) -> String {
+        let mut ret = Vec::new();
+        if let Some(title) = &self.title {
+            ret.push(format!("title={}", escape_property(title)));
+        }
+        if let Some(location) = &self.location {
+            let file = location.file.to_string_lossy().replace('\\', "/");
+            ret.push(format!("file={}", escape_property(&file)));
+            let numbers = [
+                ("line", location.line),
+                ("endLine", location.end_line),
+                ("col", location.column),
+                ("endColumn", location.end_column),
+            ];
+            for (name, value) in numbers {
+                if let Some(value) = value {
+                    ret.push(format!("{name}={value}"));
+                }
+            }
+        }
+        ret.join(",")
     }
 
     pub fn send(&self) {
-        println!("::{} ::{}", self.level, self.text);
+        println!("::{} {}::{}", self.level, self.parameters(), escape_data(&self.text));
     }
 }
 
+/// Escape the message text, so it can span multiple lines.
+///
+/// See: <https://github.com/actions/toolkit/blob/main/packages/core/src/command.ts>
+pub fn escape_data(text: &str) -> String {
+    text.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+/// Escape the value of a workflow command parameter.
+pub fn escape_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
 pub fn message(level: MessageLevel, text: impl AsRef<str>) {
-    Message { level, text: text.as_ref().into() }.send()
+    Message::new(level, text).send()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_command_entries() -> Result {
+        assert_eq!(file_command_entry("VERSION", "2022.1.1")?, "VERSION=2022.1.1\n");
+        let entry = file_command_entry("NOTES", "first\nsecond")?;
+        let (header, rest) = entry.split_once('\n').unwrap();
+        let delimiter = header.strip_prefix("NOTES<<").unwrap();
+        assert!(delimiter.starts_with("ghadelimiter_"));
+        assert_eq!(rest, format!("first\nsecond\n{delimiter}\n"));
+        assert!(file_command_entry("A=B", "value").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn masking_multiline_secrets() {
+        let lines = mask_lines("-----BEGIN KEY-----\r\nabc\n\n-----END KEY-----\n").collect_vec();
+        assert_eq!(lines, ["-----BEGIN KEY-----", "abc", "-----END KEY-----"]);
+    }
 }
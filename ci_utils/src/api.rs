@@ -0,0 +1,195 @@
+//! Stable facade for using this crate as a library in other automation tools.
+//!
+//! Most of this crate evolves together with the Enso build script and can change at any time.
+//! The items re-exported here are the ones we commit to keep stable:
+//! * [`storage`] — the local cache of downloads and other generated artifacts;
+//! * [`program`] — lookup and invocation of external programs;
+//! * [`archive`] — creating, extracting and comparing archives;
+//! * [`github`] — common GitHub API operations.
+//!
+//! Functions of this module return the [`Error`] type rather than [`anyhow::Error`], so callers
+//! can match on the failing subsystem. The cache and the GitHub client are taken as arguments, and
+//! no progress bars are drawn.
+//!
+//! The [`program`] functions are however affected by the process-wide settings of the
+//! [command audit and dry-run mode](crate::program::audit), the
+//! [environment overlays](crate::env::overlay) of the current task and the cached
+//! [program lookups](crate::program::Program::lookup).
+
+use crate::prelude::*;
+
+use snafu::Snafu;
+
+
+/// Underlying cause of an [`Error`].
+pub type Source = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// Error returned by the functions of the library facade.
+///
+/// The variants denote the subsystem that failed. The underlying error, with the full chain of
+/// causes, is available through [`std::error::Error::source`].
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Cache operation failed."))]
+    Storage { source: Source },
+    #[snafu(display("External program failed."))]
+    Program { source: Source },
+    #[snafu(display("Archive operation failed."))]
+    Archive { source: Source },
+    #[snafu(display("GitHub operation failed."))]
+    GitHub { source: Source },
+}
+
+impl Error {
+    fn storage(source: anyhow::Error) -> Self {
+        Self::Storage { source: source.into() }
+    }
+
+    fn program(source: anyhow::Error) -> Self {
+        Self::Program { source: source.into() }
+    }
+
+    fn archive(source: anyhow::Error) -> Self {
+        Self::Archive { source: source.into() }
+    }
+
+    fn github(source: anyhow::Error) -> Self {
+        Self::GitHub { source: source.into() }
+    }
+}
+
+/// Result type of the library facade.
+pub type Result<T = ()> = std::result::Result<T, Error>;
+
+pub mod storage {
+    //! Local cache of the artifacts that are expensive to obtain.
+
+    use super::*;
+
+    pub use crate::cache::default_path;
+    pub use crate::cache::download::DownloadFile;
+    pub use crate::cache::Cache;
+    pub use crate::cache::Storable;
+
+    /// Open the cache in the given directory, creating it if needed.
+    pub async fn open(root: impl Into<PathBuf>) -> Result<Cache> {
+        Cache::new(root).await.map_err(Error::storage)
+    }
+
+    /// Get the value from the cache, generating it if it is not present.
+    pub async fn get<S: Storable>(cache: &Cache, storable: S) -> Result<S::Output> {
+        cache.get(storable).await.map_err(Error::storage)
+    }
+}
+
+pub mod program {
+    //! Lookup and invocation of external programs.
+
+    use super::*;
+
+    pub use crate::program::Command;
+    pub use crate::program::Program;
+    pub use crate::program::ProgramExt;
+    pub use crate::programs::Git;
+    pub use crate::programs::SevenZip;
+    pub use crate::programs::Tar;
+
+    /// Run the command and wait for it to succeed.
+    ///
+    /// In the [dry-run mode](crate::program::audit::set_dry_run) the command is not executed.
+    pub async fn run(mut command: Command) -> Result {
+        command.run_ok().await.map_err(Error::program)
+    }
+
+    /// Run the command and return its standard output.
+    pub async fn run_stdout(mut command: Command) -> Result<String> {
+        command.run_stdout().await.map_err(Error::program)
+    }
+
+    /// Locate the program executable.
+    pub fn lookup<P: Program>(program: &P) -> Result<PathBuf> {
+        program.lookup().map(|location| location.executable_path).map_err(Error::program)
+    }
+}
+
+pub mod archive {
+    //! Creating, extracting and comparing archives.
+
+    use super::*;
+
+    pub use crate::archive::ArchiveDiff;
+    pub use crate::archive::CompressionOptions;
+    pub use crate::archive::Format;
+
+    /// Create an archive with the given paths. The format is deduced from the archive's name.
+    pub async fn create(
+        output_archive: impl AsRef<Path>,
+        paths_to_pack: impl IntoIterator<Item: AsRef<Path>>,
+        options: CompressionOptions,
+    ) -> Result {
+        crate::archive::create(output_archive, paths_to_pack, options).await.map_err(Error::archive)
+    }
+
+    /// Extract the whole archive into the output directory.
+    pub async fn extract(archive: impl AsRef<Path>, output_directory: impl AsRef<Path>) -> Result {
+        crate::archive::extract_to(archive, output_directory).await.map_err(Error::archive)
+    }
+
+    /// Compare the entries of two archives.
+    pub async fn diff(old: impl AsRef<Path>, new: impl AsRef<Path>) -> Result<ArchiveDiff> {
+        crate::archive::diff(old, new).await.map_err(Error::archive)
+    }
+}
+
+pub mod github {
+    //! Common GitHub API operations.
+
+    use super::*;
+
+    pub use crate::github::permissions::Requirement;
+    pub use crate::github::permissions::TokenPermissions;
+    pub use crate::github::RepoPointer;
+    pub use crate::models::config::RepoContext;
+    pub use octocrab::models::repos::Release;
+    pub use octocrab::Octocrab;
+
+    /// Get the latest (non-draft, non-prerelease) release of the repository.
+    pub async fn latest_release(
+        octocrab: &Octocrab,
+        repo: &(impl RepoPointer + Sync),
+    ) -> Result<Release> {
+        repo.latest_release(octocrab).await.map_err(Error::github)
+    }
+
+    /// Get all the releases of the repository.
+    pub async fn all_releases(
+        octocrab: &Octocrab,
+        repo: &(impl RepoPointer + Sync),
+    ) -> Result<Vec<Release>> {
+        repo.all_releases(octocrab).await.map_err(Error::github)
+    }
+
+    /// Fail if the token does not have all the required permissions.
+    pub async fn check_permissions(token: &str, requirements: &[Requirement]) -> Result {
+        let check = async {
+            let permissions = TokenPermissions::query(token).await?;
+            permissions.check(requirements)
+        };
+        check.await.map_err(Error::github)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_sources() {
+        use std::error::Error as _;
+        let error = Error::archive(anyhow!("Disk full.").context("Writing entry."));
+        assert_eq!(error.to_string(), "Archive operation failed.");
+        let source = error.source().unwrap();
+        assert_eq!(source.to_string(), "Writing entry.");
+        assert_eq!(source.source().unwrap().to_string(), "Disk full.");
+    }
+}
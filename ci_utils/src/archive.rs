@@ -6,11 +6,78 @@ use crate::programs::tar::Compression;
 use crate::programs::tar::Tar;
 use crate::programs::SevenZip;
 
+use tokio::io::AsyncRead;
 use tracing::Span;
 
+pub mod diff;
 pub mod tar;
 pub mod zip;
 
+pub use diff::diff;
+pub use diff::ArchiveDiff;
+pub use diff::EntryInfo;
+
+/// Tuning of the compression when creating archives.
+///
+/// Unset values leave the defaults of the underlying tool.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CompressionOptions {
+    /// Compression level, from 0 (fastest) to 9 (smallest archive).
+    pub level:   Option<u32>,
+    /// Number of threads to use, if the compression method supports multithreading. Zero means
+    /// as many threads as there are CPU cores.
+    pub threads: Option<usize>,
+    /// Use the slower preset of the compressor that squeezes out a bit more, if it has one (like
+    /// `xz -e`).
+    pub extreme: bool,
+}
+
+/// Number of threads used by [`CompressionOptions::best`].
+///
+/// Each thread of `xz -9` needs about 700 MiB of memory, so using all the cores of a big runner
+/// could exhaust its memory.
+pub const BEST_COMPRESSION_THREADS: usize = 4;
+
+impl CompressionOptions {
+    /// Fast compression using all the cores, e.g. for artifacts passed between CI jobs.
+    pub const fn fast() -> Self {
+        Self { level: Some(1), threads: Some(0), extreme: false }
+    }
+
+    /// The best compression, e.g. for release packages.
+    ///
+    /// The number of threads is bounded by [`BEST_COMPRESSION_THREADS`].
+    pub const fn best() -> Self {
+        Self { level: Some(9), threads: Some(BEST_COMPRESSION_THREADS), extreme: true }
+    }
+
+    pub fn validate(&self) -> Result {
+        if let Some(level) = self.level {
+            ensure!(level <= 9, "Compression level must be between 0 and 9, got {level}.");
+        }
+        Ok(())
+    }
+
+    /// The number of threads, with zero resolved to the number of available cores.
+    pub fn resolved_threads(&self) -> Option<usize> {
+        self.threads.map(|threads| match threads {
+            0 => std::thread::available_parallelism().map_or(1, |count| count.get()),
+            threads => threads,
+        })
+    }
+}
+
+/// Options of the archive extraction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ExtractOptions {
+    /// Restore the owners of the files recorded in the tarball, instead of making the extracting
+    /// user own them. Off by default, even when extracting as root (unlike tar's own default).
+    ///
+    /// This requires privileges, like running as root when assembling the root filesystem of a
+    /// Docker image. Zip and 7z archives do not record the owners, so they are not affected.
+    pub preserve_ownership: bool,
+}
+
 /// Archive formats that we handle.
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Format {
@@ -47,7 +114,66 @@ impl Format {
         }
     }
 
+    /// Deduce the archive format from the leading bytes of its contents.
+    ///
+    /// Compressed streams (gzip, bzip2, xz, zstd) are assumed to contain a tarball.
+    pub fn from_content(mut reader: impl Read) -> Result<Self> {
+        // Tar header is 512 bytes, with the `ustar` magic at offset 257.
+        let mut header = Vec::with_capacity(512);
+        reader.by_ref().take(512).read_to_end(&mut header)?;
+        Self::from_magic_bytes(&header)
+            .ok_or_else(|| anyhow!("Failed to recognize archive format from its contents."))
+    }
+
+    /// Recognize the archive format from the magic bytes at the beginning of the data.
+    pub fn from_magic_bytes(header: &[u8]) -> Option<Self> {
+        const TAR_MAGIC_OFFSET: usize = 257;
+        if header.starts_with(b"PK\x03\x04") || header.starts_with(b"PK\x05\x06") {
+            Some(Format::Zip)
+        } else if header.starts_with(b"7z\xBC\xAF\x27\x1C") {
+            Some(Format::SevenZip)
+        } else if header.starts_with(b"\x1F\x8B") {
+            Some(Format::Tar(Some(Compression::Gzip)))
+        } else if header.starts_with(b"\xFD7zXZ\x00") {
+            Some(Format::Tar(Some(Compression::Xz)))
+        } else if header.starts_with(b"BZh") {
+            Some(Format::Tar(Some(Compression::Bzip2)))
+        } else if header.starts_with(b"\x28\xB5\x2F\xFD") {
+            Some(Format::Tar(Some(Compression::Zstd)))
+        } else if header.get(TAR_MAGIC_OFFSET..).map_or(false, |tail| tail.starts_with(b"ustar")) {
+            Some(Format::Tar(None))
+        } else {
+            None
+        }
+    }
+
+    /// Deduce the format of the archive file, looking both at its contents and name.
+    ///
+    /// Contents take precedence, as the file names might be missing an extension (like GitHub
+    /// artifact downloads) or be misleading.
+    #[context("Deducing archive format of {}.", path.as_ref().display())]
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let from_name = Self::from_filename(path);
+        match Self::from_content(crate::fs::open(path)?) {
+            Ok(from_content) => {
+                if let Ok(from_name) = from_name && from_name != from_content {
+                    warn!(
+                        "Archive {} is named like {from_name:?}, but its contents are \
+                        {from_content:?}.",
+                        path.display()
+                    );
+                }
+                Ok(from_content)
+            }
+            Err(e) => from_name.context(e),
+        }
+    }
+
     /// Extract an archive of this format into a given output directory.
+    ///
+    /// The 7z archives are not supported, as they need the external [`SevenZip`] program. Use
+    /// [`extract_async`](Format::extract_async) for them.
     #[tracing::instrument(
         name="Unpacking archive.",
         skip_all,
@@ -64,39 +190,152 @@ impl Format {
                 let mut archive = zip::ZipArchive::new(compressed_data)?;
                 archive.extract(output_dir)?;
             }
-            Format::Tar(Some(Compression::Gzip)) => {
-                let tar_stream = flate2::read::GzDecoder::new(compressed_data);
+            Format::Tar(compression) => {
+                let tar_stream = tar::Decoder::new(compression, compressed_data)?;
                 let mut archive = ::tar::Archive::new(tar_stream);
                 archive.unpack(output_dir)?;
             }
-            // Format::SevenZip => {
-            //     let mut cmd = SevenZip.unpack_from_stdin_cmd(output_dir)?;
-            //     cmd.stdin(Stdio::piped());
-            //     let mut child = cmd.as_std().clone().spawn()?;
-            //     //let child = cmd.spawn_nicer()?;
-            //     let mut stdin =
-            //         child.stdin.ok_or_else(|| anyhow!("Failed to get 7z stdin handle"))?;
-            //     std::io::copy(&mut compressed_data, &mut stdin)?;
-            //     drop(stdin);
-            //     child.wait()?.exit_ok()?;
-            // }
-            _ => todo!("Not supported!"),
+            Format::SevenZip => {
+                // Blocking on the command here would panic when called from the async code.
+                bail!(
+                    "7z archives are extracted by an external program, use \
+                    `Format::extract_async` instead."
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Extract an archive of this format, read from the asynchronous source.
+    ///
+    /// Unlike [`extract`](Format::extract), this does not block the runtime: tarballs are
+    /// decompressed on a blocking thread while being streamed, other formats need random access,
+    /// so they are first spooled to a temporary file.
+    pub async fn extract_async(
+        self,
+        compressed_data: impl AsyncRead + Send + Unpin + 'static,
+        output_dir: impl AsRef<Path>,
+    ) -> Result {
+        self.extract_async_with(compressed_data, output_dir, default()).await
+    }
+
+    /// Like [`extract_async`](Format::extract_async), with the given options.
+    #[tracing::instrument(
+        name="Unpacking archive.",
+        skip_all,
+        fields(self, dest=%output_dir.as_ref().display()),
+        err)]
+    pub async fn extract_async_with(
+        self,
+        mut compressed_data: impl AsyncRead + Send + Unpin + 'static,
+        output_dir: impl AsRef<Path>,
+        options: ExtractOptions,
+    ) -> Result {
+        let output_dir = output_dir.as_ref().to_path_buf();
+        crate::fs::tokio::create_dir_if_missing(&output_dir).await?;
+        match self {
+            Format::Tar(compression) => {
+                // The bridge must be created within the runtime, it is used from the blocking
+                // thread.
+                let reader = tokio_util::io::SyncIoBridge::new(compressed_data);
+                tokio::task::spawn_blocking(move || -> Result {
+                    let tar_stream = tar::Decoder::new(compression, reader)?;
+                    let mut archive = ::tar::Archive::new(tar_stream);
+                    archive.set_preserve_ownerships(options.preserve_ownership);
+                    archive.unpack(output_dir)?;
+                    Ok(())
+                })
+                .instrument(Span::current())
+                .await??;
+            }
+            Format::Zip => {
+                let temp = crate::fs::temp::file("archive", ".zip")?;
+                let mut file = tokio::fs::File::from_std(temp.reopen()?);
+                tokio::io::copy(&mut compressed_data, &mut file).await?;
+                tokio::task::spawn_blocking(move || {
+                    // The temporary file is removed only after the extraction.
+                    let temp = temp;
+                    self.extract(crate::fs::open(temp.path())?, output_dir)
+                })
+                .instrument(Span::current())
+                .await??;
+            }
+            Format::SevenZip => {
+                let temp = crate::fs::temp::file("archive", ".7z")?;
+                let mut file = tokio::fs::File::from_std(temp.reopen()?);
+                tokio::io::copy(&mut compressed_data, &mut file).await?;
+                drop(file);
+                SevenZip.unpack_cmd(temp.path(), output_dir)?.run_ok().await?;
+            }
         }
         Ok(())
     }
+
+    /// Extract the archive file of this format, without blocking the runtime.
+    pub async fn extract_file_async(
+        self,
+        archive_path: impl AsRef<Path>,
+        output_dir: impl AsRef<Path>,
+    ) -> Result {
+        let file = crate::fs::tokio::open(archive_path).await?;
+        self.extract_async(file, output_dir).await
+    }
+
+    /// Pack the directory contents into an archive of this format, without blocking the runtime.
+    ///
+    /// Zip archives and tarballs are created in-process on a blocking thread, so no external
+    /// program is needed. 7z archives are created using [`SevenZip`].
+    #[tracing::instrument(
+        name="Packing archive.",
+        skip_all,
+        fields(
+            self,
+            src  = %root_directory.as_ref().display(),
+            dest = %output_archive.as_ref().display()),
+        err)]
+    pub async fn create_async(
+        self,
+        output_archive: impl AsRef<Path>,
+        root_directory: impl AsRef<Path>,
+        options: CompressionOptions,
+    ) -> Result {
+        options.validate()?;
+        let output_archive = output_archive.as_ref().to_path_buf();
+        let root_directory = root_directory.as_ref().to_path_buf();
+        crate::fs::tokio::create_parent_dir_if_missing(&output_archive).await?;
+        let pack_task = match self {
+            Format::SevenZip =>
+                return SevenZip
+                    .pack_directory_contents(output_archive, root_directory, options)
+                    .await,
+            Format::Zip => tokio::task::spawn_blocking(move || -> Result {
+                let output = crate::fs::create(&output_archive)?;
+                zip::pack_directory(output, options.level, &root_directory)?;
+                Ok(())
+            }),
+            Format::Tar(compression) => tokio::task::spawn_blocking(move || -> Result {
+                let output = std::io::BufWriter::new(crate::fs::create(&output_archive)?);
+                tar::pack_directory(output, compression, options.level, &root_directory)?
+                    .into_inner()?;
+                Ok(())
+            }),
+        };
+        pack_task.instrument(Span::current()).await?
+    }
 }
 
 
 pub async fn create(
     output_archive: impl AsRef<Path>,
     paths_to_pack: impl IntoIterator<Item: AsRef<Path>>,
+    options: CompressionOptions,
 ) -> Result {
     let span = info_span!("Creating an archive", target = output_archive.as_ref().as_str());
     let format = Format::from_filename(&output_archive)?;
     match format {
         Format::Zip | Format::SevenZip =>
-            SevenZip.pack(output_archive, paths_to_pack).instrument(span).await,
-        Format::Tar(_) => Tar.pack(output_archive, paths_to_pack).instrument(span).await,
+            SevenZip.pack(output_archive, paths_to_pack, options).instrument(span).await,
+        Format::Tar(_) => Tar.pack(output_archive, paths_to_pack, options).instrument(span).await,
     }
 }
 
@@ -113,57 +352,201 @@ pub fn is_archive_name(path: impl AsRef<Path>) -> bool {
 pub async fn pack_directory_contents(
     output_archive: impl AsRef<Path>,
     root_directory: impl AsRef<Path>,
+    options: CompressionOptions,
 ) -> Result {
     let format = Format::from_filename(&output_archive)?;
     match format {
         Format::Zip | Format::SevenZip =>
-            SevenZip.pack_directory_contents(output_archive, root_directory).await,
+            SevenZip.pack_directory_contents(output_archive, root_directory, options).await,
         Format::Tar(compression) =>
-            Tar.pack_directory_contents(compression, output_archive, root_directory).await,
+            Tar.pack_directory_contents(compression, output_archive, root_directory, options).await,
     }
 }
 
+/// Check if the path matches any of the patterns.
+///
+/// Path separators are matched literally, so `*.jar` will match only top-level entries, while
+/// `**/*.jar` matches them at any depth.
+pub fn matches_any(patterns: &[glob::Pattern], path: impl AsRef<Path>) -> bool {
+    let options = glob::MatchOptions { require_literal_separator: true, ..default() };
+    patterns.iter().any(|pattern| pattern.matches_path_with(path.as_ref(), options))
+}
+
+/// The archive entries to be extracted by [`extract_item`].
+#[derive(Clone, Debug)]
+pub enum Selection {
+    /// The entry with the given path, along with its descendants. They are extracted relative to
+    /// that path.
+    Subtree(PathBuf),
+    /// The entries matching any of the glob patterns (e.g. `bin/**` or `*.jar`), see
+    /// [`matches_any`]. They keep their paths relative to the archive root.
+    Matching(Vec<glob::Pattern>),
+}
+
+impl Selection {
+    /// Select the entries matching any of the glob patterns.
+    pub fn matching(patterns: impl IntoIterator<Item: AsRef<str>>) -> Result<Self> {
+        let patterns = patterns
+            .into_iter()
+            .map(|pattern| {
+                glob::Pattern::new(pattern.as_ref())
+                    .with_context(|| format!("Invalid glob pattern: {}", pattern.as_ref()))
+            })
+            .collect_result()?;
+        Ok(Self::Matching(patterns))
+    }
+
+    /// Where the selected entry with the given path should be extracted. `None` if the entry is
+    /// not selected.
+    pub fn output_path(&self, path_in_archive: &Path, output: &Path) -> Option<PathBuf> {
+        match self {
+            Selection::Subtree(prefix) =>
+                path_in_archive.strip_prefix(prefix).ok().map(|relative| output.join(relative)),
+            Selection::Matching(patterns) =>
+                matches_any(patterns, path_in_archive).then(|| output.join(path_in_archive)),
+        }
+    }
+
+    /// Copy the selected files from the directory with the extracted archive.
+    pub fn copy_selected(&self, extracted: &Path, output: &Path) -> Result {
+        for entry in walkdir::WalkDir::new(extracted).min_depth(1) {
+            let entry = entry?;
+            if entry.file_type().is_dir() {
+                continue;
+            }
+            let path_in_archive = entry.path().strip_prefix(extracted)?;
+            if let Some(output) = self.output_path(path_in_archive, output) {
+                trace!("Extracting {}", output.display());
+                crate::fs::copy(entry.path(), output)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<PathBuf> for Selection {
+    fn from(prefix: PathBuf) -> Self {
+        Self::Subtree(prefix)
+    }
+}
+
+impl From<&Path> for Selection {
+    fn from(prefix: &Path) -> Self {
+        Self::Subtree(prefix.to_owned())
+    }
+}
+
+/// Extract the selected entries of the archive.
+///
+/// The archive is scanned only once. The 7z archives are extracted with the external
+/// [`SevenZip`] program to a temporary directory first.
 #[tracing::instrument(
-    name="Extracting item from archive.",
-    skip(archive_path, item_path, output_path),
+    name="Extracting items from archive.",
+    skip(archive_path, selection, output_path),
     fields(
         src  = %archive_path.as_ref().display(),
-        item = %item_path.as_ref().display(),
         dest = %output_path.as_ref().display()),
     err)]
 pub async fn extract_item(
     archive_path: impl AsRef<Path>,
-    item_path: impl AsRef<Path>,
+    selection: impl Into<Selection>,
     output_path: impl AsRef<Path>,
 ) -> Result {
     let format = Format::from_filename(&archive_path)?;
+    let selection = selection.into();
+    debug!("Extracting {selection:?}.");
     let archive_path = archive_path.as_ref().to_path_buf();
-    let item_path = item_path.as_ref().to_path_buf();
     let output_path = output_path.as_ref().to_path_buf();
 
     let extract_task = match format {
         Format::Zip => {
             let mut archive = zip::open(&archive_path)?;
-            tokio::task::spawn_blocking(move || {
-                zip::extract_subtree(&mut archive, item_path, output_path)
+            tokio::task::spawn_blocking(move || match &selection {
+                Selection::Subtree(prefix) =>
+                    zip::extract_subtree(&mut archive, prefix, output_path),
+                Selection::Matching(patterns) =>
+                    zip::extract_matching(&mut archive, patterns, output_path),
             })
         }
-        Format::Tar(Some(Compression::Gzip)) => {
-            let mut archive = tar::open_tar_gz(&archive_path)?;
+        Format::Tar(compression) => {
+            let mut archive = tar::open(&archive_path, compression)?;
+            tokio::task::spawn_blocking(move || match &selection {
+                Selection::Subtree(prefix) =>
+                    tar::extract_subtree(&mut archive, prefix, output_path),
+                Selection::Matching(patterns) =>
+                    tar::extract_matching(&mut archive, patterns, output_path),
+            })
+        }
+        Format::SevenZip => {
+            let extracted = crate::fs::temp::dir("7z-extraction")?;
+            SevenZip.unpack_cmd(&archive_path, extracted.path())?.run_ok().await?;
             tokio::task::spawn_blocking(move || {
-                tar::extract_subtree(&mut archive, item_path, output_path)
+                selection.copy_selected(extracted.path(), &output_path)
             })
         }
-        _ => todo!(),
     };
     extract_task.instrument(Span::current()).await??;
     Ok(())
 }
 
+/// List the archive entries, keyed by their paths.
+///
+/// The 7z archives are listed using the external [`SevenZip`] program.
+#[context("Failed to list entries of the archive {}.", path.as_ref().display())]
+pub async fn list(path: impl AsRef<Path>) -> Result<BTreeMap<PathBuf, EntryInfo>> {
+    let path = path.as_ref().to_path_buf();
+    match Format::from_file(&path)? {
+        Format::SevenZip => {
+            let entries = SevenZip.list(&path).await?;
+            Ok(entries
+                .into_iter()
+                .map(|entry| (entry.path, EntryInfo { size: entry.size, is_dir: entry.is_dir }))
+                .collect())
+        }
+        _ => tokio::task::spawn_blocking(move || diff::list_entries(path)).await?,
+    }
+}
+
+/// Check the archive integrity, by decompressing all its entries without writing them anywhere.
+#[tracing::instrument(
+    name="Verifying archive.",
+    skip_all,
+    fields(path = %path.as_ref().display()),
+    err)]
+pub async fn verify(path: impl AsRef<Path>) -> Result {
+    let path = path.as_ref().to_path_buf();
+    match Format::from_file(&path)? {
+        Format::Zip | Format::SevenZip => SevenZip.test(&path).await,
+        Format::Tar(compression) =>
+            tokio::task::spawn_blocking(move || -> Result {
+                let mut archive = tar::open(&path, compression)?;
+                for entry in archive.entries()? {
+                    let mut entry = entry?;
+                    let entry_path = entry.path()?.to_path_buf();
+                    std::io::copy(&mut entry, &mut std::io::sink()).with_context(|| {
+                        format!("Failed to read entry {}.", entry_path.display())
+                    })?;
+                }
+                Ok(())
+            })
+            .instrument(Span::current())
+            .await?,
+    }
+}
+
 #[tracing::instrument(name="Extracting the archive to a directory.", skip(archive_path,output_directory), fields(src=%archive_path.as_ref().display(), dest=%output_directory.as_ref().display()), err)]
 pub async fn extract_to(
     archive_path: impl AsRef<Path>,
     output_directory: impl AsRef<Path>,
+) -> Result {
+    extract_to_with(archive_path, output_directory, default()).await
+}
+
+/// Like [`extract_to`], with the given options.
+pub async fn extract_to_with(
+    archive_path: impl AsRef<Path>,
+    output_directory: impl AsRef<Path>,
+    options: ExtractOptions,
 ) -> Result {
     // Don't clean the output directory. Perhaps even the archive lives there.
     let span = info_span!(
@@ -171,11 +554,12 @@ pub async fn extract_to(
         source = archive_path.as_ref().as_str(),
         target = output_directory.as_ref().as_str()
     );
-    let format = Format::from_filename(&archive_path)?;
+    let format = Format::from_file(&archive_path)?;
     match format {
         Format::Zip | Format::SevenZip =>
             SevenZip.unpack_cmd(archive_path, output_directory)?.run_ok().instrument(span).await,
-        Format::Tar(_) => Tar.unpack(archive_path, output_directory).instrument(span).await,
+        Format::Tar(_) =>
+            Tar.unpack_with(archive_path, output_directory, options).instrument(span).await,
     }
 }
 
@@ -193,7 +577,7 @@ mod tests {
         crate::fs::reset_dir(&out)?;
 
         let archive = PathBuf::from(r"C:\Temp\foo.tar.gz");
-        pack_directory_contents(&archive, &target).await?;
+        pack_directory_contents(&archive, &target, default()).await?;
 
         extract_to(&archive, &out).await?;
 
@@ -209,6 +593,86 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn glob_matching() -> Result {
+        let patterns = [glob::Pattern::new("bin/**")?, glob::Pattern::new("*.jar")?];
+        assert!(matches_any(&patterns, "bin/enso"));
+        assert!(matches_any(&patterns, "bin/nested/enso"));
+        assert!(matches_any(&patterns, "runtime.jar"));
+        assert!(!matches_any(&patterns, "lib/runtime.jar"));
+        Ok(())
+    }
+
+    #[test]
+    fn selecting_entries() -> Result {
+        let output = Path::new("out");
+        let subtree = Selection::from(PathBuf::from("bin"));
+        assert_eq!(subtree.output_path(Path::new("bin/enso"), output), Some(output.join("enso")));
+        assert_eq!(subtree.output_path(Path::new("lib/enso.jar"), output), None);
+        let matching = Selection::matching(["*.jar"])?;
+        assert_eq!(
+            matching.output_path(Path::new("runtime.jar"), output),
+            Some(output.join("runtime.jar"))
+        );
+        assert_eq!(matching.output_path(Path::new("lib/runtime.jar"), output), None);
+        assert!(Selection::matching(["[invalid"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn format_from_content() -> Result {
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        zip.start_file("file.txt", default())?;
+        let zip = zip.finish()?.into_inner();
+        assert_eq!(Format::from_content(zip.as_slice())?, Format::Zip);
+
+        let mut tar = ::tar::Builder::new(Vec::new());
+        let mut header = ::tar::Header::new_ustar();
+        header.set_size(0);
+        header.set_cksum();
+        tar.append_data(&mut header, "file.txt", std::io::empty())?;
+        let tar = tar.into_inner()?;
+        assert_eq!(Format::from_content(tar.as_slice())?, Format::Tar(None));
+
+        let gzip = [0x1F, 0x8B, 0x08, 0x00];
+        assert_eq!(Format::from_content(gzip.as_slice())?, Format::Tar(Some(Compression::Gzip)));
+        assert!(Format::from_content(b"plain text".as_slice()).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn blocking_seven_zip_extraction_is_rejected() -> Result {
+        let temp = tempfile::tempdir()?;
+        let data = std::io::Cursor::new(b"7z\xBC\xAF\x27\x1C".to_vec());
+        // Used to panic, as the runtime cannot be blocked on from within the async code.
+        assert!(Format::SevenZip.extract(data, temp.path()).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn async_round_trip() -> Result {
+        let temp = tempfile::tempdir()?;
+        let source = temp.path().join("source");
+        crate::fs::write(source.join("top.txt"), "top")?;
+        crate::fs::write(source.join("nested").join("inner.txt"), "inner")?;
+
+        let formats = [Format::Zip, Format::Tar(None), Format::Tar(Some(Compression::Gzip))];
+        for (index, format) in formats.into_iter().enumerate() {
+            let archive = temp.path().join(format!("archive{index}"));
+            format.create_async(&archive, &source, CompressionOptions::fast()).await?;
+            assert_eq!(Format::from_file(&archive)?, format);
+
+            let output = temp.path().join(format!("output{index}"));
+            format.extract_file_async(&archive, &output).await?;
+            assert_eq!(crate::fs::read_to_string(output.join("top.txt"))?, "top");
+            assert_eq!(
+                crate::fs::read_to_string(output.join("nested").join("inner.txt"))?,
+                "inner"
+            );
+        }
+        Ok(())
+    }
+
     #[test]
     fn archive_checker() {
         assert!(is_archive_name("enso-project-manager-0.2.31-linux-amd64.tar.gz"));
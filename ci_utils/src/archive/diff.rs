@@ -0,0 +1,166 @@
+//! Comparing the contents of two archives.
+
+use crate::prelude::*;
+
+use crate::archive::Format;
+
+
+/// Description of a single archive entry, as relevant for comparison.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EntryInfo {
+    /// Uncompressed size in bytes.
+    pub size:   u64,
+    pub is_dir: bool,
+}
+
+/// Entry that is present in both archives, but with a different size.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SizeChange {
+    pub path:     PathBuf,
+    pub old_size: u64,
+    pub new_size: u64,
+}
+
+/// Differences between two archives.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ArchiveDiff {
+    /// Entries present only in the new archive.
+    pub added:        Vec<PathBuf>,
+    /// Entries present only in the old archive.
+    pub removed:      Vec<PathBuf>,
+    /// Entries present in both archives, which have different sizes.
+    pub size_changed: Vec<SizeChange>,
+}
+
+impl ArchiveDiff {
+    /// Compare two archive listings.
+    pub fn new(old: &BTreeMap<PathBuf, EntryInfo>, new: &BTreeMap<PathBuf, EntryInfo>) -> Self {
+        let added = new.keys().filter(|path| !old.contains_key(*path)).cloned().collect();
+        let removed = old.keys().filter(|path| !new.contains_key(*path)).cloned().collect();
+        let size_changed = old
+            .iter()
+            .filter_map(|(path, old_entry)| {
+                let new_entry = new.get(path)?;
+                (old_entry.size != new_entry.size).then(|| SizeChange {
+                    path:     path.clone(),
+                    old_size: old_entry.size,
+                    new_size: new_entry.size,
+                })
+            })
+            .collect();
+        Self { added, removed, size_changed }
+    }
+
+    /// Whether the archives have the same entries of the same sizes.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.size_changed.is_empty()
+    }
+}
+
+impl Display for ArchiveDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for path in &self.added {
+            writeln!(f, "+ {}", path.display())?;
+        }
+        for path in &self.removed {
+            writeln!(f, "- {}", path.display())?;
+        }
+        for SizeChange { path, old_size, new_size } in &self.size_changed {
+            writeln!(f, "~ {} ({old_size} -> {new_size} bytes)", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// Normalize path of the archive entry, so listings of differently packed archives match.
+///
+/// E.g. `./bin/` and `bin` should be considered the same entry.
+fn normalize_entry_path(path: &Path) -> PathBuf {
+    path.components().filter(|component| component != &std::path::Component::CurDir).collect()
+}
+
+/// List all the entries in the archive.
+#[context("Failed to list entries of the archive {}.", path.as_ref().display())]
+pub fn list_entries(path: impl AsRef<Path>) -> Result<BTreeMap<PathBuf, EntryInfo>> {
+    let mut ret = BTreeMap::new();
+    match Format::from_file(&path)? {
+        Format::Zip => {
+            let mut archive = crate::archive::zip::open(&path)?;
+            for index in 0..archive.len() {
+                let file = archive.by_index(index)?;
+                let entry_path = file
+                    .enclosed_name()
+                    .with_context(|| format!("Illegal path in the archive: {}", file.name()))?;
+                let info = EntryInfo { size: file.size(), is_dir: file.is_dir() };
+                ret.insert(normalize_entry_path(entry_path), info);
+            }
+        }
+        Format::Tar(compression) => {
+            let mut archive = crate::archive::tar::open(&path, compression)?;
+            for entry in archive.entries()? {
+                let entry = entry?;
+                let header = entry.header();
+                let info =
+                    EntryInfo { size: header.size()?, is_dir: header.entry_type().is_dir() };
+                ret.insert(normalize_entry_path(&entry.path()?), info);
+            }
+        }
+        Format::SevenZip => bail!("Listing 7z archive entries is not supported."),
+    }
+    ret.remove(Path::new(""));
+    Ok(ret)
+}
+
+/// Compare the entries of two archives.
+///
+/// Entries are matched by their path. The archives might be of different formats.
+#[tracing::instrument(
+    name="Comparing archives.",
+    skip_all,
+    fields(old = %old.as_ref().display(), new = %new.as_ref().display()),
+    err)]
+pub async fn diff(old: impl AsRef<Path>, new: impl AsRef<Path>) -> Result<ArchiveDiff> {
+    let old = old.as_ref().to_path_buf();
+    let new = new.as_ref().to_path_buf();
+    let old_entries = tokio::task::spawn_blocking(move || list_entries(old));
+    let new_entries = tokio::task::spawn_blocking(move || list_entries(new));
+    let (old_entries, new_entries) = futures::try_join!(old_entries, new_entries)?;
+    Ok(ArchiveDiff::new(&old_entries?, &new_entries?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(size: u64) -> EntryInfo {
+        EntryInfo { size, is_dir: false }
+    }
+
+    #[test]
+    fn compare_listings() {
+        let old = BTreeMap::from_iter([
+            (PathBuf::from("bin/enso"), file(100)),
+            (PathBuf::from("lib/runtime.jar"), file(1000)),
+            (PathBuf::from("lib/removed.jar"), file(10)),
+        ]);
+        let new = BTreeMap::from_iter([
+            (PathBuf::from("bin/enso"), file(100)),
+            (PathBuf::from("lib/runtime.jar"), file(1200)),
+            (PathBuf::from("lib/added.jar"), file(10)),
+        ]);
+        let diff = ArchiveDiff::new(&old, &new);
+        assert_eq!(diff.added, vec![PathBuf::from("lib/added.jar")]);
+        assert_eq!(diff.removed, vec![PathBuf::from("lib/removed.jar")]);
+        assert_eq!(diff.size_changed, vec![SizeChange {
+            path:     PathBuf::from("lib/runtime.jar"),
+            old_size: 1000,
+            new_size: 1200,
+        }]);
+        assert!(ArchiveDiff::new(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn normalize_paths() {
+        assert_eq!(normalize_entry_path(Path::new("./bin/")), PathBuf::from("bin"));
+    }
+}
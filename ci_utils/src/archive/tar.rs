@@ -1,15 +1,164 @@
 use crate::prelude::*;
 
+use crate::programs::tar::Compression;
+
 use flate2::read::GzDecoder;
 use std::fs::File;
+use std::io::Write;
 use tar::Archive;
 
+
+/// Reader that transparently decompresses the tarball stream using one of the supported
+/// compression algorithms.
+///
+/// Some of the decoders cannot be debug-formatted, so only the variant is shown for them.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub enum Decoder<R: Read> {
+    Plain(R),
+    Bzip2(#[derivative(Debug = "ignore")] bzip2::read::BzDecoder<R>),
+    Gzip(GzDecoder<R>),
+    /// Both `.xz` and legacy `.lzma` streams are handled by the `xz2` decoder.
+    Xz(#[derivative(Debug = "ignore")] xz2::read::XzDecoder<R>),
+    Zstd(
+        #[derivative(Debug = "ignore")] zstd::stream::read::Decoder<'static, std::io::BufReader<R>>,
+    ),
+}
+
+impl<R: Read> Decoder<R> {
+    pub fn new(compression: Option<Compression>, compressed_data: R) -> Result<Self> {
+        Ok(match compression {
+            None => Decoder::Plain(compressed_data),
+            Some(Compression::Bzip2) =>
+                Decoder::Bzip2(bzip2::read::BzDecoder::new(compressed_data)),
+            Some(Compression::Gzip) => Decoder::Gzip(GzDecoder::new(compressed_data)),
+            Some(Compression::Xz) => Decoder::Xz(xz2::read::XzDecoder::new(compressed_data)),
+            Some(Compression::Lzma) => {
+                let stream = xz2::stream::Stream::new_lzma_decoder(u64::MAX)?;
+                Decoder::Xz(xz2::read::XzDecoder::new_stream(compressed_data, stream))
+            }
+            Some(Compression::Zstd) =>
+                Decoder::Zstd(zstd::stream::read::Decoder::new(compressed_data)?),
+        })
+    }
+}
+
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Decoder::Plain(reader) => reader.read(buf),
+            Decoder::Bzip2(reader) => reader.read(buf),
+            Decoder::Gzip(reader) => reader.read(buf),
+            Decoder::Xz(reader) => reader.read(buf),
+            Decoder::Zstd(reader) => reader.read(buf),
+        }
+    }
+}
+
+/// Writer that compresses the tarball stream using one of the supported compression algorithms.
+pub enum Encoder<W: Write> {
+    Plain(W),
+    Bzip2(bzip2::write::BzEncoder<W>),
+    Gzip(flate2::write::GzEncoder<W>),
+    Xz(xz2::write::XzEncoder<W>),
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+}
+
+impl<W: Write> Encoder<W> {
+    /// Create the encoder. The level is from 0 (fastest) to 9 (smallest output), if not given
+    /// the algorithm's default is used.
+    pub fn new(compression: Option<Compression>, level: Option<u32>, output: W) -> Result<Self> {
+        Ok(match compression {
+            None => Encoder::Plain(output),
+            Some(Compression::Bzip2) => {
+                let level =
+                    level.map_or_else(default, |level| bzip2::Compression::new(level.max(1)));
+                Encoder::Bzip2(bzip2::write::BzEncoder::new(output, level))
+            }
+            Some(Compression::Gzip) => {
+                let level = level.map_or_else(default, flate2::Compression::new);
+                Encoder::Gzip(flate2::write::GzEncoder::new(output, level))
+            }
+            Some(Compression::Xz) =>
+                Encoder::Xz(xz2::write::XzEncoder::new(output, level.unwrap_or(6))),
+            Some(Compression::Lzma) => bail!("Creating legacy LZMA tarballs is not supported."),
+            Some(Compression::Zstd) => {
+                // Zstd levels go up to 19, the 0 means the default one.
+                let level = level.map_or(0, |level| (level * 2 + 1) as i32);
+                Encoder::Zstd(zstd::stream::write::Encoder::new(output, level)?)
+            }
+        })
+    }
+
+    /// Write the remaining compressed data, returning the underlying writer.
+    pub fn finish(self) -> Result<W> {
+        Ok(match self {
+            Encoder::Plain(writer) => writer,
+            Encoder::Bzip2(encoder) => encoder.finish()?,
+            Encoder::Gzip(encoder) => encoder.finish()?,
+            Encoder::Xz(encoder) => encoder.finish()?,
+            Encoder::Zstd(encoder) => encoder.finish()?,
+        })
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Encoder::Plain(writer) => writer.write(buf),
+            Encoder::Bzip2(writer) => writer.write(buf),
+            Encoder::Gzip(writer) => writer.write(buf),
+            Encoder::Xz(writer) => writer.write(buf),
+            Encoder::Zstd(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Encoder::Plain(writer) => writer.flush(),
+            Encoder::Bzip2(writer) => writer.flush(),
+            Encoder::Gzip(writer) => writer.flush(),
+            Encoder::Xz(writer) => writer.flush(),
+            Encoder::Zstd(writer) => writer.flush(),
+        }
+    }
+}
+
+/// Pack the directory contents into the compressed tarball written to the output.
+///
+/// This is blocking, so in the async code it should be run on a dedicated thread.
+pub fn pack_directory<W: Write>(
+    output: W,
+    compression: Option<Compression>,
+    level: Option<u32>,
+    root_directory: &Path,
+) -> Result<W> {
+    let mut builder = tar::Builder::new(Encoder::new(compression, level, output)?);
+    builder.follow_symlinks(false);
+    for entry in walkdir::WalkDir::new(root_directory).min_depth(1).sort_by_file_name() {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(root_directory)?;
+        builder.append_path_with_name(entry.path(), relative)?;
+    }
+    builder.into_inner()?.finish()
+}
+
 pub fn open_tar_gz(path: impl AsRef<Path>) -> Result<Archive<GzDecoder<File>>> {
     let file = crate::fs::open(&path)?;
     let tar_stream = flate2::read::GzDecoder::new(file);
     Ok(tar::Archive::new(tar_stream))
 }
 
+/// Open a tarball, decompressing it with the given algorithm.
+#[context("Failed to open tarball {}.", path.as_ref().display())]
+pub fn open(
+    path: impl AsRef<Path>,
+    compression: Option<Compression>,
+) -> Result<Archive<Decoder<File>>> {
+    let file = crate::fs::open(&path)?;
+    Ok(tar::Archive::new(Decoder::new(compression, file)?))
+}
+
 pub fn extract_subtree<R: Read>(
     archive: &mut Archive<R>,
     prefix: impl AsRef<Path>,
@@ -26,3 +175,89 @@ pub fn extract_subtree<R: Read>(
     }
     Ok(())
 }
+
+/// Extract all archive entries that match any of the given patterns.
+///
+/// Entries keep their paths relative to the archive root. The archive is traversed only once.
+pub fn extract_matching<R: Read>(
+    archive: &mut Archive<R>,
+    patterns: &[glob::Pattern],
+    output: impl AsRef<Path>,
+) -> Result {
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path_in_archive = entry.path()?.into_owned();
+        if crate::archive::matches_any(patterns, &path_in_archive) {
+            trace!("Extracting {}", output.as_ref().join(&path_in_archive).display());
+            entry.unpack_in(&output)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn sample_tarball() -> Result<Vec<u8>> {
+        let contents = b"Hello, world!";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        let mut builder = tar::Builder::new(Vec::new());
+        builder.append_data(&mut header, "dir/hello.txt", contents.as_slice())?;
+        Ok(builder.into_inner()?)
+    }
+
+    fn check_roundtrip(compression: Compression, compressed: Vec<u8>) -> Result {
+        let decoder = Decoder::new(Some(compression), compressed.as_slice())?;
+        let mut archive = Archive::new(decoder);
+        let temp = tempfile::tempdir()?;
+        extract_subtree(&mut archive, "dir", temp.path())?;
+        assert_eq!(std::fs::read_to_string(temp.path().join("hello.txt"))?, "Hello, world!");
+        Ok(())
+    }
+
+    #[test]
+    fn extract_by_glob() -> Result {
+        let tarball = sample_tarball()?;
+        let temp = tempfile::tempdir()?;
+
+        let mut archive = Archive::new(tarball.as_slice());
+        extract_matching(&mut archive, &[glob::Pattern::new("*.txt")?], temp.path())?;
+        assert!(!temp.path().join("dir").exists());
+
+        let mut archive = Archive::new(tarball.as_slice());
+        extract_matching(&mut archive, &[glob::Pattern::new("dir/**")?], temp.path())?;
+        assert!(temp.path().join("dir/hello.txt").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn decode_all_compressions() -> Result {
+        let tarball = sample_tarball()?;
+
+        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), default());
+        encoder.write_all(&tarball)?;
+        check_roundtrip(Compression::Bzip2, encoder.finish()?)?;
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), default());
+        encoder.write_all(&tarball)?;
+        check_roundtrip(Compression::Gzip, encoder.finish()?)?;
+
+        let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+        encoder.write_all(&tarball)?;
+        check_roundtrip(Compression::Xz, encoder.finish()?)?;
+
+        let options = xz2::stream::LzmaOptions::new_preset(6)?;
+        let stream = xz2::stream::Stream::new_lzma_encoder(&options)?;
+        let mut encoder = xz2::write::XzEncoder::new_stream(Vec::new(), stream);
+        encoder.write_all(&tarball)?;
+        check_roundtrip(Compression::Lzma, encoder.finish()?)?;
+
+        check_roundtrip(Compression::Zstd, zstd::encode_all(tarball.as_slice(), 0)?)?;
+        Ok(())
+    }
+}
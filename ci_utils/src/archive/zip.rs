@@ -2,10 +2,18 @@ use crate::prelude::*;
 
 use anyhow::Context;
 use std::io::Cursor;
+use std::io::Write;
 use zip::read::ZipFile;
 
 pub use ::zip::*;
 
+
+/// Number of entries above which the archive needs the Zip64 extensions.
+pub const ZIP64_ENTRY_THRESHOLD: usize = u16::MAX as usize;
+
+/// Size above which the entry needs the Zip64 extensions.
+pub const ZIP64_SIZE_THRESHOLD: u64 = u32::MAX as u64;
+
 pub fn open(path: impl AsRef<Path>) -> Result<ZipArchive<std::fs::File>> {
     ZipArchive::new(crate::fs::open(path)?).anyhow_err()
 }
@@ -17,6 +25,117 @@ pub fn extract_bytes(bytes: Bytes, output_dir: impl AsRef<Path>) -> Result {
     Ok(())
 }
 
+/// Name of the archive entry at the given path. Zip always uses forward slashes.
+fn entry_name(entry_path: &Path) -> String {
+    entry_path.iter().map(|part| part.to_string_lossy()).join("/")
+}
+
+/// Read the file entry into memory, without extracting anything to disk.
+///
+/// Meant for the small files, like `META-INF/MANIFEST.MF`, in possibly large archives.
+#[context("Failed to read {} from the archive.", entry_path.as_ref().display())]
+pub fn read_file_to_bytes(
+    archive: &mut ZipArchive<impl Read + Seek>,
+    entry_path: impl AsRef<Path>,
+) -> Result<Vec<u8>> {
+    let mut file = archive.by_name(&entry_name(entry_path.as_ref()))?;
+    ensure!(!file.is_dir(), "The entry is a directory.");
+    let mut ret = Vec::with_capacity(file.size().try_into().unwrap_or_default());
+    file.read_to_end(&mut ret)?;
+    Ok(ret)
+}
+
+/// Read the file entry into memory as UTF-8 text. See [`read_file_to_bytes`].
+pub fn read_file_to_string(
+    archive: &mut ZipArchive<impl Read + Seek>,
+    entry_path: impl AsRef<Path>,
+) -> Result<String> {
+    let bytes = read_file_to_bytes(archive, &entry_path)?;
+    String::from_utf8(bytes).with_context(|| {
+        format!("The archive entry {} is not valid UTF-8.", entry_path.as_ref().display())
+    })
+}
+
+/// Name of the entry to be added to the archive.
+///
+/// Unlike [`entry_name`], fails if the path cannot be represented exactly.
+fn new_entry_name(entry_path: &Path) -> Result<String> {
+    let parts = entry_path
+        .iter()
+        .map(|part| {
+            part.to_str().with_context(|| {
+                format!(
+                    "Cannot represent {} in the zip archive, as it is not valid UTF-8.",
+                    entry_path.display()
+                )
+            })
+        })
+        .collect_result()?;
+    let name = parts.join("/");
+    ensure!(
+        name.len() <= u16::MAX as usize,
+        "Cannot represent {} in the zip archive, as the path is longer than {} bytes.",
+        entry_path.display(),
+        u16::MAX
+    );
+    Ok(name)
+}
+
+/// Options for the files added to the archive.
+///
+/// The Zip64 extensions are enabled for every entry, so the files larger than
+/// [`ZIP64_SIZE_THRESHOLD`] can be written. The archives with more than [`ZIP64_ENTRY_THRESHOLD`]
+/// entries get the Zip64 end of central directory regardless.
+fn file_options(level: Option<u32>) -> write::FileOptions {
+    write::FileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .compression_level(level.map(|level| level as i32))
+        .large_file(true)
+}
+
+/// Pack the directory contents into the zip archive written to the output.
+///
+/// Fails before writing anything if any of the paths cannot be represented in the archive.
+///
+/// This is blocking, so in the async code it should be run on a dedicated thread.
+pub fn pack_directory<W: Write + Seek>(
+    output: W,
+    level: Option<u32>,
+    root_directory: &Path,
+) -> Result<W> {
+    let entries = walkdir::WalkDir::new(root_directory)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .map(|entry| -> Result<_> {
+            let entry = entry?;
+            let name = new_entry_name(entry.path().strip_prefix(root_directory)?)?;
+            Ok((name, entry))
+        })
+        .collect_result()?;
+    if entries.len() > ZIP64_ENTRY_THRESHOLD {
+        debug!("Packing {} entries, the archive will use Zip64.", entries.len());
+    }
+
+    let mut writer = ZipWriter::new(output);
+    let base_options = file_options(level);
+    for (name, entry) in entries {
+        let mut options = base_options;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            options = options.unix_permissions(entry.metadata()?.permissions().mode());
+        }
+        if entry.file_type().is_dir() {
+            writer.add_directory(name, options)?;
+        } else {
+            writer.start_file(name, options)?;
+            std::io::copy(&mut crate::fs::open(entry.path())?, &mut writer)?;
+        }
+    }
+    Ok(writer.finish()?)
+}
+
 pub fn extract_file(file: &mut ZipFile, output: impl AsRef<Path>) -> Result {
     if file.is_dir() {
         crate::fs::create_dir_if_missing(&output)?;
@@ -40,6 +159,74 @@ pub fn extract_file(file: &mut ZipFile, output: impl AsRef<Path>) -> Result {
 }
 
 
+/// Extract the whole archive, processing entries concurrently.
+///
+/// Unlike [`ZipArchive::extract`], which processes entries one by one, this opens the archive in
+/// several worker tasks (bounded by the CPU count) and lets each decompress its share of entries.
+/// As zip supports random access to entries, this scales well for large archives with many files.
+///
+/// The directory permissions are applied only after all the entries are extracted, as a read-only
+/// directory could not be populated otherwise.
+#[tracing::instrument(
+    name="Extracting archive in parallel.",
+    skip_all,
+    fields(
+        src  = %path.as_ref().display(),
+        dest = %output.as_ref().display()),
+        err)]
+pub async fn extract_parallel(path: impl AsRef<Path>, output: impl AsRef<Path>) -> Result {
+    let path = path.as_ref().to_path_buf();
+    let output = output.as_ref().to_path_buf();
+    let entry_count = open(&path)?.len();
+    let parallelism = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let worker_count = parallelism.min(entry_count).max(1);
+    debug!("Extracting {entry_count} entries using {worker_count} workers.");
+    let workers = (0..worker_count).map(|worker_index| {
+        let path = path.clone();
+        let output = output.clone();
+        tokio::task::spawn_blocking(move || -> Result<Vec<(PathBuf, Option<u32>)>> {
+            let mut archive = open(&path)?;
+            let mut directories = Vec::new();
+            for index in (worker_index..entry_count).step_by(worker_count) {
+                let mut file = archive.by_index(index)?;
+                let path_in_archive = file
+                    .enclosed_name()
+                    .context(format!("Illegal path in the archive: {}", file.name()))?
+                    .to_owned();
+                let output = output.join(path_in_archive);
+                trace!("Extracting {}", output.display());
+                // Entries are not processed in order, so the parent might not have been created.
+                crate::fs::create_parent_dir_if_missing(&output)?;
+                if file.is_dir() {
+                    crate::fs::create_dir_if_missing(&output)?;
+                    directories.push((output, file.unix_mode()));
+                } else {
+                    extract_file(&mut file, output)?;
+                }
+            }
+            Ok(directories)
+        })
+    });
+    let mut directories = Vec::new();
+    for result in futures::future::try_join_all(workers).await? {
+        directories.extend(result?);
+    }
+    // The deepest directories go first, so their parents are still writable.
+    directories.sort_by_key(|(path, _)| std::cmp::Reverse(path.components().count()));
+    for (path, mode) in directories {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if let Some(mode) = mode {
+                std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode))?;
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = (path, mode);
+    }
+    Ok(())
+}
+
 #[tracing::instrument(
     name="Extracting subtree from archive.",
     skip_all,
@@ -66,3 +253,150 @@ pub fn extract_subtree(
     }
     Ok(())
 }
+
+/// Extract all archive entries that match any of the given patterns.
+///
+/// Entries keep their paths relative to the archive root. The archive is traversed only once.
+#[tracing::instrument(
+    name="Extracting matching entries from archive.",
+    skip_all,
+    fields(dest = %output.as_ref().display()),
+    err)]
+pub fn extract_matching(
+    archive: &mut ZipArchive<impl Read + Seek>,
+    patterns: &[glob::Pattern],
+    output: impl AsRef<Path>,
+) -> Result {
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        let path_in_archive = file
+            .enclosed_name()
+            .context(format!("Illegal path in the archive: {}", file.name()))?
+            .to_owned();
+        if crate::archive::matches_any(patterns, &path_in_archive) {
+            let output = output.as_ref().join(&path_in_archive);
+            trace!("Extracting {}", output.display());
+            crate::fs::create_parent_dir_if_missing(&output)?;
+            extract_file(&mut file, output)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[tokio::test]
+    async fn parallel_extraction() -> Result {
+        let temp = tempfile::tempdir()?;
+        let archive_path = temp.path().join("archive.zip");
+        let mut writer = ZipWriter::new(crate::fs::create(&archive_path)?);
+        for i in 0..32 {
+            writer.start_file(format!("dir{}/file{i}.txt", i % 4), default())?;
+            writer.write_all(format!("Contents of {i}").as_bytes())?;
+        }
+        writer.finish()?;
+
+        let output = temp.path().join("out");
+        extract_parallel(&archive_path, &output).await?;
+        for i in 0..32 {
+            let path = output.join(format!("dir{}/file{i}.txt", i % 4));
+            assert_eq!(std::fs::read_to_string(path)?, format!("Contents of {i}"));
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn parallel_extraction_into_read_only_directory() -> Result {
+        use std::os::unix::fs::PermissionsExt;
+        let temp = tempfile::tempdir()?;
+        let archive_path = temp.path().join("archive.zip");
+        let mut writer = ZipWriter::new(crate::fs::create(&archive_path)?);
+        writer.add_directory("locked", write::FileOptions::default().unix_permissions(0o555))?;
+        for i in 0..8 {
+            writer.start_file(format!("locked/file{i}.txt"), default())?;
+            writer.write_all(format!("Contents of {i}").as_bytes())?;
+        }
+        writer.finish()?;
+
+        let output = temp.path().join("out");
+        extract_parallel(&archive_path, &output).await?;
+        let locked = output.join("locked");
+        let mode = std::fs::metadata(&locked)?.permissions().mode();
+        // Otherwise the temporary directory could not be removed.
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755))?;
+        assert_eq!(mode & 0o777, 0o555);
+        for i in 0..8 {
+            let path = locked.join(format!("file{i}.txt"));
+            assert_eq!(std::fs::read_to_string(path)?, format!("Contents of {i}"));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn reading_entries() -> Result {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.add_directory("META-INF", default())?;
+        writer.start_file("META-INF/MANIFEST.MF", default())?;
+        writer.write_all(b"Manifest-Version: 1.0\n")?;
+        writer.start_file("binary", default())?;
+        writer.write_all(&[0xFF, 0xFE])?;
+        let mut archive = ZipArchive::new(writer.finish()?)?;
+
+        let manifest = Path::new("META-INF").join("MANIFEST.MF");
+        assert_eq!(read_file_to_string(&mut archive, &manifest)?, "Manifest-Version: 1.0\n");
+        assert_eq!(read_file_to_bytes(&mut archive, "binary")?, vec![0xFF, 0xFE]);
+        assert!(read_file_to_string(&mut archive, "binary").is_err());
+        assert!(read_file_to_bytes(&mut archive, "missing").is_err());
+        assert!(read_file_to_bytes(&mut archive, "META-INF").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn many_entries() -> Result {
+        let count = ZIP64_ENTRY_THRESHOLD + 2;
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for i in 0..count {
+            writer.start_file(format!("{i}.txt"), file_options(None))?;
+        }
+        writer.write_all(b"last")?;
+        let mut archive = ZipArchive::new(writer.finish()?)?;
+        assert_eq!(archive.len(), count);
+        assert_eq!(read_file_to_string(&mut archive, format!("{}.txt", count - 1))?, "last");
+        Ok(())
+    }
+
+    #[test]
+    #[ignore]
+    fn large_entry() -> Result {
+        // Zeros compress well, so the archive itself stays small.
+        let size = ZIP64_SIZE_THRESHOLD + 1024 * 1024;
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("large.bin", file_options(Some(1)))?;
+        std::io::copy(&mut std::io::repeat(0).take(size), &mut writer)?;
+        let mut archive = ZipArchive::new(writer.finish()?)?;
+        let mut file = archive.by_name("large.bin")?;
+        assert_eq!(file.size(), size);
+        assert_eq!(std::io::copy(&mut file, &mut std::io::sink())?, size);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn unrepresentable_names() -> Result {
+        use std::os::unix::ffi::OsStrExt;
+        let temp = tempfile::tempdir()?;
+        crate::fs::write(temp.path().join("fine.txt"), "fine")?;
+        crate::fs::write(temp.path().join(OsStr::from_bytes(b"invalid-\xFF.txt")), "invalid")?;
+        let error = pack_directory(Cursor::new(Vec::new()), None, temp.path()).unwrap_err();
+        assert!(error.to_string().contains("not valid UTF-8"), "{error}");
+
+        let long_name = "a".repeat(usize::from(u16::MAX) + 1);
+        assert!(new_entry_name(Path::new(&long_name)).is_err());
+        assert_eq!(new_entry_name(&Path::new("dir").join("file"))?, "dir/file");
+        Ok(())
+    }
+}
@@ -0,0 +1,252 @@
+//! Common CI operations, callable directly from the workflow steps.
+
+use ide_ci::prelude::*;
+
+use clap::Args;
+use clap::Parser;
+use clap::Subcommand;
+use ide_ci::actions::artifacts;
+use ide_ci::actions::artifacts::context::Context;
+use ide_ci::actions::artifacts::download::ArtifactDownloader;
+use ide_ci::actions::artifacts::run_session::SessionClient;
+use ide_ci::archive::CompressionOptions;
+use ide_ci::archive::ExtractOptions;
+use ide_ci::cache::Cache;
+use ide_ci::github::release::ReleaseSpec;
+use ide_ci::log::setup_logging;
+use ide_ci::models::config::RepoContext;
+use octocrab::models::ReleaseId;
+
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum Artifact {
+    /// Upload the file or the directory as an artifact of the current run.
+    Upload {
+        path:  PathBuf,
+        /// Name of the artifact. Defaults to the name of the uploaded file or directory.
+        #[clap(long)]
+        name:  Option<String>,
+        /// Upload the directory's files to the run's shared content-addressed store, skipping
+        /// the ones already there. Such an artifact must be downloaded with `--dedup` as well.
+        #[clap(long)]
+        dedup: bool,
+    },
+    /// Download all the files of the artifact of the current run.
+    Download {
+        name:   String,
+        /// Directory to download the files to.
+        #[clap(default_value = ".")]
+        output: PathBuf,
+        /// The artifact was uploaded with `--dedup`.
+        #[clap(long)]
+        dedup:  bool,
+    },
+    /// List the artifacts of the current run.
+    List,
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum Archive {
+    /// Pack the directory contents. The format is deduced from the archive's extension.
+    Pack {
+        archive:   PathBuf,
+        directory: PathBuf,
+        /// Compression level, from 0 (fastest) to 9 (smallest archive).
+        #[clap(long)]
+        level:     Option<u32>,
+    },
+    /// Extract the archive to the directory.
+    Unpack {
+        archive:            PathBuf,
+        #[clap(default_value = ".")]
+        directory:          PathBuf,
+        /// Restore the owners of the files recorded in the tarball. Requires root privileges.
+        #[clap(long)]
+        preserve_ownership: bool,
+    },
+    /// List the archive entries.
+    List { archive: PathBuf },
+    /// Check that the archive can be extracted without errors.
+    Verify { archive: PathBuf },
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct Release {
+    /// The repository, in the `owner/repo_name` format.
+    #[clap(long, env = "GITHUB_REPOSITORY")]
+    pub repo:   RepoContext,
+    #[clap(subcommand)]
+    pub action: ReleaseAction,
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum ReleaseAction {
+    /// Create a draft release, or update the existing draft with the same tag. Prints the
+    /// release's ID.
+    Create {
+        #[clap(long)]
+        tag:        String,
+        /// Defaults to the tag.
+        #[clap(long)]
+        name:       Option<String>,
+        /// Release notes, in Markdown.
+        #[clap(long, default_value = "")]
+        body:       String,
+        /// Commit (or branch) to create the tag from, if it does not exist yet.
+        #[clap(long)]
+        target:     Option<String>,
+        #[clap(long)]
+        prerelease: bool,
+    },
+    /// Upload the files as the release assets, named after the files.
+    UploadAsset {
+        #[clap(long, env = "ENSO_RELEASE_ID")]
+        release_id: ReleaseId,
+        #[clap(required = true)]
+        files:      Vec<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum CacheAction {
+    /// Remove the least recently used entries from the cache.
+    Clean {
+        /// Cache directory. Defaults to the shared cache in the home directory.
+        #[clap(long)]
+        path:     Option<PathBuf>,
+        /// Size to which the cache is trimmed, like `10GB`. By default, all the entries that are
+        /// not in use are removed.
+        #[clap(long)]
+        max_size: Option<byte_unit::Byte>,
+    },
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum Command {
+    /// Manage the artifacts of the current GitHub Actions run.
+    #[clap(subcommand)]
+    Artifact(Artifact),
+    /// Manage the archives.
+    #[clap(subcommand)]
+    Archive(Archive),
+    /// Manage the GitHub releases.
+    Release(Release),
+    /// Manage the local cache of the downloaded and built items.
+    #[clap(subcommand)]
+    Cache(CacheAction),
+}
+
+/// Common CI operations.
+#[derive(Clone, Debug, Parser)]
+#[clap(author, version, about, long_about = None)]
+pub struct Cli {
+    #[clap(subcommand)]
+    pub command: Command,
+}
+
+async fn artifact(command: Artifact) -> Result {
+    match command {
+        Artifact::Upload { path, name, dedup } => {
+            let name = match name {
+                Some(name) => name,
+                None =>
+                    path.file_name().context("Cannot name the artifact.")?.to_string_lossy().into(),
+            };
+            if dedup {
+                ensure!(path.is_dir(), "Only directories can be uploaded with deduplication.");
+                let context = Context::new_from_env()?;
+                artifacts::dedup::upload_directory(&context, &path, &name, default()).await?;
+                Ok(())
+            } else if path.is_dir() {
+                artifacts::upload_directory(path, name).await
+            } else {
+                artifacts::upload_single_file(path, name).await
+            }
+        }
+        Artifact::Download { name, output, dedup: true } => {
+            let context = Context::new_from_env()?;
+            artifacts::dedup::download_directory(&context, &name, &output).await?;
+            Ok(())
+        }
+        Artifact::Download { name, output, dedup: false } => {
+            let downloader = ArtifactDownloader::new(SessionClient::new_from_env()?, name).await?;
+            downloader.download_all_to(&output).await
+        }
+        Artifact::List => {
+            for artifact in SessionClient::new_from_env()?.list_artifacts().await? {
+                println!("{}\t{}", artifact.name, artifact.size);
+            }
+            Ok(())
+        }
+    }
+}
+
+async fn archive(command: Archive) -> Result {
+    match command {
+        Archive::Pack { archive, directory, level } => {
+            let options = CompressionOptions { level, ..CompressionOptions::fast() };
+            options.validate()?;
+            ide_ci::archive::pack_directory_contents(archive, directory, options).await
+        }
+        Archive::Unpack { archive, directory, preserve_ownership } => {
+            let options = ExtractOptions { preserve_ownership };
+            ide_ci::archive::extract_to_with(archive, directory, options).await
+        }
+        Archive::List { archive } => {
+            for (path, entry) in ide_ci::archive::list(archive).await? {
+                let suffix = if entry.is_dir { "/" } else { "" };
+                println!("{}{suffix}\t{}", path.display(), entry.size);
+            }
+            Ok(())
+        }
+        Archive::Verify { archive } => ide_ci::archive::verify(archive).await,
+    }
+}
+
+async fn release(Release { repo, action }: Release) -> Result {
+    match action {
+        ReleaseAction::Create { tag, name, body, target, prerelease } => {
+            let client = ide_ci::github::Client::from_env()?;
+            let name = name.unwrap_or_else(|| tag.clone());
+            let spec = ReleaseSpec { tag, name, body, target_commitish: target, prerelease };
+            let release =
+                ide_ci::github::release::create_or_update_draft(&repo, &client, &spec).await?;
+            println!("{}", release.id);
+            Ok(())
+        }
+        ReleaseAction::UploadAsset { release_id, files } => {
+            let client = ide_ci::github::create_client(ide_ci::github::client::token_from_env()?)?;
+            for file in files {
+                ide_ci::github::release::upload_asset(&repo, &client, release_id, file).await?;
+            }
+            Ok(())
+        }
+    }
+}
+
+async fn cache(command: CacheAction) -> Result {
+    match command {
+        CacheAction::Clean { path, max_size } => {
+            let cache = match path {
+                Some(path) => Cache::new(path).await?,
+                None => Cache::new_default().await?,
+            };
+            let max_size = max_size.map_or(0, |size| size.get_bytes() as u64);
+            let freed = cache.evict(max_size).await?;
+            let freed = byte_unit::Byte::from_bytes(freed.into()).get_appropriate_unit(true);
+            info!("Freed {freed} from {}.", cache.root().display());
+            Ok(())
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result {
+    setup_logging()?;
+    match Cli::parse().command {
+        Command::Artifact(command) => artifact(command).await,
+        Command::Archive(command) => archive(command).await,
+        Command::Release(command) => release(command).await,
+        Command::Cache(command) => cache(command).await,
+    }
+}
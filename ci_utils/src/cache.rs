@@ -8,15 +8,28 @@ use crate::prelude::*;
 use anyhow::Context;
 use std::hash::Hasher;
 
+use crate::download::Download;
+use filetime::FileTime;
+use fs2::FileExt;
+use reqwest::IntoUrl;
 use serde::de::DeserializeOwned;
 use sha2::Digest;
+use std::time::SystemTime;
 
 pub use goodie::Goodie;
 
 pub const VERSION: u8 = 1;
 
+/// Extension of the index files, that describe the complete cache entries.
+pub const INDEX_EXTENSION: &str = "json";
+
+/// Extension of the lock files, that guard the cache entries against concurrent access.
+pub const LOCK_EXTENSION: &str = "lock";
+
 pub fn default_path() -> Result<PathBuf> {
-    Ok(dirs::home_dir().context("Cannot locate home directory.")?.join_iter([".enso-ci", "cache"]))
+    Ok(dirs::home_dir()
+        .context("Cannot locate home directory.")?
+        .join_iter([".cache", "enso-build"]))
 }
 
 pub trait Storable: Debug + Send + Sync + 'static {
@@ -42,9 +55,14 @@ pub struct EntryIndex<S: Storable> {
 }
 
 pub struct HashToDigest<'a, D: Digest>(&'a mut D);
-impl<'a, D: Digest> Hasher for HashToDigest<'a, D> {
+impl<'a, D: Digest + Clone> Hasher for HashToDigest<'a, D> {
+    /// The leading bytes of the digest of the data written so far.
     fn finish(&self) -> u64 {
-        todo!()
+        let digest = self.0.clone().finalize();
+        let mut bytes = [0; 8];
+        let length = digest.len().min(bytes.len());
+        bytes[..length].copy_from_slice(&digest[..length]);
+        u64::from_le_bytes(bytes)
     }
 
     fn write(&mut self, bytes: &[u8]) {
@@ -65,9 +83,143 @@ pub fn digest<S: Storable>(storable: &S) -> Result<String> {
     Ok(data_encoding::BASE64URL_NOPAD.encode(&digest))
 }
 
+/// Download the archive and extract it into the [default cache](default_path).
+///
+/// See [`Cache::download_and_extract`].
+pub async fn download_and_extract(url: impl IntoUrl, sha256: Option<&str>) -> Result<EntryGuard> {
+    Cache::new_default().await?.download_and_extract(url, sha256).await
+}
+
+/// Key of the cache entry with the extracted archive.
+fn extracted_archive_key(url: &Url, sha256: Option<&str>) -> (&'static str, String) {
+    match sha256 {
+        Some(sha256) => ("extracted-sha256", sha256.trim().to_lowercase()),
+        None => ("extracted-url", url.to_string()),
+    }
+}
+
+/// Digest of the key passed to [`Cache::get_or_create`].
+pub fn key_digest<K: Serialize + ?Sized>(key: &K) -> Result<String> {
+    let mut digest = sha2::Sha224::default();
+    sha2::Digest::update(&mut digest, &[VERSION]);
+    sha2::Digest::update(&mut digest, &serde_json::to_vec(key)?);
+    Ok(data_encoding::BASE64URL_NOPAD.encode(&digest.finalize()))
+}
+
+/// Access to a cache entry, released when dropped.
+///
+/// The exclusive lock is held while the entry is created or removed, the shared ones while it is
+/// used. The lock is held by the operating system, so it is released even if the process crashes.
+#[derive(Debug)]
+pub struct EntryLock {
+    file: std::fs::File,
+}
+
+impl EntryLock {
+    /// Wait until the lock file can be locked exclusively.
+    pub async fn acquire(path: PathBuf) -> Result<Self> {
+        let file = std::fs::OpenOptions::new().create(true).write(true).open(&path)?;
+        if file.try_lock_exclusive().is_err() {
+            debug!("Waiting for the lock on {}.", path.display());
+            let file =
+                tokio::task::spawn_blocking(move || file.lock_exclusive().map(|_| file)).await??;
+            return Ok(Self { file });
+        }
+        Ok(Self { file })
+    }
+
+    /// Wait until the lock file can be locked for shared use.
+    pub async fn acquire_shared(path: PathBuf) -> Result<Self> {
+        let file = std::fs::OpenOptions::new().create(true).write(true).open(&path)?;
+        if file.try_lock_shared().is_err() {
+            debug!("Waiting for the shared lock on {}.", path.display());
+            let file =
+                tokio::task::spawn_blocking(move || file.lock_shared().map(|_| file)).await??;
+            return Ok(Self { file });
+        }
+        Ok(Self { file })
+    }
+
+    /// Lock the file, unless it is already locked.
+    pub fn try_acquire(path: &Path) -> Result<Option<Self>> {
+        let file = std::fs::OpenOptions::new().create(true).write(true).open(path)?;
+        Ok(file.try_lock_exclusive().is_ok().then(|| Self { file }))
+    }
+}
+
+impl Drop for EntryLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+/// Directory of a complete cache entry, which is not evicted while this handle is alive.
+#[derive(Debug)]
+pub struct EntryGuard {
+    path:  PathBuf,
+    _lock: EntryLock,
+}
+
+impl EntryGuard {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Deref for EntryGuard {
+    type Target = Path;
+    fn deref(&self) -> &Self::Target {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for EntryGuard {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Size and the last use of a complete cache entry, used to decide what to evict.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntryUsage {
+    /// The digest naming the entry.
+    pub code:      String,
+    pub size:      u64,
+    pub last_used: SystemTime,
+}
+
+/// Entries to be evicted, so the remaining ones fit the size limit. The least recently used
+/// entries go first.
+pub fn select_for_eviction(mut entries: Vec<EntryUsage>, max_size: u64) -> Vec<EntryUsage> {
+    entries.sort_by_key(|entry| entry.last_used);
+    let mut total: u64 = entries.iter().map(|entry| entry.size).sum();
+    entries
+        .into_iter()
+        .take_while(|entry| {
+            let evict = total > max_size;
+            total -= entry.size;
+            evict
+        })
+        .collect()
+}
+
+/// Mark the entry as just used, so it is the last one to be evicted.
+fn touch(index: &Path) -> Result {
+    filetime::set_file_mtime(index, FileTime::now())?;
+    Ok(())
+}
+
+/// Storage of the values reused between the runs, like downloaded SDKs or extracted archives.
+///
+/// Each entry is a directory named after the digest of its key. The entry is complete once its
+/// index file is written. The entries are guarded by the lock files, so many processes can share
+/// the cache.
 #[derive(Clone, Debug)]
 pub struct Cache {
-    root: PathBuf,
+    root:     PathBuf,
+    /// If set, the least recently used entries are evicted after adding new ones, so the total
+    /// size of the entries does not exceed this many bytes.
+    max_size: Option<u64>,
 }
 
 impl Cache {
@@ -79,7 +231,147 @@ impl Cache {
         let root = path.into();
         crate::fs::tokio::create_dir_if_missing(&root).await?;
         debug!("Prepared cache in {}", root.display());
-        Ok(Self { root })
+        Ok(Self { root, max_size: None })
+    }
+
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn lock_path(&self, code: &str) -> PathBuf {
+        self.root.join(code).with_appended_extension(LOCK_EXTENSION)
+    }
+
+    /// Lock the entry with the given digest for exclusive use.
+    pub async fn lock_entry(&self, code: &str) -> Result<EntryLock> {
+        EntryLock::acquire(self.lock_path(code)).await
+    }
+
+    /// Directory with the value for the given key, created by the builder if it is not cached.
+    ///
+    /// The builder is given the empty entry directory to fill. If it fails, the partial entry is
+    /// removed. The returned guard keeps the entry from being evicted, including by other
+    /// processes, until it is dropped.
+    pub async fn get_or_create<K, F, Fut>(&self, key: &K, builder: F) -> Result<EntryGuard>
+    where
+        K: Serialize + ?Sized,
+        F: FnOnce(PathBuf) -> Fut,
+        Fut: Future<Output = Result>, {
+        let code = key_digest(key)?;
+        let entry_dir = self.root.join(&code);
+        let index = entry_dir.with_appended_extension(INDEX_EXTENSION);
+        let is_complete = || index.exists() && entry_dir.exists();
+        let mut builder = Some(builder);
+        let mut created = false;
+        loop {
+            let shared = EntryLock::acquire_shared(self.lock_path(&code)).await?;
+            if is_complete() {
+                if !created {
+                    crate::metrics::record_cache_lookup(true);
+                    debug!("Found {} in the cache.", entry_dir.display());
+                }
+                touch(&index)?;
+                let guard = EntryGuard { path: entry_dir.clone(), _lock: shared };
+                if created && let Some(max_size) = self.max_size {
+                    self.evict(max_size).await?;
+                }
+                return Ok(guard);
+            }
+            drop(shared);
+
+            // The shared lock cannot be upgraded, so another process might create the entry (or
+            // evict the one we have just created) before we get the exclusive one.
+            let builder = builder.take().with_context(|| {
+                format!("The cache entry {} was evicted right after creation.", entry_dir.display())
+            })?;
+            let exclusive = self.lock_entry(&code).await?;
+            if !is_complete() {
+                crate::metrics::record_cache_lookup(false);
+                crate::fs::tokio::reset_dir(&entry_dir).await?;
+                if let Err(e) = builder(entry_dir.clone()).await {
+                    crate::fs::remove_dir_if_exists(&entry_dir)?;
+                    return Err(e);
+                }
+                crate::fs::write_atomic(
+                    &index,
+                    serde_json::to_vec(&serde_json::json!({ "key": key }))?,
+                )?;
+            }
+            drop(exclusive);
+            created = true;
+        }
+    }
+
+    /// Directory with the contents of the archive at the given URL.
+    ///
+    /// If the archive was already extracted, neither the network nor the archive is touched.
+    /// Otherwise it is downloaded (resuming the interrupted download, if any), verified against
+    /// the SHA-256 digest if given, and extracted. The archive is removed afterwards, so only the
+    /// extracted entry counts towards the size limit. The entries of the archives with the pinned
+    /// digest are shared by all URLs, so the mirrors don't duplicate them.
+    pub async fn download_and_extract(
+        &self,
+        url: impl IntoUrl,
+        sha256: Option<&str>,
+    ) -> Result<EntryGuard> {
+        let mut download = Download::new(url)?;
+        if let Some(sha256) = sha256 {
+            download = download.sha256(sha256);
+        }
+        let key = extracted_archive_key(&download.urls[0], sha256);
+        self.get_or_create(&key, |entry_dir| async move {
+            let archive = download.fetch_to(&self.root).await?;
+            let result = crate::archive::extract_to(&archive, &entry_dir).await;
+            crate::fs::tokio::remove_dir_if_exists(download.cache_entry(&self.root)).await?;
+            result
+        })
+        .await
+    }
+
+    /// Usage of the complete entries in the cache.
+    pub fn entries(&self) -> Result<Vec<EntryUsage>> {
+        let mut entries = Vec::new();
+        for item in crate::fs::read_dir(&self.root)? {
+            let index = item?.path();
+            if index.extension() != Some(OsStr::new(INDEX_EXTENSION)) {
+                continue;
+            }
+            let code = match index.file_stem().and_then(OsStr::to_str) {
+                Some(code) => code.to_string(),
+                None => continue,
+            };
+            let last_used = index.metadata()?.modified()?;
+            let size = crate::fs::tree_size(self.root.join(&code))?;
+            entries.push(EntryUsage { code, size, last_used });
+        }
+        Ok(entries)
+    }
+
+    /// Remove the least recently used entries, until the rest fits the size limit.
+    ///
+    /// The entries being used by other processes are skipped. Returns the number of freed bytes.
+    #[context("Failed to evict the entries from the cache in {}.", self.root.display())]
+    pub async fn evict(&self, max_size: u64) -> Result<u64> {
+        let mut freed = 0;
+        for entry in select_for_eviction(self.entries()?, max_size) {
+            let lock_path = self.root.join(&entry.code).with_appended_extension(LOCK_EXTENSION);
+            let _lock = match EntryLock::try_acquire(&lock_path)? {
+                Some(lock) => lock,
+                None => continue,
+            };
+            debug!("Evicting {} ({} bytes) from the cache.", entry.code, entry.size);
+            // The index goes first, so the entry is never seen as complete when partially removed.
+            let entry_dir = self.root.join(&entry.code);
+            crate::fs::remove_file_if_exists(entry_dir.with_appended_extension(INDEX_EXTENSION))?;
+            crate::fs::remove_dir_if_exists(&entry_dir)?;
+            freed += entry.size;
+        }
+        Ok(freed)
     }
 
     pub fn get<S>(&self, storable: S) -> BoxFuture<'static, Result<S::Output>>
@@ -89,32 +381,53 @@ impl Cache {
             // FIXME trace
             let code = digest(&storable)?;
             let entry_dir = this.root.join(&code);
-            let entry_meta = entry_dir.with_appended_extension("json");
-
-            let retrieve = async {
+            let entry_meta = entry_dir.with_appended_extension(INDEX_EXTENSION);
+            let retrieve = || async {
                 let info = entry_meta.read_to_json::<EntryIndex<S>>()?;
                 crate::fs::require_exist(&entry_dir)?;
                 storable.adapt(entry_dir.clone(), info.metadata).await
             };
 
-            match retrieve.await {
+            // Hits only need the entry not to be removed, so they can be served concurrently.
+            let shared = EntryLock::acquire_shared(this.lock_path(&code)).await?;
+            if let Ok(out) = retrieve().await {
+                crate::metrics::record_cache_lookup(true);
+                debug!("Found in cache, skipping generation.");
+                touch(&entry_meta)?;
+                return Ok(out);
+            }
+            drop(shared);
+
+            // Another process might have generated the value while we were waiting for the lock.
+            let lock = this.lock_entry(&code).await?;
+            match retrieve().await {
                 Ok(out) => {
+                    crate::metrics::record_cache_lookup(true);
                     debug!("Found in cache, skipping generation.");
-                    return Ok(out);
+                    touch(&entry_meta)?;
+                    Ok(out)
                 }
                 Err(e) => {
+                    crate::metrics::record_cache_lookup(false);
                     debug!("Value cannot be retrieved from cache because: {e}");
-                    crate::fs::reset_dir(&entry_dir)?;
+                    crate::fs::tokio::reset_dir(&entry_dir).await?;
                     let key = storable.key();
                     let info = EntryIndex::<S> {
                         metadata: storable
-                            .generate(this, entry_dir.clone())
+                            .generate(this.clone(), entry_dir.clone())
                             .instrument(info_span!("Generating value to be cached.", ?key))
                             .await?,
                         key:      key.clone(),
                     };
-                    entry_meta.write_as_json(&info)?;
-                    storable.adapt(entry_dir, info.metadata).await
+                    crate::fs::write_atomic(&entry_meta, serde_json::to_vec(&info)?)?;
+                    let out = storable.adapt(entry_dir, info.metadata).await?;
+                    drop(lock);
+                    if let Some(max_size) = this.max_size {
+                        // The shared lock keeps the new entry from being evicted by ourselves.
+                        let _shared = EntryLock::acquire_shared(this.lock_path(&code)).await?;
+                        this.evict(max_size).await?;
+                    }
+                    Ok(out)
                 }
             }
         }
@@ -122,7 +435,6 @@ impl Cache {
     }
 }
 
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -138,4 +450,71 @@ mod tests {
         cache.get(download_task).await?;
         Ok(())
     }
+
+    #[tokio::test]
+    async fn get_or_create_builds_once() -> Result {
+        let dir = tempfile::tempdir()?;
+        let cache = Cache::new(dir.path()).await?;
+        let build = |path: PathBuf| async move { crate::fs::write(path.join("data"), "contents") };
+        let entry = cache.get_or_create("sdk-1.0", build).await?;
+        assert_eq!(crate::fs::read_to_string(entry.join("data"))?, "contents");
+
+        let reused = cache.get_or_create("sdk-1.0", |_| async { bail!("Not cached.") }).await?;
+        assert_eq!(reused.path(), entry.path());
+        assert!(cache.get_or_create("sdk-2.0", |_| async { bail!("Failed.") }).await.is_err());
+        assert_eq!(cache.entries()?.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn used_entries_are_not_evicted() -> Result {
+        let dir = tempfile::tempdir()?;
+        let cache = Cache::new(dir.path()).await?;
+        let build = |path: PathBuf| async move { crate::fs::write(path.join("data"), "contents") };
+        let entry = cache.get_or_create("sdk-1.0", build).await?;
+        assert_eq!(cache.evict(0).await?, 0);
+        assert!(entry.join("data").exists());
+        let entry_dir = entry.path().to_owned();
+        drop(entry);
+        assert!(cache.evict(0).await? > 0);
+        assert!(!entry_dir.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn hashing_to_digest() {
+        let mut digest = sha2::Sha224::default();
+        let mut hasher = HashToDigest(&mut digest);
+        hasher.write(b"enso");
+        assert_eq!(hasher.finish(), hasher.finish());
+        assert_ne!(hasher.finish(), HashToDigest(&mut sha2::Sha224::default()).finish());
+    }
+
+    #[tokio::test]
+    async fn extracted_archive_is_reused() -> Result {
+        let dir = tempfile::tempdir()?;
+        let cache = Cache::new(dir.path()).await?;
+        let sha256 = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        let url = Url::parse("https://example.com/sdk.tar.gz")?;
+        let key = extracted_archive_key(&url, Some(sha256));
+        let extracted = cache.get_or_create(&key, |_| ready(Ok(()))).await?;
+        // The mirror is never accessed, as the archive with the same digest was extracted.
+        let mirror = "https://example.invalid/sdk.tar.gz";
+        let found = cache.download_and_extract(mirror, Some(&sha256.to_uppercase())).await?;
+        assert_eq!(found.path(), extracted.path());
+        Ok(())
+    }
+
+    #[test]
+    fn evicting_least_recently_used() {
+        let entry = |code: &str, size, seconds| EntryUsage {
+            code: code.into(),
+            size,
+            last_used: SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(seconds),
+        };
+        let entries = vec![entry("new", 30, 300), entry("old", 50, 100), entry("mid", 40, 200)];
+        let evicted = select_for_eviction(entries.clone(), 60);
+        assert_eq!(evicted.iter().map(|entry| entry.code.as_str()).collect_vec(), ["old", "mid"]);
+        assert!(select_for_eviction(entries, 120).is_empty());
+    }
 }
@@ -35,7 +35,7 @@ impl DownloadFile {
     pub fn new(url: impl IntoUrl) -> Result<Self> {
         Ok(Self {
             key:    Key { url: url.into_url()?, additional_headers: default() },
-            client: default(),
+            client: crate::net::client()?,
         })
     }
 
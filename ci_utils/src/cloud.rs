@@ -0,0 +1,4 @@
+//! Publishing to the cloud storage services.
+
+#[cfg(feature = "aws")]
+pub mod s3;
@@ -0,0 +1,262 @@
+//! Uploading release bundles and nightly builds to the S3 buckets.
+//!
+//! Besides GitHub releases, Enso distributes the engine packages through S3, usually behind a
+//! CloudFront distribution. Large files are sent with the multipart upload, so a single failed
+//! request does not restart the whole transfer.
+
+use crate::prelude::*;
+
+use crate::github::release::content_type;
+use aws_sdk_cloudfront::model::InvalidationBatch;
+use aws_sdk_cloudfront::model::Paths;
+use aws_sdk_s3::model::CompletedMultipartUpload;
+use aws_sdk_s3::model::CompletedPart;
+use aws_sdk_s3::model::ObjectCannedAcl;
+use aws_sdk_s3::types::ByteStream;
+use path_slash::PathExt;
+use tokio::io::AsyncReadExt;
+
+
+/// Files of at least this size are sent with the multipart upload.
+pub const MULTIPART_THRESHOLD: u64 = 64 * 1024 * 1024;
+
+/// Size of a single part of the multipart upload. S3 requires at least 5 MiB.
+pub const PART_SIZE: u64 = 16 * 1024 * 1024;
+
+/// `Cache-Control` for the files that never change once published, like versioned packages.
+pub const CACHE_IMMUTABLE: &str = "public, max-age=31536000, immutable";
+
+/// `Cache-Control` for the files that are overwritten, like the manifests listing the nightlies.
+pub const CACHE_REVALIDATE: &str = "no-cache";
+
+/// Uploads files to a bucket, optionally invalidating the CloudFront distribution serving it.
+#[derive(Clone, Debug)]
+pub struct Uploader {
+    pub client:        aws_sdk_s3::Client,
+    pub bucket:        String,
+    /// Prefix of the object keys, without the trailing slash.
+    pub key_prefix:    Option<String>,
+    pub acl:           Option<ObjectCannedAcl>,
+    pub cache_control: Option<String>,
+    /// CloudFront client and the ID of the distribution to be invalidated after uploads.
+    pub cloudfront:    Option<(aws_sdk_cloudfront::Client, String)>,
+}
+
+impl Uploader {
+    pub fn new(client: aws_sdk_s3::Client, bucket: impl Into<String>) -> Self {
+        Self {
+            client,
+            bucket: bucket.into(),
+            key_prefix: None,
+            acl: None,
+            cache_control: None,
+            cloudfront: None,
+        }
+    }
+
+    /// Create the uploader with the credentials and region taken from the environment.
+    pub async fn from_env(bucket: impl Into<String>) -> Self {
+        let config = aws_config::load_from_env().await;
+        Self::new(aws_sdk_s3::Client::new(&config), bucket)
+    }
+
+    pub fn key_prefix(mut self, prefix: impl AsRef<str>) -> Self {
+        let prefix = prefix.as_ref().trim_matches('/');
+        self.key_prefix = (!prefix.is_empty()).then(|| prefix.to_string());
+        self
+    }
+
+    pub fn acl(mut self, acl: ObjectCannedAcl) -> Self {
+        self.acl = Some(acl);
+        self
+    }
+
+    pub fn cache_control(mut self, cache_control: impl Into<String>) -> Self {
+        self.cache_control = Some(cache_control.into());
+        self
+    }
+
+    /// Invalidate the given CloudFront distribution after each [upload](Self::upload_dir).
+    pub async fn invalidate_distribution(mut self, distribution_id: impl Into<String>) -> Self {
+        let config = aws_config::load_from_env().await;
+        self.cloudfront = Some((aws_sdk_cloudfront::Client::new(&config), distribution_id.into()));
+        self
+    }
+
+    /// Full key of the object at the given path, relative to the prefix.
+    pub fn key(&self, path: &str) -> String {
+        let path = path.trim_start_matches('/');
+        match &self.key_prefix {
+            Some(prefix) => format!("{prefix}/{path}"),
+            None => path.to_string(),
+        }
+    }
+
+    /// Upload the file to the given path, relative to the key prefix. Returns the object key.
+    #[context("Failed to upload {} to the S3 bucket {}.", file.display(), self.bucket)]
+    pub async fn upload_file(&self, file: &Path, path: &str) -> Result<String> {
+        let key = self.key(path);
+        let size = crate::fs::tokio::metadata(file).await?.len();
+        let content_type = content_type(file).to_string();
+        info!("Uploading {} to s3://{}/{key}.", file.display(), self.bucket);
+        if size >= MULTIPART_THRESHOLD {
+            self.upload_multipart(file, &key, &content_type, size).await?;
+        } else {
+            self.client
+                .put_object()
+                .bucket(&self.bucket)
+                .key(&key)
+                .content_type(content_type)
+                .set_cache_control(self.cache_control.clone())
+                .set_acl(self.acl.clone())
+                .body(ByteStream::from_path(file).await?)
+                .send()
+                .await?;
+        }
+        Ok(key)
+    }
+
+    /// Upload all files in the directory, keeping their relative paths under the given path.
+    ///
+    /// If the CloudFront distribution is set, the uploaded paths are invalidated afterwards.
+    /// Returns the keys of the uploaded objects.
+    pub async fn upload_dir(&self, dir: &Path, path: &str) -> Result<Vec<String>> {
+        let files = walkdir::WalkDir::new(dir)
+            .into_iter()
+            .collect_result()?
+            .into_iter()
+            .filter(|entry| !entry.file_type().is_dir())
+            .collect_vec();
+        let mut keys = Vec::with_capacity(files.len());
+        for entry in files {
+            let relative = entry.path().strip_prefix(dir)?.to_slash_lossy();
+            let target = format!("{}/{relative}", path.trim_end_matches('/'));
+            keys.push(self.upload_file(entry.path(), &target).await?);
+        }
+        if self.cloudfront.is_some() {
+            self.invalidate(&keys).await?;
+        }
+        Ok(keys)
+    }
+
+    async fn upload_multipart(
+        &self,
+        file: &Path,
+        key: &str,
+        content_type: &str,
+        size: u64,
+    ) -> Result {
+        let upload = self
+            .client
+            .create_multipart_upload()
+            .bucket(&self.bucket)
+            .key(key)
+            .content_type(content_type)
+            .set_cache_control(self.cache_control.clone())
+            .set_acl(self.acl.clone())
+            .send()
+            .await?;
+        let upload_id = upload.upload_id().context("S3 did not assign the upload ID.")?;
+        let result = self.upload_parts(file, key, upload_id, size).await;
+        if result.is_err() {
+            // Otherwise the bucket keeps (and bills) the uploaded parts.
+            let abort = self
+                .client
+                .abort_multipart_upload()
+                .bucket(&self.bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .send()
+                .await;
+            if let Err(e) = abort {
+                warn!("Failed to abort the multipart upload of {key}: {e}");
+            }
+        }
+        result
+    }
+
+    async fn upload_parts(&self, file: &Path, key: &str, upload_id: &str, size: u64) -> Result {
+        let mut file = crate::fs::tokio::open(file).await?;
+        let mut parts = Vec::new();
+        for (index, length) in part_lengths(size, PART_SIZE).into_iter().enumerate() {
+            let part_number = i32::try_from(index + 1)?;
+            let mut buffer = vec![0; usize::try_from(length)?];
+            file.read_exact(&mut buffer).await?;
+            debug!("Uploading part {part_number} of {key} ({length} bytes).");
+            let part = self
+                .client
+                .upload_part()
+                .bucket(&self.bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .part_number(part_number)
+                .body(ByteStream::from(buffer))
+                .send()
+                .await?;
+            let e_tag = part.e_tag().context("S3 did not return the ETag of the part.")?;
+            parts.push(CompletedPart::builder().e_tag(e_tag).part_number(part_number).build());
+        }
+        self.client
+            .complete_multipart_upload()
+            .bucket(&self.bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .multipart_upload(CompletedMultipartUpload::builder().set_parts(Some(parts)).build())
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    /// Invalidate the CloudFront cache of the objects with the given keys.
+    #[context("Failed to invalidate the CloudFront cache.")]
+    pub async fn invalidate(&self, keys: &[String]) -> Result {
+        let (client, distribution_id) =
+            self.cloudfront.as_ref().context("No CloudFront distribution is set.")?;
+        let items = keys.iter().map(|key| format!("/{key}")).collect_vec();
+        let paths = Paths::builder().quantity(i32::try_from(items.len())?).set_items(Some(items));
+        let batch = InvalidationBatch::builder()
+            .paths(paths.build())
+            .caller_reference(uuid::Uuid::new_v4().to_string())
+            .build();
+        info!("Invalidating {} paths in the distribution {distribution_id}.", keys.len());
+        client
+            .create_invalidation()
+            .distribution_id(distribution_id)
+            .invalidation_batch(batch)
+            .send()
+            .await?;
+        Ok(())
+    }
+}
+
+/// Lengths of the consecutive parts the file of the given size is split into.
+pub fn part_lengths(size: u64, part_size: u64) -> Vec<u64> {
+    let full_parts = size / part_size;
+    let remainder = size % part_size;
+    let mut lengths = vec![part_size; usize::try_from(full_parts).unwrap_or(usize::MAX)];
+    if remainder > 0 {
+        lengths.push(remainder);
+    }
+    lengths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splitting_into_parts() {
+        assert_eq!(part_lengths(10, 4), vec![4, 4, 2]);
+        assert_eq!(part_lengths(8, 4), vec![4, 4]);
+        assert!(part_lengths(0, 4).is_empty());
+    }
+
+    #[tokio::test]
+    async fn object_keys() {
+        let config = aws_sdk_s3::Config::builder().build();
+        let uploader = Uploader::new(aws_sdk_s3::Client::from_conf(config), "packages");
+        assert_eq!(uploader.key("/enso.zip"), "enso.zip");
+        let uploader = uploader.key_prefix("/enso/nightly/");
+        assert_eq!(uploader.key("enso.zip"), "enso/nightly/enso.zip");
+    }
+}
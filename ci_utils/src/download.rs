@@ -0,0 +1,226 @@
+//! Downloading third-party tools and SDKs into the cache directory.
+//!
+//! Unlike [`crate::io::web::download_file`], the downloaded files are kept between the runs, can
+//! be served by multiple mirrors, are verified against a pinned SHA-256 digest and interrupted
+//! downloads are resumed.
+
+use crate::prelude::*;
+
+use crate::io::web::handle_error_response;
+use reqwest::header::RANGE;
+use reqwest::IntoUrl;
+use reqwest::StatusCode;
+use sha2::Digest;
+use tokio::io::AsyncWriteExt;
+
+
+/// Name of the cache subdirectory with the downloads.
+pub const CACHE_SUBDIRECTORY: &str = "downloads";
+
+/// Fail if the file's SHA-256 digest does not match the expected one (given as a hex string).
+///
+/// Hashing large files takes a while, so async code should use [`verify_sha256_async`].
+pub fn verify_sha256(path: impl AsRef<Path>, expected: &str) -> Result {
+    let path = path.as_ref();
+    let actual = sha256_file(path)?;
+    ensure!(
+        actual.eq_ignore_ascii_case(expected.trim()),
+        "Checksum mismatch for {}: expected {expected}, got {actual}.",
+        path.display()
+    );
+    Ok(())
+}
+
+/// [`verify_sha256`] run on a blocking thread.
+pub async fn verify_sha256_async(path: impl AsRef<Path>, expected: impl Into<String>) -> Result {
+    let path = path.as_ref().to_owned();
+    let expected = expected.into();
+    tokio::task::spawn_blocking(move || verify_sha256(path, &expected)).await?
+}
+
+/// SHA-256 digest of the file, as a lowercase hex string.
+pub fn sha256_file(path: impl AsRef<Path>) -> Result<String> {
+    let mut file = crate::fs::open(path)?;
+    let mut hasher = sha2::Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(data_encoding::HEXLOWER.encode(&hasher.finalize()))
+}
+
+/// File to be downloaded to the cache.
+#[derive(Clone, Debug)]
+pub struct Download {
+    /// Alternative locations of the same file, tried in order.
+    pub urls:     Vec<Url>,
+    /// Expected SHA-256 digest of the file, as a hex string.
+    pub sha256:   Option<String>,
+    /// Name of the file in the cache.
+    pub filename: PathBuf,
+    pub client:   reqwest::Client,
+}
+
+impl Download {
+    pub fn new(url: impl IntoUrl) -> Result<Self> {
+        let url = url.into_url()?;
+        let filename = crate::io::filename_from_url(&url)?;
+        Ok(Self { urls: vec![url], sha256: None, filename, client: crate::net::client()? })
+    }
+
+    /// Add a mirror, which is tried if the previous locations fail.
+    pub fn mirror(mut self, url: impl IntoUrl) -> Result<Self> {
+        self.urls.push(url.into_url()?);
+        Ok(self)
+    }
+
+    /// Pin the expected SHA-256 digest of the file.
+    pub fn sha256(mut self, digest: impl Into<String>) -> Self {
+        self.sha256 = Some(digest.into());
+        self
+    }
+
+    /// Directory within the cache where the file is stored.
+    ///
+    /// It is named after the pinned digest, so all the mirrors share the entry. Without the digest,
+    /// it is named after the primary URL.
+    pub fn cache_entry(&self, cache_root: &Path) -> PathBuf {
+        let key = match &self.sha256 {
+            Some(digest) => digest.trim().to_lowercase(),
+            None => {
+                let url = self.urls.first().map_or("", Url::as_str);
+                data_encoding::HEXLOWER.encode(&sha2::Sha256::digest(url.as_bytes()))
+            }
+        };
+        cache_root.join(CACHE_SUBDIRECTORY).join(key)
+    }
+
+    /// Download the file to the [default cache](crate::cache::default_path), unless it is
+    /// already there. Returns the path to the file.
+    pub async fn fetch(&self) -> Result<PathBuf> {
+        self.fetch_to(&crate::cache::default_path()?).await
+    }
+
+    /// Download the file to the given cache, unless it is already there. Returns the path to the
+    /// file.
+    ///
+    /// The download is guarded by a lock file, so the processes fetching the same file wait for
+    /// each other rather than writing to the same partial file.
+    #[context("Failed to download {}.", self.filename.display())]
+    pub async fn fetch_to(&self, cache_root: &Path) -> Result<PathBuf> {
+        let directory = self.cache_entry(cache_root);
+        let target = directory.join(&self.filename);
+        crate::fs::tokio::create_dir_if_missing(&directory).await?;
+        let lock_path = target.with_appended_extension(crate::cache::LOCK_EXTENSION);
+        let _lock = crate::cache::EntryLock::acquire(lock_path).await?;
+        if target.exists() {
+            match self.verify(&target).await {
+                Ok(()) => {
+                    debug!("Found {} in the cache.", target.display());
+                    return Ok(target);
+                }
+                Err(e) => {
+                    warn!("Discarding the cached file: {e:#}");
+                    crate::fs::remove_file_if_exists(&target)?;
+                }
+            }
+        }
+        // Incomplete downloads are kept aside, so they are never mistaken for the complete ones.
+        let partial = target.with_appended_extension("part");
+        let mut failures = Vec::new();
+        for url in &self.urls {
+            match self.fetch_from(url, &partial).await {
+                Ok(()) => {
+                    tokio::fs::rename(&partial, &target).await?;
+                    return Ok(target);
+                }
+                Err(e) => {
+                    warn!("Failed to download from {url}: {e:#}");
+                    failures.push(format!("{url}: {e:#}"));
+                }
+            }
+        }
+        bail!("All {} locations failed:\n{}", self.urls.len(), failures.join("\n"))
+    }
+
+    async fn verify(&self, path: &Path) -> Result {
+        match &self.sha256 {
+            Some(expected) => verify_sha256_async(path, expected).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Download the file from the URL to the partial file, resuming the previous download if
+    /// the server supports it.
+    async fn fetch_from(&self, url: &Url, partial: &Path) -> Result {
+        for resume in [true, false] {
+            let offset = match resume {
+                true => tokio::fs::metadata(partial).await.map_or(0, |metadata| metadata.len()),
+                false => 0,
+            };
+            let mut request = self.client.get(url.clone());
+            if offset > 0 {
+                request = request.header(RANGE, format!("bytes={offset}-"));
+            }
+            let response = request.send().await?;
+            if offset > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+                debug!("Cannot resume the download of {url}, starting over.");
+                continue;
+            }
+            let response = handle_error_response(response).await?;
+            // The servers not supporting the ranges just send the whole file.
+            let append = response.status() == StatusCode::PARTIAL_CONTENT;
+            if append {
+                info!("Resuming the download of {url} from byte {offset}.");
+            } else {
+                info!("Downloading {url}.");
+            }
+            let mut options = tokio::fs::OpenOptions::new();
+            match append {
+                true => options.append(true),
+                false => options.write(true).create(true).truncate(true),
+            };
+            let mut file = options.open(partial).await?;
+            let mut body = response.bytes_stream();
+            while let Some(chunk) = body.next().await {
+                file.write_all(&chunk?).await?;
+            }
+            file.flush().await?;
+            drop(file);
+            return self.verify(partial).await.inspect_err(|_| {
+                // The partial file is useless, as it is not a prefix of the expected file.
+                let _ = crate::fs::remove_file_if_exists(partial);
+            });
+        }
+        bail!("Failed to download {url}.")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    #[test]
+    fn cache_entries() -> Result {
+        let root = Path::new("cache");
+        let download = Download::new("https://example.com/tools/sbt-1.5.5.tgz")?
+            .mirror("https://mirror.example.com/sbt-1.5.5.tgz")?;
+        let unpinned = download.cache_entry(root);
+        let pinned = download.clone().sha256(HELLO_SHA256.to_uppercase()).cache_entry(root);
+        assert_eq!(pinned, root.join(CACHE_SUBDIRECTORY).join(HELLO_SHA256));
+        assert_ne!(unpinned, pinned);
+        assert_eq!(download.filename, Path::new("sbt-1.5.5.tgz"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn uses_verified_cached_file() -> Result {
+        let cache = tempfile::tempdir()?;
+        // The URL is never accessed, as the file is already in the cache.
+        let download = Download::new("https://example.invalid/hello.txt")?.sha256(HELLO_SHA256);
+        let cached = download.cache_entry(cache.path()).join("hello.txt");
+        crate::fs::create_parent_dir_if_missing(&cached)?;
+        crate::fs::write(&cached, "hello")?;
+        assert_eq!(download.fetch_to(cache.path()).await?, cached);
+        Ok(())
+    }
+}
@@ -7,21 +7,50 @@ use std::env::set_var;
 use std::env::split_paths;
 use unicase::UniCase;
 
+/// Declare an environment variable, together with the type of its value.
+///
+/// The variable is required unless `optional` follows the type, e.g. `ENSO_KEEP_TEMP, bool,
+/// optional`. All declared variables are listed by [`registry::dump`].
 #[macro_export]
 macro_rules! define_env_var {
-    ($(#[$attr:meta])* $name: ident, PathBuf) => {
+    (@register $name: ident, $ty_name: expr) => {
+        $crate::define_env_var!(@register $name, $ty_name, true);
+    };
+    (@register $name: ident, $ty_name: expr, optional) => {
+        $crate::define_env_var!(@register $name, $ty_name, false);
+    };
+    (@register $name: ident, $ty_name: expr, $required: literal) => {
+        $crate::env::registry::inventory::submit! {
+            $crate::env::registry::Declaration {
+                name:      stringify!($name),
+                type_name: $ty_name,
+                required:  $required,
+            }
+        }
+    };
+    ($(#[$attr:meta])* $name: ident, PathBuf $(, $requirement: ident)?) => {
+        $crate::define_env_var!(@register $name, "PathBuf" $(, $requirement)?);
         #[allow(non_upper_case_globals)]
         $(#[$attr])*
         pub const $name: $crate::env::new::PathBufVariable =
             $crate::env::new::PathBufVariable(stringify!($name));
     };
-    ($(#[$attr:meta])* $name: ident, String) => {
+    ($(#[$attr:meta])* $name: ident, String $(, $requirement: ident)?) => {
+        $crate::define_env_var!(@register $name, "String" $(, $requirement)?);
         #[allow(non_upper_case_globals)]
         $(#[$attr])*
         pub const $name: $crate::env::new::SimpleVariable<String, str> =
             $crate::env::new::SimpleVariable::new(stringify!($name));
     };
-    ($(#[$attr:meta])* $name: ident, $ty_name: ty) => {
+    ($(#[$attr:meta])* $name: ident, bool $(, $requirement: ident)?) => {
+        $crate::define_env_var!(@register $name, "bool" $(, $requirement)?);
+        #[allow(non_upper_case_globals)]
+        $(#[$attr])*
+        pub const $name: $crate::env::new::BoolVariable =
+            $crate::env::new::BoolVariable(stringify!($name));
+    };
+    ($(#[$attr:meta])* $name: ident, $ty_name: ty $(, $requirement: ident)?) => {
+        $crate::define_env_var!(@register $name, stringify!($ty_name) $(, $requirement)?);
         #[allow(non_upper_case_globals)]
         $(#[$attr])*
         pub const $name: $crate::env::new::SimpleVariable<$ty_name> =
@@ -32,6 +61,11 @@ macro_rules! define_env_var {
 
 
 pub mod known;
+pub mod overlay;
+pub mod registry;
+
+pub use overlay::EnvironmentOverlay;
+pub use overlay::ProcessGuard;
 
 pub mod new {
     use super::*;
@@ -70,13 +104,22 @@ pub mod new {
             self.parse(self.get_raw()?.as_str())
         }
 
+        /// Like [`get`](Self::get), but the variable not being set is not an error.
+        fn try_get(&self) -> Result<Option<Self::Value>> {
+            match std::env::var(self.name()) {
+                Ok(value) => self.parse(&value).map(Some),
+                Err(std::env::VarError::NotPresent) => Ok(None),
+                Err(e) => Err(e).context(format!("Failed to read {}.", self.name())),
+            }
+        }
+
         fn set(&self, value: impl AsRef<Self::Borrowed>) -> Result {
             let value = self.generate(value.as_ref())?;
             Ok(self.set_raw(value))
         }
 
         fn set_workflow_output(&self, value: impl Borrow<Self::Borrowed>) -> Result {
-            Ok(crate::actions::workflow::set_output(self.name(), &self.generate(value.borrow())?))
+            crate::actions::workflow::set_output(self.name(), &self.generate(value.borrow())?)
         }
         fn set_workflow_env(&self, value: impl Borrow<Self::Borrowed>) -> Result {
             crate::actions::workflow::set_env(self.name(), &self.generate(value.borrow())?)
@@ -123,6 +166,30 @@ pub mod new {
         }
     }
 
+    /// Flag, accepting the common spellings like `1`, `yes` or `false`.
+    #[derive(Clone, Copy, Debug, Display, Ord, PartialOrd, Eq, PartialEq)]
+    pub struct BoolVariable(pub &'static str);
+
+    impl RawVariable for BoolVariable {
+        fn name(&self) -> &str {
+            self.0
+        }
+    }
+
+    impl TypedVariable for BoolVariable {
+        type Value = bool;
+        fn parse(&self, value: &str) -> Result<Self::Value> {
+            match value.trim().to_lowercase().as_str() {
+                "1" | "true" | "yes" | "on" => Ok(true),
+                "0" | "false" | "no" | "off" | "" => Ok(false),
+                other => bail!("Invalid value of {}: `{other}`, expected a boolean.", self.0),
+            }
+        }
+        fn generate(&self, value: &Self::Borrowed) -> Result<String> {
+            Ok(value.to_string())
+        }
+    }
+
     pub struct SimpleVariable<Value, Borrowed: ?Sized = Value> {
         pub name:          Cow<'static, str>,
         pub phantom_data:  PhantomData<Value>,
@@ -302,8 +369,7 @@ pub trait Variable {
     fn emit(&self, value: &Self::Value) -> Result
     where Self::Value: ToString {
         self.emit_env(value)?;
-        crate::actions::workflow::set_output(self.name(), value);
-        Ok(())
+        crate::actions::workflow::set_output(self.name(), value)
     }
 
     fn is_set(&self) -> bool {
@@ -359,6 +425,26 @@ pub enum Action {
     PrependPaths(Vec<PathBuf>),
 }
 
+impl Action {
+    /// The value of the variable after the action, given its current value. `None` means that the
+    /// variable is not set.
+    ///
+    /// Prepended paths that were already present are moved to the front, not duplicated.
+    pub fn new_value(&self, current: Option<&OsStr>) -> Result<Option<OsString>> {
+        match self {
+            Action::Remove => Ok(None),
+            Action::Set(value) => Ok(Some(value.into())),
+            Action::PrependPaths(paths_to_prepend) => {
+                let new_paths_set = paths_to_prepend.iter().collect::<BTreeSet<_>>();
+                let old_paths = current.map(split_paths).into_iter().flatten();
+                let old_paths_filtered = old_paths.filter(|path| !new_paths_set.contains(path));
+                let new_paths = paths_to_prepend.iter().cloned().chain(old_paths_filtered);
+                Ok(Some(join_paths(new_paths)?))
+            }
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Modification {
     pub variable_name: UniCase<String>,
@@ -377,23 +463,16 @@ impl Modification {
                 debug!("Setting {}={}", self.variable_name, value);
                 std::env::set_var(normalized_name, &value);
             }
-            Action::PrependPaths(paths_to_prepend) =>
-                if let Ok(old_value) = std::env::var(normalized_name) {
-                    debug!(
-                        "Prepending to {} the following paths: {:?}",
-                        self.variable_name, paths_to_prepend
-                    );
-                    let new_paths_set = paths_to_prepend.iter().collect::<BTreeSet<_>>();
-                    let old_paths = split_paths(&old_value).collect_vec();
-
-                    let old_paths_filtered =
-                        old_paths.iter().filter(|old_path| !new_paths_set.contains(old_path));
-                    let new_value = join_paths(paths_to_prepend.iter().chain(old_paths_filtered))?;
-                    std::env::set_var(&*self.variable_name, new_value);
-                } else {
-                    let new_value = join_paths(paths_to_prepend)?;
-                    set_var(&*self.variable_name, new_value);
-                },
+            Action::PrependPaths(paths_to_prepend) => {
+                debug!(
+                    "Prepending to {} the following paths: {:?}",
+                    self.variable_name, paths_to_prepend
+                );
+                let old_value = std::env::var_os(normalized_name);
+                if let Some(new_value) = self.action.new_value(old_value.as_deref())? {
+                    set_var(normalized_name, new_value);
+                }
+            }
         };
         Ok(())
     }
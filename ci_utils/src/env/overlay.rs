@@ -0,0 +1,264 @@
+//! Sets of environment modifications that can be applied to a single command or to all the
+//! commands spawned within a scope.
+
+use crate::prelude::*;
+
+use crate::env::new::TypedVariable;
+use crate::env::Action;
+use crate::env::Modification;
+use crate::program::command::FallibleManipulator;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use unicase::UniCase;
+
+
+lazy_static::lazy_static! {
+    /// Held by the [`ProcessGuard`]s, so the process-wide modifications do not interleave.
+    static ref PROCESS_LOCK: Mutex<()> = Mutex::new(());
+}
+
+tokio::task_local! {
+    /// Overlays of the [scopes](EnvironmentOverlay::scope) entered by the current task.
+    static SCOPED: Arc<Vec<EnvironmentOverlay>>;
+}
+
+/// Key identifying the variable. Variable names are case-insensitive only on Windows.
+fn key(name: &str) -> String {
+    match TARGET_OS {
+        OS::Windows => name.to_uppercase(),
+        _ => name.to_string(),
+    }
+}
+
+/// Ordered set of environment modifications.
+///
+/// Can be applied to a command (as a manipulator), to the current process, or to all the commands
+/// spawned within a [scope](EnvironmentOverlay::scope).
+#[derive(Clone, Debug, Default)]
+pub struct EnvironmentOverlay {
+    pub modifications: Vec<Modification>,
+}
+
+impl EnvironmentOverlay {
+    pub fn new() -> Self {
+        default()
+    }
+
+    fn with(mut self, name: impl Into<String>, action: Action) -> Self {
+        let variable_name = UniCase::new(name.into());
+        self.modifications.push(Modification { variable_name, action });
+        self
+    }
+
+    /// Set the typed variable.
+    pub fn set<T: TypedVariable>(self, variable: T, value: &T::Borrowed) -> Result<Self> {
+        let value = variable.generate(value)?;
+        Ok(self.with(variable.name(), Action::Set(value)))
+    }
+
+    pub fn set_raw(self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.with(name, Action::Set(value.into()))
+    }
+
+    pub fn remove(self, name: impl Into<String>) -> Self {
+        self.with(name, Action::Remove)
+    }
+
+    /// Prepend the paths to a path-list variable, like `PATH`. The paths that were already
+    /// present are moved to the front rather than duplicated.
+    pub fn prepend_paths(
+        self,
+        name: impl Into<String>,
+        paths: impl IntoIterator<Item: Into<PathBuf>>,
+    ) -> Self {
+        let paths = paths.into_iter().map(Into::into).collect();
+        self.with(name, Action::PrependPaths(paths))
+    }
+
+    /// Prepend the directory to the `PATH`.
+    pub fn prepend_to_path(self, directory: impl Into<PathBuf>) -> Self {
+        self.prepend_paths(crate::env::known::PATH.0, [directory])
+    }
+
+    /// Compute the final values of the modified variables.
+    ///
+    /// The `current` function provides the values the modifications are applied on. `None` as
+    /// the resulting value means the variable is removed.
+    pub fn resolve(
+        &self,
+        current: impl Fn(&str) -> Option<OsString>,
+    ) -> Result<Vec<(String, Option<OsString>)>> {
+        let mut values = indexmap::IndexMap::<String, (String, Option<OsString>)>::new();
+        for Modification { variable_name, action } in &self.modifications {
+            let name = variable_name.as_str();
+            let current = match values.get(&key(name)) {
+                Some((_, value)) => value.clone(),
+                None => current(name),
+            };
+            let new_value = action.new_value(current.as_deref())?;
+            values.insert(key(name), (name.to_string(), new_value));
+        }
+        Ok(values.into_iter().map(|(_, value)| value).collect())
+    }
+
+    /// Apply to the environment of the current process.
+    pub fn apply_to_process(&self) -> Result {
+        for (name, value) in self.resolve(|name| std::env::var_os(name))? {
+            match value {
+                Some(value) => std::env::set_var(name, value),
+                None => std::env::remove_var(name),
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply to the environment of the current process until the returned guard is dropped.
+    ///
+    /// The guard also keeps other guards from modifying the environment in the meantime, so
+    /// e.g. tests running in parallel do not observe each other's variables. Thus, a thread must
+    /// not create another guard while holding one. Prefer applying the overlay to the commands
+    /// or [scopes](Self::scope) where possible.
+    pub fn apply_guarded(&self) -> Result<ProcessGuard> {
+        // The lock protects no data, so the poisoning (a test holding it panicked) is harmless.
+        let lock = PROCESS_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let resolved = self.resolve(|name| std::env::var_os(name))?;
+        let previous =
+            resolved.iter().map(|(name, _)| (name.clone(), std::env::var_os(name))).collect();
+        let guard = ProcessGuard { previous, _lock: lock };
+        for (name, value) in resolved {
+            match value {
+                Some(value) => std::env::set_var(name, value),
+                None => std::env::remove_var(name),
+            }
+        }
+        Ok(guard)
+    }
+
+    /// Run the future with the overlay applied to all the commands it spawns.
+    ///
+    /// The scope is bound to the current task: the commands spawned from the tasks started
+    /// with `tokio::spawn` are not affected. Scopes can be nested, the inner overlay is applied
+    /// after the outer ones.
+    pub async fn scope<F: Future>(self, f: F) -> F::Output {
+        let mut overlays = SCOPED.try_with(|overlays| (**overlays).clone()).unwrap_or_default();
+        overlays.push(self);
+        SCOPED.scope(Arc::new(overlays), f).await
+    }
+}
+
+/// Restores the variables modified by [`EnvironmentOverlay::apply_guarded`] when dropped.
+#[derive(Debug)]
+pub struct ProcessGuard {
+    previous: Vec<(String, Option<OsString>)>,
+    _lock:    MutexGuard<'static, ()>,
+}
+
+impl Drop for ProcessGuard {
+    fn drop(&mut self) {
+        for (name, value) in self.previous.drain(..) {
+            match value {
+                Some(value) => std::env::set_var(name, value),
+                None => std::env::remove_var(name),
+            }
+        }
+    }
+}
+
+impl FallibleManipulator for EnvironmentOverlay {
+    fn try_applying<C: IsCommandWrapper + ?Sized>(&self, command: &mut C) -> Result {
+        // Variables set explicitly on the command take precedence over the process environment.
+        let explicit: HashMap<String, Option<OsString>> = command
+            .borrow_mut_command()
+            .as_std()
+            .get_envs()
+            .map(|(name, value)| (key(&name.to_string_lossy()), value.map(ToOwned::to_owned)))
+            .collect();
+        let current = |name: &str| match explicit.get(&key(name)) {
+            Some(value) => value.clone(),
+            None => std::env::var_os(name),
+        };
+        for (name, value) in self.resolve(current)? {
+            match value {
+                Some(value) => command.env(name, value),
+                None => command.env_remove(name),
+            };
+        }
+        Ok(())
+    }
+}
+
+/// Apply the overlays of the current [scope](EnvironmentOverlay::scope) to the command.
+pub fn apply_scoped<C: IsCommandWrapper + ?Sized>(command: &mut C) -> Result {
+    let overlays = SCOPED.try_with(|overlays| overlays.clone()).unwrap_or_default();
+    for overlay in overlays.iter() {
+        overlay.try_applying(command)?;
+    }
+    Ok(())
+}
+
+/// Value of the variable as seen by the commands spawned in the current scope.
+pub fn var_os(name: &str) -> Option<OsString> {
+    let overlays = SCOPED.try_with(|overlays| overlays.clone()).unwrap_or_default();
+    let mut value = std::env::var_os(name);
+    for overlay in overlays.iter() {
+        let current = |n: &str| match key(n) == key(name) {
+            true => value.clone(),
+            false => std::env::var_os(n),
+        };
+        let resolved = overlay.resolve(current).ok()?;
+        if let Some((_, new_value)) = resolved.into_iter().find(|(n, _)| key(n) == key(name)) {
+            value = new_value;
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prepending_paths() -> Result {
+        let base = std::env::join_paths(["/usr/bin", "/opt/tool/bin"])?;
+        let overlay = EnvironmentOverlay::new()
+            .set_raw("JAVA_HOME", "/opt/graalvm")
+            .prepend_to_path("/opt/tool/bin")
+            .prepend_to_path("/opt/graalvm/bin")
+            .remove("SBT_OPTS");
+        let resolved = overlay.resolve(|name| (name == "PATH").then(|| base.clone()))?;
+        let expected_path =
+            std::env::join_paths(["/opt/graalvm/bin", "/opt/tool/bin", "/usr/bin"])?;
+        assert_eq!(resolved, vec![
+            ("JAVA_HOME".to_string(), Some("/opt/graalvm".into())),
+            ("PATH".to_string(), Some(expected_path)),
+            ("SBT_OPTS".to_string(), None),
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn guarded_process_modification() -> Result {
+        let name = "ENSO_TEST_GUARDED_VARIABLE";
+        std::env::set_var(name, "original");
+        let guard = EnvironmentOverlay::new().set_raw(name, "modified").apply_guarded()?;
+        assert_eq!(std::env::var(name)?, "modified");
+        drop(guard);
+        assert_eq!(std::env::var(name)?, "original");
+
+        let guard = EnvironmentOverlay::new().remove(name).apply_guarded()?;
+        assert!(std::env::var_os(name).is_none());
+        drop(guard);
+        assert_eq!(std::env::var(name)?, "original");
+        std::env::remove_var(name);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn scoped_variables() {
+        let name = "ENSO_TEST_SCOPED_VARIABLE";
+        let overlay = EnvironmentOverlay::new().set_raw(name, "value");
+        let scoped = overlay.scope(async { var_os(name) }).await;
+        assert_eq!(scoped, Some("value".into()));
+        assert_eq!(var_os(name), None);
+    }
+}
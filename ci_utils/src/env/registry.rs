@@ -0,0 +1,99 @@
+//! Registry of the environment variables declared with [`define_env_var`](crate::define_env_var).
+//!
+//! Every declaration, in any crate, is collected here, so all the variables that the build
+//! script cares about can be listed when debugging its behavior.
+
+use crate::prelude::*;
+
+pub use inventory;
+
+
+/// Environment variable known to the build script.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Declaration {
+    pub name:      &'static str,
+    /// The Rust type of the value, as written in the declaration.
+    pub type_name: &'static str,
+    /// Whether the operations reading the variable fail when it is not set.
+    pub required:  bool,
+}
+
+inventory::collect!(Declaration);
+
+impl Declaration {
+    /// Whether the value should be kept out of the logs.
+    ///
+    /// There is no explicit marker, the names of the credentials are distinctive enough.
+    pub fn is_sensitive(&self) -> bool {
+        crate::program::audit::is_sensitive_name(self.name)
+    }
+
+    /// The current value, or `None` if the variable is not set.
+    pub fn value(&self) -> Option<OsString> {
+        std::env::var_os(self.name)
+    }
+}
+
+/// All declared variables, sorted by name.
+///
+/// The same variable might be declared in several places, it is listed once.
+pub fn all() -> Vec<&'static Declaration> {
+    let mut ret = inventory::iter::<Declaration>.into_iter().collect_vec();
+    ret.sort_by_key(|declaration| declaration.name);
+    ret.dedup_by_key(|declaration| declaration.name);
+    ret
+}
+
+/// The required variables that are not set.
+pub fn missing_required() -> Vec<&'static Declaration> {
+    all()
+        .into_iter()
+        .filter(|declaration| declaration.required && declaration.value().is_none())
+        .collect()
+}
+
+/// Describe the values of all declared variables, one per line. The sensitive values are hidden.
+pub fn dump() -> String {
+    all()
+        .into_iter()
+        .map(|declaration| {
+            let value = match declaration.value() {
+                Some(_) if declaration.is_sensitive() =>
+                    crate::program::audit::REDACTED.to_string(),
+                Some(value) => format!("{:?}", value.to_string_lossy()),
+                None if declaration.required => "<not set, required>".to_string(),
+                None => "<not set>".to_string(),
+            };
+            format!("{} ({}) = {value}\n", declaration.name, declaration.type_name)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    crate::define_env_var! {
+        /// Declared only for the test.
+        ENSO_REGISTRY_TEST_FLAG, bool, optional
+    }
+
+    #[test]
+    fn declared_variables_are_registered() -> Result {
+        let declaration = all()
+            .into_iter()
+            .find(|declaration| declaration.name == "ENSO_REGISTRY_TEST_FLAG")
+            .context("The test variable is not registered.")?;
+        assert!(!declaration.required);
+        assert_eq!(declaration.type_name, "bool");
+
+        assert_eq!(ENSO_REGISTRY_TEST_FLAG.try_get()?, None);
+        ENSO_REGISTRY_TEST_FLAG.set_raw("yes");
+        assert!(ENSO_REGISTRY_TEST_FLAG.get()?);
+        assert!(dump().contains("ENSO_REGISTRY_TEST_FLAG (bool) = \"yes\""));
+        ENSO_REGISTRY_TEST_FLAG.remove();
+
+        assert!(all().iter().any(|declaration| declaration.name == "GITHUB_RUN_ID"));
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 use crate::prelude::*;
 
-use std::fmt::Write;
+use crate::program::audit::Record;
+use crate::program::audit::ScriptKind;
 
 pub trait CommandExt {
     // fn run_ok(&mut self, program: &impl Program) -> BoxFuture<'static, Result<()>>;
@@ -11,27 +12,16 @@ pub trait CommandExt {
 
     fn as_std(&self) -> &std::process::Command;
 
+    /// Render the invocation as a line that can be pasted into the native shell of the current
+    /// platform, including the working directory and environment changes.
+    ///
+    /// Used in the error messages, so the failing command can be reproduced locally.
+    fn display(&self) -> String {
+        ScriptKind::current().render_line(&Record::new(self.as_std()))
+    }
+
     fn describe(&self) -> String {
-        let mut ret = String::new();
-        let pretty_printed = format!("{:?}", self.as_std());
-        let _ = write!(ret, "Command:\n\t{}", pretty_printed);
-        if let Some(cwd) = self.as_std().get_current_dir() {
-            let _ = write!(ret, "\n\twith working directory: {}", cwd.display());
-        };
-        let env = self.as_std().get_envs();
-        if !env.is_empty() {
-            let _ = write!(ret, "\n\twith environment overrides:");
-        }
-        for (name, val) in self.as_std().get_envs() {
-            let _ = write!(
-                ret,
-                "\n\t\t{}={}",
-                name.to_string_lossy(),
-                val.map_or(default(), OsStr::to_string_lossy)
-            );
-        }
-        ret
-        // ?self.as_std().get_program()
+        format!("Command:\n\t{}", self.display())
     }
 }
 
@@ -1,5 +1,6 @@
 use crate::prelude::*;
 
+use crate::program::command::CapturedOutput;
 use anyhow::Context;
 
 pub trait OutputExt {
@@ -13,23 +14,14 @@ pub trait OutputExt {
 
 impl OutputExt for std::process::Output {
     fn single_line_stdout(&self) -> Result<String> {
-        // self.run_ok()?;
-        let lines = non_empty_lines(&self.stdout)?.collect_vec();
-        match lines.as_slice() {
-            [line] => Ok(line.to_string()),
-            other => bail!("Expected exactly 1 non-empty line. Found: {:?}", other),
-        }
+        single_line(&self.stdout)
     }
 
     // fn run_ok(&self) -> Result {
     //     self.status.exit_ok().with_context(|| self.describe())
     // }
     fn describe(&self) -> String {
-        format!(
-            "Stdout:\n{:?}\n\nStderr:\n{:?}\n",
-            std::str::from_utf8(&self.stdout).unwrap_or("<INVALID ENCODING>"),
-            std::str::from_utf8(&self.stderr).unwrap_or("<INVALID ENCODING>"),
-        )
+        describe(&self.stdout, &self.stderr)
     }
 
     fn stdout_as_str(&self) -> Result<&str> {
@@ -41,6 +33,40 @@ impl OutputExt for std::process::Output {
     }
 }
 
+impl OutputExt for CapturedOutput {
+    fn single_line_stdout(&self) -> Result<String> {
+        single_line(&self.stdout)
+    }
+
+    fn describe(&self) -> String {
+        describe(&self.stdout, &self.stderr)
+    }
+
+    fn stdout_as_str(&self) -> Result<&str> {
+        std::str::from_utf8(&self.stdout).context("The command stdout is not a valid text.")
+    }
+
+    fn into_stdout_string(self) -> Result<String> {
+        String::from_utf8(self.stdout).context("The command stdout is not a valid text.")
+    }
+}
+
+fn single_line(stdout: &[u8]) -> Result<String> {
+    let lines = non_empty_lines(stdout)?.collect_vec();
+    match lines.as_slice() {
+        [line] => Ok(line.to_string()),
+        other => bail!("Expected exactly 1 non-empty line. Found: {:?}", other),
+    }
+}
+
+fn describe(stdout: &[u8], stderr: &[u8]) -> String {
+    format!(
+        "Stdout:\n{:?}\n\nStderr:\n{:?}\n",
+        std::str::from_utf8(stdout).unwrap_or("<INVALID ENCODING>"),
+        std::str::from_utf8(stderr).unwrap_or("<INVALID ENCODING>"),
+    )
+}
+
 pub fn non_empty_lines(bytes: &[u8]) -> Result<impl Iterator<Item = &str>> {
     Ok(std::str::from_utf8(&bytes)?.lines().map(str::trim).filter(|line| !line.is_empty()))
 }
@@ -2,14 +2,23 @@ use crate::prelude::*;
 
 use fs_extra::dir::CopyOptions;
 
+pub mod clone;
+pub mod mirror;
+pub mod temp;
 pub mod tokio;
+pub mod watch;
 pub mod wrappers;
 
+pub use clone::clone_directory_cheap;
+pub use mirror::mirror_directory;
+pub use mirror::MirrorOptions;
 pub use wrappers::*;
 
 use async_compression::tokio::bufread::GzipEncoder;
 use async_compression::Level;
 use std::fs::File;
+use std::io::Write;
+use std::time::Duration;
 
 /////////////////////////////
 
@@ -29,6 +38,65 @@ pub fn write_json(path: impl AsRef<Path>, contents: &impl Serialize) -> Result {
     write(&path, &contents)
 }
 
+/// Write the file, so that the other processes see either its old or its new contents, never a
+/// partially written file.
+///
+/// The contents are written and synced to a temporary file in the same directory, which then
+/// [replaces](replace_file) the target. Missing parent directories are created.
+#[context("Failed to atomically write path: {}", path.as_ref().display())]
+pub fn write_atomic(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> Result {
+    let path = path.as_ref();
+    let parent = create_parent_dir_if_missing(path)?;
+    let filename = path.file_name().context("The path has no file name.")?;
+    let temporary =
+        parent.join(format!(".{}.{}.tmp", filename.to_string_lossy(), uuid::Uuid::new_v4()));
+    let result = (|| -> Result {
+        let mut file = wrappers::create(&temporary)?;
+        file.write_all(contents.as_ref())?;
+        file.sync_all()?;
+        drop(file);
+        replace_file(&temporary, path)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temporary);
+    }
+    result
+}
+
+/// How many times the replacement of a file is attempted on Windows.
+const REPLACE_ATTEMPTS: u32 = 5;
+
+/// Move the file to the destination, replacing the existing file.
+///
+/// On Windows, the replacement fails if any process (often an antivirus or the search indexer)
+/// has the destination open, so it is retried a few times. On Unix, the directory is synced, so
+/// the rename survives a crash.
+#[context("Failed to replace {} with {}", to.as_ref().display(), from.as_ref().display())]
+pub fn replace_file(from: impl AsRef<Path>, to: impl AsRef<Path>) -> Result {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    let mut attempt = 1;
+    loop {
+        match std::fs::rename(from, to) {
+            Ok(()) => break,
+            Err(e)
+                if TARGET_OS == OS::Windows
+                    && e.kind() == std::io::ErrorKind::PermissionDenied
+                    && attempt < REPLACE_ATTEMPTS =>
+            {
+                debug!("Destination {} is in use, retrying: {e}", to.display());
+                std::thread::sleep(Duration::from_millis(100 * u64::from(attempt)));
+                attempt += 1;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    if TARGET_OS != OS::Windows && let Some(parent) = to.parent() {
+        // Directories cannot be opened (nor synced) this way on Windows.
+        let _ = File::open(parent).and_then(|directory| directory.sync_all());
+    }
+    Ok(())
+}
+
 /// Like the standard version but will create any missing parent directories from the path.
 #[context("Failed to open path for writing: {}", path.as_ref().display())]
 pub fn create(path: impl AsRef<Path>) -> Result<File> {
@@ -81,6 +149,155 @@ pub fn remove_dir_if_exists(path: impl AsRef<Path>) -> Result {
     }
 }
 
+/// How many times the removal of a directory tree is attempted by [`remove_dir_robust`].
+const REMOVE_ATTEMPTS: u32 = 6;
+
+/// Remove a directory with all its subtree, retrying on the transient errors.
+///
+/// On Windows, removal often fails because some process (an antivirus, the search indexer, or
+/// a build tool that is just exiting) still has a file open, or because a file is read-only.
+/// Such failures are retried with a growing delay, after clearing the read-only attributes. The
+/// path is used in the verbatim (`\\?\`) form, so the trees nested deeper than `MAX_PATH`
+/// (common in `node_modules`) can be removed.
+///
+/// If the path is a symbolic link or a junction, only the link is removed, not the tree it
+/// points to.
+///
+/// The thread is blocked while waiting for the retry, so async code should use
+/// [`tokio::remove_dir_robust`] instead.
+///
+/// Does not fail if the directory is not found.
+#[tracing::instrument(fields(path = %path.as_ref().display()))]
+#[context("Failed to remove directory {}", path.as_ref().display())]
+pub fn remove_dir_robust(path: impl AsRef<Path>) -> Result {
+    let path = path.as_ref();
+    match path.symlink_metadata() {
+        // Junctions are reported as links as well.
+        Ok(metadata) if metadata.file_type().is_symlink() => return remove_link(path),
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    }
+    let path = verbatim_path(path)?;
+    let mut attempt = 1;
+    loop {
+        match std::fs::remove_dir_all(&path) {
+            Ok(()) => return Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) if attempt < REMOVE_ATTEMPTS && is_transient_removal_error(&e) => {
+                warn!("Failed to remove {} (attempt {attempt}), will retry: {e}", path.display());
+                if e.kind() == std::io::ErrorKind::PermissionDenied {
+                    clear_readonly(&path);
+                }
+                std::thread::sleep(removal_retry_delay(attempt));
+                attempt += 1;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Delay before the next attempt of [`remove_dir_robust`].
+fn removal_retry_delay(attempt: u32) -> Duration {
+    Duration::from_millis(200 * 2u64.pow(attempt - 1))
+}
+
+/// Remove the link itself, leaving its target intact.
+fn remove_link(path: &Path) -> Result {
+    // On Windows, the links to directories (including the junctions) are removed as directories.
+    if TARGET_OS == OS::Windows && path.is_dir() {
+        std::fs::remove_dir(path).anyhow_err()
+    } else {
+        std::fs::remove_file(path).anyhow_err()
+    }
+}
+
+/// The verbatim (`\\?\`) form of the absolute path on Windows, which is not limited by
+/// `MAX_PATH`. On other platforms the path is returned as is.
+///
+/// Unlike [`std::fs::canonicalize`], this does not resolve the links. The `..` components are
+/// resolved lexically, like the Windows API does for the non-verbatim paths.
+pub fn verbatim_path(path: impl AsRef<Path>) -> Result<PathBuf> {
+    use std::path::Component;
+    use std::path::Prefix;
+    let path = path.as_ref();
+    if TARGET_OS != OS::Windows {
+        return Ok(path.to_path_buf());
+    }
+    let absolute =
+        if path.is_absolute() { path.to_path_buf() } else { std::env::current_dir()?.join(path) };
+    let mut ret = OsString::new();
+    let mut parts = Vec::new();
+    for component in absolute.components() {
+        match component {
+            Component::Prefix(prefix) => match prefix.kind() {
+                Prefix::Disk(_) => {
+                    ret.push(r"\\?\");
+                    ret.push(prefix.as_os_str());
+                }
+                Prefix::UNC(server, share) => {
+                    ret.push(r"\\?\UNC\");
+                    ret.push(server);
+                    ret.push(r"\");
+                    ret.push(share);
+                }
+                // Already verbatim, or a device path.
+                _ => return Ok(absolute.clone()),
+            },
+            Component::RootDir | Component::CurDir => {}
+            Component::ParentDir => {
+                parts.pop();
+            }
+            Component::Normal(part) => parts.push(part),
+        }
+    }
+    for part in parts {
+        ret.push(r"\");
+        ret.push(part);
+    }
+    Ok(ret.into())
+}
+
+/// Whether the failed removal might succeed if retried.
+fn is_transient_removal_error(error: &std::io::Error) -> bool {
+    /// `ERROR_SHARING_VIOLATION`: the file is being used by another process.
+    const SHARING_VIOLATION: i32 = 32;
+    /// `ERROR_DIR_NOT_EMPTY`: the removed files are still pending deletion.
+    const DIR_NOT_EMPTY: i32 = 145;
+    error.kind() == std::io::ErrorKind::PermissionDenied
+        || TARGET_OS == OS::Windows
+            && matches!(error.raw_os_error(), Some(SHARING_VIOLATION | DIR_NOT_EMPTY))
+}
+
+/// Make all entries in the tree writable by their owner, ignoring the failures.
+fn clear_readonly(path: &Path) {
+    for entry in walkdir::WalkDir::new(path).into_iter().filter_map(|entry| entry.ok()) {
+        if let Ok(metadata) = entry.path().symlink_metadata() {
+            if metadata.file_type().is_symlink() {
+                continue;
+            }
+            let mut permissions = metadata.permissions();
+            #[cfg(unix)]
+            {
+                use std::os::unix::prelude::*;
+                let owner_can_write = 0o0200;
+                if permissions.mode() & owner_can_write != 0 {
+                    continue;
+                }
+                permissions.set_mode(permissions.mode() | owner_can_write);
+            }
+            #[cfg(not(unix))]
+            {
+                if !permissions.readonly() {
+                    continue;
+                }
+                permissions.set_readonly(false);
+            }
+            let _ = std::fs::set_permissions(entry.path(), permissions);
+        }
+    }
+}
+
 /// Remove a regular file.
 ///
 /// Does not fail if the file is not found.
@@ -117,7 +334,7 @@ pub fn symlink_auto(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> Result {
 pub fn reset_dir(path: impl AsRef<Path>) -> Result {
     let path = path.as_ref();
     debug!("Will reset directory {}", path.display());
-    remove_dir_if_exists(&path)?;
+    remove_dir_robust(&path)?;
     create_dir_if_missing(&path)?;
     Ok(())
 }
@@ -174,21 +391,6 @@ pub fn same_existing_path(source: impl AsRef<Path>, destination: impl AsRef<Path
     Ok(wrappers::canonicalize(source)? == wrappers::canonicalize(destination)?)
 }
 
-pub async fn mirror_directory(source: impl AsRef<Path>, destination: impl AsRef<Path>) -> Result {
-    create_dir_if_missing(destination.as_ref())?;
-
-    // Robocopy seems to waste much time when running with the same path as source and destination.
-    if same_existing_path(&source, &destination)? {
-        return Ok(());
-    }
-
-    if TARGET_OS == OS::Windows {
-        crate::programs::robocopy::mirror_directory(source, destination).await
-    } else {
-        crate::programs::rsync::mirror_directory(source, destination).await
-    }
-}
-
 #[context("Failed because the path does not point to a directory: {}", path.as_ref().display())]
 pub fn expect_dir(path: impl AsRef<Path>) -> Result {
     let filetype = metadata(&path)?.file_type();
@@ -231,6 +433,42 @@ pub fn allow_owner_execute(path: impl AsRef<Path>) -> Result {
     Ok(())
 }
 
+/// Space available to the current user on the filesystem containing the path.
+#[context("Failed to get the available space on the filesystem with {}.", path.as_ref().display())]
+pub fn available_space(path: impl AsRef<Path>) -> Result<u64> {
+    fs2::available_space(path.as_ref()).anyhow_err()
+}
+
+/// Fail unless the filesystem containing the path has at least the given number of bytes free.
+///
+/// The path does not need to exist, its closest existing ancestor is checked.
+pub fn require_free_space(path: impl AsRef<Path>, required: u64) -> Result {
+    let path = path.as_ref();
+    let existing = path.ancestors().find(|ancestor| ancestor.exists()).unwrap_or(path);
+    let available = available_space(existing)?;
+    let format = |bytes: u64| byte_unit::Byte::from_bytes(bytes.into()).get_appropriate_unit(true);
+    ensure!(
+        available >= required,
+        "Not enough free space on the filesystem with {}: {} required, {} available.",
+        path.display(),
+        format(required),
+        format(available)
+    );
+    Ok(())
+}
+
+/// Total size of the regular files in the directory tree. For a file, its size.
+///
+/// The symbolic links are not followed.
+#[context("Failed to compute the size of {}.", path.as_ref().display())]
+pub fn tree_size(path: impl AsRef<Path>) -> Result<u64> {
+    walkdir::WalkDir::new(path).into_iter().try_fold(0, |size, entry| -> Result<u64> {
+        let entry = entry?;
+        let file_size = if entry.file_type().is_file() { entry.metadata()?.len() } else { 0 };
+        Ok(size + file_size)
+    })
+}
+
 /// Get the size of a file after gzip compression.
 pub async fn compressed_size(path: impl AsRef<Path>) -> Result<byte_unit::Byte> {
     let file = ::tokio::io::BufReader::new(crate::fs::tokio::open(&path).await?);
@@ -292,6 +530,79 @@ mod tests {
     use crate::log::setup_logging;
     use ::tokio;
 
+    #[test]
+    fn writing_atomically() -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("config").join("version.txt");
+        write_atomic(&path, "2022.1.1")?;
+        write_atomic(&path, "2022.1.2")?;
+        assert_eq!(read_to_string(&path)?, "2022.1.2");
+        // No temporary files are left behind.
+        assert_eq!(read_dir(path.parent().unwrap())?.count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn removing_readonly_tree() -> Result {
+        let dir = tempfile::tempdir()?;
+        let target = dir.path().join("target");
+        let file = target.join_iter(["scala-2.13", "classes", "Main.class"]);
+        write(&file, "class")?;
+        let mut permissions = metadata(&file)?.permissions();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(&file, permissions)?;
+        remove_dir_robust(&target)?;
+        assert!(!target.exists());
+        remove_dir_robust(&target)?;
+        Ok(())
+    }
+
+    #[test]
+    fn removing_link_keeps_target() -> Result {
+        let dir = tempfile::tempdir()?;
+        let real = dir.path().join("real");
+        write(real.join("data.txt"), "kept")?;
+        let link = dir.path().join_iter(["work", "link"]);
+        symlink_auto(&real, &link)?;
+        remove_dir_robust(&link)?;
+        assert!(link.symlink_metadata().is_err());
+        assert_eq!(read_to_string(real.join("data.txt"))?, "kept");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn clearing_readonly_only_for_owner() -> Result {
+        use std::os::unix::prelude::*;
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("file");
+        write(&file, "contents")?;
+        std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o444))?;
+        clear_readonly(dir.path());
+        assert_eq!(metadata(&file)?.permissions().mode() & 0o777, 0o644);
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn verbatim_paths() -> Result {
+        assert_eq!(verbatim_path(r"C:\temp\a\..\b")?, Path::new(r"\\?\C:\temp\b"));
+        assert_eq!(verbatim_path(r"\\server\share\dir")?, Path::new(r"\\?\UNC\server\share\dir"));
+        assert_eq!(verbatim_path(r"\\?\C:\x")?, Path::new(r"\\?\C:\x"));
+        Ok(())
+    }
+
+    #[test]
+    fn measuring_space() -> Result {
+        let dir = tempfile::tempdir()?;
+        write(dir.path().join_iter(["a", "b.txt"]), "12345")?;
+        write(dir.path().join("c.txt"), "123")?;
+        assert_eq!(tree_size(dir.path())?, 8);
+        require_free_space(dir.path().join("not-yet-created"), 1)?;
+        assert!(require_free_space(dir.path(), u64::MAX).is_err());
+        Ok(())
+    }
+
     #[tokio::test]
     async fn copy_if_different_test() -> Result {
         setup_logging()?;
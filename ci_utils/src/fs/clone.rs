@@ -0,0 +1,128 @@
+//! Cheap copies of large directory trees, sharing the file contents with the source.
+
+use crate::prelude::*;
+
+use walkdir::WalkDir;
+
+
+/// How a file was placed in the destination.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CloneMethod {
+    /// Copy-on-write clone, supported by some filesystems (APFS, Btrfs, XFS, ReFS).
+    Reflink,
+    /// Another name for the same file.
+    HardLink,
+    /// Regular copy.
+    Copy,
+}
+
+/// Places the files in the destination using the cheapest method that works.
+///
+/// Once a method fails, it is not attempted for the following files, as they are most likely on
+/// the same filesystems.
+#[derive(Clone, Debug)]
+pub struct Cloner {
+    reflink_works:   bool,
+    hard_link_works: bool,
+}
+
+impl Default for Cloner {
+    fn default() -> Self {
+        Self { reflink_works: true, hard_link_works: true }
+    }
+}
+
+impl Cloner {
+    /// Place the file at the target path, replacing any existing file.
+    pub fn clone_file(&mut self, source: &Path, target: &Path) -> Result<CloneMethod> {
+        crate::fs::remove_if_exists(target)?;
+        if self.reflink_works {
+            match reflink_copy::reflink(source, target) {
+                Ok(()) => return Ok(CloneMethod::Reflink),
+                Err(e) => {
+                    debug!("Cannot reflink {}, will not try again: {e}", source.display());
+                    self.reflink_works = false;
+                }
+            }
+        }
+        if self.hard_link_works {
+            match std::fs::hard_link(source, target) {
+                Ok(()) => return Ok(CloneMethod::HardLink),
+                Err(e) => {
+                    debug!("Cannot hard link {}, will not try again: {e}", source.display());
+                    self.hard_link_works = false;
+                }
+            }
+        }
+        crate::fs::copy(source, target)?;
+        Ok(CloneMethod::Copy)
+    }
+}
+
+/// Recreate the source directory tree in the destination, without copying the file contents if
+/// possible.
+///
+/// The files are reflinked where the filesystem supports it, otherwise hard linked, and copied
+/// only if neither works (e.g. when the destination is on another device). The hard-linked files
+/// share their contents with the source, so they must be replaced rather than modified in place.
+///
+/// Symbolic links are recreated. The destination files not present in the source are kept.
+#[tracing::instrument(skip_all, fields(
+    src  = %source.as_ref().display(),
+    dest = %destination.as_ref().display()),
+    err)]
+pub async fn clone_directory_cheap(
+    source: impl AsRef<Path>,
+    destination: impl AsRef<Path>,
+) -> Result<HashMap<CloneMethod, usize>> {
+    let source = source.as_ref().to_path_buf();
+    let destination = destination.as_ref().to_path_buf();
+    tokio::task::spawn_blocking(move || clone_directory_cheap_sync(&source, &destination)).await?
+}
+
+/// Blocking version of [`clone_directory_cheap`]. Returns how many files were placed with each
+/// method.
+pub fn clone_directory_cheap_sync(
+    source: &Path,
+    destination: &Path,
+) -> Result<HashMap<CloneMethod, usize>> {
+    crate::fs::create_dir_if_missing(destination)?;
+    let mut cloner = Cloner::default();
+    let mut counts = HashMap::new();
+    for entry in WalkDir::new(source).min_depth(1) {
+        let entry = entry?;
+        let target = destination.join(entry.path().strip_prefix(source)?);
+        let file_type = entry.file_type();
+        if file_type.is_dir() {
+            crate::fs::create_dir_if_missing(&target)?;
+        } else if file_type.is_symlink() {
+            crate::fs::remove_if_exists(&target)?;
+            crate::fs::symlink_auto(std::fs::read_link(entry.path())?, &target)?;
+        } else {
+            let method = cloner.clone_file(entry.path(), &target)?;
+            *counts.entry(method).or_default() += 1;
+        }
+    }
+    debug!("Cloned {} into {}: {counts:?}", source.display(), destination.display());
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn cloning_directory() -> Result {
+        let dir = tempfile::tempdir()?;
+        let (source, destination) = (dir.path().join("built"), dir.path().join("release"));
+        crate::fs::write(source.join_iter(["bin", "project-manager"]), "binary")?;
+        crate::fs::write(source.join_iter(["lib", "runtime.jar"]), "jar")?;
+        crate::fs::write(destination.join_iter(["lib", "runtime.jar"]), "outdated")?;
+
+        let counts = clone_directory_cheap(&source, &destination).await?;
+        assert_eq!(counts.values().sum::<usize>(), 2);
+        let jar = destination.join_iter(["lib", "runtime.jar"]);
+        assert_eq!(crate::fs::read_to_string(jar)?, "jar");
+        Ok(())
+    }
+}
@@ -0,0 +1,349 @@
+//! Mirroring directory trees, like `rsync --archive --delete` does.
+
+use crate::prelude::*;
+
+use crate::archive::matches_any;
+use filetime::FileTime;
+use std::fs::Metadata;
+use walkdir::WalkDir;
+
+
+/// What to do with the symbolic links found in the source tree.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Recreate the link in the destination, with the same target.
+    Preserve,
+    /// Copy the file or directory that the link points to.
+    Follow,
+    /// Leave the link out.
+    Skip,
+}
+
+impl Default for SymlinkPolicy {
+    fn default() -> Self {
+        Self::Preserve
+    }
+}
+
+/// State of the mirroring, reported after each copied file.
+#[derive(Clone, Debug)]
+pub struct MirrorProgress<'a> {
+    /// The file just handled, relative to the source directory.
+    pub path:         &'a Path,
+    pub files_done:   usize,
+    pub files_total:  usize,
+    /// Bytes copied so far. The files that are already up-to-date are not counted.
+    pub bytes_copied: u64,
+}
+
+pub type ProgressCallback = Arc<dyn Fn(&MirrorProgress) + Send + Sync>;
+
+/// Options of [`mirror_directory`].
+///
+/// The default options mirror the whole tree: the links are preserved, the executable bits are
+/// kept, and the files not present in the source are removed from the destination.
+#[derive(Clone, Derivative)]
+#[derivative(Debug)]
+pub struct MirrorOptions {
+    /// If not empty, only the files matching any of these patterns are copied.
+    pub include:             Vec<glob::Pattern>,
+    /// The files and directories matching any of these patterns are neither copied nor deleted.
+    pub exclude:             Vec<glob::Pattern>,
+    pub symlinks:            SymlinkPolicy,
+    /// Whether the executable bits of the copied files are kept. Ignored on Windows.
+    pub preserve_executable: bool,
+    /// Whether the destination files that are not in the source are removed.
+    pub delete_extraneous:   bool,
+    #[derivative(Debug = "ignore")]
+    pub progress:            Option<ProgressCallback>,
+}
+
+impl Default for MirrorOptions {
+    fn default() -> Self {
+        Self {
+            include:             default(),
+            exclude:             default(),
+            symlinks:            default(),
+            preserve_executable: true,
+            delete_extraneous:   true,
+            progress:            None,
+        }
+    }
+}
+
+impl MirrorOptions {
+    /// Copy only the files matching the glob pattern, like `bin/**` or `**/*.jar`.
+    pub fn include(mut self, pattern: &str) -> Result<Self> {
+        self.include.push(parse_pattern(pattern)?);
+        Ok(self)
+    }
+
+    /// Skip the files and directories matching the glob pattern.
+    pub fn exclude(mut self, pattern: &str) -> Result<Self> {
+        self.exclude.push(parse_pattern(pattern)?);
+        Ok(self)
+    }
+
+    pub fn symlinks(mut self, policy: SymlinkPolicy) -> Self {
+        self.symlinks = policy;
+        self
+    }
+
+    pub fn preserve_executable(mut self, preserve: bool) -> Self {
+        self.preserve_executable = preserve;
+        self
+    }
+
+    pub fn delete_extraneous(mut self, delete: bool) -> Self {
+        self.delete_extraneous = delete;
+        self
+    }
+
+    pub fn on_progress(
+        mut self,
+        callback: impl Fn(&MirrorProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.progress = Some(Arc::new(callback));
+        self
+    }
+
+    fn is_excluded(&self, relative_path: &Path) -> bool {
+        matches_any(&self.exclude, relative_path)
+    }
+
+    fn is_included(&self, relative_path: &Path) -> bool {
+        self.include.is_empty() || matches_any(&self.include, relative_path)
+    }
+}
+
+/// External program that can mirror the directory trees faster than we do it in-process, like
+/// [Robocopy](crate::programs::robocopy::Robocopy) or [rsync](crate::programs::rsync::Rsync).
+pub trait FastCopy: Program {
+    /// Whether the program can mirror the directory honoring all the options.
+    fn supports(&self, options: &MirrorOptions) -> bool;
+
+    /// Command mirroring the source directory to the destination.
+    fn mirror_cmd(
+        &self,
+        source: &Path,
+        destination: &Path,
+        options: &MirrorOptions,
+    ) -> Result<Command>;
+}
+
+/// Command mirroring the directory using the platform's copying tool, if it is available and
+/// supports the options.
+fn fast_copy_cmd(source: &Path, destination: &Path, options: &MirrorOptions) -> Option<Command> {
+    #[cfg(target_os = "windows")]
+    let program = crate::programs::robocopy::Robocopy;
+    #[cfg(not(target_os = "windows"))]
+    let program = crate::programs::rsync::Rsync;
+    if !program.supports(options) {
+        return None;
+    }
+    match program.mirror_cmd(source, destination, options) {
+        Ok(cmd) => Some(cmd),
+        Err(e) => {
+            debug!(
+                "Cannot use {} for mirroring, copying in-process: {e}",
+                program.executable_name()
+            );
+            None
+        }
+    }
+}
+
+fn parse_pattern(pattern: &str) -> Result<glob::Pattern> {
+    glob::Pattern::new(pattern).with_context(|| format!("Invalid glob pattern: {pattern}"))
+}
+
+/// Make the destination directory a copy of the source directory.
+///
+/// The files that have the same size and modification time in both directories are not copied
+/// again. If the options allow it, the work is delegated to the platform's [`FastCopy`] program,
+/// which is much faster for large trees.
+#[tracing::instrument(skip_all, fields(
+    src  = %source.as_ref().display(),
+    dest = %destination.as_ref().display()),
+    err)]
+pub async fn mirror_directory(
+    source: impl AsRef<Path>,
+    destination: impl AsRef<Path>,
+    options: MirrorOptions,
+) -> Result {
+    let source = source.as_ref().to_path_buf();
+    let destination = destination.as_ref().to_path_buf();
+    crate::fs::create_dir_if_missing(&destination)?;
+    if crate::fs::same_existing_path(&source, &destination)? {
+        return Ok(());
+    }
+    if let Some(mut cmd) = fast_copy_cmd(&source, &destination, &options) {
+        return cmd.run_ok().await;
+    }
+    tokio::task::spawn_blocking(move || mirror_directory_sync(&source, &destination, &options))
+        .await?
+}
+
+/// Blocking version of [`mirror_directory`].
+pub fn mirror_directory_sync(source: &Path, destination: &Path, options: &MirrorOptions) -> Result {
+    let follow_links = options.symlinks == SymlinkPolicy::Follow;
+    let walker = WalkDir::new(source).min_depth(1).follow_links(follow_links).into_iter();
+    let entries = walker
+        .filter_entry(|entry| {
+            entry.path().strip_prefix(source).map_or(true, |path| !options.is_excluded(path))
+        })
+        .collect_result()?;
+
+    let mut selected = Vec::new();
+    for entry in entries {
+        let relative_path = entry.path().strip_prefix(source)?.to_path_buf();
+        let file_type = entry.file_type();
+        let keep = if file_type.is_dir() {
+            true
+        } else if file_type.is_symlink() {
+            options.symlinks == SymlinkPolicy::Preserve && options.is_included(&relative_path)
+        } else {
+            options.is_included(&relative_path)
+        };
+        if keep {
+            selected.push((relative_path, entry));
+        }
+    }
+
+    let files_total = selected.iter().filter(|(_, entry)| !entry.file_type().is_dir()).count();
+    let mut files_done = 0;
+    let mut bytes_copied = 0;
+    let mut expected = HashSet::new();
+    for (relative_path, entry) in &selected {
+        let target = destination.join(relative_path);
+        let file_type = entry.file_type();
+        if file_type.is_dir() {
+            remove_unless(&target, Metadata::is_dir)?;
+            crate::fs::create_dir_if_missing(&target)?;
+        } else if file_type.is_symlink() {
+            crate::fs::remove_if_exists(&target)?;
+            let link_target = std::fs::read_link(entry.path())?;
+            crate::fs::symlink_auto(link_target, &target)?;
+        } else {
+            bytes_copied += copy_file(entry.path(), &target, options.preserve_executable)?;
+        }
+        expected.insert(relative_path.clone());
+        if !file_type.is_dir() {
+            files_done += 1;
+            if let Some(callback) = &options.progress {
+                callback(&MirrorProgress {
+                    path: relative_path,
+                    files_done,
+                    files_total,
+                    bytes_copied,
+                });
+            }
+        }
+    }
+
+    if options.delete_extraneous {
+        delete_extraneous(destination, &expected, options)?;
+    }
+    Ok(())
+}
+
+/// Remove the path if it exists and does not satisfy the predicate.
+fn remove_unless(path: &Path, predicate: impl FnOnce(&Metadata) -> bool) -> Result {
+    match path.symlink_metadata() {
+        Ok(metadata) if !predicate(&metadata) => crate::fs::remove_if_exists(path),
+        _ => Ok(()),
+    }
+}
+
+/// Copy the file, unless the target has the same size and modification time. Returns the number
+/// of copied bytes.
+fn copy_file(source: &Path, target: &Path, preserve_executable: bool) -> Result<u64> {
+    let source_metadata = crate::fs::metadata(source)?;
+    let modified = FileTime::from_last_modification_time(&source_metadata);
+    if let Ok(target_metadata) = target.symlink_metadata() {
+        if target_metadata.is_file()
+            && target_metadata.len() == source_metadata.len()
+            && FileTime::from_last_modification_time(&target_metadata) == modified
+        {
+            return Ok(0);
+        }
+        // The target might be a directory or a link in place of the file.
+        crate::fs::remove_if_exists(target)?;
+    }
+    let copied = crate::fs::wrappers::copy(source, target)?;
+    if !preserve_executable {
+        clear_executable_bits(target)?;
+    }
+    filetime::set_file_mtime(target, modified)?;
+    Ok(copied)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn clear_executable_bits(path: &Path) -> Result {
+    use std::os::unix::prelude::*;
+    let mut permissions = crate::fs::metadata(path)?.permissions();
+    permissions.set_mode(permissions.mode() & !0o111);
+    std::fs::set_permissions(path, permissions)?;
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+fn clear_executable_bits(_path: &Path) -> Result {
+    // No executable bits on Windows.
+    Ok(())
+}
+
+/// Remove the destination entries that were not mirrored from the source.
+fn delete_extraneous(
+    destination: &Path,
+    expected: &HashSet<PathBuf>,
+    options: &MirrorOptions,
+) -> Result {
+    let walker = WalkDir::new(destination).min_depth(1).contents_first(true).into_iter();
+    for entry in walker.filter_entry(|entry| {
+        entry.path().strip_prefix(destination).map_or(true, |path| !options.is_excluded(path))
+    }) {
+        let entry = entry?;
+        let relative_path = entry.path().strip_prefix(destination)?;
+        if !expected.contains(relative_path) {
+            debug!("Removing extraneous {}.", entry.path().display());
+            crate::fs::remove_if_exists(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn mirroring() -> Result {
+        let dir = tempfile::tempdir()?;
+        let (source, destination) = (dir.path().join("source"), dir.path().join("destination"));
+        crate::fs::write(source.join_iter(["bin", "enso"]), "launcher")?;
+        crate::fs::write(source.join_iter(["lib", "std.jar"]), "jar")?;
+        crate::fs::write(source.join_iter(["lib", "cache", "index.bin"]), "cache")?;
+        crate::fs::write(destination.join_iter(["lib", "old.jar"]), "stale")?;
+        crate::fs::write(destination.join_iter(["local.conf"]), "kept")?;
+
+        let handled = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let handled_by_callback = handled.clone();
+        let options = MirrorOptions::default()
+            .exclude("lib/cache")?
+            .exclude("local.conf")?
+            .on_progress(move |progress| {
+                handled_by_callback.lock().unwrap().push(progress.path.to_path_buf())
+            });
+        mirror_directory(&source, &destination, options).await?;
+
+        assert_eq!(crate::fs::read_to_string(destination.join_iter(["bin", "enso"]))?, "launcher");
+        assert!(destination.join_iter(["lib", "std.jar"]).exists());
+        assert!(!destination.join_iter(["lib", "old.jar"]).exists());
+        assert!(!destination.join_iter(["lib", "cache"]).exists());
+        // Excluded files are not deleted.
+        assert!(destination.join("local.conf").exists());
+        assert_eq!(handled.lock().unwrap().len(), 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+//! Temporary files and directories, placed and cleaned up according to the crate-wide policy.
+//!
+//! On CI the temporary entries are created under `RUNNER_TEMP`, which the runner cleans between
+//! the jobs. Locally, the system temporary directory is used. If `ENSO_KEEP_TEMP` is set, the
+//! directories of the failed operations are kept for inspection.
+
+use crate::prelude::*;
+
+use crate::actions::env::RUNNER_TEMP;
+
+
+crate::define_env_var! {
+    /// If set, the temporary directories are not removed when the operation using them fails.
+    ENSO_KEEP_TEMP, bool, optional
+}
+
+/// Whether the temporary directories of the failed operations should be kept.
+pub fn keep_on_failure() -> bool {
+    ENSO_KEEP_TEMP.try_get().ok().flatten().unwrap_or(false)
+}
+
+/// Directory where the temporary entries are created.
+pub fn root() -> PathBuf {
+    RUNNER_TEMP.get().unwrap_or_else(|_| std::env::temp_dir())
+}
+
+/// Prefix of the temporary entry names, so they can be told apart when kept.
+fn prefix(purpose: &str) -> String {
+    format!("enso-{purpose}-")
+}
+
+/// Temporary directory, removed when dropped.
+///
+/// If dropped during a panic, it is kept when [`keep_on_failure`] is set.
+#[derive(Debug)]
+pub struct TempDir {
+    inner: Option<tempfile::TempDir>,
+}
+
+impl TempDir {
+    pub fn path(&self) -> &Path {
+        // The inner directory is taken only when `self` is consumed or dropped.
+        self.inner.as_ref().unwrap().path()
+    }
+
+    /// Keep the directory, returning its path.
+    pub fn keep(mut self) -> PathBuf {
+        self.inner.take().unwrap().into_path()
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        self.path()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.take() {
+            if std::thread::panicking() && keep_on_failure() {
+                warn!("Keeping the temporary directory {}.", inner.into_path().display());
+            } else {
+                trace!("Removing the temporary directory {}.", inner.path().display());
+            }
+        }
+    }
+}
+
+/// Create a temporary directory for the given purpose, like `wasm-build`.
+#[context("Failed to create a temporary directory for {purpose}.")]
+pub fn dir(purpose: &str) -> Result<TempDir> {
+    let root = root();
+    crate::fs::create_dir_if_missing(&root)?;
+    let inner = tempfile::Builder::new().prefix(&prefix(purpose)).tempdir_in(&root)?;
+    debug!("Created the temporary directory {} for {purpose}.", inner.path().display());
+    Ok(TempDir { inner: Some(inner) })
+}
+
+/// Create a temporary file with the given extension (like `.zip`), removed when dropped.
+#[context("Failed to create a temporary file for {purpose}.")]
+pub fn file(purpose: &str, suffix: &str) -> Result<tempfile::NamedTempFile> {
+    let root = root();
+    crate::fs::create_dir_if_missing(&root)?;
+    let file =
+        tempfile::Builder::new().prefix(&prefix(purpose)).suffix(suffix).tempfile_in(&root)?;
+    debug!("Created the temporary file {} for {purpose}.", file.path().display());
+    Ok(file)
+}
+
+/// Run the operation with a temporary directory, removed afterwards.
+///
+/// If the operation fails and [`keep_on_failure`] is set, the directory is kept.
+pub async fn with_dir<F, Fut, T>(purpose: &str, operation: F) -> Result<T>
+where
+    F: FnOnce(PathBuf) -> Fut,
+    Fut: Future<Output = Result<T>>, {
+    let dir = dir(purpose)?;
+    let result = operation(dir.path().to_path_buf()).await;
+    if result.is_err() && keep_on_failure() {
+        warn!("Keeping the temporary directory {} of the failed {purpose}.", dir.keep().display());
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn scoped_directories() -> Result {
+        let temp = dir("test")?;
+        let path = temp.path().to_path_buf();
+        assert!(path.file_name().unwrap().to_string_lossy().starts_with("enso-test-"));
+        drop(temp);
+        assert!(!path.exists());
+
+        let kept = dir("test")?.keep();
+        assert!(kept.exists());
+        crate::fs::remove_dir_if_exists(&kept)?;
+
+        let used = with_dir("test", |path| async move { Ok(path) }).await?;
+        assert!(!used.exists());
+        Ok(())
+    }
+}
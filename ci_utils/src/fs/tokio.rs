@@ -57,10 +57,17 @@ pub async fn remove_dir_if_exists(path: impl AsRef<Path>) -> Result {
     }
 }
 
+/// See [`crate::fs::remove_dir_robust`]. The removal runs on a blocking thread, so waiting for the
+/// retries does not stall the async executor.
+pub async fn remove_dir_robust(path: impl AsRef<Path>) -> Result {
+    let path = path.as_ref().to_path_buf();
+    tokio::task::spawn_blocking(move || crate::fs::remove_dir_robust(path)).await?
+}
+
 /// Recreate directory, so it exists and is empty.
 pub async fn reset_dir(path: impl AsRef<Path>) -> Result {
     let path = path.as_ref();
-    remove_dir_if_exists(&path).await?;
+    remove_dir_robust(&path).await?;
     create_dir_if_missing(&path).await?;
     Ok(())
 }
@@ -0,0 +1,140 @@
+//! Watching directory trees for changes, e.g. to rebuild the sources touched during development.
+
+use crate::prelude::*;
+
+use crate::archive::matches_any;
+use notify::EventKind;
+use notify::RecursiveMode;
+use notify::Watcher;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+
+/// How long to wait for more changes before reporting them. Editors and build tools usually
+/// touch several files in a row.
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Options of [`watch`].
+#[derive(Clone, Debug)]
+pub struct WatchOptions {
+    /// If not empty, only the changes to the files matching any of these patterns are reported.
+    pub include:  Vec<glob::Pattern>,
+    /// The changes to the files matching any of these patterns are ignored.
+    pub exclude:  Vec<glob::Pattern>,
+    pub debounce: Duration,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self { include: default(), exclude: default(), debounce: DEFAULT_DEBOUNCE }
+    }
+}
+
+impl WatchOptions {
+    /// Report only the changes to the files matching the glob pattern, like `src/**/*.rs`.
+    pub fn include(mut self, pattern: &str) -> Result<Self> {
+        self.include.push(glob::Pattern::new(pattern)?);
+        Ok(self)
+    }
+
+    /// Ignore the changes to the files matching the glob pattern, like `target/**`.
+    pub fn exclude(mut self, pattern: &str) -> Result<Self> {
+        self.exclude.push(glob::Pattern::new(pattern)?);
+        Ok(self)
+    }
+
+    pub fn debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Whether the change to the path (relative to the watched root) should be reported.
+    pub fn is_relevant(&self, relative_path: &Path) -> bool {
+        (self.include.is_empty() || matches_any(&self.include, relative_path))
+            && !matches_any(&self.exclude, relative_path)
+    }
+}
+
+/// State of the stream returned by [`watch`].
+struct State {
+    /// Kept alive for as long as the stream is.
+    _watcher: notify::RecommendedWatcher,
+    events:   UnboundedReceiver<notify::Result<notify::Event>>,
+    root:     PathBuf,
+    options:  WatchOptions,
+}
+
+impl State {
+    /// Relevant paths changed by the event, relative to the watched root.
+    fn relevant_paths(&self, event: notify::Event) -> Vec<PathBuf> {
+        if matches!(event.kind, EventKind::Access(_)) {
+            return default();
+        }
+        event
+            .paths
+            .into_iter()
+            .filter_map(|path| path.strip_prefix(&self.root).ok().map(Path::to_path_buf))
+            .filter(|path| self.options.is_relevant(path))
+            .collect()
+    }
+
+    /// Wait for the relevant changes, then collect the following ones until there is a pause.
+    async fn next_changes(&mut self) -> Option<Result<BTreeSet<PathBuf>>> {
+        let mut changes = BTreeSet::new();
+        while changes.is_empty() {
+            match self.events.recv().await? {
+                Ok(event) => changes.extend(self.relevant_paths(event)),
+                Err(e) => return Some(Err(e.into())),
+            }
+        }
+        while let Ok(Some(event)) =
+            tokio::time::timeout(self.options.debounce, self.events.recv()).await
+        {
+            match event {
+                Ok(event) => changes.extend(self.relevant_paths(event)),
+                Err(e) => return Some(Err(e.into())),
+            }
+        }
+        Some(Ok(changes))
+    }
+}
+
+/// Watch the directory tree for changes.
+///
+/// Each item of the stream is a set of the changed paths, relative to the root, that were
+/// touched in a quick succession. The watching stops when the stream is dropped.
+#[context("Failed to watch {} for changes.", root.as_ref().display())]
+pub fn watch(
+    root: impl AsRef<Path>,
+    options: WatchOptions,
+) -> Result<BoxStream<'static, Result<BTreeSet<PathBuf>>>> {
+    // The reported paths are canonical, at least on macOS.
+    let root = crate::fs::canonicalize(&root)?;
+    let (sender, events) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        // Fails only if the stream was dropped, so nobody is interested anymore.
+        let _ = sender.send(event);
+    })?;
+    watcher.watch(&root, RecursiveMode::Recursive)?;
+    debug!("Watching {} for changes.", root.display());
+    let state = State { _watcher: watcher, events, root, options };
+    let stream = futures::stream::unfold(state, |mut state| async move {
+        let changes = state.next_changes().await?;
+        Some((changes, state))
+    });
+    Ok(stream.boxed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filtering_changes() -> Result {
+        let options = WatchOptions::default().include("**/*.rs")?.exclude("target/**")?;
+        assert!(options.is_relevant(Path::new("lib/rust/parser/src/lib.rs")));
+        assert!(!options.is_relevant(Path::new("target/debug/build/out.rs")));
+        assert!(!options.is_relevant(Path::new("README.md")));
+        Ok(())
+    }
+}
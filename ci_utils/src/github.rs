@@ -17,9 +17,14 @@ use reqwest::Response;
 
 const MAX_PER_PAGE: u8 = 100;
 
+pub mod client;
+pub mod event;
 pub mod model;
+pub mod permissions;
 pub mod release;
 
+pub use client::Client;
+
 /// Goes over all the pages and returns result.
 ///
 /// We prefer taking a future page result rather than page itself to be able to easily wrap both
@@ -67,7 +72,9 @@ pub trait RepoPointer: Display {
         &self,
         client: &Octocrab,
     ) -> Result<Vec<octocrab::models::repos::Release>> {
-        get_all(client, self.repos(client).releases().list().per_page(MAX_PER_PAGE).send())
+        Client::new(client.clone())
+            .releases(self)
+            .try_collect()
             .await
             .context(format!("Failed to list all releases in the {self} repository."))
     }
@@ -113,20 +120,31 @@ pub trait RepoPointer: Display {
         run_id: RunId,
         name: &str,
     ) -> Result<WorkflowListArtifact> {
-        let artifacts = client
-            .actions()
-            .list_workflow_run_artifacts(self.owner(), self.name(), run_id)
-            .per_page(100)
-            .send()
+        self.try_find_artifact_by_name(client, run_id, name)
+            .await?
+            .with_context(|| format!("Failed to find artifact by name '{name}'."))
+    }
+
+    /// Like [`find_artifact_by_name`](Self::find_artifact_by_name), but the missing artifact is
+    /// not an error. Failures of listing the artifacts are.
+    #[tracing::instrument(skip(client), fields(%self, %run_id, %name), err)]
+    async fn try_find_artifact_by_name(
+        &self,
+        client: &Octocrab,
+        run_id: RunId,
+        name: &str,
+    ) -> Result<Option<WorkflowListArtifact>> {
+        let mut artifacts = Client::new(client.clone()).run_artifacts(self, run_id);
+        while let Some(artifact) = artifacts
+            .try_next()
             .await
             .context(format!("Failed to list artifacts of run {run_id} in {self}."))?
-            .value
-            .context(format!("Failed to find any artifacts."))?;
-
-        artifacts
-            .into_iter()
-            .find(|artifact| artifact.name == name)
-            .context(format!("Failed to find artifact by name '{name}'."))
+        {
+            if artifact.name == name {
+                return Ok(Some(artifact));
+            }
+        }
+        Ok(None)
     }
 
     async fn download_artifact(&self, client: &Octocrab, artifact_id: ArtifactId) -> Result<Bytes> {
@@ -144,8 +162,9 @@ pub trait RepoPointer: Display {
         output_dir: &Path,
     ) -> Result {
         let bytes = self.download_artifact(client, artifact_id).await?;
-        crate::archive::zip::extract_bytes(bytes, output_dir)?;
-        Ok(())
+        let output_dir = output_dir.to_path_buf();
+        tokio::task::spawn_blocking(move || crate::archive::zip::extract_bytes(bytes, output_dir))
+            .await?
     }
 
     #[tracing::instrument(name="Get the asset information.", skip(client), fields(self=%self), err)]
@@ -258,9 +277,5 @@ pub async fn fetch_runner(octocrab: &Octocrab, os: OS, output_dir: impl AsRef<Pa
 pub fn create_client(pat: impl AsRef<str>) -> Result<reqwest::Client> {
     let mut header_map = reqwest::header::HeaderMap::new();
     header_map.append(reqwest::header::AUTHORIZATION, format!("Bearer {}", pat.as_ref()).parse()?);
-    reqwest::Client::builder()
-        .user_agent("enso-build")
-        .default_headers(header_map)
-        .build()
-        .anyhow_err()
+    crate::net::client_builder()?.default_headers(header_map).build().anyhow_err()
 }
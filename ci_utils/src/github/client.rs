@@ -0,0 +1,326 @@
+//! Shared client for the GitHub REST API.
+//!
+//! Centralizes the discovery of the access token, the handling of the secondary rate limits and
+//! the pagination, so the individual API wrappers don't need to reimplement them.
+
+use crate::prelude::*;
+
+use crate::github::permissions::explain_forbidden;
+use octocrab::models::repos::Release;
+use octocrab::models::workflows::Run;
+use octocrab::models::workflows::WorkflowListArtifact;
+use octocrab::models::Repository;
+use octocrab::models::RunId;
+use reqwest::header::HeaderMap;
+use reqwest::Response;
+use reqwest::StatusCode;
+use std::time::Duration;
+
+
+/// Environment variable with the GitHub access token.
+pub const TOKEN_VARIABLE: &str = "GITHUB_TOKEN";
+
+/// How many times a request is retried after hitting the secondary rate limit.
+pub const DEFAULT_MAX_RETRIES: usize = 3;
+
+/// Delay before retrying a request hitting the secondary rate limit, if the response does not
+/// specify one. This is what GitHub recommends.
+pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Get the GitHub access token.
+///
+/// The token is read from the `GITHUB_TOKEN` environment variable or, if it is not set, from the
+/// `GITHUB_TOKEN` file in the home directory.
+pub fn token_from_env() -> Result<String> {
+    fn get_token_from_file() -> Result<String> {
+        let path =
+            dirs::home_dir().context("Failed to locate home directory.")?.join(TOKEN_VARIABLE);
+        let content = crate::fs::read_to_string(path)?;
+        Ok(content.trim().into())
+    }
+
+    crate::env::expect_var(TOKEN_VARIABLE)
+        .inspect(|_| debug!("Will use {TOKEN_VARIABLE} environment variable."))
+        .or_else(|_| get_token_from_file())
+}
+
+/// Client for the GitHub REST API, shared by the API wrappers in this crate.
+///
+/// Dereferences to [`Octocrab`], so it can be used wherever the typed octocrab APIs are needed.
+#[derive(Clone, Debug)]
+pub struct Client {
+    pub octocrab:    Octocrab,
+    /// How many times a request hitting the secondary rate limit is retried.
+    pub max_retries: usize,
+}
+
+impl Deref for Client {
+    type Target = Octocrab;
+    fn deref(&self) -> &Self::Target {
+        &self.octocrab
+    }
+}
+
+impl From<Octocrab> for Client {
+    fn from(octocrab: Octocrab) -> Self {
+        Self::new(octocrab)
+    }
+}
+
+impl Client {
+    pub fn new(octocrab: Octocrab) -> Self {
+        Self { octocrab, max_retries: DEFAULT_MAX_RETRIES }
+    }
+
+    /// Create the `octocrab` client on top of the HTTP client with the
+    /// [network settings from the environment](crate::net).
+    ///
+    /// The authorization header is set on the HTTP client, as `octocrab` does not touch the headers
+    /// of a client it has been given.
+    fn build_octocrab(token: Option<&str>) -> Result<Octocrab> {
+        let mut header_map = HeaderMap::new();
+        header_map.append(reqwest::header::ACCEPT, "application/vnd.github.v3+json".parse()?);
+        if let Some(token) = token {
+            header_map.append(reqwest::header::AUTHORIZATION, format!("Bearer {token}").parse()?);
+        }
+        let client = crate::net::client_builder()?.default_headers(header_map).build()?;
+        Ok(Octocrab::builder().client(client).build()?)
+    }
+
+    /// Create a client authorized with the given personal access token.
+    pub fn with_token(token: impl Into<String>) -> Result<Self> {
+        Ok(Self::new(Self::build_octocrab(Some(&token.into()))?))
+    }
+
+    /// Create a client without the authorization, subject to much lower rate limits.
+    pub fn anonymous() -> Result<Self> {
+        Ok(Self::new(Self::build_octocrab(None)?))
+    }
+
+    /// Create a client authorized with the [token from the environment](token_from_env).
+    ///
+    /// If there is no token, the client is [anonymous](Self::anonymous).
+    pub fn from_env() -> Result<Self> {
+        match token_from_env() {
+            Ok(token) => Self::with_token(token),
+            Err(e) => {
+                warn!("No GitHub access token available, using anonymous access: {e:#}");
+                Self::anonymous()
+            }
+        }
+    }
+
+    /// Send the request, retrying it after the delay requested by GitHub if it hits the secondary
+    /// rate limit.
+    async fn send_retrying<Fut>(&self, send: impl Fn() -> Fut) -> Result<Response>
+    where Fut: Future<Output = octocrab::Result<Response>> {
+        let mut retries = 0;
+        loop {
+            let response = send().await?;
+            let error = match response.error_for_status_ref().err() {
+                Some(error) => error,
+                None => return Ok(response),
+            };
+            // The secondary rate limit is sometimes told apart from the permission errors only
+            // by the message, so the body is needed.
+            let status = response.status();
+            let headers = response.headers().clone();
+            let body = response.text().await.unwrap_or_default();
+            match secondary_rate_limit_delay(status, &headers, &body) {
+                Some(delay) if retries < self.max_retries => {
+                    retries += 1;
+                    warn!(
+                        "Hit the GitHub secondary rate limit, retry {retries}/{} in {delay:?}.",
+                        self.max_retries
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                _ => {
+                    let error = match explain_forbidden(&headers) {
+                        Some(explanation) => Err(error).context(explanation),
+                        None => Err(error).anyhow_err(),
+                    };
+                    return error.context(format!("Error message body: {body}"));
+                }
+            }
+        }
+    }
+
+    /// Send a GET request to the given API path or URL.
+    ///
+    /// Requests hitting the secondary rate limit are retried after the delay requested by GitHub.
+    pub async fn get_response(&self, url: impl AsRef<str>) -> Result<Response> {
+        let url = self.octocrab.absolute_url(url)?;
+        self.send_retrying(|| self.octocrab._get(url.as_str(), None::<&()>)).await
+    }
+
+    /// Send a DELETE request to the given API path or URL.
+    ///
+    /// Requests hitting the secondary rate limit are retried, like in [`Self::get_response`].
+    pub async fn delete(&self, url: impl AsRef<str>) -> Result<Response> {
+        let url = self.octocrab.absolute_url(url)?;
+        self.send_retrying(|| self.octocrab._delete(url.as_str(), None::<&()>)).await
+    }
+
+    /// Send a GET request and deserialize the JSON response.
+    pub async fn get_json<T: DeserializeOwned>(&self, url: impl AsRef<str>) -> Result<T> {
+        let url = url.as_ref();
+        let response = self.get_response(url).await?;
+        response.json().await.context(format!("Failed to deserialize the response from {url}."))
+    }
+
+    /// Stream all the items of a paginated listing, fetching the pages as they are needed.
+    ///
+    /// The `field` names the array of items in the response object, like `workflow_runs`. If it
+    /// is `None`, the response is expected to be the array itself.
+    pub fn paginate<T: DeserializeOwned + Send + 'static>(
+        &self,
+        path: impl AsRef<str>,
+        field: Option<&'static str>,
+    ) -> BoxStream<'static, Result<T>> {
+        let mut url = match self.octocrab.absolute_url(path) {
+            Ok(url) => url,
+            Err(e) => return futures::stream::once(ready(Err(e.into()))).boxed(),
+        };
+        url.query_pairs_mut().append_pair("per_page", &super::MAX_PER_PAGE.to_string());
+        let client = self.clone();
+        futures::stream::try_unfold(Some(url), move |url| {
+            let client = client.clone();
+            async move {
+                let url = match url {
+                    Some(url) => url,
+                    None => return Ok(None),
+                };
+                let response = client.get_response(&url).await?;
+                let next = next_page_url(response.headers());
+                let mut body: serde_json::Value = response.json().await?;
+                let items = match field {
+                    Some(field) => body
+                        .get_mut(field)
+                        .map(serde_json::Value::take)
+                        .context(format!("No `{field}` in the response from {url}."))?,
+                    None => body,
+                };
+                let items: Vec<T> = serde_json::from_value(items)?;
+                let items = futures::stream::iter(items.into_iter().map(Ok));
+                Ok::<_, anyhow::Error>(Some((items, next)))
+            }
+        })
+        .try_flatten()
+        .boxed()
+    }
+
+    /// Releases in the repository, including the drafts, newest first.
+    pub fn releases(
+        &self,
+        repo: &(impl RepoPointer + ?Sized),
+    ) -> BoxStream<'static, Result<Release>> {
+        self.paginate(format!("/repos/{}/{}/releases", repo.owner(), repo.name()), None)
+    }
+
+    /// All the repositories of the organization.
+    pub fn org_repos(&self, org: &str) -> BoxStream<'static, Result<Repository>> {
+        self.paginate(format!("/orgs/{org}/repos"), None)
+    }
+
+    /// Workflow runs in the repository, newest first.
+    pub fn workflow_runs(
+        &self,
+        repo: &(impl RepoPointer + ?Sized),
+    ) -> BoxStream<'static, Result<Run>> {
+        let path = format!("/repos/{}/{}/actions/runs", repo.owner(), repo.name());
+        self.paginate(path, Some("workflow_runs"))
+    }
+
+    /// Artifacts of the workflow run.
+    pub fn run_artifacts(
+        &self,
+        repo: &(impl RepoPointer + ?Sized),
+        run_id: RunId,
+    ) -> BoxStream<'static, Result<WorkflowListArtifact>> {
+        let path =
+            format!("/repos/{}/{}/actions/runs/{run_id}/artifacts", repo.owner(), repo.name());
+        self.paginate(path, Some("artifacts"))
+    }
+}
+
+/// Delay before retrying the request, if the response denotes hitting the secondary rate limit.
+///
+/// Exhausting the primary rate limit is not retried, as it might take up to an hour to reset.
+pub fn secondary_rate_limit_delay(
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: &str,
+) -> Option<Duration> {
+    if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    if let Some(seconds) = header("retry-after").and_then(|value| value.trim().parse().ok()) {
+        Some(Duration::from_secs(seconds))
+    } else if header("x-ratelimit-remaining") == Some("0") {
+        None
+    } else {
+        // Otherwise, a 403 is a permission error, unless the message says it is the rate limit.
+        let is_rate_limit = status == StatusCode::TOO_MANY_REQUESTS
+            || body.to_lowercase().contains("secondary rate limit");
+        is_rate_limit.then(|| DEFAULT_RETRY_DELAY)
+    }
+}
+
+/// Get the URL of the next page from the `Link` header of a paginated response.
+pub fn next_page_url(headers: &HeaderMap) -> Option<Url> {
+    let link = headers.get(reqwest::header::LINK)?.to_str().ok()?;
+    link.split(',').find_map(|entry| {
+        let (url, params) = entry.split_once(';')?;
+        let is_next = params.split(';').any(|param| param.trim() == r#"rel="next""#);
+        let url = url.trim().strip_prefix('<')?.strip_suffix('>')?;
+        is_next.then(|| url.parse().ok()).flatten()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(entries: &[(&'static str, &'static str)]) -> HeaderMap {
+        entries
+            .iter()
+            .map(|(name, value)| (name.parse().unwrap(), HeaderValue::from_static(value)))
+            .collect()
+    }
+
+    #[test]
+    fn secondary_rate_limit() {
+        let forbidden = StatusCode::FORBIDDEN;
+        let retry_after = headers(&[("retry-after", "30")]);
+        let delay = secondary_rate_limit_delay(forbidden, &retry_after, "");
+        assert_eq!(delay, Some(Duration::from_secs(30)));
+        let delay = secondary_rate_limit_delay(StatusCode::TOO_MANY_REQUESTS, &default(), "");
+        assert_eq!(delay, Some(DEFAULT_RETRY_DELAY));
+        let message = r#"{"message":"You have exceeded a secondary rate limit."}"#;
+        let delay = secondary_rate_limit_delay(forbidden, &default(), message);
+        assert_eq!(delay, Some(DEFAULT_RETRY_DELAY));
+
+        let exhausted = headers(&[("x-ratelimit-remaining", "0")]);
+        assert_eq!(secondary_rate_limit_delay(forbidden, &exhausted, ""), None);
+        let message = r#"{"message":"Resource not accessible by integration"}"#;
+        assert_eq!(secondary_rate_limit_delay(forbidden, &default(), message), None);
+        assert_eq!(secondary_rate_limit_delay(StatusCode::NOT_FOUND, &retry_after, ""), None);
+    }
+
+    #[test]
+    fn next_page() {
+        let link = headers(&[(
+            "link",
+            r#"<https://api.github.com/repositories/1/actions/runs?page=2>; rel="next", <https://api.github.com/repositories/1/actions/runs?page=9>; rel="last""#,
+        )]);
+        let next = next_page_url(&link).unwrap();
+        assert_eq!(next.as_str(), "https://api.github.com/repositories/1/actions/runs?page=2");
+
+        let last = headers(&[("link", r#"<https://api.github.com/x?page=1>; rel="prev""#)]);
+        assert_eq!(next_page_url(&last), None);
+        assert_eq!(next_page_url(&default()), None);
+    }
+}
@@ -0,0 +1,273 @@
+//! Payloads of the events that trigger the workflows.
+//!
+//! Only the fields used by the build scripts are described, the rest of the payload is ignored.
+//!
+//! See: <https://docs.github.com/en/developers/webhooks-and-events/webhooks/webhook-events-and-payloads>
+
+use crate::prelude::*;
+
+use crate::actions::env;
+
+
+/// Event that triggered the workflow run.
+#[derive(Clone, Debug)]
+pub enum Event {
+    Push(PushEvent),
+    PullRequest(PullRequestEvent),
+    Release(ReleaseEvent),
+    WorkflowDispatch(WorkflowDispatchEvent),
+    /// Event of a kind that is not described by a dedicated type.
+    Other {
+        name:    String,
+        payload: serde_json::Value,
+    },
+}
+
+impl Event {
+    /// Parse the payload of the event with the given name, like `pull_request`.
+    pub fn parse(name: &str, payload: &str) -> Result<Self> {
+        let event = match name {
+            "push" => Event::Push(serde_json::from_str(payload)?),
+            "pull_request" | "pull_request_target" =>
+                Event::PullRequest(serde_json::from_str(payload)?),
+            "release" => Event::Release(serde_json::from_str(payload)?),
+            "workflow_dispatch" => Event::WorkflowDispatch(serde_json::from_str(payload)?),
+            _ => Event::Other { name: name.into(), payload: serde_json::from_str(payload)? },
+        };
+        Ok(event)
+    }
+
+    /// Read the event that triggered the current workflow run.
+    #[context("Failed to read the event that triggered the workflow.")]
+    pub fn from_env() -> Result<Self> {
+        let name = env::GITHUB_EVENT_NAME.get()?;
+        let payload = crate::fs::read_to_string(env::GITHUB_EVENT_PATH.get()?)?;
+        Self::parse(&name, &payload).context(format!("Failed to parse the {name} event payload."))
+    }
+
+    pub fn as_pull_request(&self) -> Option<&PullRequestEvent> {
+        match self {
+            Event::PullRequest(event) => Some(event),
+            _ => None,
+        }
+    }
+
+    pub fn as_workflow_dispatch(&self) -> Option<&WorkflowDispatchEvent> {
+        match self {
+            Event::WorkflowDispatch(event) => Some(event),
+            _ => None,
+        }
+    }
+}
+
+/// Repository in which the event occurred.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Repository {
+    pub full_name:      String,
+    pub default_branch: Option<String>,
+}
+
+/// Commit in the [`PushEvent`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Commit {
+    pub id:       String,
+    pub message:  String,
+    #[serde(default)]
+    pub added:    Vec<String>,
+    #[serde(default)]
+    pub removed:  Vec<String>,
+    #[serde(default)]
+    pub modified: Vec<String>,
+}
+
+/// One or more commits pushed to a branch or a tag.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PushEvent {
+    /// The full ref that was pushed, like `refs/heads/develop`.
+    #[serde(rename = "ref")]
+    pub git_ref:     String,
+    pub before:      String,
+    pub after:       String,
+    #[serde(default)]
+    pub created:     bool,
+    #[serde(default)]
+    pub deleted:     bool,
+    #[serde(default)]
+    pub forced:      bool,
+    /// The pushed commits. GitHub lists at most 20 of them.
+    #[serde(default)]
+    pub commits:     Vec<Commit>,
+    pub head_commit: Option<Commit>,
+    pub repository:  Repository,
+}
+
+impl PushEvent {
+    /// Paths of the files touched by the listed commits.
+    pub fn changed_files(&self) -> BTreeSet<&str> {
+        self.commits
+            .iter()
+            .flat_map(|commit| commit.added.iter().chain(&commit.removed).chain(&commit.modified))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Label {
+    pub name: String,
+}
+
+/// Head or base of the pull request.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PullRequestBranch {
+    /// Branch name, like `develop`.
+    #[serde(rename = "ref")]
+    pub git_ref: String,
+    pub sha:     String,
+    pub label:   Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PullRequest {
+    pub number:        u64,
+    pub title:         String,
+    pub body:          Option<String>,
+    #[serde(default)]
+    pub draft:         bool,
+    #[serde(default)]
+    pub merged:        bool,
+    pub head:          PullRequestBranch,
+    pub base:          PullRequestBranch,
+    #[serde(default)]
+    pub labels:        Vec<Label>,
+    /// Number of files changed by the pull request.
+    pub changed_files: Option<u64>,
+}
+
+impl PullRequest {
+    pub fn has_label(&self, name: &str) -> bool {
+        self.labels.iter().any(|label| label.name == name)
+    }
+}
+
+/// Activity on a pull request.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PullRequestEvent {
+    /// What happened, like `opened`, `synchronize` or `labeled`.
+    pub action:       String,
+    pub number:       u64,
+    pub pull_request: PullRequest,
+    pub repository:   Repository,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Release {
+    pub id:         u64,
+    pub tag_name:   String,
+    pub name:       Option<String>,
+    #[serde(default)]
+    pub draft:      bool,
+    #[serde(default)]
+    pub prerelease: bool,
+}
+
+/// Activity on a release.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReleaseEvent {
+    /// What happened, like `published` or `created`.
+    pub action:     String,
+    pub release:    Release,
+    pub repository: Repository,
+}
+
+/// Manual trigger of the workflow.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WorkflowDispatchEvent {
+    /// The ref on which the workflow was dispatched.
+    #[serde(rename = "ref")]
+    pub git_ref:  String,
+    /// Path to the workflow file.
+    pub workflow: String,
+    /// Values of the workflow inputs. Booleans and numbers might be passed as JSON values,
+    /// rather than strings.
+    #[serde(default)]
+    pub inputs:   BTreeMap<String, serde_json::Value>,
+}
+
+impl WorkflowDispatchEvent {
+    /// Value of the input, converted to a string.
+    pub fn input(&self, name: &str) -> Option<String> {
+        self.inputs.get(name).and_then(|value| match value {
+            serde_json::Value::Null => None,
+            serde_json::Value::String(text) => Some(text.clone()),
+            other => Some(other.to_string()),
+        })
+    }
+
+    /// Value of the boolean input. Missing inputs are `false`.
+    pub fn flag(&self, name: &str) -> Result<bool> {
+        match self.input(name) {
+            Some(value) => value.parse().context(format!("Input {name} is not a boolean.")),
+            None => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPOSITORY: &str =
+        r#""repository": {"full_name": "enso-org/enso", "default_branch": "develop"}"#;
+
+    #[test]
+    fn pull_request() -> Result {
+        let payload = format!(
+            r#"{{"action": "labeled", "number": 3500, {REPOSITORY}, "pull_request": {{
+                "number": 3500, "title": "Fix docs", "body": null, "draft": false,
+                "head": {{"ref": "wip/docs", "sha": "1a2b3c", "label": "enso-org:wip/docs"}},
+                "base": {{"ref": "develop", "sha": "4d5e6f"}},
+                "labels": [{{"id": 1, "name": "CI: No changelog needed"}}], "changed_files": 2
+            }}}}"#
+        );
+        let event = Event::parse("pull_request", &payload)?;
+        let pull_request = &event.as_pull_request().unwrap().pull_request;
+        assert_eq!(pull_request.base.git_ref, "develop");
+        assert!(pull_request.has_label("CI: No changelog needed"));
+        Ok(())
+    }
+
+    #[test]
+    fn push() -> Result {
+        let payload = format!(
+            r#"{{"ref": "refs/heads/develop", "before": "0000", "after": "1a2b", {REPOSITORY},
+                "commits": [
+                    {{"id": "1a", "message": "A", "added": ["docs/a.md"],
+                        "modified": ["README.md"]}},
+                    {{"id": "2b", "message": "B", "removed": ["docs/a.md"]}}
+                ], "head_commit": null}}"#
+        );
+        match Event::parse("push", &payload)? {
+            Event::Push(push) =>
+                assert_eq!(push.changed_files(), ["README.md", "docs/a.md"].into()),
+            other => panic!("Unexpected event: {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn workflow_dispatch() -> Result {
+        let payload = format!(
+            r#"{{"ref": "refs/heads/develop", "workflow": ".github/workflows/nightly.yml",
+                "inputs": {{"version": "2022.1.1", "prerelease": true, "empty": null}},
+                {REPOSITORY}}}"#
+        );
+        let event = Event::parse("workflow_dispatch", &payload)?;
+        let dispatch = event.as_workflow_dispatch().unwrap();
+        assert_eq!(dispatch.input("version").as_deref(), Some("2022.1.1"));
+        assert!(dispatch.flag("prerelease")?);
+        assert!(!dispatch.flag("empty")?);
+        assert!(matches!(Event::parse("schedule", "{}")?, Event::Other { .. }));
+        Ok(())
+    }
+}
@@ -0,0 +1,251 @@
+//! Introspection of the permissions granted to the GitHub access token.
+//!
+//! GitHub reports insufficient permissions with a generic "Resource not accessible by integration"
+//! message, often deep into the pipeline. The utilities in this module allow declaring the
+//! permissions that an operation needs and checking them upfront, so the missing permission can
+//! be named explicitly.
+
+use crate::prelude::*;
+
+use reqwest::header::HeaderMap;
+
+
+/// Header with the OAuth scopes granted to a classic Personal Access Token.
+pub const OAUTH_SCOPES_HEADER: &str = "X-OAuth-Scopes";
+
+/// Header with the permissions that would have been accepted for the failed request.
+///
+/// Present on responses to requests authorized by GitHub App installation tokens (including the
+/// `GITHUB_TOKEN` in GitHub Actions) and fine-grained Personal Access Tokens.
+pub const ACCEPTED_PERMISSIONS_HEADER: &str = "X-Accepted-GitHub-Permissions";
+
+/// Permission that can be granted to a GitHub token.
+///
+/// See: <https://docs.github.com/en/actions/security-guides/automatic-token-authentication#permissions-for-the-github_token>
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, strum::Display, strum::AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum Permission {
+    Actions,
+    Checks,
+    Contents,
+    Deployments,
+    Issues,
+    Metadata,
+    Packages,
+    PullRequests,
+    Statuses,
+}
+
+/// Level of access to a given resource.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, strum::Display, strum::AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// A single permission that is needed by some operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Requirement {
+    pub permission: Permission,
+    pub access:     Access,
+}
+
+impl Display for Requirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.permission, self.access)
+    }
+}
+
+impl Requirement {
+    pub const fn read(permission: Permission) -> Self {
+        Self { permission, access: Access::Read }
+    }
+
+    pub const fn write(permission: Permission) -> Self {
+        Self { permission, access: Access::Write }
+    }
+
+    /// Classic Personal Access Token scopes, any of which satisfies this requirement.
+    ///
+    /// Empty list means that no scope is needed.
+    pub fn satisfying_scopes(&self) -> &'static [&'static str] {
+        match (self.permission, self.access) {
+            (Permission::Metadata, _) => &[],
+            (Permission::Packages, Access::Read) => &["read:packages", "write:packages"],
+            (Permission::Packages, Access::Write) => &["write:packages"],
+            // Public repositories can be read with any token. We cannot tell whether the
+            // repository is private, so we optimistically assume that reads are fine.
+            (_, Access::Read) => &[],
+            (Permission::Statuses, Access::Write) => &["repo", "repo:status"],
+            (Permission::Deployments, Access::Write) => &["repo", "repo_deployment"],
+            (_, Access::Write) => &["repo", "public_repo"],
+        }
+    }
+}
+
+/// Kind of the GitHub access token, as deduced from its prefix.
+///
+/// See: <https://github.blog/2021-04-05-behind-githubs-new-authentication-token-formats/>
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Classic Personal Access Token (`ghp_`).
+    Classic,
+    /// Fine-grained Personal Access Token (`github_pat_`).
+    FineGrained,
+    /// GitHub App installation token, like the `GITHUB_TOKEN` in GitHub Actions (`ghs_`).
+    Installation,
+    /// OAuth App or GitHub App user-to-server token (`gho_`, `ghu_`).
+    OAuth,
+    /// Token in an unrecognized (possibly legacy) format.
+    Unknown,
+}
+
+impl TokenKind {
+    pub fn from_token(token: &str) -> Self {
+        if token.starts_with("ghp_") {
+            TokenKind::Classic
+        } else if token.starts_with("github_pat_") {
+            TokenKind::FineGrained
+        } else if token.starts_with("ghs_") {
+            TokenKind::Installation
+        } else if token.starts_with("gho_") || token.starts_with("ghu_") {
+            TokenKind::OAuth
+        } else {
+            TokenKind::Unknown
+        }
+    }
+}
+
+/// Effective permissions of a GitHub access token.
+#[derive(Clone, Debug)]
+pub struct TokenPermissions {
+    pub kind:   TokenKind,
+    /// OAuth scopes of the token. Known only for the classic and OAuth tokens.
+    pub scopes: Option<BTreeSet<String>>,
+}
+
+impl TokenPermissions {
+    /// Query the GitHub REST API for the permissions of the given token.
+    #[context("Failed to query the permissions of the GitHub access token.")]
+    pub async fn query(token: &str) -> Result<Self> {
+        let client = crate::github::create_client(token)?;
+        Self::query_with(&client, token).await
+    }
+
+    /// Like [`query`](Self::query), but using a client that has been
+    /// [authorized](crate::github::create_client) with the token already.
+    pub async fn query_with(client: &reqwest::Client, token: &str) -> Result<Self> {
+        let kind = TokenKind::from_token(token);
+        let response = client.get("https://api.github.com/rate_limit").send().await?;
+        let response = crate::io::web::handle_error_response(response).await?;
+        let scopes = response
+            .headers()
+            .get(OAUTH_SCOPES_HEADER)
+            .map(|value| value.to_str().map(parse_scopes))
+            .transpose()?;
+        debug!("Token kind: {kind:?}, scopes: {scopes:?}.");
+        Ok(Self { kind, scopes })
+    }
+
+    /// Requirements that are known not to be satisfied by this token.
+    ///
+    /// Only tokens that report their scopes can be checked. For other tokens (like the
+    /// `GITHUB_TOKEN`) the permissions can't be introspected, so nothing is reported as missing.
+    pub fn missing<'a>(
+        &self,
+        requirements: impl IntoIterator<Item = &'a Requirement>,
+    ) -> Vec<Requirement> {
+        let scopes = match &self.scopes {
+            Some(scopes) => scopes,
+            None => return default(),
+        };
+        requirements
+            .into_iter()
+            .filter(|requirement| {
+                let satisfying = requirement.satisfying_scopes();
+                !satisfying.is_empty() && !satisfying.iter().any(|scope| scopes.contains(*scope))
+            })
+            .copied()
+            .collect()
+    }
+
+    /// Fail with a descriptive error if any of the requirements is not satisfied.
+    pub fn check<'a>(&self, requirements: impl IntoIterator<Item = &'a Requirement>) -> Result {
+        let missing = self.missing(requirements);
+        if missing.is_empty() {
+            if self.scopes.is_none() {
+                debug!(
+                    "Permissions of the {:?} token cannot be introspected, assuming they are \
+                    sufficient.",
+                    self.kind
+                );
+            }
+            Ok(())
+        } else {
+            let explanation = missing
+                .iter()
+                .map(|requirement| {
+                    format!(
+                        "{requirement} (scope: {})",
+                        requirement.satisfying_scopes().join(" or ")
+                    )
+                })
+                .join(", ");
+            bail!("The GitHub access token is missing required permissions: {explanation}.")
+        }
+    }
+}
+
+/// Parse the value of the [`OAUTH_SCOPES_HEADER`] header.
+pub fn parse_scopes(header_value: &str) -> BTreeSet<String> {
+    header_value
+        .split(',')
+        .map(str::trim)
+        .filter(|scope| !scope.is_empty())
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// Describe the permissions that GitHub expected, if the headers of the response to a failed
+/// request have them.
+///
+/// This is meant to augment the "Resource not accessible by integration" errors.
+pub fn explain_forbidden(headers: &HeaderMap) -> Option<String> {
+    let accepted = headers.get(ACCEPTED_PERMISSIONS_HEADER)?.to_str().ok()?;
+    Some(format!(
+        "The request requires one of the following token permission sets: {}.",
+        accepted.split(';').map(str::trim).join(" or ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_kinds() {
+        assert_eq!(TokenKind::from_token("ghp_abcdef"), TokenKind::Classic);
+        assert_eq!(TokenKind::from_token("ghs_abcdef"), TokenKind::Installation);
+        assert_eq!(TokenKind::from_token("github_pat_abcdef"), TokenKind::FineGrained);
+        assert_eq!(TokenKind::from_token("0123456789abcdef"), TokenKind::Unknown);
+    }
+
+    #[test]
+    fn missing_scopes() {
+        let token = TokenPermissions {
+            kind:   TokenKind::Classic,
+            scopes: Some(parse_scopes("read:packages, workflow")),
+        };
+        let requirements = [
+            Requirement::read(Permission::Contents),
+            Requirement::write(Permission::Contents),
+            Requirement::read(Permission::Packages),
+        ];
+        assert_eq!(token.missing(&requirements), vec![Requirement::write(Permission::Contents)]);
+        assert!(token.check(&requirements).is_err());
+
+        let installation = TokenPermissions { kind: TokenKind::Installation, scopes: None };
+        assert!(installation.check(&requirements).is_ok());
+    }
+}
@@ -1,9 +1,143 @@
+//! Management of the GitHub releases: drafting, uploading assets, publishing and fetching assets.
+
 use crate::prelude::*;
 
+use crate::program::retry::AttemptFailure;
+use crate::program::retry::RetryPolicy;
+use octocrab::models::repos::Asset;
+use octocrab::models::repos::Release;
 use octocrab::models::ReleaseId;
 use reqwest::Body;
+use std::time::Duration;
 use tracing::instrument;
 
+
+/// Contents of a release to be created or updated.
+#[derive(Clone, Debug, Default)]
+pub struct ReleaseSpec {
+    pub tag:              String,
+    pub name:             String,
+    /// Release notes, in Markdown.
+    pub body:             String,
+    /// Commit (or branch) that the tag is created from, if it does not exist yet.
+    pub target_commitish: Option<String>,
+    pub prerelease:       bool,
+}
+
+/// Create a draft release.
+#[context("Failed to create a draft release {} in {repo}.", spec.tag)]
+pub async fn create_draft(
+    repo: &(impl RepoPointer + Send + Sync),
+    octocrab: &Octocrab,
+    spec: &ReleaseSpec,
+) -> Result<Release> {
+    let releases = repo.repos(octocrab).releases();
+    let mut builder = releases
+        .create(&spec.tag)
+        .name(&spec.name)
+        .body(&spec.body)
+        .prerelease(spec.prerelease)
+        .draft(true);
+    if let Some(target_commitish) = &spec.target_commitish {
+        builder = builder.target_commitish(target_commitish);
+    }
+    let release = builder.send().await?;
+    info!("Created draft release {} with ID {}.", release.tag_name, release.id);
+    Ok(release)
+}
+
+/// Update the existing draft release with the same tag or, if there is none, create a new one.
+///
+/// Allows re-running the release workflow without leaving behind duplicate drafts.
+#[context("Failed to create or update a draft release {} in {repo}.", spec.tag)]
+pub async fn create_or_update_draft(
+    repo: &(impl RepoPointer + Send + Sync),
+    octocrab: &Octocrab,
+    spec: &ReleaseSpec,
+) -> Result<Release> {
+    let existing = repo
+        .all_releases(octocrab)
+        .await?
+        .into_iter()
+        .find(|release| release.draft && release.tag_name == spec.tag);
+    match existing {
+        Some(release) => {
+            debug!("Updating the existing draft release {}.", release.id);
+            repo.repos(octocrab)
+                .releases()
+                .update(release.id.0)
+                .name(&spec.name)
+                .body(&spec.body)
+                .prerelease(spec.prerelease)
+                .send()
+                .await
+                .anyhow_err()
+        }
+        None => create_draft(repo, octocrab, spec).await,
+    }
+}
+
+/// Publish the draft release. Fails if the release is already published.
+#[context("Failed to publish the release {release_id} in {repo}.")]
+pub async fn publish(
+    repo: &(impl RepoPointer + Send + Sync),
+    octocrab: &Octocrab,
+    release_id: ReleaseId,
+) -> Result<Release> {
+    let release = repo.find_release_by_id(octocrab, release_id).await?;
+    ensure!(release.draft, "Release has been already published!");
+    let release = repo.repos(octocrab).releases().update(release_id.0).draft(false).send().await?;
+    info!("Published release {}: {}", release.tag_name, release.html_url);
+    Ok(release)
+}
+
+/// Find the asset with the given name. The name can be also a glob pattern, like
+/// `*-linux-*.tar.gz`.
+///
+/// Fails if no asset or more than one asset matches.
+pub fn find_asset<'a>(release: &'a Release, pattern: &str) -> Result<&'a Asset> {
+    let glob = glob::Pattern::new(pattern)?;
+    let matching = release
+        .assets
+        .iter()
+        .filter(|asset| asset.name == pattern || glob.matches(&asset.name))
+        .collect_vec();
+    match matching.as_slice() {
+        [asset] => Ok(asset),
+        [] => bail!("No asset matching `{pattern}` in the release {}.", release.tag_name),
+        _ => bail!(
+            "Multiple assets matching `{pattern}` in the release {}: {}.",
+            release.tag_name,
+            matching.iter().map(|asset| &asset.name).join(", ")
+        ),
+    }
+}
+
+/// Download the release asset with the given name (or matching the pattern) to the file.
+pub async fn download_asset(
+    repo: &(impl RepoPointer + Send + Sync),
+    octocrab: &Octocrab,
+    release: &Release,
+    pattern: &str,
+    output_path: impl AsRef<Path>,
+) -> Result {
+    let asset = find_asset(release, pattern)?;
+    repo.download_asset_as(octocrab, asset.id, output_path.as_ref().to_owned()).await
+}
+
+/// Content type of the uploaded asset, guessed from its file name.
+pub fn content_type(asset_path: &Path) -> mime::Mime {
+    match asset_path.extension().and_then(|extension| extension.to_str()) {
+        // Checksum files are not known to the MIME database.
+        Some("sha256" | "sha512" | "md5") => mime::TEXT_PLAIN,
+        _ => new_mime_guess::from_path(asset_path).first_or_octet_stream(),
+    }
+}
+
+/// Upload the file as a release asset, named after the file.
+///
+/// Failed uploads are retried. GitHub keeps the partially uploaded assets, so before each retry
+/// the asset with the same name is removed.
 #[context("Failed to upload the asset {}", asset.as_ref().display())]
 #[instrument(skip_all, fields(source = %asset.as_ref().display(), %repo, %release))]
 pub async fn upload_asset(
@@ -11,6 +145,31 @@ pub async fn upload_asset(
     client: &reqwest::Client,
     release: ReleaseId,
     asset: impl AsRef<Path> + Send + Sync,
+) -> Result {
+    let asset_path = asset.as_ref();
+    let asset_name = asset_path.file_name().context("The asset path has no file name.")?;
+    let asset_name = asset_name.to_string_lossy();
+    let policy = RetryPolicy::new(3).backoff(Duration::from_secs(5), 2);
+    let attempt = |is_retry: bool| {
+        let asset_name = asset_name.to_string();
+        async move {
+            if is_retry {
+                delete_asset_if_exists(repo, client, release, &asset_name).await?;
+            }
+            upload_asset_attempt(repo, client, release, asset_path, &asset_name)
+                .await
+                .map_err(AttemptFailure::from)
+        }
+    };
+    policy.run(attempt(false), || attempt(true)).await
+}
+
+async fn upload_asset_attempt(
+    repo: &(impl RepoPointer + Send + Sync),
+    client: &reqwest::Client,
+    release: ReleaseId,
+    asset_path: &Path,
+    asset_name: &str,
 ) -> Result {
     let upload_url = format!(
         "https://uploads.github.com/repos/{}/{}/releases/{}/assets",
@@ -18,26 +177,43 @@ pub async fn upload_asset(
         repo.name(),
         release
     );
-    let asset_path = asset.as_ref();
-    let mime = new_mime_guess::from_path(asset_path).first_or_octet_stream();
     let file = tokio::fs::File::open(asset_path).await?;
     let file_size = file.metadata().await?.len();
     let file_contents_stream = tokio_util::io::ReaderStream::new(file);
     let body = Body::wrap_stream(file_contents_stream);
-    let asset_name = asset_path.file_name().unwrap().to_string_lossy();
     let request = client
         .post(upload_url)
-        .query(&[("name", asset_name.as_ref())])
+        .query(&[("name", asset_name)])
         .header(reqwest::header::ACCEPT, "application/vnd.github.v3+json")
-        .header(reqwest::header::CONTENT_TYPE, mime.to_string())
+        .header(reqwest::header::CONTENT_TYPE, content_type(asset_path).to_string())
         .header(reqwest::header::CONTENT_LENGTH, file_size)
         .body(body)
         .build()?;
+    debug!("Uploading {} ({file_size} bytes).", asset_path.display());
+    client.execute(request).await?.error_for_status()?;
+    Ok(())
+}
 
-    dbg!(&request);
-    let response = client.execute(request).await?;
-    dbg!(&response);
-    response.error_for_status()?;
+/// Remove the asset with the given name from the release, if present.
+async fn delete_asset_if_exists(
+    repo: &(impl RepoPointer + Send + Sync),
+    client: &reqwest::Client,
+    release: ReleaseId,
+    asset_name: &str,
+) -> Result {
+    let api = format!("https://api.github.com/repos/{}/{}/releases", repo.owner(), repo.name());
+    let assets: Vec<Asset> = client
+        .get(format!("{api}/{release}/assets"))
+        .query(&[("per_page", "100")])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    if let Some(asset) = assets.into_iter().find(|asset| asset.name == asset_name) {
+        debug!("Removing the previously uploaded asset {asset_name}.");
+        client.delete(format!("{api}/assets/{}", asset.id)).send().await?.error_for_status()?;
+    }
     Ok(())
 }
 
@@ -48,12 +224,19 @@ mod tests {
     use reqwest::header::HeaderMap;
     use reqwest::Body;
 
+    #[test]
+    fn content_types() {
+        assert_eq!(content_type(Path::new("enso-engine.zip")), "application/zip");
+        assert_eq!(content_type(Path::new("enso.AppImage.sha256")), mime::TEXT_PLAIN);
+        assert_eq!(content_type(Path::new("manifest")), mime::APPLICATION_OCTET_STREAM);
+    }
+
     #[tokio::test]
     #[ignore]
     pub async fn create_release() -> Result {
         let pat = std::env::var("GITHUB_TOKEN").unwrap();
 
-        let octocrab = octocrab::Octocrab::builder().personal_token(pat.clone()).build()?;
+        let octocrab = crate::github::Client::with_token(&pat)?;
         let repo = octocrab.repos("enso-org", "ci-build");
         let release = if let Ok(release) = repo.releases().get_latest().await {
             release
@@ -71,10 +254,7 @@ mod tests {
 
         let mut header_map = HeaderMap::new();
         header_map.append(reqwest::header::AUTHORIZATION, format!("Bearer {}", pat).parse()?);
-        let client = reqwest::Client::builder()
-            .user_agent("enso-build")
-            .default_headers(header_map)
-            .build()?;
+        let client = crate::net::client_builder()?.default_headers(header_map).build()?;
 
         // TODO label?
 
@@ -2,12 +2,9 @@ use crate::prelude::*;
 
 use crate::future::try_join_all;
 use crate::future::AsyncPolicy;
-use indicatif::MultiProgress;
 use indicatif::ProgressBar;
-use indicatif::WeakProgressBar;
 use std::lazy::SyncLazy;
 use std::sync::Mutex;
-use std::time::Duration;
 use tokio::task::JoinHandle;
 
 /// Turns given text into a static string.
@@ -24,56 +21,16 @@ pub fn store_static_text(text: impl AsRef<str>) -> &'static str {
     *STRING_STORAGE.lock().unwrap().get_or_insert_with(text.as_ref(), |text| Box::leak(text.into()))
 }
 
-const REFRESHES_PER_SECOND: u32 = 100;
-
-#[derive(derivative::Derivative)]
-#[derivative(Debug)]
+#[derive(Debug, Default)]
 struct GlobalState {
-    mp:            MultiProgress,
-    #[derivative(Debug = "ignore")]
-    bars:          Vec<WeakProgressBar>,
-    _tick_thread:  std::thread::JoinHandle<()>,
     ongoing_tasks: Vec<JoinHandle<Result>>,
 }
 
-impl GlobalState {
-    pub fn tick(&mut self) {
-        let mut to_remove = vec![];
-        for (index, bar) in self.bars.iter().enumerate() {
-            if let Some(bar) = bar.upgrade() {
-                bar.tick()
-            } else {
-                to_remove.push(index)
-            }
-        }
-
-        for to_remove in to_remove.iter().rev() {
-            self.bars.remove(*to_remove);
-        }
-    }
-}
-
-impl Default for GlobalState {
-    fn default() -> Self {
-        GlobalState {
-            mp:            MultiProgress::new(),
-            bars:          default(),
-            _tick_thread:  std::thread::spawn(|| {
-                GLOBAL.lock().unwrap().tick();
-                std::thread::sleep(Duration::SECOND / REFRESHES_PER_SECOND);
-            }),
-            ongoing_tasks: default(),
-        }
-    }
-}
-
 static GLOBAL: SyncLazy<Mutex<GlobalState>> = SyncLazy::new(default);
 
+/// Add the bar to the global set of bars, see [`crate::progress::add`].
 pub fn progress_bar(f: impl FnOnce() -> ProgressBar) -> ProgressBar {
-    let ret = f();
-    let ret = GLOBAL.lock().unwrap().mp.add(ret);
-    GLOBAL.lock().unwrap().bars.push(ret.downgrade());
-    ret
+    crate::progress::add(f())
 }
 
 pub fn new_spinner(message: impl Into<Cow<'static, str>>) -> ProgressBar {
@@ -83,13 +40,7 @@ pub fn new_spinner(message: impl Into<Cow<'static, str>>) -> ProgressBar {
 }
 
 pub fn println(msg: impl AsRef<str>) {
-    if let Ok(state) = GLOBAL.lock() {
-        if !indicatif::ProgressDrawTarget::stderr().is_hidden() {
-            let _ = state.mp.println(msg);
-            return;
-        }
-    };
-    println!("{}", msg.as_ref());
+    crate::progress::println(msg)
 }
 
 pub fn spawn(name: impl AsRef<str>, f: impl Future<Output = Result> + Send + 'static) {
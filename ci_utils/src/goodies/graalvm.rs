@@ -1,10 +1,28 @@
 use crate::prelude::*;
 
+use crate::env::new::TypedVariable;
+
+use crate::download::Download;
 use crate::extensions::path::PathExt;
 use crate::goodie::GoodieDatabase;
 use crate::models::config::RepoContext;
-
+use crate::program::location::Location;
+use crate::programs::graal;
 use crate::programs::java;
+use crate::programs::Java;
+
+pub use crate::download::verify_sha256;
+
+
+crate::define_env_var!(
+    /// Repository (`owner/name`) with the GraalVM Enterprise Edition packages.
+    ///
+    /// The Enterprise Edition packages are not publicly available, so they need to be mirrored
+    /// as release assets of a private repository, using the same naming scheme as the Community
+    /// Edition builds.
+    ENSO_BUILD_GRAALVM_EE_REPOSITORY,
+    String
+);
 
 #[derive(Clone, Debug)]
 pub struct Instance {
@@ -12,24 +30,59 @@ pub struct Instance {
     pub path: PathBuf,
 }
 
-impl crate::goodie::Instance for Instance {
-    fn add_to_environment(&self) -> anyhow::Result<()> {
-        let root = match TARGET_OS {
+impl Instance {
+    /// The `JAVA_HOME` directory of this GraalVM.
+    pub fn java_home(&self) -> PathBuf {
+        match TARGET_OS {
             OS::MacOS => self.path.join_iter(["Contents", "Home"]),
             _ => self.path.clone(),
-        };
+        }
+    }
+}
 
-        std::env::set_var("JAVA_HOME", &root);
+impl crate::goodie::Instance for Instance {
+    fn add_to_environment(&self) -> anyhow::Result<()> {
+        let root = self.java_home();
+        java::JAVA_HOME.set(&root)?;
         std::env::set_var("GRAALVM_HOME", &root);
         crate::env::prepend_to_path(root.join("bin"))?;
         Ok(())
     }
 }
 
-const PACKAGE_PREFIX: &str = "graalvm-ce";
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edition {
+    Community,
+    Enterprise,
+}
+
+impl Default for Edition {
+    fn default() -> Self {
+        Edition::Community
+    }
+}
+
+impl Edition {
+    pub fn package_prefix(self) -> &'static str {
+        match self {
+            Edition::Community => "graalvm-ce",
+            Edition::Enterprise => "graalvm-ee",
+        }
+    }
+
+    /// Repository with the release packages as assets.
+    pub fn repository(self) -> Result<RepoContext> {
+        match self {
+            Edition::Community =>
+                Ok(RepoContext { owner: "graalvm".into(), name: "graalvm-ce-builds".into() }),
+            Edition::Enterprise => RepoContext::from_str(&ENSO_BUILD_GRAALVM_EE_REPOSITORY.get()?),
+        }
+    }
+}
 
 pub struct GraalVM<'a> {
     pub client:        &'a Octocrab,
+    pub edition:       Edition,
     pub graal_version: Version,
     pub java_version:  java::LanguageVersion,
     pub os:            OS,
@@ -47,8 +100,14 @@ impl<'a> GraalVM<'a> {
         crate::program::version::find_in_text(line)
     }
 
-    async fn url(&self) -> anyhow::Result<Url> {
-        let Self { graal_version, java_version, client, arch, os } = &self;
+    /// Name of the package directory, after extraction.
+    fn package_dir_name(&self) -> String {
+        format!("{}-{}-{}", self.edition.package_prefix(), self.java_version, self.graal_version)
+    }
+
+    /// URLs of the package and of its SHA-256 checksum, if one was published.
+    async fn urls(&self) -> Result<(Url, Option<Url>)> {
+        let Self { graal_version, java_version, client, arch, os, edition } = &self;
 
         let os_name = match *os {
             OS::Linux => "linux",
@@ -68,10 +127,43 @@ impl<'a> GraalVM<'a> {
         let java_version = format!("java{}", java_version.0);
 
         let platform_string =
-            format!("{}-{}-{}-{}", PACKAGE_PREFIX, java_version, os_name, arch_name);
-        let repo = RepoContext { owner: "graalvm".into(), name: "graalvm-ce-builds".into() };
+            format!("{}-{}-{}-{}", edition.package_prefix(), java_version, os_name, arch_name);
+        let repo = edition.repository()?;
         let release = repo.find_release_by_text(client, &graal_version.to_string()).await?;
-        crate::github::find_asset_url_by_text(&release, &platform_string).cloned()
+        let package_url = crate::github::find_asset_url_by_text(&release, &platform_string)?;
+        let package_name = package_url
+            .path_segments()
+            .and_then(|segments| segments.last())
+            .context(format!("Cannot get the file name from the URL {package_url}."))?;
+        let checksum_name = format!("{package_name}.sha256");
+        let checksum_url = release
+            .assets
+            .iter()
+            .find(|asset| asset.name == checksum_name)
+            .map(|asset| asset.browser_download_url.clone());
+        Ok((package_url.clone(), checksum_url))
+    }
+
+    /// Make sure that GraalVM with the given components is available and return its `java`.
+    ///
+    /// The executable is taken from the GraalVM installation, regardless of other Java
+    /// installations in `PATH`.
+    pub async fn provision(
+        &self,
+        database: &GoodieDatabase,
+        components: impl IntoIterator<Item = graal::Component>,
+    ) -> Result<Location<Java>> {
+        database.require(self).await?;
+        graal::install_missing_components(components).await?;
+        let java_home = match self.lookup(database).await {
+            Ok(instance) => instance.java_home(),
+            // The required GraalVM was already available in the environment.
+            Err(_) => java::JAVA_HOME.get()?,
+        };
+        let executable_name = format!("{}{}", Java.executable_name(), std::env::consts::EXE_SUFFIX);
+        let executable = java_home.join("bin").join(executable_name);
+        ensure!(executable.exists(), "There is no Java executable at {}.", executable.display());
+        Ok(Location::new(executable))
     }
 }
 
@@ -85,10 +177,7 @@ impl<'a> Goodie for GraalVM<'a> {
     }
 
     async fn lookup(&self, database: &GoodieDatabase) -> Result<Self::Instance> {
-        let expected_dir_name = PathBuf::from(format!(
-            "{}-{}-{}",
-            PACKAGE_PREFIX, self.java_version, self.graal_version
-        ));
+        let expected_dir_name = PathBuf::from(self.package_dir_name());
         for entry in crate::fs::read_dir(&database.root_directory)? {
             let entry = entry?;
             if entry.file_type()?.is_dir() && entry.path().file_name().contains(&expected_dir_name)
@@ -100,8 +189,38 @@ impl<'a> Goodie for GraalVM<'a> {
     }
 
     async fn install(&self, database: &GoodieDatabase) -> Result<Self::Instance> {
-        let graal_url = self.url().await?;
-        crate::io::download_and_extract(graal_url.clone(), &database.root_directory).await?;
+        let (package_url, checksum_url) = self.urls().await?;
+        let mut download = Download::new(package_url.clone())?;
+        match checksum_url {
+            Some(checksum_url) => {
+                let checksum = crate::io::download_all(checksum_url).await?;
+                let checksum = std::str::from_utf8(&checksum)?;
+                let expected = checksum.split_whitespace().next().unwrap_or_default();
+                download = download.sha256(expected);
+            }
+            None if self.edition == Edition::Enterprise =>
+                bail!("No checksum published for the Enterprise Edition package {package_url}."),
+            None => warn!("No checksum published for {package_url}, skipping verification."),
+        }
+        let package = download.fetch().await?;
+        crate::archive::extract_to(&package, &database.root_directory).await?;
         self.lookup(database).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksum_verification() -> Result {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("package.tar.gz");
+        crate::fs::write(&path, "hello")?;
+        let digest = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        verify_sha256(&path, digest)?;
+        verify_sha256(&path, &digest.to_uppercase())?;
+        assert!(verify_sha256(&path, &digest.replace('2', "3")).is_err());
+        Ok(())
+    }
+}
@@ -59,8 +59,8 @@ impl Goodie for Musl {
         // let url = format!("https://musl.cc/{}.{}", filename_stem(), archive_format);
         let downloaded_dir = database.root_directory.join(filename_stem());
         let target_dir = database.root_directory.join("musl");
-        crate::fs::reset_dir(&downloaded_dir)?;
-        crate::fs::reset_dir(&target_dir)?;
+        crate::fs::tokio::reset_dir(&downloaded_dir).await?;
+        crate::fs::tokio::reset_dir(&target_dir).await?;
         // let result = (async move || -> Result {
         crate::io::download_and_extract(url.clone(), &database.root_directory).await?;
         add_zlib(&downloaded_dir).await?;
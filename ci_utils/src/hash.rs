@@ -0,0 +1,205 @@
+//! Checksums of files and directory trees, and the `SHASUMS256.txt` files listing them.
+
+use crate::prelude::*;
+
+use sha2::digest::DynDigest;
+use sha2::Digest;
+use tokio::io::AsyncReadExt;
+
+
+/// Name of the checksum file published alongside the release assets.
+pub const SHASUMS_FILENAME: &str = "SHASUMS256.txt";
+
+/// Size of the chunks in which the files are read.
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Display)]
+pub enum Algorithm {
+    #[display(fmt = "SHA-256")]
+    Sha256,
+    /// Only for the interoperability with the tools that still use it, it is not secure.
+    #[display(fmt = "SHA-1")]
+    Sha1,
+}
+
+impl Algorithm {
+    pub fn hasher(self) -> Box<dyn DynDigest + Send> {
+        match self {
+            Algorithm::Sha256 => Box::new(sha2::Sha256::new()),
+            Algorithm::Sha1 => Box::new(sha1::Sha1::new()),
+        }
+    }
+}
+
+/// Lowercase hex digest of the file.
+pub async fn file(path: impl AsRef<Path>, algorithm: Algorithm) -> Result<String> {
+    file_with_progress(path, algorithm, |_, _| {}).await
+}
+
+/// Lowercase hex digest of the file, calling the callback with the number of bytes hashed so far
+/// and the total size after each chunk.
+#[context("Failed to compute the {algorithm} digest of {}.", path.as_ref().display())]
+pub async fn file_with_progress(
+    path: impl AsRef<Path>,
+    algorithm: Algorithm,
+    mut progress: impl FnMut(u64, u64),
+) -> Result<String> {
+    let mut file = crate::fs::tokio::open(&path).await?;
+    let total = file.metadata().await?.len();
+    let mut hasher = algorithm.hasher();
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut done = 0;
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        done += read as u64;
+        progress(done, total);
+    }
+    Ok(data_encoding::HEXLOWER.encode(&hasher.finalize()))
+}
+
+/// Digest of the directory tree, covering both the paths and the contents of the files.
+///
+/// It depends neither on the order in which the filesystem lists the entries nor on the
+/// platform's path separator, so the same tree has the same digest everywhere. Symbolic links
+/// are hashed by their target paths. Empty directories are not taken into account.
+#[context("Failed to compute the {algorithm} digest of the tree {}.", root.as_ref().display())]
+pub async fn tree(root: impl AsRef<Path>, algorithm: Algorithm) -> Result<String> {
+    let root = root.as_ref();
+    let mut entries = walkdir::WalkDir::new(root)
+        .min_depth(1)
+        .into_iter()
+        .collect_result()?
+        .into_iter()
+        .filter(|entry| !entry.file_type().is_dir())
+        .map(|entry| -> Result<_> {
+            let relative = entry.path().strip_prefix(root)?;
+            let key = relative.iter().map(|part| part.to_string_lossy()).join("/");
+            Ok((key, entry))
+        })
+        .collect_result()?;
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut hasher = algorithm.hasher();
+    for (key, entry) in entries {
+        let content = if entry.file_type().is_symlink() {
+            let target = std::fs::read_link(entry.path())?;
+            format!("link:{}", target.to_string_lossy())
+        } else {
+            file(entry.path(), algorithm).await?
+        };
+        // The separators keep the entries unambiguous.
+        hasher.update(key.as_bytes());
+        hasher.update(&[0]);
+        hasher.update(content.as_bytes());
+        hasher.update(&[b'\n']);
+    }
+    Ok(data_encoding::HEXLOWER.encode(&hasher.finalize()))
+}
+
+/// Entry of the `SHASUMS256.txt` file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShasumEntry {
+    /// Lowercase hex SHA-256 digest.
+    pub digest:   String,
+    pub filename: String,
+}
+
+/// Format the entries like `sha256sum` does.
+pub fn format_shasums(entries: &[ShasumEntry]) -> String {
+    entries.iter().map(|entry| format!("{}  {}\n", entry.digest, entry.filename)).collect()
+}
+
+/// Parse the output of `sha256sum`, in either the text or the binary mode.
+pub fn parse_shasums(text: &str) -> Result<Vec<ShasumEntry>> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (digest, filename) =
+                line.split_once(' ').with_context(|| format!("Invalid checksum line: {line}"))?;
+            let filename = filename.strip_prefix([' ', '*']).unwrap_or(filename);
+            ensure!(
+                digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit()),
+                "Invalid SHA-256 digest in line: {line}"
+            );
+            Ok(ShasumEntry { digest: digest.to_lowercase(), filename: filename.into() })
+        })
+        .collect()
+}
+
+/// Write the `SHASUMS256.txt` file listing the given files in the output directory.
+///
+/// The files are listed by their names, so they are expected to be published together.
+pub async fn write_shasums(output_dir: impl AsRef<Path>, files: &[PathBuf]) -> Result<PathBuf> {
+    let mut entries = Vec::new();
+    for path in files {
+        let filename = path.file_name().context("The file has no name.")?;
+        let digest = file(path, Algorithm::Sha256).await?;
+        entries.push(ShasumEntry { digest, filename: filename.to_string_lossy().into() });
+    }
+    let output = output_dir.as_ref().join(SHASUMS_FILENAME);
+    crate::fs::write_atomic(&output, format_shasums(&entries))?;
+    Ok(output)
+}
+
+/// Verify the files listed in the `SHASUMS256.txt` file, located next to it.
+#[context("Failed to verify the checksums from {}.", shasums_file.as_ref().display())]
+pub async fn verify_shasums(shasums_file: impl AsRef<Path>) -> Result {
+    let shasums_file = shasums_file.as_ref();
+    let directory = shasums_file.parent().context("The checksum file has no parent.")?;
+    let entries = parse_shasums(&crate::fs::read_to_string(shasums_file)?)?;
+    let mut mismatched = Vec::new();
+    for entry in entries {
+        let actual = file(directory.join(&entry.filename), Algorithm::Sha256).await?;
+        if actual != entry.digest {
+            mismatched.push(entry.filename);
+        }
+    }
+    ensure!(mismatched.is_empty(), "Checksum mismatch for: {}.", mismatched.join(", "));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    #[tokio::test]
+    async fn hashing_files_and_trees() -> Result {
+        let dir = tempfile::tempdir()?;
+        let hello = dir.path().join_iter(["a", "hello.txt"]);
+        crate::fs::write(&hello, "hello")?;
+        assert_eq!(file(&hello, Algorithm::Sha256).await?, HELLO_SHA256);
+        assert_eq!(
+            file(&hello, Algorithm::Sha1).await?,
+            "aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d"
+        );
+
+        let before = tree(dir.path(), Algorithm::Sha256).await?;
+        crate::fs::write(dir.path().join("b.txt"), "")?;
+        assert_ne!(tree(dir.path(), Algorithm::Sha256).await?, before);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn shasums() -> Result {
+        let dir = tempfile::tempdir()?;
+        let asset = dir.path().join("enso-linux.tar.gz");
+        crate::fs::write(&asset, "hello")?;
+        let shasums = write_shasums(dir.path(), &[asset.clone()]).await?;
+        let expected = format!("{HELLO_SHA256}  enso-linux.tar.gz\n");
+        assert_eq!(crate::fs::read_to_string(&shasums)?, expected);
+        verify_shasums(&shasums).await?;
+
+        crate::fs::write(&asset, "tampered")?;
+        assert!(verify_shasums(&shasums).await.is_err());
+
+        let binary_mode = format!("{} *enso.zip\n", HELLO_SHA256.to_uppercase());
+        assert_eq!(parse_shasums(&binary_mode)?[0].filename, "enso.zip");
+        Ok(())
+    }
+}
@@ -4,11 +4,9 @@ use crate::prelude::*;
 
 use anyhow::Context;
 use reqwest::IntoUrl;
-use std::time::Duration;
 use tokio::io::AsyncRead;
 
 use crate::archive::Format;
-use crate::global::progress_bar;
 
 /// Read the whole input and return its length.
 ///
@@ -20,16 +18,14 @@ pub async fn read_length(mut read: impl AsyncRead + Unpin) -> Result<u64> {
 
 /// Get the the response body as a byte stream.
 pub async fn download(url: impl IntoUrl) -> Result<impl Stream<Item = reqwest::Result<Bytes>>> {
-    Ok(reqwest::get(url).await?.error_for_status()?.bytes_stream())
+    Ok(crate::net::client()?.get(url).send().await?.error_for_status()?.bytes_stream())
 }
 
 /// Get the full response body from URL as bytes.
 pub async fn download_all(url: impl IntoUrl) -> anyhow::Result<Bytes> {
     let url = url.into_url()?;
-    let bar = progress_bar(indicatif::ProgressBar::new_spinner);
-    bar.enable_steady_tick(Duration::from_millis(100));
-    bar.set_message(format!("Downloading {}", url));
-    let response = reqwest::get(url).await?;
+    let _progress = crate::progress::spinner(format!("Downloading {url}"));
+    let response = crate::net::client()?.get(url).send().await?;
     if let Some(e) = response.error_for_status_ref().err() {
         let body = response.text().await?;
         Err(e).context(body)
@@ -71,8 +67,9 @@ pub async fn download_and_extract(
 
     debug!("Extracting {} to {}", filename.display(), output_dir.as_ref().display());
     let format = Format::from_filename(&PathBuf::from(filename))?;
-    format.extract(buffer, output_dir.as_ref()).with_context(|| {
-        format!("Failed to extract data from {} to {}.", url_text, output_dir.as_ref().display(),)
+    let output_dir = output_dir.as_ref();
+    format.extract_async(buffer, output_dir).await.with_context(|| {
+        format!("Failed to extract data from {} to {}.", url_text, output_dir.display())
     })
 }
 
@@ -153,8 +150,8 @@ mod tests {
 
         copy(foo.parent().unwrap(), foo.parent().unwrap().with_file_name("dest"))?;
 
-        mirror_directory(foo.parent().unwrap(), foo.parent().unwrap().with_file_name("dest2"))
-            .await?;
+        let dest2 = foo.parent().unwrap().with_file_name("dest2");
+        mirror_directory(foo.parent().unwrap(), dest2, default()).await?;
 
         tokio::process::Command::new(r"C:\msys64\usr\bin\ls.exe")
             .arg("-laR")
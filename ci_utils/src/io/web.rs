@@ -33,18 +33,18 @@ pub async fn execute(request_builder: RequestBuilder) -> Result<Response> {
 pub async fn download_stream(
     url: impl IntoUrl,
 ) -> Result<impl Stream<Item = reqwest::Result<Bytes>>> {
-    Ok(handle_error_response(reqwest::get(url).await?).await?.bytes_stream())
+    Ok(get(&crate::net::client()?, url).await?.bytes_stream())
 }
 
 /// Get the the response body as a byte stream.
 pub async fn download_reader(url: impl IntoUrl) -> Result<impl AsyncBufRead + Unpin> {
-    let response = reqwest::get(url).await?.error_for_status()?;
+    let response = crate::net::client()?.get(url).send().await?.error_for_status()?;
     Ok(async_reader(response))
 }
 
 /// Get the the response body as a byte stream.
 pub async fn download_file(url: impl IntoUrl, output: impl AsRef<Path>) -> Result {
-    stream_response_to_file(reqwest::get(url).await?, output).await
+    stream_response_to_file(crate::net::client()?.get(url).send().await?, output).await
 }
 
 
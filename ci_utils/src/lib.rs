@@ -25,11 +25,14 @@
 
 pub mod actions;
 pub mod anyhow;
+pub mod api;
 pub mod archive;
 pub mod buffer;
 pub mod cache;
 pub mod ci;
+pub mod cloud;
 pub mod deploy;
+pub mod download;
 pub mod env;
 pub mod extensions;
 pub mod fmt;
@@ -39,16 +42,23 @@ pub mod github;
 pub mod global;
 pub mod goodie;
 pub mod goodies;
+pub mod hash;
 pub mod io;
 pub mod log;
+pub mod metrics;
 pub mod models;
+pub mod net;
 pub mod os;
+pub mod packaging;
 pub mod paths;
+pub mod pipeline;
 pub mod platform;
 pub mod program;
 pub mod programs;
+pub mod progress;
 pub mod reqwest;
 pub mod serde;
+pub mod testing;
 
 pub mod prelude {
 
@@ -1,75 +1,198 @@
+//! Logging setup shared by all the binaries and tests.
+//!
+//! The log is filtered by the `ENSO_BUILD_LOG` environment variable, using the
+//! [`EnvFilter`](tracing_subscriber::EnvFilter) syntax, like `info,ide_ci::program=trace`.
+//! Locally, the output is compact. On GitHub Actions, the root spans become collapsible groups
+//! and the errors and warnings become annotations.
+
 use crate::prelude::*;
 use tracing_subscriber::prelude::*;
 
+use crate::actions::workflow::LogGroup;
+use crate::actions::workflow::Message;
+use crate::actions::workflow::MessageLevel;
+use std::sync::Mutex;
+use tracing::field::Field;
+use tracing::field::Visit;
 use tracing::span::Attributes;
-use tracing::subscriber::Interest;
 use tracing::Event;
 use tracing::Id;
-use tracing::Metadata;
+use tracing::Level;
 use tracing::Subscriber;
-use tracing_subscriber::filter::LevelFilter;
 use tracing_subscriber::fmt::format::FmtSpan;
 use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::EnvFilter;
 use tracing_subscriber::Registry;
 
+
+pub mod timer;
+
+/// Name of the environment variable with the log filter.
+pub const FILTER_ENV_VAR: &str = "ENSO_BUILD_LOG";
+
+/// Filter used if [`FILTER_ENV_VAR`] is not set: everything from our crates, only the warnings
+/// from the dependencies.
+pub const DEFAULT_FILTER: &str = "warn,ide_ci=trace,enso_build=trace,enso_build_cli=trace";
+
 pub fn is_our_module_path(path: impl AsRef<str>) -> bool {
     ["ide_ci::", "enso_build", "enso_build2"]
         .into_iter()
         .any(|prefix| path.as_ref().starts_with(prefix))
 }
 
-pub struct MyLayer;
+/// The filter from [`FILTER_ENV_VAR`], or the [default](DEFAULT_FILTER) one. The invalid
+/// directives are ignored.
+pub fn filter() -> EnvFilter {
+    match std::env::var(FILTER_ENV_VAR) {
+        Ok(directives) => EnvFilter::builder().parse_lossy(directives),
+        Err(_) => EnvFilter::new(DEFAULT_FILTER),
+    }
+}
+
+/// Collects the message and the other fields of an event into a single line.
+#[derive(Clone, Debug, Default)]
+struct EventText {
+    message: String,
+    fields:  Vec<String>,
+}
 
-impl<S: Subscriber + Debug + for<'a> LookupSpan<'a>> tracing_subscriber::Layer<S> for MyLayer {
-    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
-        if metadata.module_path().is_some_and(|p| is_our_module_path(p)) {
-            Interest::always()
+impl Visit for EventText {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.message = format!("{value:?}");
         } else {
-            // dbg!(metadata);
-            Interest::never()
+            self.fields.push(format!("{}={value:?}", field.name()));
         }
     }
+}
+
+impl Display for EventText {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", once(&self.message).chain(&self.fields).join(" "))
+    }
+}
+
+/// Name of the field marking the events that were already [reported as
+/// annotations](crate::actions::annotations::report), like `warn!(annotated = true, "...")`.
+///
+/// Such events are not annotated again.
+pub const ANNOTATED_FIELD: &str = "annotated";
+
+/// The annotation for the event, if it is an error or a warning.
+pub fn annotation(event: &Event) -> Option<Message> {
+    if event.metadata().fields().field(ANNOTATED_FIELD).is_some() {
+        return None;
+    }
+    let level = match *event.metadata().level() {
+        Level::ERROR => MessageLevel::Error,
+        Level::WARN => MessageLevel::Warning,
+        _ => return None,
+    };
+    let mut text = EventText::default();
+    event.record(&mut text);
+    Some(Message::new(level, text.to_string()))
+}
 
+/// Layer formatting the log for GitHub Actions.
+///
+/// The groups cannot be nested, so only the first of the concurrently running root spans gets
+/// one, and none is opened while another group (like the [output of a
+/// command](crate::program::command::LogStreaming::grouped)) is open.
+#[derive(Debug, Default)]
+pub struct GitHubLayer {
+    /// The span that owns the currently open group.
+    group: Mutex<Option<(u64, LogGroup)>>,
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> tracing_subscriber::Layer<S> for GitHubLayer {
     fn on_new_span(
         &self,
-        _attrs: &Attributes<'_>,
-        _id: &Id,
-        _ctx: tracing_subscriber::layer::Context<'_, S>,
+        attrs: &Attributes<'_>,
+        id: &Id,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
     ) {
-        // let span = ctx.span(id).unwrap();
-        // let bar = crate::global::new_spinner(format!("In span {id:?}: {:?}", span.name()));
-        // span.extensions_mut().insert(bar);
-        // crate::global::println(format!("Create {id:?}"));
-    }
-    fn on_event(&self, _event: &Event<'_>, _ctx: tracing_subscriber::layer::Context<'_, S>) {
-        // tracing_log::dbg!(event);
-    }
-    fn on_enter(&self, _id: &Id, _ctx: tracing_subscriber::layer::Context<'_, S>) {
-        // ide_ci::global::println(format!("Enter {id:?}"));
+        let is_root =
+            attrs.is_root() || (attrs.is_contextual() && ctx.current_span().id().is_none());
+        if is_root {
+            let mut group = self.group.lock().unwrap();
+            if group.is_none() && !crate::actions::workflow::is_group_open() {
+                let mut title = EventText::default();
+                attrs.record(&mut title);
+                title.message = attrs.metadata().name().to_string();
+                *group = Some((id.into_u64(), LogGroup::new(title.to_string())));
+            }
+        }
     }
-    fn on_exit(&self, _id: &Id, _ctx: tracing_subscriber::layer::Context<'_, S>) {
-        // ide_ci::global::println(format!("Leave {id:?}"));
+
+    fn on_event(&self, event: &Event<'_>, _ctx: tracing_subscriber::layer::Context<'_, S>) {
+        if let Some(message) = annotation(event) {
+            message.send();
+        }
     }
 
-    fn on_close(&self, _id: Id, _ctx: tracing_subscriber::layer::Context<'_, S>) {
-        // crate::global::println(format!("Close {id:?}"));
+    fn on_close(&self, id: Id, _ctx: tracing_subscriber::layer::Context<'_, S>) {
+        let mut group = self.group.lock().unwrap();
+        if group.as_ref().map_or(false, |(owner, _)| *owner == id.into_u64()) {
+            // Dropping the guard ends the group.
+            *group = None;
+        }
     }
 }
 
+/// Install the global tracing subscriber, formatting the log for the current environment.
+pub fn setup() -> Result {
+    crate::metrics::enable_from_env()?;
+    let registry = Registry::default()
+        .with(filter())
+        .with(crate::progress::Layer)
+        .with(timer::TimerLayer)
+        .with(crate::metrics::MetricsLayer);
+    let result = if crate::actions::workflow::is_in_env() {
+        let fmt = tracing_subscriber::fmt::layer()
+            .without_time()
+            .with_ansi(false)
+            .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE);
+        tracing::subscriber::set_global_default(registry.with(fmt).with(GitHubLayer::default()))
+    } else {
+        let fmt = tracing_subscriber::fmt::layer().compact().without_time();
+        tracing::subscriber::set_global_default(registry.with(fmt))
+    };
+    result.anyhow_err()
+}
 
+/// Same as [`setup`].
 pub fn setup_logging() -> Result {
-    let filter = tracing_subscriber::EnvFilter::builder()
-        .with_env_var("ENSO_BUILD_LOG")
-        .with_default_directive(LevelFilter::TRACE.into())
-        .from_env_lossy();
-
-    tracing::subscriber::set_global_default(
-        Registry::default().with(MyLayer).with(
-            tracing_subscriber::fmt::layer()
-                .without_time()
-                .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
-                .with_filter(filter),
-        ),
-    )
-    .anyhow_err()
+    setup()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Collects the annotations of the events.
+    struct Collect(Arc<Mutex<Vec<String>>>);
+
+    impl<S: Subscriber> tracing_subscriber::Layer<S> for Collect {
+        fn on_event(&self, event: &Event<'_>, _ctx: tracing_subscriber::layer::Context<'_, S>) {
+            if let Some(message) = annotation(event) {
+                self.0.lock().unwrap().push(format!("{}: {}", message.level, message.text));
+            }
+        }
+    }
+
+    #[test]
+    fn annotating_events() {
+        let collected = Arc::new(Mutex::new(Vec::new()));
+        let subscriber = Registry::default().with(Collect(collected.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            info!("Building.");
+            warn!(attempt = 2, "Retrying the download.");
+            warn!(annotated = true, "Unused variable.");
+            error!("Build failed.");
+        });
+        assert_eq!(*collected.lock().unwrap(), vec![
+            "warning: Retrying the download. attempt=2".to_string(),
+            "error: Build failed.".to_string(),
+        ]);
+    }
 }
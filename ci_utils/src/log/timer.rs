@@ -0,0 +1,292 @@
+//! Durations of the tracing spans, to see which build steps dominate the wall-clock time.
+//!
+//! The [`TimerLayer`] adds every closed span to the totals of the spans with the same name. At the
+//! end of the process, [`report`] appends the summary table to the step summary (or to the log,
+//! when running locally). If `ENSO_BUILD_TIMING_TRACE` is set, the individual spans are also
+//! written there in the Chrome trace format, which can be opened in `chrome://tracing` or
+//! <https://ui.perfetto.dev>.
+
+use crate::prelude::*;
+
+use crate::actions::step_summary;
+use crate::actions::step_summary::Markdown;
+use std::lazy::SyncLazy;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+use tracing::span::Attributes;
+use tracing::Id;
+use tracing::Subscriber;
+use tracing_subscriber::registry::LookupSpan;
+
+
+crate::define_env_var! {
+    /// Path where the Chrome trace of the build steps should be written.
+    ENSO_BUILD_TIMING_TRACE, PathBuf, optional
+}
+
+/// Number of the slowest steps listed in the summary.
+pub const SUMMARY_ROWS: usize = 30;
+
+/// Maximum number of the individual spans kept for the trace. The later ones are only summarized.
+pub const MAX_RECORDS: usize = 100_000;
+
+/// Reference point for the span start times.
+static START: SyncLazy<Instant> = SyncLazy::new(Instant::now);
+
+/// Totals of the spans closed so far, by their names.
+static STEPS: SyncLazy<Mutex<BTreeMap<&'static str, StepSummary>>> = SyncLazy::new(default);
+
+/// Whether the individual spans are kept, see [`ENSO_BUILD_TIMING_TRACE`].
+static KEEP_RECORDS: SyncLazy<bool> =
+    SyncLazy::new(|| ENSO_BUILD_TIMING_TRACE.try_get().map_or(false, |path| path.is_some()));
+
+/// Timings of the spans closed so far, if they are kept. At most [`MAX_RECORDS`] of them.
+static RECORDS: SyncLazy<Mutex<Vec<SpanTiming>>> = SyncLazy::new(default);
+
+/// Timing of a closed span.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpanTiming {
+    pub name:   &'static str,
+    pub target: &'static str,
+    /// Creation time, relative to the first span.
+    pub start:  Duration,
+    /// Time from the creation to the closing.
+    pub wall:   Duration,
+    /// Time spent inside the span. For the async code, this excludes the time spent waiting.
+    pub busy:   Duration,
+    /// Id of the root span, the concurrent steps have different ones.
+    pub root:   u64,
+}
+
+/// Timing state of an open span, kept in its extensions.
+#[derive(Clone, Debug)]
+struct OpenSpan {
+    created: Instant,
+    entered: Option<Instant>,
+    busy:    Duration,
+    root:    u64,
+}
+
+/// Layer recording the durations of all spans.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TimerLayer;
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> tracing_subscriber::Layer<S> for TimerLayer {
+    fn on_new_span(
+        &self,
+        _attrs: &Attributes<'_>,
+        id: &Id,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        if let Some(span) = ctx.span(id) {
+            let root = span.scope().last().map_or(id.into_u64(), |root| root.id().into_u64());
+            // Make sure the reference point precedes all the spans.
+            SyncLazy::force(&START);
+            let created = Instant::now();
+            span.extensions_mut().insert(OpenSpan {
+                created,
+                entered: None,
+                busy: default(),
+                root,
+            });
+        }
+    }
+
+    fn on_enter(&self, id: &Id, ctx: tracing_subscriber::layer::Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(open) = span.extensions_mut().get_mut::<OpenSpan>() {
+                open.entered = Some(Instant::now());
+            }
+        }
+    }
+
+    fn on_exit(&self, id: &Id, ctx: tracing_subscriber::layer::Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(open) = span.extensions_mut().get_mut::<OpenSpan>() {
+                if let Some(entered) = open.entered.take() {
+                    open.busy += entered.elapsed();
+                }
+            }
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: tracing_subscriber::layer::Context<'_, S>) {
+        if let Some(span) = ctx.span(&id) {
+            let open = span.extensions_mut().remove::<OpenSpan>();
+            if let Some(open) = open {
+                let metadata = span.metadata();
+                let record = SpanTiming {
+                    name:   metadata.name(),
+                    target: metadata.target(),
+                    start:  open.created.saturating_duration_since(*START),
+                    wall:   open.created.elapsed(),
+                    busy:   open.busy,
+                    root:   open.root,
+                };
+                add_to_summary(&mut STEPS.lock().unwrap(), &record);
+                if *KEEP_RECORDS {
+                    let mut records = RECORDS.lock().unwrap();
+                    if records.len() < MAX_RECORDS {
+                        records.push(record);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Timings of the spans closed so far.
+///
+/// They are kept only if [`ENSO_BUILD_TIMING_TRACE`] is set, and only the first [`MAX_RECORDS`].
+pub fn records() -> Vec<SpanTiming> {
+    RECORDS.lock().unwrap().clone()
+}
+
+/// Total durations of the spans with the same name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StepSummary {
+    pub name:  &'static str,
+    pub count: usize,
+    pub wall:  Duration,
+    pub busy:  Duration,
+    /// The longest of the spans.
+    pub max:   Duration,
+}
+
+fn add_to_summary(steps: &mut BTreeMap<&'static str, StepSummary>, record: &SpanTiming) {
+    let step = steps.entry(record.name).or_insert_with(|| StepSummary {
+        name:  record.name,
+        count: 0,
+        wall:  default(),
+        busy:  default(),
+        max:   default(),
+    });
+    step.count += 1;
+    step.wall += record.wall;
+    step.busy += record.busy;
+    step.max = step.max.max(record.wall);
+}
+
+fn sorted_by_wall(steps: BTreeMap<&'static str, StepSummary>) -> Vec<StepSummary> {
+    let mut ret = steps.into_values().collect_vec();
+    ret.sort_by(|a, b| b.wall.cmp(&a.wall));
+    ret
+}
+
+/// Summarize the spans by their names, the longest-running first.
+pub fn summarize(records: &[SpanTiming]) -> Vec<StepSummary> {
+    let mut steps = BTreeMap::new();
+    for record in records {
+        add_to_summary(&mut steps, record);
+    }
+    sorted_by_wall(steps)
+}
+
+/// Summary of all the spans closed so far, the longest-running first.
+pub fn summary() -> Vec<StepSummary> {
+    sorted_by_wall(STEPS.lock().unwrap().clone())
+}
+
+/// Table of the slowest steps.
+pub fn summary_table(steps: &[StepSummary], rows: usize) -> Markdown {
+    let format = |duration: Duration| format!("{:.1?}", duration);
+    let rows = steps.iter().take(rows).map(|step| {
+        [
+            step.name.to_string(),
+            step.count.to_string(),
+            format(step.wall),
+            format(step.busy),
+            format(step.max),
+        ]
+    });
+    Markdown::new().table(["Step", "Count", "Total", "Busy", "Longest"], rows)
+}
+
+/// The spans as complete events of the Chrome trace format. Each root span gets its own lane.
+///
+/// See: <https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU>
+pub fn chrome_trace(records: &[SpanTiming]) -> serde_json::Value {
+    let events = records
+        .iter()
+        .map(|record| {
+            serde_json::json!({
+                "name": record.name,
+                "cat":  record.target,
+                "ph":   "X",
+                "ts":   record.start.as_micros() as u64,
+                "dur":  record.wall.as_micros() as u64,
+                "pid":  1,
+                "tid":  record.root,
+            })
+        })
+        .collect_vec();
+    serde_json::json!({ "traceEvents": events })
+}
+
+/// Publish the timings of the spans closed so far.
+pub fn report() -> Result {
+    let steps = summary();
+    if steps.is_empty() {
+        return Ok(());
+    }
+    let content = Markdown::new()
+        .header(3, "Step timings")
+        .block(summary_table(&steps, SUMMARY_ROWS).as_str());
+    step_summary::append(&content)?;
+    if let Some(path) = ENSO_BUILD_TIMING_TRACE.try_get()? {
+        let records = records();
+        if records.len() == MAX_RECORDS {
+            warn!("Only the first {MAX_RECORDS} spans are included in the timing trace.");
+        }
+        path.write_as_json(&chrome_trace(&records))?;
+        info!("Wrote the timing trace to {}.", path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::prelude::*;
+    use tracing_subscriber::Registry;
+
+    #[test]
+    fn recording_spans() {
+        let subscriber = Registry::default().with(TimerLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            let outer = info_span!("timer_test_outer").entered();
+            for _ in 0..2 {
+                let _inner = info_span!("timer_test_inner").entered();
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            drop(outer);
+        });
+        let steps =
+            summary().into_iter().filter(|step| step.name.starts_with("timer_test_")).collect_vec();
+        assert_eq!(steps[0].name, "timer_test_outer");
+        assert_eq!(steps[1].count, 2);
+        assert!(steps[1].busy >= Duration::from_millis(10));
+    }
+
+    #[test]
+    fn summarizing_records() {
+        let record = |name, wall, root| SpanTiming {
+            name,
+            target: "ide_ci::log::timer",
+            start: default(),
+            wall: Duration::from_millis(wall),
+            busy: default(),
+            root,
+        };
+        let records = [record("build", 30, 1), record("test", 5, 1), record("test", 20, 2)];
+        let summary = summarize(&records);
+        assert_eq!(summary[0].name, "build");
+        assert_eq!((summary[1].count, summary[1].max), (2, Duration::from_millis(20)));
+        assert_eq!(summary[1].wall, Duration::from_millis(25));
+
+        let trace = chrome_trace(&records);
+        assert_eq!(trace["traceEvents"].as_array().map(Vec::len), Some(3));
+        assert_eq!(trace["traceEvents"][2]["tid"], 2);
+    }
+}
@@ -0,0 +1,304 @@
+//! Metrics of the build runs, for tracking the CI performance over time.
+//!
+//! Recording is disabled unless [`ENSO_BUILD_METRICS_FILE`] or
+//! [`ENSO_BUILD_METRICS_OTLP_ENDPOINT`] is set (or [`enable`] is called), so the recording calls
+//! scattered over the code cost nothing by default. The step durations are taken by the
+//! [`MetricsLayer`] from the `step` spans of the [pipeline](crate::pipeline). At the end of the
+//! process, [`report`] writes the JSON file (uploading it as an artifact on GitHub Actions) and
+//! pushes the metrics to the OpenTelemetry collector.
+
+use crate::prelude::*;
+
+use std::lazy::SyncLazy;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+use tracing::field::Field;
+use tracing::field::Visit;
+use tracing::span::Attributes;
+use tracing::Id;
+use tracing::Subscriber;
+use tracing_subscriber::registry::LookupSpan;
+
+
+crate::define_env_var! {
+    /// Path where the JSON file with the run's metrics should be written.
+    ENSO_BUILD_METRICS_FILE, PathBuf, optional
+}
+crate::define_env_var! {
+    /// Base URL of the OpenTelemetry collector's OTLP/HTTP endpoint, like `http://localhost:4318`.
+    ENSO_BUILD_METRICS_OTLP_ENDPOINT, Url, optional
+}
+
+/// Name of the artifact with the metrics file.
+pub const ARTIFACT_NAME: &str = "build-metrics";
+
+/// Name of the spans whose durations are recorded. Their `name` field identifies the step.
+pub const STEP_SPAN_NAME: &str = "step";
+
+/// Metrics collected during the run.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metrics {
+    /// Durations of the steps' runs, in seconds.
+    pub step_durations: BTreeMap<String, Vec<f64>>,
+    pub cache_hits:     u64,
+    pub cache_misses:   u64,
+    /// Sizes of the uploaded artifacts, in bytes.
+    pub artifact_sizes: BTreeMap<String, u64>,
+    /// Number of retries, by the retried operation.
+    pub retries:        BTreeMap<String, u64>,
+}
+
+impl Metrics {
+    /// Fraction of the cache lookups that were hits, if there were any lookups.
+    pub fn cache_hit_rate(&self) -> Option<f64> {
+        let lookups = self.cache_hits + self.cache_misses;
+        (lookups > 0).then(|| self.cache_hits as f64 / lookups as f64)
+    }
+
+    /// The metrics in the OTLP/HTTP JSON encoding.
+    ///
+    /// See: <https://opentelemetry.io/docs/specs/otlp/#json-protobuf-encoding>
+    pub fn to_otlp(&self, timestamp: SystemTime) -> Result<serde_json::Value> {
+        let time = timestamp.duration_since(SystemTime::UNIX_EPOCH)?.as_nanos().to_string();
+        let point = |value: serde_json::Value, attributes: &[(&str, &str)]| {
+            let attributes = attributes
+                .iter()
+                .map(
+                    |(key, value)| serde_json::json!({"key": key, "value": {"stringValue": value}}),
+                )
+                .collect_vec();
+            let mut point = serde_json::json!({"timeUnixNano": time, "attributes": attributes});
+            let kind = if value.is_u64() { "asInt" } else { "asDouble" };
+            // The 64-bit integers are encoded as strings.
+            let value = if value.is_u64() { value.to_string().into() } else { value };
+            point[kind] = value;
+            point
+        };
+        fn gauge(name: &str, unit: &str, points: Vec<serde_json::Value>) -> serde_json::Value {
+            serde_json::json!({"name": name, "unit": unit, "gauge": {"dataPoints": points}})
+        }
+        fn counter(name: &str, points: Vec<serde_json::Value>) -> serde_json::Value {
+            let sum = serde_json::json!({
+                "dataPoints": points,
+                // Cumulative.
+                "aggregationTemporality": 2,
+                "isMonotonic": true,
+            });
+            serde_json::json!({"name": name, "unit": "1", "sum": sum})
+        }
+
+        let step_durations = self
+            .step_durations
+            .iter()
+            .map(|(step, durations)| {
+                point(durations.iter().sum::<f64>().into(), &[("step", step.as_str())])
+            })
+            .collect();
+        let artifact_sizes = self
+            .artifact_sizes
+            .iter()
+            .map(|(artifact, size)| point((*size).into(), &[("artifact", artifact.as_str())]))
+            .collect();
+        let retries = self
+            .retries
+            .iter()
+            .map(|(operation, count)| point((*count).into(), &[("operation", operation.as_str())]))
+            .collect();
+        let cache_lookups = vec![
+            point(self.cache_hits.into(), &[("result", "hit")]),
+            point(self.cache_misses.into(), &[("result", "miss")]),
+        ];
+        let metrics = vec![
+            gauge("build.step.duration", "s", step_durations),
+            gauge("build.artifact.size", "By", artifact_sizes),
+            counter("build.cache.lookups", cache_lookups),
+            counter("build.retries", retries),
+        ];
+        Ok(serde_json::json!({
+            "resourceMetrics": [{
+                "resource": {
+                    "attributes": [{"key": "service.name", "value": {"stringValue": "enso-build"}}],
+                },
+                "scopeMetrics": [{"scope": {"name": "ide_ci"}, "metrics": metrics}],
+            }],
+        }))
+    }
+}
+
+/// Metrics of the current run, if recording is enabled.
+static METRICS: SyncLazy<Mutex<Option<Metrics>>> = SyncLazy::new(default);
+
+/// Start recording the metrics.
+pub fn enable() {
+    METRICS.lock().unwrap().get_or_insert_with(default);
+}
+
+/// Start recording the metrics if any of the outputs is configured in the environment.
+pub fn enable_from_env() -> Result {
+    if ENSO_BUILD_METRICS_FILE.try_get()?.is_some()
+        || ENSO_BUILD_METRICS_OTLP_ENDPOINT.try_get()?.is_some()
+    {
+        enable();
+    }
+    Ok(())
+}
+
+pub fn is_enabled() -> bool {
+    METRICS.lock().unwrap().is_some()
+}
+
+/// Metrics recorded so far, if recording is enabled.
+pub fn snapshot() -> Option<Metrics> {
+    METRICS.lock().unwrap().clone()
+}
+
+fn record(f: impl FnOnce(&mut Metrics)) {
+    if let Some(metrics) = METRICS.lock().unwrap().as_mut() {
+        f(metrics)
+    }
+}
+
+pub fn record_step_duration(step: impl Into<String>, duration: Duration) {
+    record(|metrics| {
+        metrics.step_durations.entry(step.into()).or_default().push(duration.as_secs_f64())
+    })
+}
+
+pub fn record_cache_lookup(hit: bool) {
+    record(|metrics| {
+        if hit {
+            metrics.cache_hits += 1;
+        } else {
+            metrics.cache_misses += 1;
+        }
+    })
+}
+
+pub fn record_artifact_size(artifact: impl Into<String>, size: u64) {
+    record(|metrics| {
+        *metrics.artifact_sizes.entry(artifact.into()).or_default() += size;
+    })
+}
+
+pub fn record_retry(operation: impl Into<String>) {
+    record(|metrics| {
+        *metrics.retries.entry(operation.into()).or_default() += 1;
+    })
+}
+
+/// The `name` field of the step span.
+#[derive(Clone, Debug, Default)]
+struct StepName(Option<String>);
+
+impl Visit for StepName {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "name" {
+            self.0 = Some(format!("{value:?}"));
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "name" {
+            self.0 = Some(value.to_string());
+        }
+    }
+}
+
+/// State of an open step span, kept in its extensions.
+#[derive(Clone, Debug)]
+struct OpenStep {
+    name:    String,
+    created: Instant,
+}
+
+/// Layer recording the durations of the [step spans](STEP_SPAN_NAME).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MetricsLayer;
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> tracing_subscriber::Layer<S> for MetricsLayer {
+    fn on_new_span(
+        &self,
+        attrs: &Attributes<'_>,
+        id: &Id,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        if attrs.metadata().name() != STEP_SPAN_NAME || !is_enabled() {
+            return;
+        }
+        let mut name = StepName::default();
+        attrs.record(&mut name);
+        if let (Some(span), Some(name)) = (ctx.span(id), name.0) {
+            span.extensions_mut().insert(OpenStep { name, created: Instant::now() });
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: tracing_subscriber::layer::Context<'_, S>) {
+        if let Some(span) = ctx.span(&id) {
+            if let Some(step) = span.extensions_mut().remove::<OpenStep>() {
+                record_step_duration(step.name, step.created.elapsed());
+            }
+        }
+    }
+}
+
+/// Send the metrics to the OpenTelemetry collector.
+pub async fn push_otlp(endpoint: &Url, metrics: &Metrics) -> Result {
+    let url = endpoint.join("v1/metrics")?;
+    let body = metrics.to_otlp(SystemTime::now())?;
+    crate::net::client()?
+        .post(url.clone())
+        .json(&body)
+        .send()
+        .await?
+        .error_for_status()
+        .with_context(|| format!("Failed to push the metrics to {url}."))?;
+    Ok(())
+}
+
+/// Publish the metrics recorded so far, to the outputs configured in the environment.
+pub async fn report() -> Result {
+    let metrics = match snapshot() {
+        Some(metrics) => metrics,
+        None => return Ok(()),
+    };
+    if let Some(path) = ENSO_BUILD_METRICS_FILE.try_get()? {
+        path.write_as_json(&metrics)?;
+        info!("Wrote the build metrics to {}.", path.display());
+        if crate::actions::workflow::is_in_env() {
+            crate::actions::artifacts::upload_single_file(&path, ARTIFACT_NAME).await?;
+        }
+    }
+    if let Some(endpoint) = ENSO_BUILD_METRICS_OTLP_ENDPOINT.try_get()? {
+        push_otlp(&endpoint, &metrics).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn otlp_encoding() -> Result {
+        let metrics = Metrics {
+            step_durations: BTreeMap::from_iter([("build".into(), vec![1.5, 2.0])]),
+            cache_hits:     3,
+            cache_misses:   1,
+            artifact_sizes: BTreeMap::from_iter([("ide-linux".into(), 1024)]),
+            retries:        default(),
+        };
+        assert_eq!(metrics.cache_hit_rate(), Some(0.75));
+        let timestamp = SystemTime::UNIX_EPOCH + Duration::from_secs(1);
+        let otlp = metrics.to_otlp(timestamp)?;
+        let metrics = &otlp["resourceMetrics"][0]["scopeMetrics"][0]["metrics"];
+        let duration = &metrics[0]["gauge"]["dataPoints"][0];
+        assert_eq!(duration["asDouble"], 3.5);
+        assert_eq!(duration["timeUnixNano"], "1000000000");
+        assert_eq!(duration["attributes"][0]["value"]["stringValue"], "build");
+        assert_eq!(metrics[1]["gauge"]["dataPoints"][0]["asInt"], "1024");
+        assert_eq!(metrics[2]["sum"]["dataPoints"][1]["asInt"], "1");
+        Ok(())
+    }
+}
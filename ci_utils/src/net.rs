@@ -0,0 +1,205 @@
+//! Construction of the HTTP clients.
+//!
+//! All the `reqwest` clients should be created through [`client_builder`] or [`client`], so they
+//! respect the network settings of the environment. This is needed on the self-hosted runners in
+//! corporate networks, which often can reach the internet only through a proxy that re-signs the
+//! TLS traffic with its own certificate:
+//! * [`HTTPS_PROXY`] is used for all the requests, except for the hosts listed in [`NO_PROXY`] (the
+//!   lowercase variants of the variables are honored as well);
+//! * the certificates from [`ENSO_BUILD_EXTRA_CA_CERTIFICATES`] are trusted in addition to the
+//!   built-in roots.
+//!
+//! This includes the [GitHub API client](crate::github::Client), whose `octocrab` instance is given
+//! a client from [`client_builder`].
+
+use crate::prelude::*;
+
+use reqwest::Certificate;
+use reqwest::Client;
+use reqwest::ClientBuilder;
+use reqwest::Proxy;
+use std::lazy::SyncLazy;
+
+
+crate::define_env_var! {
+    /// URL of the proxy server, like `http://proxy.example.com:8080`. The scheme can be omitted.
+    HTTPS_PROXY, String, optional
+}
+crate::define_env_var! {
+    /// Comma-separated hosts that are connected to directly, like `localhost,.example.com`.
+    NO_PROXY, String, optional
+}
+crate::define_env_var! {
+    /// Same as [`HTTPS_PROXY`], takes precedence if set.
+    https_proxy, String, optional
+}
+crate::define_env_var! {
+    /// Same as [`NO_PROXY`], takes precedence if set.
+    no_proxy, String, optional
+}
+crate::define_env_var! {
+    /// PEM file with the certificates to be trusted in addition to the built-in roots.
+    ENSO_BUILD_EXTRA_CA_CERTIFICATES, PathBuf, optional
+}
+
+/// Hosts that should not be connected to through the proxy, in the `NO_PROXY` format.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NoProxy {
+    entries: Vec<String>,
+}
+
+impl NoProxy {
+    pub fn parse(text: &str) -> Self {
+        let entries = text
+            .split(',')
+            .map(|entry| {
+                let entry = entry.trim().trim_start_matches('.');
+                // The port, if present, is ignored.
+                let host = match entry.rsplit_once(':') {
+                    Some((host, port)) if port.parse::<u16>().is_ok() => host,
+                    _ => entry,
+                };
+                host.to_lowercase()
+            })
+            .filter(|entry| !entry.is_empty())
+            .collect();
+        Self { entries }
+    }
+
+    /// Whether the host matches any of the entries, either exactly or as a subdomain.
+    pub fn matches(&self, host: &str) -> bool {
+        let host = host.to_lowercase();
+        self.entries
+            .iter()
+            .any(|entry| entry == "*" || host == *entry || host.ends_with(&format!(".{entry}")))
+    }
+}
+
+/// Parse the proxy URL, assuming the `http` scheme if none is given.
+pub fn parse_proxy_url(text: &str) -> Result<Url> {
+    let text = text.trim();
+    let url = if text.contains("://") { text.parse() } else { format!("http://{text}").parse() };
+    url.with_context(|| format!("Invalid proxy URL: {text}"))
+}
+
+/// Value of the first variable that is set to a non-empty value.
+fn first_set(
+    variables: [&crate::env::new::SimpleVariable<String, str>; 2],
+) -> Result<Option<String>> {
+    for variable in variables {
+        if let Some(value) = variable.try_get()? && !value.trim().is_empty() {
+            return Ok(Some(value));
+        }
+    }
+    Ok(None)
+}
+
+/// The proxy configured in the environment, if any.
+pub fn proxy_from_env() -> Result<Option<Proxy>> {
+    let proxy_url = match first_set([&https_proxy, &HTTPS_PROXY])? {
+        Some(text) => parse_proxy_url(&text)?,
+        None => return Ok(None),
+    };
+    let excluded = NoProxy::parse(&first_set([&no_proxy, &NO_PROXY])?.unwrap_or_default());
+    debug!("Using the proxy {proxy_url}, except for {excluded:?}.");
+    let proxy = Proxy::custom(move |target| {
+        let host = target.host_str()?;
+        (!excluded.matches(host)).then(|| proxy_url.clone())
+    });
+    Ok(Some(proxy))
+}
+
+/// Split the PEM bundle into the separate certificates.
+pub fn split_pem_bundle(text: &str) -> Vec<&str> {
+    const BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+    const END: &str = "-----END CERTIFICATE-----";
+    let mut ret = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(BEGIN) && let Some(length) = rest[start..].find(END) {
+        let end = start + length + END.len();
+        ret.push(&rest[start..end]);
+        rest = &rest[end..];
+    }
+    ret
+}
+
+/// The additional certificates configured in the environment.
+pub fn extra_certificates() -> Result<Vec<Certificate>> {
+    let path = match ENSO_BUILD_EXTRA_CA_CERTIFICATES.try_get()? {
+        Some(path) => path,
+        None => return Ok(default()),
+    };
+    let bundle = crate::fs::read_to_string(&path)?;
+    let certificates = split_pem_bundle(&bundle)
+        .into_iter()
+        .map(|pem| Certificate::from_pem(pem.as_bytes()))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .with_context(|| format!("Failed to parse the certificates from {}.", path.display()))?;
+    ensure!(!certificates.is_empty(), "No certificates found in {}.", path.display());
+    Ok(certificates)
+}
+
+/// Builder of the HTTP client, with the network settings from the environment applied.
+pub fn client_builder() -> Result<ClientBuilder> {
+    let mut builder = ClientBuilder::new().user_agent(crate::USER_AGENT);
+    if let Some(proxy) = proxy_from_env()? {
+        // Otherwise the system proxy would be used for the hosts excluded by `NO_PROXY`.
+        builder = builder.no_proxy().proxy(proxy);
+    }
+    for certificate in extra_certificates()? {
+        builder = builder.add_root_certificate(certificate);
+    }
+    Ok(builder)
+}
+
+/// The client shared by the whole process, or the error message if it could not be created.
+static SHARED_CLIENT: SyncLazy<std::result::Result<Client, String>> = SyncLazy::new(|| {
+    let client = client_builder().and_then(|builder| builder.build().anyhow_err());
+    client.map_err(|e| format!("{e:?}"))
+});
+
+/// HTTP client with the network settings from the environment.
+///
+/// The client is created on the first call and shared afterwards, so the connections are pooled
+/// and the certificates are read once. Use [`client_builder`] for the clients that need custom
+/// settings.
+pub fn client() -> Result<Client> {
+    SHARED_CLIENT.clone().map_err(|e| anyhow!("Failed to create the HTTP client: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proxy_settings() -> Result {
+        let excluded = NoProxy::parse("localhost, .example.com,10.0.0.1:8080,");
+        assert!(excluded.matches("localhost"));
+        assert!(excluded.matches("example.com"));
+        assert!(excluded.matches("artifacts.EXAMPLE.com"));
+        assert!(excluded.matches("10.0.0.1"));
+        assert!(!excluded.matches("github.com"));
+        assert!(!excluded.matches("notexample.com"));
+        assert!(NoProxy::parse("*").matches("github.com"));
+
+        assert_eq!(parse_proxy_url("proxy:3128")?.as_str(), "http://proxy:3128/");
+        assert_eq!(parse_proxy_url("https://proxy")?.scheme(), "https");
+        Ok(())
+    }
+
+    #[test]
+    fn splitting_bundle() {
+        let certificate = |body: &str| {
+            format!("-----BEGIN CERTIFICATE-----\n{body}\n-----END CERTIFICATE-----\n")
+        };
+        // The incomplete trailing certificate is ignored.
+        let bundle = format!(
+            "# Root\n{}{}-----BEGIN CERTIFICATE-----",
+            certificate("AAA"),
+            certificate("BBB")
+        );
+        let certificates = split_pem_bundle(&bundle);
+        assert_eq!(certificates.len(), 2);
+        assert!(certificates[1].contains("BBB") && certificates[1].ends_with("-----"));
+    }
+}
@@ -1,4 +1,5 @@
 pub mod target;
+pub mod windows;
 
 pub use target::TARGET_ARCH;
 pub use target::TARGET_OS;
@@ -0,0 +1,129 @@
+//! Windows-specific path and command line handling.
+//!
+//! The functions are available on all platforms, as they operate just on text.
+
+use crate::prelude::*;
+
+use std::iter::repeat;
+
+
+/// Maximum length of a path accepted by the Win32 API functions, unless the path is verbatim.
+pub const MAX_PATH: usize = 260;
+
+/// Length from which the paths are made verbatim. Creating a directory requires leaving room for
+/// an 8.3 file name, so the limit is lower than [`MAX_PATH`].
+pub const LONG_PATH_THRESHOLD: usize = MAX_PATH - 12;
+
+/// Prefix of the verbatim paths, which are passed to the file system without any parsing.
+pub const VERBATIM_PREFIX: &str = r"\\?\";
+
+/// Make the long absolute path verbatim (`\\?\C:\...`), so it is not subject to the `MAX_PATH`
+/// limit.
+///
+/// As the verbatim paths are not normalized by the system, the forward slashes are replaced and
+/// the `.` and `..` components are resolved. Short, relative and already verbatim paths are
+/// returned unchanged.
+pub fn long_path(path: &Path) -> Cow<Path> {
+    let text = match path.to_str() {
+        Some(text) if text.len() >= LONG_PATH_THRESHOLD && !text.starts_with(VERBATIM_PREFIX) =>
+            text.replace('/', r"\"),
+        _ => return Cow::Borrowed(path),
+    };
+    let is_drive_absolute = |text: &str| {
+        let bytes = text.as_bytes();
+        bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && bytes[2] == b'\\'
+    };
+    // Number of leading components that form the root and cannot be popped by `..`.
+    let (prefix, rest, root_length) = if let Some(unc) = text.strip_prefix(r"\\") {
+        (r"\\?\UNC\", unc, 2)
+    } else if is_drive_absolute(&text) {
+        (VERBATIM_PREFIX, text.as_str(), 1)
+    } else {
+        return Cow::Borrowed(path);
+    };
+    let mut components = Vec::new();
+    for component in rest.split('\\') {
+        match component {
+            "" | "." => {}
+            ".." =>
+                if components.len() > root_length {
+                    components.pop();
+                },
+            component => components.push(component),
+        }
+    }
+    Cow::Owned(PathBuf::from(format!("{prefix}{}", components.join(r"\"))))
+}
+
+/// Whether the program is run by the `cmd.exe` interpreter, i.e. it is the interpreter itself or a
+/// batch script.
+///
+/// The interpreter does not support verbatim paths, so it cannot be given one as the working
+/// directory.
+pub fn runs_in_cmd(program: &OsStr) -> bool {
+    let program = program.to_string_lossy().to_lowercase();
+    let name = program.rsplit(|c: char| c == '\\' || c == '/').next().unwrap_or_default();
+    name == "cmd" || name == "cmd.exe" || name.ends_with(".bat") || name.ends_with(".cmd")
+}
+
+/// Quote the argument, so it is parsed back verbatim by `CommandLineToArgvW` and the Microsoft C
+/// runtime, which most Windows programs use to split their command lines.
+///
+/// Backslashes are literal, unless they precede a double quote. Thus, those preceding an
+/// escaped quote or the closing quote are doubled.
+pub fn quote_argument(arg: &str) -> String {
+    let mut ret = String::from('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        if c == '\\' {
+            backslashes += 1;
+            continue;
+        }
+        let count = if c == '"' { backslashes * 2 + 1 } else { backslashes };
+        ret.extend(repeat('\\').take(count));
+        ret.push(c);
+        backslashes = 0;
+    }
+    ret.extend(repeat('\\').take(backslashes * 2));
+    ret.push('"');
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_paths() {
+        let deep = "node_modules\\".repeat(20);
+        let path = format!("C:/repo/./app/gui/../ide/{deep}");
+        let expected = format!(r"\\?\C:\repo\app\ide\{}", deep.trim_end_matches('\\'));
+        assert_eq!(long_path(Path::new(&path)), Path::new(&expected));
+
+        let unc = format!(r"\\server\share\..\..\{deep}");
+        let expected = format!(r"\\?\UNC\server\share\{}", deep.trim_end_matches('\\'));
+        assert_eq!(long_path(Path::new(&unc)), Path::new(&expected));
+
+        let short = Path::new(r"C:\repo\..\dist");
+        assert!(matches!(long_path(short), Cow::Borrowed(_)));
+        let relative = Path::new(&deep);
+        assert!(matches!(long_path(relative), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn cmd_programs() {
+        assert!(runs_in_cmd(OsStr::new("cmd")));
+        assert!(runs_in_cmd(OsStr::new(r"C:\Windows\System32\CMD.EXE")));
+        assert!(runs_in_cmd(OsStr::new(r"C:\Program Files\nodejs\npm.cmd")));
+        assert!(runs_in_cmd(OsStr::new("scripts/build.bat")));
+        assert!(!runs_in_cmd(OsStr::new(r"C:\Program Files\nodejs\node.exe")));
+        assert!(!runs_in_cmd(OsStr::new(r"C:\cmd\git.exe")));
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(quote_argument("C:\\Program Files\\"), r#""C:\Program Files\\""#);
+        assert_eq!(quote_argument(r#"say \"hi""#), r#""say \\\"hi\"""#);
+        assert_eq!(quote_argument(""), r#""""#);
+    }
+}
@@ -0,0 +1,4 @@
+//! Building the platform-specific distribution packages out of the staged application files.
+
+pub mod linux;
+pub mod macos;
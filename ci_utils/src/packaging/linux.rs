@@ -0,0 +1,251 @@
+//! Linux distribution packages: AppImages and Debian packages.
+//!
+//! Both are built from a staged directory with the application's files, which is placed under
+//! `/usr/lib/<name>` in the AppImage and under `/opt/<name>` in the Debian package. The desktop
+//! entry and the icon are put where the desktop environments look for them.
+
+use crate::prelude::*;
+
+use crate::programs::appimagetool::AppImageTool;
+use crate::programs::dpkg;
+use crate::programs::dpkg::DpkgDeb;
+
+
+/// Freedesktop menu entry of the application.
+///
+/// See https://specifications.freedesktop.org/desktop-entry-spec/latest/
+#[derive(Clone, Debug)]
+pub struct DesktopEntry {
+    /// Name shown in the menu.
+    pub name:       String,
+    pub comment:    Option<String>,
+    /// Menu categories, like `Development`.
+    pub categories: Vec<String>,
+    pub terminal:   bool,
+}
+
+impl DesktopEntry {
+    /// Render the `.desktop` file contents.
+    pub fn render(&self, exec: &str, icon: &str) -> String {
+        let mut ret = String::from("[Desktop Entry]\nType=Application\n");
+        ret += &format!("Name={}\nExec={exec} %U\nIcon={icon}\n", self.name);
+        if let Some(comment) = &self.comment {
+            ret += &format!("Comment={comment}\n");
+        }
+        // The list must be terminated with a semicolon.
+        ret += &format!("Categories={};\n", self.categories.join(";"));
+        ret += &format!("Terminal={}\n", self.terminal);
+        ret
+    }
+}
+
+/// Description of the packaged application.
+#[derive(Clone, Debug)]
+pub struct PackageInfo {
+    /// Package identifier, like `enso`. Used for the directory, desktop entry and icon names.
+    pub name:          String,
+    pub version:       Version,
+    pub description:   String,
+    /// Contact of the maintainer, like `Enso Team <contact@enso.org>`.
+    pub maintainer:    String,
+    pub homepage:      Option<Url>,
+    pub arch:          Arch,
+    /// Debian packages that must be installed as well.
+    pub depends:       Vec<String>,
+    /// Path of the main executable, relative to the staged directory.
+    pub executable:    PathBuf,
+    /// PNG icon of the application.
+    pub icon:          PathBuf,
+    /// Size of the icon in pixels, like 256.
+    pub icon_size:     u32,
+    pub desktop_entry: DesktopEntry,
+}
+
+impl PackageInfo {
+    /// Path where the icon is installed, relative to the `/usr` directory.
+    fn icon_path(&self) -> PathBuf {
+        let size = format!("{0}x{0}", self.icon_size);
+        ["share", "icons", "hicolor", size.as_str(), "apps"]
+            .into_iter()
+            .collect::<PathBuf>()
+            .join(format!("{}.png", self.name))
+    }
+
+    fn desktop_entry_path(&self) -> PathBuf {
+        PathBuf::from_iter(["share", "applications"]).join(format!("{}.desktop", self.name))
+    }
+
+    /// The Debian package's control file.
+    pub fn debian_control(&self, installed_size_kib: u64) -> Result<String> {
+        let mut ret = format!(
+            "Package: {}\nVersion: {}\nArchitecture: {}\nMaintainer: {}\nInstalled-Size: {}\n",
+            self.name,
+            self.version,
+            dpkg::arch_name(self.arch)?,
+            self.maintainer,
+            installed_size_kib
+        );
+        if !self.depends.is_empty() {
+            ret += &format!("Depends: {}\n", self.depends.join(", "));
+        }
+        if let Some(homepage) = &self.homepage {
+            ret += &format!("Homepage: {homepage}\n");
+        }
+        // Continuation lines of the description must be indented, and empty ones are marked with
+        // a dot.
+        let mut description = self.description.lines();
+        ret += &format!("Description: {}\n", description.next().unwrap_or_default());
+        for line in description {
+            let line = if line.trim().is_empty() { "." } else { line };
+            ret += &format!(" {line}\n");
+        }
+        Ok(ret)
+    }
+}
+
+/// Total size of the files in the directory, in KiB, rounded up.
+fn directory_size_kib(path: &Path) -> Result<u64> {
+    let mut bytes = 0;
+    for entry in walkdir::WalkDir::new(path) {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            bytes += entry.metadata()?.len();
+        }
+    }
+    Ok((bytes + 1023) / 1024)
+}
+
+/// Lay out the `AppDir` of the AppImage.
+pub fn stage_app_dir(staged: &Path, info: &PackageInfo, app_dir: &Path) -> Result {
+    let usr = app_dir.join("usr");
+    let install_dir = usr.join_iter(["lib", info.name.as_str()]);
+    crate::fs::copy(staged, &install_dir)?;
+
+    let icon_name = format!("{}.png", info.name);
+    crate::fs::copy(&info.icon, usr.join(info.icon_path()))?;
+    crate::fs::copy(&info.icon, app_dir.join(&icon_name))?;
+    crate::fs::symlink_auto(&icon_name, app_dir.join(".DirIcon"))?;
+
+    let desktop_entry = info.desktop_entry.render(&info.name, &info.name);
+    crate::fs::write(usr.join(info.desktop_entry_path()), &desktop_entry)?;
+    crate::fs::write(app_dir.join(format!("{}.desktop", info.name)), &desktop_entry)?;
+
+    let executable = PathBuf::from_iter(["usr", "lib", info.name.as_str()]).join(&info.executable);
+    let app_run = app_dir.join("AppRun");
+    crate::fs::write(
+        &app_run,
+        format!(
+            "#!/bin/sh\nHERE=\"$(dirname \"$(readlink -f \"$0\")\")\"\nexec \"$HERE/{}\" \"$@\"\n",
+            executable.display()
+        ),
+    )?;
+    crate::fs::allow_owner_execute(&app_run)?;
+    Ok(())
+}
+
+/// Lay out the root of the Debian package.
+pub fn stage_deb_root(staged: &Path, info: &PackageInfo, root: &Path) -> Result {
+    let install_dir = Path::new("/opt").join(&info.name);
+    crate::fs::copy(staged, root.join_iter(["opt", info.name.as_str()]))?;
+
+    let usr = root.join("usr");
+    crate::fs::copy(&info.icon, usr.join(info.icon_path()))?;
+    let executable = install_dir.join(&info.executable);
+    crate::fs::symlink_auto(&executable, usr.join_iter(["bin", info.name.as_str()]))?;
+    let desktop_entry = info.desktop_entry.render(executable.as_str(), &info.name);
+    crate::fs::write(usr.join(info.desktop_entry_path()), desktop_entry)?;
+
+    let control = info.debian_control(directory_size_kib(root)?)?;
+    crate::fs::write(root.join_iter(["DEBIAN", "control"]), control)?;
+    Ok(())
+}
+
+/// Build the AppImage out of the staged application files.
+#[tracing::instrument(skip(info), fields(name = %info.name), err)]
+pub async fn create_appimage(
+    staged: impl AsRef<Path> + Debug,
+    info: &PackageInfo,
+    output: impl AsRef<Path> + Debug,
+) -> Result {
+    let app_dir = crate::fs::temp::dir("AppDir")?;
+    stage_app_dir(staged.as_ref(), info, app_dir.path())?;
+    crate::fs::tokio::create_parent_dir_if_missing(&output).await?;
+    AppImageTool.build(app_dir.path(), info.arch, output).await
+}
+
+/// Build the Debian package out of the staged application files.
+#[tracing::instrument(skip(info), fields(name = %info.name), err)]
+pub async fn create_deb(
+    staged: impl AsRef<Path> + Debug,
+    info: &PackageInfo,
+    output: impl AsRef<Path> + Debug,
+) -> Result {
+    let root = crate::fs::temp::dir("deb")?;
+    stage_deb_root(staged.as_ref(), info, root.path())?;
+    crate::fs::tokio::create_parent_dir_if_missing(&output).await?;
+    DpkgDeb.build(root.path(), output).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package_info(icon: PathBuf) -> PackageInfo {
+        PackageInfo {
+            name: "enso".into(),
+            version: Version::new(2022, 1, 1),
+            description: "Enso IDE.\nVisual and textual programming.\n\nFor data science.".into(),
+            maintainer: "Enso Team <contact@enso.org>".into(),
+            homepage: Some("https://enso.org".parse().unwrap()),
+            arch: Arch::X86_64,
+            depends: vec!["libgtk-3-0".into(), "libnss3".into()],
+            executable: "enso".into(),
+            icon,
+            icon_size: 256,
+            desktop_entry: DesktopEntry {
+                name:       "Enso".into(),
+                comment:    None,
+                categories: vec!["Development".into()],
+                terminal:   false,
+            },
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn debian_package_layout() -> Result {
+        let temp = tempfile::tempdir()?;
+        let staged = temp.path().join("staged");
+        crate::fs::write(staged.join("enso"), "binary")?;
+        crate::fs::write(staged.join_iter(["resources", "app.asar"]), "resources")?;
+        let icon = temp.path().join("icon.png");
+        crate::fs::write(&icon, "png")?;
+        let info = package_info(icon);
+
+        let root = temp.path().join("root");
+        stage_deb_root(&staged, &info, &root)?;
+        assert!(root.join_iter(["opt", "enso", "resources", "app.asar"]).exists());
+        assert!(root
+            .join_iter(["usr", "share", "icons", "hicolor", "256x256", "apps", "enso.png"])
+            .exists());
+        let desktop_entry = crate::fs::read_to_string(root.join_iter([
+            "usr",
+            "share",
+            "applications",
+            "enso.desktop",
+        ]))?;
+        assert!(desktop_entry.contains("Exec=/opt/enso/enso %U\n"), "{desktop_entry}");
+        assert!(desktop_entry.contains("Categories=Development;\n"), "{desktop_entry}");
+
+        let control = crate::fs::read_to_string(root.join_iter(["DEBIAN", "control"]))?;
+        assert!(control.starts_with("Package: enso\nVersion: 2022.1.1\nArchitecture: amd64\n"));
+        assert!(control.contains("Depends: libgtk-3-0, libnss3\n"), "{control}");
+        assert!(
+            control.ends_with(
+                "Description: Enso IDE.\n Visual and textual programming.\n .\n For data science.\n"
+            ),
+            "{control}"
+        );
+        Ok(())
+    }
+}
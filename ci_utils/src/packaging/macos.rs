@@ -0,0 +1,137 @@
+//! macOS disk images (`.dmg`) with the signed and notarized application bundle.
+
+use crate::prelude::*;
+
+use crate::programs::hdiutil;
+use crate::programs::hdiutil::Hdiutil;
+use crate::programs::signing::codesign;
+use crate::programs::signing::notarytool::Ditto;
+use crate::programs::signing::Codesign;
+use crate::programs::signing::MacOsSigning;
+use crate::programs::signing::Xcrun;
+
+
+/// Options of [`create_signed_dmg`].
+#[derive(Clone, Debug)]
+pub struct DmgOptions {
+    /// Path of the created image.
+    pub output:            PathBuf,
+    /// Name of the mounted volume. Defaults to the bundle's name without the `.app` extension.
+    pub volume_name:       Option<String>,
+    pub format:            hdiutil::ImageFormat,
+    /// Whether to place a link to `/Applications` next to the bundle, so the user can install
+    /// the application by dragging it there.
+    pub applications_link: bool,
+    /// If set, both the bundle and the image are signed, and the image is notarized if the
+    /// credentials are given.
+    pub signing:           Option<MacOsSigning>,
+}
+
+impl DmgOptions {
+    pub fn new(output: impl Into<PathBuf>) -> Self {
+        Self {
+            output:            output.into(),
+            volume_name:       None,
+            format:            default(),
+            applications_link: true,
+            signing:           None,
+        }
+    }
+
+    pub fn signing(mut self, signing: MacOsSigning) -> Self {
+        self.signing = Some(signing);
+        self
+    }
+}
+
+/// Pack the application bundle into a disk image, signing and notarizing both if configured.
+///
+/// The steps are:
+/// 1. sign the bundle (with the nested code) and verify the signature;
+/// 2. stage the bundle, with the `/Applications` link, and create the image;
+/// 3. sign the image, notarize it and staple the ticket, so Gatekeeper accepts it offline;
+/// 4. verify the image's checksum, signature and ticket.
+///
+/// Returns the path of the image.
+#[tracing::instrument(
+    skip_all,
+    fields(
+        src  = %app_bundle.as_ref().display(),
+        dest = %options.output.display()),
+    err)]
+pub async fn create_signed_dmg(
+    app_bundle: impl AsRef<Path>,
+    options: DmgOptions,
+) -> Result<PathBuf> {
+    let app_bundle = app_bundle.as_ref();
+    let bundle_name = app_bundle.file_name().context("The bundle path has no file name.")?;
+    if let Some(signing) = &options.signing {
+        Codesign.sign(&signing.codesign, app_bundle).await?;
+        Codesign.verify(app_bundle).await?;
+    }
+
+    let staging = crate::fs::temp::dir("dmg")?;
+    // Unlike a plain copy, `ditto` keeps the bundle's symlinks, permissions and extended
+    // attributes, which the signature covers.
+    Ditto.cmd()?.arg(app_bundle).arg(staging.path().join(bundle_name)).run_ok().await?;
+    if options.applications_link {
+        crate::fs::symlink_auto("/Applications", staging.path().join("Applications"))?;
+    }
+    let volume_name = match &options.volume_name {
+        Some(name) => name.clone(),
+        None => app_bundle.file_stem().unwrap_or(bundle_name).to_string_lossy().into(),
+    };
+    let create_options = hdiutil::CreateOptions {
+        format: options.format,
+        ..hdiutil::CreateOptions::new(volume_name, staging.path())
+    };
+    crate::fs::tokio::create_parent_dir_if_missing(&options.output).await?;
+    Hdiutil.create(&create_options, &options.output).await?;
+
+    let dmg = &options.output;
+    if let Some(signing) = &options.signing {
+        // The image itself is not code, so the hardened runtime and nested signing do not apply.
+        let dmg_signing = codesign_options_for_image(&signing.codesign);
+        Codesign.sign(&dmg_signing, dmg).await?;
+        if let Some(credentials) = &signing.notarization {
+            Xcrun.notarize(dmg, credentials).await?;
+            Xcrun.staple(dmg).await?;
+        }
+    }
+
+    Hdiutil.verify(dmg).await?;
+    if let Some(signing) = &options.signing {
+        Codesign.verify(dmg).await?;
+        if signing.notarization.is_some() {
+            Xcrun.validate_staple(dmg).await?;
+        }
+    }
+    Ok(dmg.clone())
+}
+
+fn codesign_options_for_image(bundle_options: &codesign::SignOptions) -> codesign::SignOptions {
+    codesign::SignOptions {
+        entitlements: None,
+        hardened_runtime: false,
+        deep: false,
+        ..bundle_options.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_signing_options() {
+        let mut bundle = codesign::SignOptions::new("Developer ID Application: Enso");
+        bundle.entitlements = Some("entitlements.plist".into());
+        let image = codesign_options_for_image(&bundle);
+        assert_eq!(image.args(), [
+            "--sign",
+            "Developer ID Application: Enso",
+            "--force",
+            "--timestamp"
+        ]);
+    }
+}
@@ -11,6 +11,10 @@ use regex::Regex;
 use serde_yaml::Value;
 use std::collections::BTreeSet;
 use std::iter::zip;
+use std::str::FromStr;
+use yaml_rust::parser::Event;
+use yaml_rust::parser::MarkedEventReceiver;
+use yaml_rust::scanner::Marker;
 
 
 fn to_ident(name: impl AsRef<str>) -> Ident {
@@ -37,6 +41,27 @@ impl ParameterRegex {
     }
 }
 
+/// Kind of the filesystem entry, declared explicitly with `kind` in the complex key.
+///
+/// Without the declaration, the nodes ending with `/` or having children are directories.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Directory,
+    File,
+}
+
+impl FromStr for Kind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "dir" | "directory" => Ok(Kind::Directory),
+            "file" => Ok(Kind::File),
+            other => bail!("Invalid kind `{other}`, expected `dir` or `file`."),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum Shape {
     File,
@@ -55,21 +80,54 @@ impl Shape {
 
 #[derive(Clone, Debug, PartialEq, Shrinkwrap)]
 pub struct Node {
+    /// The key as written in the YAML, used to locate it in the diagnostics.
+    key:        String,
     #[shrinkwrap(main_field)]
     value:      String,
     parameters: BTreeSet<String>, // Wasteful but paths won't be that huge.
     /// The name that replaces value in variable-like contexts.
     /// Basically, we might not want use filepath name as name in the code.
     var_name:   Option<String>,
+    /// Kind declared in the YAML, if any.
+    kind:       Option<Kind>,
+    /// Environment variable overriding the location of this node.
+    env:        Option<EnvOverride>,
     shape:      Shape,
 }
 
+/// Environment variable that can override the location of a node, like `CARGO_TARGET_DIR` does.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnvOverride {
+    pub name:    String,
+    /// Location used if the variable is not set. If `None`, it is the regular location of the
+    /// node.
+    pub default: Option<String>,
+}
+
 impl Node {
     pub fn new(value: impl AsRef<str>, var_name: Option<String>) -> Self {
         let shape = Shape::new(value.as_ref());
+        let key = value.as_ref().to_string();
         let value = value.as_ref().trim_end_matches('/').to_string();
         let parameters = default();
-        Self { var_name, parameters, shape, value }
+        Self { key, var_name, parameters, kind: None, env: None, shape, value }
+    }
+
+    pub fn with_kind(mut self, kind: Kind) -> Self {
+        if kind == Kind::Directory && self.shape == Shape::File {
+            self.shape = Shape::Directory(default());
+        }
+        self.kind = Some(kind);
+        self
+    }
+
+    pub fn with_env(mut self, name: impl Into<String>, default: Option<String>) -> Self {
+        self.env = Some(EnvOverride { name: name.into(), default });
+        self
+    }
+
+    pub fn is_directory(&self) -> bool {
+        matches!(self.shape, Shape::Directory(_))
     }
 
     pub fn new_from_key(value: &Value) -> Result<Self> {
@@ -79,7 +137,19 @@ impl Node {
                     .as_str()
                     .context("Expected string for `path`")?
                     .to_owned();
-                Node::new(value, mapping[&"var".into()].as_str().map(into))
+                // Only the `path` is mandatory, indexing would panic on the missing keys.
+                let get = |key: &str| mapping.get(&key.into()).and_then(Value::as_str);
+                let mut node = Node::new(value, get("var").map(into));
+                if let Some(kind) = get("kind") {
+                    node = node.with_kind(kind.parse()?);
+                }
+                match get("env") {
+                    Some(env) => node.with_env(env, get("default").map(into)),
+                    None => {
+                        ensure!(get("default").is_none(), "`default` requires `env` to be set.");
+                        node
+                    }
+                }
             }
             Value::String(string) => Node::new(string, None),
             other => bail!("Cannot deserialize {} to a node.", serde_yaml::to_string(other)?),
@@ -187,10 +257,18 @@ impl Node {
         self.to_ident(Case::Snake)
     }
 
+    pub fn var_ident_text(&self) -> String {
+        self.rustify().to_case(Case::Snake)
+    }
+
     pub fn struct_ident_piece(&self) -> Ident {
         self.to_ident(Case::Pascal)
     }
 
+    pub fn struct_ident_text(&self) -> String {
+        self.rustify().to_case(Case::Pascal)
+    }
+
     pub fn to_ident(&self, case: Case) -> Ident {
         syn::Ident::new(&self.rustify().to_case(case), Span::call_site())
     }
@@ -219,17 +297,34 @@ pub fn generate_struct(full_path: &[&Node], last_node: &Node) -> TokenStream {
         full_path.into_iter().flat_map(|n| n.own_parameter_vars()).collect_vec();
 
 
+    // The same parameter might be used in many subtrees, it still is a single argument.
     let child_parameter_vars = last_node
         .children()
         .iter()
         .flat_map(|node| node.parameters.iter())
+        .unique()
         .map(to_ident)
         .collect_vec();
-    let all_parameters = {
-        let mut v = parent_parameter_vars.clone();
-        v.extend(child_parameter_vars.clone());
-        v
-    };
+    let all_parameters =
+        parent_parameter_vars.iter().chain(&child_parameter_vars).unique().cloned().collect_vec();
+
+    // Setters replacing the value of a parameter in the whole subtree.
+    let setters = child_parameter_vars.iter().map(|parameter| {
+        let setter = to_ident(format!("with_{parameter}"));
+        let arguments = child_parameter_vars.iter().map(|other| {
+            if other == parameter {
+                quote! { #parameter.as_ref() }
+            } else {
+                quote! { &self.#other }
+            }
+        });
+        quote! {
+            /// Copy of the path tree with the different value of the parameter.
+            pub fn #setter(&self, #parameter: impl AsRef<std::path::Path>) -> Self {
+                Self::new_root(&self.path, #(#arguments),*)
+            }
+        }
+    });
 
     let mut foo = vec![];
     for i in 0..full_path.len() {
@@ -238,7 +333,7 @@ pub fn generate_struct(full_path: &[&Node], last_node: &Node) -> TokenStream {
         let ty_name = struct_ident(nodes.into_iter().cloned());
         let vars = node.own_parameter_vars();
         foo.push(quote! {
-            #ty_name::segment_name(#(#vars),*)
+            #ty_name::segment_name(#(&#vars),*)
         });
     }
 
@@ -246,11 +341,81 @@ pub fn generate_struct(full_path: &[&Node], last_node: &Node) -> TokenStream {
         .map(|(child, children_struct)| {
             let child_parameters = child.all_parameters_vars();
             quote! {
-                #children_struct::new_under(&path, #(#child_parameters),*)
+                #children_struct::new_under(&path, #(&#child_parameters),*)
             }
         })
         .collect_vec();
 
+    // Files cannot be created as directories, so they do not get `ensure_dir`.
+    let ensure_dir = if last_node.is_directory() {
+        quote! {
+            /// Create the directory (and its parents), if it does not exist yet.
+            pub fn ensure_dir(&self) -> std::io::Result<&Self> {
+                std::fs::create_dir_all(&self.path).map_err(|e| {
+                    let path = self.path.display();
+                    let message = format!("Failed to create directory {}: {}", path, e);
+                    std::io::Error::new(e.kind(), message)
+                })?;
+                Ok(self)
+            }
+        }
+    } else {
+        TokenStream::new()
+    };
+
+    let from_env = last_node.env.as_ref().map(|env| {
+        let name = &env.name;
+        let doc = format!("Path from the `{name}` environment variable, if it is set.");
+        let (from_env_parameters, default) = match &env.default {
+            Some(default) => (
+                child_parameter_vars.clone(),
+                quote! { Self::new_root(#default, #(&#child_parameter_vars),*) },
+            ),
+            None => (all_parameters.clone(), quote! { Self::new(#(&#all_parameters),*) }),
+        };
+        // The variable pointing to an entry of the wrong kind is most likely a mistake.
+        let kind_check = if last_node.is_directory() {
+            quote! { ret.path.exists() && !ret.path.is_dir() }
+        } else {
+            quote! { ret.path.is_dir() }
+        };
+        let kind_name = if last_node.is_directory() { "a directory" } else { "a file" };
+        quote! {
+            /// Name of the environment variable overriding this path.
+            pub const ENV_VAR: &str = #name;
+
+            #[doc = #doc]
+            ///
+            /// Otherwise, the default location is used. Fails if the variable is set to an empty
+            /// value or points to an entry of a different kind.
+            pub fn from_env(
+                #(#from_env_parameters: impl AsRef<std::path::Path>),*
+            ) -> std::io::Result<Self> {
+                let invalid = |message: String| {
+                    std::io::Error::new(std::io::ErrorKind::InvalidInput, message)
+                };
+                match std::env::var_os(Self::ENV_VAR) {
+                    Some(value) if value.is_empty() => {
+                        let name = Self::ENV_VAR;
+                        let message = format!("The environment variable {} is empty.", name);
+                        Err(invalid(message))
+                    }
+                    Some(value) => {
+                        let ret = Self::new_root(value, #(&#child_parameter_vars),*);
+                        if #kind_check {
+                            let (name, path) = (Self::ENV_VAR, ret.path.display());
+                            let kind = #kind_name;
+                            let message = format!("{} points to {}, not {}.", name, path, kind);
+                            return Err(invalid(message));
+                        }
+                        Ok(ret)
+                    }
+                    None => Ok(#default),
+                }
+            }
+        }
+    });
+
     let opt_conversions = if parameter_vars.is_empty() {
         quote! {
             impl From<std::path::PathBuf> for #ty_name {
@@ -273,6 +438,7 @@ pub fn generate_struct(full_path: &[&Node], last_node: &Node) -> TokenStream {
         #[derive(Clone, Debug, Hash, PartialEq)]
         pub struct #ty_name {
             pub path: std::path::PathBuf,
+            #(pub #child_parameter_vars: std::path::PathBuf,)*
             #(pub #children_var: #children_struct),*
         }
 
@@ -281,18 +447,37 @@ pub fn generate_struct(full_path: &[&Node], last_node: &Node) -> TokenStream {
        impl #ty_name {
            pub fn new(#(#all_parameters: impl AsRef<std::path::Path>, )*) -> Self {
                 let path = std::path::PathBuf::from_iter([#(#foo,)*]);
-                Self::new_root(path, #(#child_parameter_vars,)*)
+                Self::new_root(path, #(&#child_parameter_vars,)*)
            }
 
            pub fn new_root(path: impl Into<std::path::PathBuf> #(, #child_parameter_vars: impl AsRef<std::path::Path>)*) -> Self {
                let path = path.into();
+               #(let #child_parameter_vars = #child_parameter_vars.as_ref().to_path_buf();)*
                #(let #children_var = #children_init;)*
-               Self { path, #(#children_var),* }
+               Self { path, #(#child_parameter_vars,)* #(#children_var),* }
            }
 
            pub fn new_under(parent: impl AsRef<std::path::Path> #(, #parameter_vars: impl AsRef<std::path::Path>)*) -> Self {
-               let path = parent.as_ref().join(Self::segment_name(#(#own_parameter_vars),*));
-               Self::new_root(path, #(#child_parameter_vars),*)
+               let path = parent.as_ref().join(Self::segment_name(#(&#own_parameter_vars),*));
+               Self::new_root(path, #(&#child_parameter_vars),*)
+           }
+
+           #(#setters)*
+
+           #ensure_dir
+
+           #from_env
+
+           /// Create the directory containing this path, if it does not exist yet.
+           pub fn ensure_parent_dir(&self) -> std::io::Result<&Self> {
+               if let Some(parent) = self.path.parent() {
+                   std::fs::create_dir_all(parent).map_err(|e| {
+                       let path = parent.display();
+                       let message = format!("Failed to create directory {}: {}", path, e);
+                       std::io::Error::new(e.kind(), message)
+                   })?;
+               }
+               Ok(self)
            }
 
             pub fn segment_name(#(#own_parameter_vars: impl AsRef<std::path::Path>),*) -> String {
@@ -382,9 +567,188 @@ pub fn convert(value: &serde_yaml::Value) -> Result<Vec<Node>> {
     }
 }
 
-pub fn process(yaml_input: impl Read) -> Result<String> {
-    let yaml = serde_yaml::from_reader(yaml_input)?;
+/// Problem with the paths definition, that would make the generated code invalid.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Position of the offending key, 1-based.
+    pub line:    usize,
+    pub column:  usize,
+    pub key:     String,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: `{}`: {}", self.line, self.column, self.key, self.message)
+    }
+}
+
+/// Positions of the mapping keys, identified by the keys on the path from the document root.
+///
+/// The complex keys (with `path` and `var`) are identified by their `path`.
+#[derive(Debug, Default)]
+pub struct KeyLocator {
+    /// For each open mapping: the last key, and whether the next scalar is a key.
+    frames:      Vec<(Option<String>, bool)>,
+    /// Depth of the complex key mapping being read, if any.
+    complex:     Option<usize>,
+    positions:   HashMap<Vec<String>, Marker>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl KeyLocator {
+    pub fn new(yaml_text: &str) -> Result<Self> {
+        let mut locator = Self::default();
+        yaml_rust::parser::Parser::new(yaml_text.chars()).load(&mut locator, false)?;
+        Ok(locator)
+    }
+
+    fn key_path(&self, key: &str) -> Vec<String> {
+        let depth = self.complex.unwrap_or(self.frames.len());
+        let parents = self.frames[..depth.saturating_sub(1)].iter();
+        parents.filter_map(|(key, _)| key.clone()).chain(once(key.to_string())).collect()
+    }
+
+    fn record_key(&mut self, key: String, marker: Marker) {
+        let path = self.key_path(&key);
+        if self.positions.contains_key(&path) {
+            self.diagnostics.push(Diagnostic {
+                line:    marker.line(),
+                column:  marker.col() + 1,
+                key:     key.clone(),
+                message: "Duplicate key.".into(),
+            });
+        } else {
+            self.positions.insert(path, marker);
+        }
+        if let Some(depth) = self.complex {
+            self.frames[depth - 1].0 = Some(key);
+        } else if let Some(frame) = self.frames.last_mut() {
+            frame.0 = Some(key);
+        }
+    }
+
+    /// Position of the node, given the nodes on the path from the root.
+    pub fn locate(&self, full_path: &[&Node]) -> (usize, usize) {
+        let path = full_path.iter().map(|node| node.key.clone()).collect_vec();
+        self.positions.get(&path).map_or((0, 0), |marker| (marker.line(), marker.col() + 1))
+    }
+}
+
+impl MarkedEventReceiver for KeyLocator {
+    fn on_event(&mut self, event: Event, marker: Marker) {
+        let in_complex_key = self.complex.map_or(false, |depth| depth + 1 == self.frames.len());
+        match event {
+            Event::MappingStart(_) => {
+                if let Some((_, expecting_key)) = self.frames.last() && *expecting_key {
+                    self.complex.get_or_insert(self.frames.len());
+                }
+                self.frames.push((None, true));
+            }
+            Event::MappingEnd => {
+                self.frames.pop();
+                if self.complex == Some(self.frames.len()) {
+                    self.complex = None;
+                    if let Some(frame) = self.frames.last_mut() {
+                        frame.1 = false;
+                    }
+                } else if let Some(frame) = self.frames.last_mut() {
+                    frame.1 = !frame.1;
+                }
+            }
+            Event::Scalar(value, ..) => {
+                if let Some(frame) = self.frames.last_mut() {
+                    let is_key = frame.1;
+                    frame.1 = !frame.1;
+                    if in_complex_key {
+                        // Inside the complex key, the value of `path` names the key.
+                        if !is_key && frame.0.as_deref() == Some("path") {
+                            self.record_key(value, marker);
+                        } else if is_key {
+                            frame.0 = Some(value);
+                        }
+                    } else if is_key {
+                        self.record_key(value, marker);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Whether the text is a valid Rust identifier, that is not a keyword.
+fn is_valid_ident(text: &str) -> bool {
+    syn::parse_str::<syn::Ident>(text).is_ok()
+}
+
+/// Whether the text is a portable environment variable name, like `CARGO_TARGET_DIR`.
+fn is_valid_env_name(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().map_or(false, |c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Check that the valid Rust code can be generated for the nodes.
+pub fn validate(forest: &[Node], locator: &KeyLocator) -> Vec<Diagnostic> {
+    let mut diagnostics = locator.diagnostics.clone();
+    let mut struct_names = HashMap::<String, String>::new();
+    for root in forest {
+        root.foreach(|full_path, node| {
+            let (line, column) = locator.locate(full_path);
+            let mut report = |message: String| {
+                diagnostics.push(Diagnostic { line, column, key: node.key.clone(), message })
+            };
+            let var_name = node.var_ident_text();
+            if !is_valid_ident(&var_name) {
+                report(format!("`{var_name}` is not a valid Rust identifier or is reserved."));
+            }
+            for parameter in node.own_parameters() {
+                if !is_valid_ident(parameter) {
+                    report(format!("Parameter `{parameter}` is not a valid Rust identifier."));
+                }
+            }
+            if node.kind == Some(Kind::File) && !node.children().is_empty() {
+                report("Declared as a file, but has children.".into());
+            }
+            if let Some(env) = &node.env && !is_valid_env_name(&env.name) {
+                report(format!("`{}` is not a valid environment variable name.", env.name));
+            }
+            let struct_name = full_path.iter().map(|node| node.struct_ident_text()).join("");
+            if let Some(other) = struct_names.insert(struct_name.clone(), node.key.clone()) {
+                report(format!("Type `{struct_name}` is also generated for `{other}`."));
+            }
+            // The members of the generated struct.
+            let mut members = HashSet::from(["path".to_string()]);
+            members.extend(node.children().iter().flat_map(|child| child.parameters.clone()));
+            let mut report = |child: &Node, message: String| {
+                let child_path = full_path.iter().cloned().chain(once(child)).collect_vec();
+                let (line, column) = locator.locate(&child_path);
+                diagnostics.push(Diagnostic { line, column, key: child.key.clone(), message })
+            };
+            for child in node.children() {
+                let name = child.var_ident_text();
+                if !members.insert(name.clone()) {
+                    report(child, format!("Field `{name}` is already defined in its parent."));
+                }
+            }
+        });
+    }
+    diagnostics
+}
+
+pub fn process(mut yaml_input: impl Read) -> Result<String> {
+    let mut yaml_text = String::new();
+    yaml_input.read_to_string(&mut yaml_text)?;
+    let locator = KeyLocator::new(&yaml_text)?;
+    let yaml = serde_yaml::from_str(&yaml_text)?;
     let forest = convert(&yaml)?;
+    let diagnostics = validate(&forest, &locator);
+    ensure!(
+        diagnostics.is_empty(),
+        "Invalid paths definition:\n{}",
+        diagnostics.iter().map(|diagnostic| diagnostic.to_string()).join("\n")
+    );
     let out = generate(forest)?;
     Ok(out.to_string())
 }
@@ -398,6 +762,154 @@ mod tests {
         let yaml_contents = include_bytes!("../../build/ide-paths.yaml");
         let code = crate::paths::process(yaml_contents.as_slice())?;
         debug!("{}", code);
+        syn::parse_file(&code)?;
+        Ok(())
+    }
+
+    #[test]
+    fn diagnostics() -> Result {
+        let yaml = r"
+<root>/:
+  type/:
+  dist/:
+    bin/:
+    bin/:
+  ? path: foo-bar
+    var: path
+  foo.bar:
+  foo_bar:
+";
+        let error = crate::paths::process(yaml.as_bytes()).unwrap_err().to_string();
+        assert!(error.contains("3:3: `type/`: `type` is not a valid Rust identifier"), "{error}");
+        assert!(error.contains("6:5: `bin/`: Duplicate key."), "{error}");
+        assert!(error.contains("7:11: `foo-bar`: Field `path` is already defined"), "{error}");
+        assert!(error.contains("10:3: `foo_bar`: Type `RootFooBar` is also generated"), "{error}");
+        Ok(())
+    }
+
+    /// Methods defined in the impl block.
+    fn impl_methods(item: &syn::ItemImpl) -> Vec<&syn::ImplItemMethod> {
+        item.items
+            .iter()
+            .filter_map(|item| match item {
+                syn::ImplItem::Method(method) => Some(method),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Names of the methods generated for the given type.
+    fn methods(file: &syn::File, ty: &str) -> Vec<String> {
+        file.items
+            .iter()
+            .filter_map(|item| match item {
+                syn::Item::Impl(item) if item.trait_.is_none() => Some(item),
+                _ => None,
+            })
+            .filter(
+                |item| matches!(&*item.self_ty, syn::Type::Path(path) if path.path.is_ident(ty)),
+            )
+            .flat_map(impl_methods)
+            .map(|method| method.sig.ident.to_string())
+            .collect()
+    }
+
+    /// Names of the arguments of the method, not including the receiver.
+    fn argument_names(method: &syn::ImplItemMethod) -> Vec<String> {
+        method
+            .sig
+            .inputs
+            .iter()
+            .filter_map(|input| match input {
+                syn::FnArg::Typed(input) => match &*input.pat {
+                    syn::Pat::Ident(pat) => Some(pat.ident.to_string()),
+                    _ => None,
+                },
+                syn::FnArg::Receiver(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn kinds() -> Result {
+        let yaml = r"
+<root>/:
+  ? path: cache
+    kind: dir
+  log.txt:
+  dist/:
+";
+        let file = syn::parse_file(&crate::paths::process(yaml.as_bytes())?)?;
+        assert!(methods(&file, "RootCache").contains(&"ensure_dir".into()));
+        assert!(methods(&file, "RootDist").contains(&"ensure_dir".into()));
+        assert!(!methods(&file, "RootLogTxt").contains(&"ensure_dir".into()));
+        assert!(methods(&file, "RootLogTxt").contains(&"ensure_parent_dir".into()));
+
+        let invalid = r"
+<root>/:
+  ? path: run
+    kind: file
+  :
+    bin/:
+";
+        let error = crate::paths::process(invalid.as_bytes()).unwrap_err().to_string();
+        assert!(error.contains("Declared as a file, but has children."), "{error}");
+        Ok(())
+    }
+
+    #[test]
+    fn env_overrides() -> Result {
+        let yaml = r"
+<root>/:
+  ? path: target/
+    env: CARGO_TARGET_DIR
+? path: enso-data/
+  env: ENSO_DATA_DIRECTORY
+  default: /tmp/enso
+";
+        let file = syn::parse_file(&crate::paths::process(yaml.as_bytes())?)?;
+        assert!(methods(&file, "RootTarget").contains(&"from_env".into()));
+        assert!(methods(&file, "EnsoData").contains(&"from_env".into()));
+        assert!(!methods(&file, "Root").contains(&"from_env".into()));
+
+        let invalid = r"
+? path: target/
+  env: CARGO-TARGET-DIR
+";
+        let error = crate::paths::process(invalid.as_bytes()).unwrap_err().to_string();
+        assert!(
+            error.contains("`CARGO-TARGET-DIR` is not a valid environment variable"),
+            "{error}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn shared_parameters() -> Result {
+        let yaml = r"
+<root>/:
+  dist-<triple>/:
+    <version>/:
+  logs-<triple>/:
+";
+        let code = crate::paths::process(yaml.as_bytes())?;
+        let file = syn::parse_file(&code)?;
+        let root = file
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                syn::Item::Impl(item) => Some(impl_methods(item)),
+                _ => None,
+            })
+            .find(|methods| methods.iter().any(|method| method.sig.ident == "with_triple"))
+            .context("No setter generated.")?;
+        let constructor = root
+            .iter()
+            .find(|method| method.sig.ident == "new_root")
+            .context("No constructor generated.")?;
+        // The shared parameter is a single argument.
+        assert_eq!(argument_names(constructor), vec!["path", "triple"]);
+        assert!(root.iter().any(|method| method.sig.ident == "with_version"));
         Ok(())
     }
 }
@@ -0,0 +1,320 @@
+//! Build steps with explicit dependencies, run by an executor that knows what is up-to-date.
+//!
+//! Each [`Step`] declares the steps it depends on, and optionally the files it reads and writes.
+//! The [`Pipeline`] runs only the steps needed for the requested targets, in parallel where the
+//! dependencies allow. A step is skipped if its inputs did not change since its last successful
+//! run, its outputs exist, and none of its dependencies had to run.
+
+use crate::prelude::*;
+
+use crate::hash::Algorithm;
+use futures_util::stream::FuturesUnordered;
+use sha2::Digest;
+use std::time::Duration;
+use std::time::Instant;
+
+
+/// Action performed by a step.
+pub type Action = Arc<dyn Fn() -> BoxFuture<'static, Result> + Send + Sync>;
+
+/// Node of the build graph.
+#[derive(Clone, Derivative)]
+#[derivative(Debug)]
+pub struct Step {
+    pub name:         String,
+    pub dependencies: Vec<String>,
+    /// Files and directories read by the step. If empty, the step is never up-to-date.
+    pub inputs:       Vec<PathBuf>,
+    /// Files and directories written by the step. The step is rerun if any of them is missing.
+    pub outputs:      Vec<PathBuf>,
+    #[derivative(Debug = "ignore")]
+    pub action:       Action,
+}
+
+impl Step {
+    pub fn new<F, Fut>(name: impl Into<String>, action: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result> + Send + 'static, {
+        Self {
+            name:         name.into(),
+            dependencies: default(),
+            inputs:       default(),
+            outputs:      default(),
+            action:       Arc::new(move || action().boxed()),
+        }
+    }
+
+    pub fn depends_on(mut self, step: impl Into<String>) -> Self {
+        self.dependencies.push(step.into());
+        self
+    }
+
+    pub fn input(mut self, path: impl Into<PathBuf>) -> Self {
+        self.inputs.push(path.into());
+        self
+    }
+
+    pub fn output(mut self, path: impl Into<PathBuf>) -> Self {
+        self.outputs.push(path.into());
+        self
+    }
+
+    /// Digest of the step's inputs. Missing inputs are allowed, they are hashed as such.
+    pub async fn fingerprint(&self) -> Result<String> {
+        let mut hasher = sha2::Sha256::new();
+        hasher.update(self.name.as_bytes());
+        for input in &self.inputs {
+            let digest = if input.is_dir() {
+                crate::hash::tree(input, Algorithm::Sha256).await?
+            } else if input.exists() {
+                crate::hash::file(input, Algorithm::Sha256).await?
+            } else {
+                "missing".into()
+            };
+            hasher.update([0]);
+            hasher.update(input.as_str().as_bytes());
+            hasher.update([0]);
+            hasher.update(digest.as_bytes());
+        }
+        Ok(data_encoding::HEXLOWER.encode(&hasher.finalize()))
+    }
+}
+
+/// What happened with a step during the [run](Pipeline::run).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The step was performed, taking the given time.
+    Ran(Duration),
+    /// The step was skipped, as its outputs were up-to-date.
+    UpToDate,
+}
+
+/// Set of steps, run by [`run`](Pipeline::run).
+#[derive(Clone, Debug)]
+pub struct Pipeline {
+    steps:        BTreeMap<String, Step>,
+    /// Where the fingerprints of the successful runs are stored. If not set, all the needed steps
+    /// are always run.
+    state_dir:    Option<PathBuf>,
+    max_parallel: usize,
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        let max_parallel = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Self { steps: default(), state_dir: None, max_parallel }
+    }
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        default()
+    }
+
+    pub fn with_state_dir(mut self, state_dir: impl Into<PathBuf>) -> Self {
+        self.state_dir = Some(state_dir.into());
+        self
+    }
+
+    pub fn max_parallel(mut self, max_parallel: usize) -> Self {
+        self.max_parallel = max_parallel.max(1);
+        self
+    }
+
+    pub fn add(&mut self, step: Step) -> Result {
+        ensure!(!self.steps.contains_key(&step.name), "Duplicate step `{}`.", step.name);
+        self.steps.insert(step.name.clone(), step);
+        Ok(())
+    }
+
+    /// Steps needed to build the targets, each after all its dependencies.
+    pub fn plan(&self, targets: &[&str]) -> Result<Vec<&Step>> {
+        #[derive(Clone, Copy, PartialEq, Eq)]
+        enum Mark {
+            Visiting,
+            Done,
+        }
+
+        fn visit<'a>(
+            pipeline: &'a Pipeline,
+            name: &str,
+            marks: &mut HashMap<String, Mark>,
+            plan: &mut Vec<&'a Step>,
+            path: &mut Vec<String>,
+        ) -> Result {
+            path.push(name.to_string());
+            match marks.get(name) {
+                Some(Mark::Done) => {}
+                Some(Mark::Visiting) => bail!("Dependency cycle: {}.", path.join(" -> ")),
+                None => {
+                    let step = pipeline.steps.get(name).with_context(|| {
+                        format!("Unknown step `{name}`, required by: {}.", path.join(" -> "))
+                    })?;
+                    marks.insert(name.to_string(), Mark::Visiting);
+                    for dependency in &step.dependencies {
+                        visit(pipeline, dependency, marks, plan, path)?;
+                    }
+                    marks.insert(name.to_string(), Mark::Done);
+                    plan.push(step);
+                }
+            }
+            path.pop();
+            Ok(())
+        }
+
+        let mut marks = HashMap::new();
+        let mut plan = Vec::new();
+        for target in targets {
+            visit(self, target, &mut marks, &mut plan, &mut Vec::new())?;
+        }
+        Ok(plan)
+    }
+
+    fn fingerprint_path(&self, step: &Step) -> Option<PathBuf> {
+        self.state_dir.as_ref().map(|dir| dir.join(format!("{}.fingerprint", step.name)))
+    }
+
+    /// Run the step, unless it is up-to-date.
+    async fn execute(&self, step: &Step, dependency_ran: bool) -> Result<Outcome> {
+        let fingerprint_path = self.fingerprint_path(step).filter(|_| !step.inputs.is_empty());
+        let fingerprint = match &fingerprint_path {
+            Some(_) => Some(step.fingerprint().await?),
+            None => None,
+        };
+        if let Some(path) = &fingerprint_path && !dependency_ran {
+            let stored = crate::fs::read_to_string(path).ok();
+            let outputs_exist = step.outputs.iter().all(|output| output.exists());
+            if stored.is_some() && stored == fingerprint && outputs_exist {
+                info!("Step {} is up-to-date.", step.name);
+                return Ok(Outcome::UpToDate);
+            }
+        }
+
+        info!("Running step {}.", step.name);
+        let started = Instant::now();
+        (step.action)().await.with_context(|| format!("Step {} failed.", step.name))?;
+        let elapsed = started.elapsed();
+        info!("Step {} completed in {elapsed:.1?}.", step.name);
+        if let (Some(path), Some(fingerprint)) = (fingerprint_path, fingerprint) {
+            crate::fs::write_atomic(path, fingerprint)?;
+        }
+        Ok(Outcome::Ran(elapsed))
+    }
+
+    /// Run the steps needed for the targets.
+    ///
+    /// After a step fails, no more steps are started. The already started ones are awaited, and
+    /// the first error is returned.
+    pub async fn run(&self, targets: &[&str]) -> Result<HashMap<String, Outcome>> {
+        let mut pending = self.plan(targets)?;
+        let mut outcomes = HashMap::<String, Outcome>::new();
+        let mut running = FuturesUnordered::new();
+        let mut failure = None;
+        loop {
+            while failure.is_none() && running.len() < self.max_parallel {
+                let ready = pending.iter().position(|step| {
+                    step.dependencies.iter().all(|dependency| outcomes.contains_key(dependency))
+                });
+                let index = match ready {
+                    Some(index) => index,
+                    None => break,
+                };
+                let step = pending.remove(index);
+                let dependency_ran = step
+                    .dependencies
+                    .iter()
+                    .any(|dependency| matches!(outcomes.get(dependency), Some(Outcome::Ran(_))));
+                let span = info_span!("step", name = %step.name, progress = true);
+                running.push(
+                    async move { (step, self.execute(step, dependency_ran).await) }
+                        .instrument(span),
+                );
+            }
+            match running.next().await {
+                Some((step, Ok(outcome))) => {
+                    outcomes.insert(step.name.clone(), outcome);
+                }
+                Some((_, Err(e))) => {
+                    failure.get_or_insert(e);
+                }
+                None => break,
+            }
+        }
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(outcomes),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    /// Step counting its runs and copying its input to its output.
+    fn copying_step(name: &str, input: &Path, output: &Path, runs: &Arc<AtomicUsize>) -> Step {
+        let (source, target, runs) = (input.to_owned(), output.to_owned(), runs.clone());
+        Step::new(name, move || {
+            let (source, target, runs) = (source.clone(), target.clone(), runs.clone());
+            async move {
+                runs.fetch_add(1, Ordering::SeqCst);
+                crate::fs::copy(&source, &target)?;
+                Ok(())
+            }
+        })
+        .input(input)
+        .output(output)
+    }
+
+    #[tokio::test]
+    async fn running_steps() -> Result {
+        let dir = tempfile::tempdir()?;
+        let [source, generated, built] =
+            ["source.txt", "generated.txt", "built.txt"].map(|name| dir.path().join(name));
+        crate::fs::write(&source, "v1")?;
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        let mut pipeline = Pipeline::new().with_state_dir(dir.path().join("state"));
+        pipeline.add(copying_step("generate", &source, &generated, &runs))?;
+        pipeline.add(copying_step("build", &generated, &built, &runs).depends_on("generate"))?;
+        pipeline.add(Step::new("unrelated", || async { bail!("Should not run.") }))?;
+
+        let outcomes = pipeline.run(&["build"]).await?;
+        assert_eq!(outcomes.len(), 2);
+        assert_eq!(crate::fs::read_to_string(&built)?, "v1");
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+
+        let outcomes = pipeline.run(&["build"]).await?;
+        assert!(outcomes.values().all(|outcome| *outcome == Outcome::UpToDate));
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+
+        // The dependent step is rerun too.
+        crate::fs::write(&source, "v2")?;
+        pipeline.run(&["build"]).await?;
+        assert_eq!(crate::fs::read_to_string(&built)?, "v2");
+        assert_eq!(runs.load(Ordering::SeqCst), 4);
+
+        assert!(pipeline.run(&["unrelated"]).await.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn planning() -> Result {
+        let noop = |name: &str| Step::new(name, || async { Ok(()) });
+        let mut pipeline = Pipeline::new();
+        pipeline.add(noop("a").depends_on("b"))?;
+        pipeline.add(noop("b").depends_on("c"))?;
+        pipeline.add(noop("c"))?;
+        pipeline.add(noop("d").depends_on("e"))?;
+        pipeline.add(noop("e").depends_on("d"))?;
+        let plan = pipeline.plan(&["a"])?.into_iter().map(|step| step.name.as_str()).collect_vec();
+        assert_eq!(plan, ["c", "b", "a"]);
+        let error = pipeline.plan(&["d"]).unwrap_err().to_string();
+        assert!(error.contains("Dependency cycle: d -> e -> d."), "{error}");
+        assert!(pipeline.add(noop("a")).is_err());
+        Ok(())
+    }
+}
@@ -1,3 +1,21 @@
+//! Model of the platforms that we build for: operating systems, architectures and the Rust
+//! target triples combining them.
+//!
+//! The [`TARGET_OS`] and [`TARGET_ARCH`] constants describe the host, so the platform-dependent
+//! behavior can be expressed as a plain `match`, rather than a `cfg!` check.
+
+use crate::prelude::*;
+
+use crate::archive;
+use crate::extensions::os::OsExt;
+use crate::programs::tar::Compression;
+
+pub use crate::os::target::TARGET_ARCH;
+pub use crate::os::target::TARGET_OS;
+pub use platforms::target::Arch;
+pub use platforms::target::OS;
+
+
 pub mod win;
 
 #[cfg(target_os = "windows")]
@@ -5,3 +23,140 @@ pub const DEFAULT_SHELL: crate::programs::Cmd = crate::programs::Cmd;
 
 #[cfg(not(target_os = "windows"))]
 pub const DEFAULT_SHELL: crate::programs::Bash = crate::programs::Bash;
+
+/// Extension of the executables on the given platform, like `exe`. Empty if there is none.
+pub fn executable_extension(os: OS) -> &'static str {
+    os.exe_extension()
+}
+
+/// File name of the executable with the given base name, like `enso.exe` on Windows.
+pub fn executable_name(name: impl AsRef<str>, os: OS) -> String {
+    format!("{}{}", name.as_ref(), os.exe_suffix())
+}
+
+/// Archive format of the packages distributed for the given platform.
+///
+/// Windows users expect ZIP files, while elsewhere gzipped tarballs preserve the file permissions.
+pub fn archive_format_for_platform(os: OS) -> archive::Format {
+    match os {
+        OS::Windows => archive::Format::Zip,
+        _ => archive::Format::Tar(Some(Compression::Gzip)),
+    }
+}
+
+/// Extension of the [package archives](archive_format_for_platform) for the given platform.
+pub const fn archive_extension(os: OS) -> &'static str {
+    match os {
+        OS::Windows => "zip",
+        _ => "tar.gz",
+    }
+}
+
+/// Rust target triple, like `x86_64-apple-darwin` or `x86_64-pc-windows-msvc`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TargetTriple {
+    pub arch:   Arch,
+    pub vendor: String,
+    pub os:     OS,
+    /// The ABI part, like `msvc` or `gnu`, if present.
+    pub env:    Option<String>,
+}
+
+impl TargetTriple {
+    /// The usual triple for the given platform, as used by the Rust toolchains we install.
+    pub fn new(arch: Arch, os: OS) -> Self {
+        let (vendor, env) = match os {
+            OS::Windows => ("pc", Some("msvc")),
+            OS::MacOS | OS::iOS => ("apple", None),
+            OS::Linux => ("unknown", Some("gnu")),
+            _ => ("unknown", None),
+        };
+        Self { arch, vendor: vendor.into(), os, env: env.map(Into::into) }
+    }
+
+    /// The triple of the host platform.
+    pub fn host() -> Self {
+        Self::new(TARGET_ARCH, TARGET_OS)
+    }
+
+    /// Name of the OS as used in the triples. It differs from [`OS::as_str`] for macOS.
+    pub fn os_name(&self) -> &'static str {
+        match self.os {
+            OS::MacOS => "darwin",
+            os => os.as_str(),
+        }
+    }
+
+    pub fn executable_extension(&self) -> &'static str {
+        executable_extension(self.os)
+    }
+}
+
+impl std::str::FromStr for TargetTriple {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parts = s.split('-').collect_vec();
+        let (arch, vendor, os, env) = match parts.as_slice() {
+            [arch, vendor, os] => (arch, vendor, os, None),
+            [arch, vendor, os, env] => (arch, vendor, os, Some(env.to_string())),
+            _ => bail!("Invalid target triple `{s}`, expected `arch-vendor-os[-env]`."),
+        };
+        let arch =
+            arch.parse::<Arch>().map_err(|e| anyhow!("Invalid architecture in `{s}`: {e}"))?;
+        let os = match *os {
+            "darwin" => OS::MacOS,
+            os => os.parse::<OS>().map_err(|e| anyhow!("Invalid OS in `{s}`: {e}"))?,
+        };
+        Ok(Self { arch, vendor: vendor.to_string(), os, env })
+    }
+}
+
+impl Display for TargetTriple {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}-{}", self.arch, self.vendor, self.os_name())?;
+        if let Some(env) = &self.env {
+            write!(f, "-{env}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn triples() -> Result {
+        for text in [
+            "x86_64-apple-darwin",
+            "aarch64-apple-darwin",
+            "x86_64-pc-windows-msvc",
+            "x86_64-unknown-linux-gnu",
+        ] {
+            let triple = text.parse::<TargetTriple>()?;
+            assert_eq!(triple.to_string(), text);
+            assert_eq!(TargetTriple::new(triple.arch, triple.os), triple);
+        }
+        let windows = "x86_64-pc-windows-msvc".parse::<TargetTriple>()?;
+        assert_eq!(windows.os, OS::Windows);
+        assert_eq!(windows.executable_extension(), "exe");
+        assert!("x86_64".parse::<TargetTriple>().is_err());
+        assert!("x86_64-pc-beos".parse::<TargetTriple>().is_err());
+        assert_eq!(TargetTriple::host().os, TARGET_OS);
+        Ok(())
+    }
+
+    #[test]
+    fn platform_conventions() {
+        assert_eq!(executable_name("enso", OS::Windows), "enso.exe");
+        assert_eq!(executable_name("enso", OS::Linux), "enso");
+        assert_eq!(archive_format_for_platform(OS::Windows), archive::Format::Zip);
+        assert_eq!(archive_extension(OS::MacOS), "tar.gz");
+        let archive_name = format!("enso.{}", archive_extension(TARGET_OS));
+        assert_eq!(
+            archive::Format::from_filename(archive_name).ok(),
+            Some(archive_format_for_platform(TARGET_OS))
+        );
+    }
+}
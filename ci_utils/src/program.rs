@@ -1,15 +1,25 @@
 use crate::prelude::*;
 use semver::VersionReq;
 
+pub mod audit;
 pub mod command;
+pub mod error;
+pub mod group;
+pub mod installer;
 pub mod location;
+pub mod process_tree;
 pub mod resolver;
+pub mod retry;
 pub mod shell;
 pub mod version;
+pub mod watchdog;
 pub mod with_cwd;
 
 pub use command::Command;
-use location::Location;
+pub use error::ProgramError;
+pub use group::CommandGroup;
+pub use installer::Installer;
+pub use location::Location;
 
 
 use crate::program::command::MyCommand;
@@ -38,10 +48,10 @@ pub trait Program: Sized + 'static {
     /// be skipped.
     fn executable_name(&self) -> &str;
 
-    /// If program can be found under more than one name, additional names are provided.
+    /// Other names the program can be found under, e.g. on other platforms or in other versions.
     ///
-    /// The primary name is provided by ['executable_name'].
-    fn executable_name_fallback() -> Vec<&'static str> {
+    /// They are tried in order, after the primary name provided by [`Program::executable_name`].
+    fn alternative_names(&self) -> Vec<&str> {
         vec![]
     }
 
@@ -57,13 +67,58 @@ pub trait Program: Sized + 'static {
     /// Locate the program executable.
     ///
     /// The lookup locations are program-defined, they typically include Path environment variable
-    /// and program-specific default locations.
+    /// and program-specific default locations. The result is cached for the process lifetime.
+    ///
+    /// The location can be overridden with [`Location::set_global`] or with the
+    /// `ENSO_PROGRAM_<NAME>` environment variable, see [`location::override_variable_name`].
     fn lookup(&self) -> anyhow::Result<Location<Self>> {
+        if let Some(location) = Location::overridden(self) {
+            return Ok(location);
+        }
         Resolver::<Self>::new(self.executable_names(), self.default_locations())?
-            .lookup()
+            .lookup_cached()
             .map(Location::new)
     }
 
+    /// Way of installing the program if it is missing, see [`Program::lookup_or_install`].
+    fn installer(&self) -> Option<Box<dyn Installer>> {
+        None
+    }
+
+    /// Locate the program, installing it first if it is missing and has an
+    /// [installer](Program::installer).
+    ///
+    /// If the program is installed outside `PATH`, its location is
+    /// [set globally](Location::set_global), so the subsequent lookups find it.
+    async fn lookup_or_install(&self) -> Result<Location<Self>> {
+        let error = match self.lookup() {
+            Ok(location) => return Ok(location),
+            Err(error) => error,
+        };
+        let installer = match self.installer() {
+            Some(installer) => installer,
+            None => return Err(error),
+        };
+        info!("{} not found, installing it with {installer:?}.", self.pretty_name());
+        let directory = installer
+            .install()
+            .await
+            .with_context(|| format!("Failed to install {}.", self.pretty_name()))?;
+        resolver::clear_cache();
+        match directory {
+            Some(directory) => {
+                let path = Resolver::<Self>::new(self.executable_names(), vec![directory])?
+                    .lookup()
+                    .with_context(|| {
+                        format!("Cannot find {} after installing it.", self.pretty_name())
+                    })?;
+                Location::<Self>::set_global(&path);
+                Ok(Location::new(path))
+            }
+            None => self.lookup(),
+        }
+    }
+
     fn require_present(&self) -> BoxFuture<'static, Result<String>> {
         let executable_name = self.executable_name().to_owned();
         let get_version_string = self.version_string();
@@ -100,6 +155,47 @@ pub trait Program: Sized + 'static {
         Ok(())
     }
 
+    /// Version of the program, probed only once per process.
+    ///
+    /// The cache is keyed by the executable path, so the result remains valid only as long as the
+    /// executable is not replaced.
+    async fn cached_version(&self) -> Result<Version> {
+        let executable = self.lookup()?.executable_path;
+        if let Some(version) = version::cached(&executable) {
+            return Ok(version);
+        }
+        let version = self.version().await?;
+        debug!("Found {} {version} at {}.", self.pretty_name(), executable.display());
+        version::remember(executable, version.clone());
+        Ok(version)
+    }
+
+    /// Fail unless the program is present in a version satisfying the requirement.
+    ///
+    /// Returns the found version. The version probe is run only once per process.
+    async fn require_version(&self, requirement: &VersionReq) -> Result<Version> {
+        let found = self.cached_version().await?;
+        version::check_requirement(self.pretty_name(), &found, requirement, None)?;
+        Ok(found)
+    }
+
+    /// Like [`Program::require_version`], but the error also says what needs the version, e.g.
+    /// `--sort` gives "Found tar 1.26.0, need >=1.30 for --sort.".
+    async fn require_version_for(
+        &self,
+        requirement: &VersionReq,
+        purpose: &str,
+    ) -> Result<Version> {
+        let found = self.cached_version().await?;
+        version::check_requirement(self.pretty_name(), &found, requirement, Some(purpose))?;
+        Ok(found)
+    }
+
+    /// Whether the program is present in a version satisfying the requirement.
+    async fn supports(&self, requirement: &VersionReq) -> bool {
+        self.cached_version().await.map_or(false, |found| requirement.matches(&found))
+    }
+
     fn cmd(&self) -> Result<Self::Command> {
         let program_path = self.lookup()?;
         let mut command = Self::Command::new_program(program_path);
@@ -118,8 +214,18 @@ pub trait Program: Sized + 'static {
         None
     }
 
+    /// Interpret the exit status of the program.
+    ///
+    /// By default only the zero exit code means success. Programs that also use other codes for
+    /// success, or that give the failure codes a specific meaning, should override this.
+    fn interpret_exit_code(
+        status: std::process::ExitStatus,
+    ) -> std::result::Result<(), ProgramError> {
+        ProgramError::check(status)
+    }
+
     fn handle_exit_status(status: std::process::ExitStatus) -> Result {
-        status.exit_ok().anyhow_err()
+        Self::interpret_exit_code(status).anyhow_err()
     }
 
     /// Command that prints to stdout the version of given program.
@@ -133,11 +239,7 @@ pub trait Program: Sized + 'static {
 
     fn version_string(&self) -> BoxFuture<'static, Result<String>> {
         let command = self.version_command();
-        async move {
-            let output = command?.borrow_mut().run_stdout().await?;
-            Ok(output.trim().to_string())
-        }
-        .boxed()
+        async move { command?.borrow_mut().run_stdout_string().await }.boxed()
     }
 
     // TODO if such need appears, likely Version should be made an associated type
@@ -149,16 +251,17 @@ pub trait Program: Sized + 'static {
     /// Retrieve semver-compatible version from the string in format provided by the
     /// `version_string`.
     ///
-    /// Some programs do not follow semver for versioning, for them this method is unspecified.
+    /// Some programs do not follow semver for versioning. By default, versions with only major and
+    /// minor components are accepted, with the patch component assumed to be zero.
     fn parse_version(&self, version_text: &str) -> Result<Version> {
-        version::find_in_text(version_text)
+        version::find_in_text_lenient(version_text)
     }
 }
 
 pub trait ProgramExt: Program {
     fn executable_names(&self) -> Vec<&str> {
         let mut ret = vec![self.executable_name()];
-        ret.extend(Self::executable_name_fallback());
+        ret.extend(self.alternative_names());
         ret
     }
 
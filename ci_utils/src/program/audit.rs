@@ -0,0 +1,468 @@
+//! Log of the external commands spawned by this process.
+//!
+//! The log can be exported as a standalone shell script, so a failing step can be replayed (and
+//! tinkered with) outside the build script, e.g. when debugging toolchain issues.
+//!
+//! The values that look like credentials (see [`is_sensitive_name`]) are [redacted](REDACTED) in
+//! the records, so the replay script needs them to be filled in by hand.
+//!
+//! In the [dry-run mode](set_dry_run) the commands run through [`Command::run_ok`] are only
+//! logged, not executed. This allows inspecting what a step will do before it mutates anything.
+
+use crate::prelude::*;
+
+use std::lazy::SyncLazy;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+static LOG: SyncLazy<Mutex<Vec<Record>>> = SyncLazy::new(default);
+
+static SECRETS: SyncLazy<Mutex<Vec<String>>> = SyncLazy::new(default);
+
+/// Placeholder replacing the recorded secret values.
+pub const REDACTED: &str = "<redacted>";
+
+/// Hide the value in all the commands recorded and described from now on.
+///
+/// This is needed for the secrets that the programs accept only as plain arguments, like the
+/// `signtool` certificate password. The value is also
+/// [masked](crate::actions::workflow::mask_value) in the GitHub Actions log.
+pub fn register_secret(value: impl Into<String>) {
+    let value = value.into();
+    if value.is_empty() {
+        return;
+    }
+    crate::actions::workflow::mask_value(&value);
+    let mut secrets = SECRETS.lock().unwrap();
+    if !secrets.contains(&value) {
+        secrets.push(value);
+    }
+}
+
+/// Replace all the [registered secrets](register_secret) in the text with [`REDACTED`].
+pub fn redact_registered(text: String) -> String {
+    let secrets = SECRETS.lock().unwrap();
+    secrets.iter().fold(text, |text, secret| {
+        if text.contains(secret.as_str()) {
+            text.replace(secret.as_str(), REDACTED)
+        } else {
+            text
+        }
+    })
+}
+
+/// Whether the environment variable or command line option with this name likely holds a secret,
+/// like `GITHUB_TOKEN`, `AWS_SECRET_ACCESS_KEY` or `--password`.
+pub fn is_sensitive_name(name: &str) -> bool {
+    let name = name.to_uppercase().replace('-', "_");
+    ["TOKEN", "SECRET", "PASSWORD", "PASSWD", "KEY", "CREDENTIAL"]
+        .iter()
+        .any(|word| name.contains(word))
+}
+
+/// Replace the values of the options named like secrets with [`REDACTED`].
+///
+/// Both `--name value` and `--name=value` forms are recognized, with any number of leading `-` or
+/// `/` characters.
+pub fn redact_args(args: impl IntoIterator<Item = String>) -> Vec<String> {
+    let is_option_name = |name: &str| {
+        !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    };
+    let mut ret = Vec::new();
+    let mut redact_next = false;
+    for arg in args {
+        if redact_next {
+            redact_next = false;
+            ret.push(REDACTED.into());
+            continue;
+        }
+        let option = arg.trim_start_matches(|c| c == '-' || c == '/');
+        if option.len() < arg.len() {
+            let prefix = &arg[..arg.len() - option.len()];
+            match option.split_once('=') {
+                Some((name, _)) if is_option_name(name) && is_sensitive_name(name) => {
+                    ret.push(format!("{prefix}{name}={REDACTED}"));
+                    continue;
+                }
+                None if is_option_name(option) && is_sensitive_name(option) => redact_next = true,
+                _ => {}
+            }
+        }
+        ret.push(arg);
+    }
+    ret
+}
+
+/// Description of a spawned command, sufficient to run it again.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Record {
+    pub program:     String,
+    pub args:        Vec<String>,
+    /// Working directory, if different from the one of this process.
+    pub current_dir: Option<PathBuf>,
+    /// Environment changes relative to this process. `None` value means variable removal.
+    pub env:         Vec<(String, Option<String>)>,
+    /// Whether the command was only recorded in the dry-run mode, rather than executed.
+    pub skipped:     bool,
+}
+
+impl Record {
+    /// Describe the command, with the secrets [redacted](REDACTED). Both the
+    /// [registered](register_secret) values and the ones [named](is_sensitive_name) like secrets
+    /// are hidden.
+    pub fn new(command: &std::process::Command) -> Self {
+        let lossy = |s: &OsStr| redact_registered(s.to_string_lossy().to_string());
+        let redact_value = |name: &str, value: String| {
+            if is_sensitive_name(name) {
+                REDACTED.into()
+            } else {
+                value
+            }
+        };
+        Self {
+            program:     lossy(command.get_program()),
+            args:        redact_args(command.get_args().map(lossy)),
+            current_dir: command.get_current_dir().map(Into::into),
+            env:         command
+                .get_envs()
+                .map(|(name, value)| {
+                    let name = lossy(name);
+                    let value = value.map(|value| redact_value(&name, lossy(value)));
+                    (name, value)
+                })
+                .collect(),
+            skipped:     false,
+        }
+    }
+}
+
+/// Enable or disable recording of the spawned commands. Recording is disabled by default.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::SeqCst);
+}
+
+/// Add the command to the log, if recording is enabled.
+pub fn record(command: &std::process::Command) {
+    if !ENABLED.load(Ordering::SeqCst) {
+        return;
+    }
+    LOG.lock().unwrap().push(Record::new(command));
+}
+
+/// Enable or disable the dry-run mode. Disabled by default.
+///
+/// In the dry-run mode, [`Command::run_ok`] does not spawn the process, but records it and reports
+/// success. Commands run for their output (like version queries) are still executed, as the
+/// subsequent logic depends on them. Note that steps relying on the effects of skipped commands
+/// (e.g. on the files they create) may still fail.
+pub fn set_dry_run(dry_run: bool) {
+    DRY_RUN.store(dry_run, Ordering::SeqCst);
+}
+
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::SeqCst)
+}
+
+/// Record the command that is not executed because of the dry-run mode.
+///
+/// Such commands are recorded even if the recording is disabled.
+pub fn record_skipped(command: &std::process::Command) {
+    let record = Record { skipped: true, ..Record::new(command) };
+    info!("Dry run, not executing: {}", ScriptKind::current().render_line(&record));
+    LOG.lock().unwrap().push(record);
+}
+
+/// All the commands recorded so far, in the order they were spawned.
+pub fn records() -> Vec<Record> {
+    LOG.lock().unwrap().clone()
+}
+
+/// Shell for which the replay script is generated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScriptKind {
+    Bash,
+    PowerShell,
+}
+
+impl ScriptKind {
+    /// The native shell of the current platform.
+    pub fn current() -> Self {
+        match TARGET_OS {
+            OS::Windows => ScriptKind::PowerShell,
+            _ => ScriptKind::Bash,
+        }
+    }
+
+    /// Deduce the script kind from the file extension, defaulting to Bash.
+    pub fn for_path(path: impl AsRef<Path>) -> Self {
+        match path.as_ref().extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("ps1") => ScriptKind::PowerShell,
+            _ => ScriptKind::Bash,
+        }
+    }
+
+    /// Quote the string, so it is passed verbatim as a single argument.
+    pub fn quote(self, text: &str) -> String {
+        match self {
+            ScriptKind::Bash => format!("'{}'", text.replace('\'', r#"'\''"#)),
+            ScriptKind::PowerShell => format!("'{}'", text.replace('\'', "''")),
+        }
+    }
+
+    /// Generate the script running the given commands.
+    ///
+    /// Each command is run with its own working directory and environment changes, which do not
+    /// affect the subsequent commands. The script stops on the first failing command.
+    pub fn render(self, records: &[Record]) -> String {
+        let mut ret = String::new();
+        match self {
+            ScriptKind::Bash => ret.push_str("#!/usr/bin/env bash\nset -euo pipefail\n"),
+            ScriptKind::PowerShell => ret.push_str("$ErrorActionPreference = 'Stop'\n"),
+        }
+        for (index, record) in records.iter().enumerate() {
+            ret.push_str(&format!("\n# Command {}\n", index + 1));
+            ret.push_str(&self.render_record(record));
+        }
+        ret
+    }
+
+    /// Quote the string only if it contains characters that the shell would interpret.
+    pub fn quote_if_needed(self, text: &str) -> Cow<str> {
+        let is_safe = |c: char| match self {
+            ScriptKind::Bash => c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c),
+            ScriptKind::PowerShell => c.is_ascii_alphanumeric() || "-_./:\\".contains(c),
+        };
+        if !text.is_empty() && text.chars().all(is_safe) {
+            Cow::Borrowed(text)
+        } else {
+            Cow::Owned(self.quote(text))
+        }
+    }
+
+    /// Render the command as a single line that can be pasted into the shell.
+    ///
+    /// The working directory and environment changes are included, though (unlike in the
+    /// [script](ScriptKind::render)) they are not reverted afterwards.
+    pub fn render_line(self, record: &Record) -> String {
+        let mut parts = vec![];
+        let words = std::iter::once(&record.program).chain(&record.args);
+        match self {
+            ScriptKind::Bash => {
+                if let Some(dir) = &record.current_dir {
+                    parts.push(format!("cd {} &&", self.quote_if_needed(&dir.to_string_lossy())));
+                }
+                if !record.env.is_empty() {
+                    parts.push("env".into());
+                }
+                // `env` stops parsing the options at the first assignment.
+                let (set, removed): (Vec<_>, Vec<_>) =
+                    record.env.iter().partition(|(_, value)| value.is_some());
+                parts.extend(removed.iter().map(|(name, _)| format!("-u {name}")));
+                for (name, value) in set {
+                    let value = value.as_deref().unwrap_or_default();
+                    parts.push(format!("{name}={}", self.quote_if_needed(value)));
+                }
+                parts.extend(words.map(|word| self.quote_if_needed(word).into_owned()));
+            }
+            ScriptKind::PowerShell => {
+                if let Some(dir) = &record.current_dir {
+                    parts.push(format!("Set-Location {};", self.quote(&dir.to_string_lossy())));
+                }
+                for (name, value) in &record.env {
+                    match value {
+                        Some(value) => parts.push(format!("$env:{name} = {};", self.quote(value))),
+                        None => parts.push(format!("Remove-Item Env:{name};")),
+                    }
+                }
+                parts.push("&".into());
+                parts.extend(words.map(|word| self.quote_if_needed(word).into_owned()));
+            }
+        }
+        parts.join(" ")
+    }
+
+    /// Quoted program invocation, without the working directory and environment changes.
+    pub fn render_invocation(self, record: &Record) -> String {
+        std::iter::once(&record.program).chain(&record.args).map(|arg| self.quote(arg)).join(" ")
+    }
+
+    fn render_record(self, record: &Record) -> String {
+        let invocation = self.render_invocation(record);
+        let mut lines = vec![];
+        match self {
+            ScriptKind::Bash => {
+                if let Some(dir) = &record.current_dir {
+                    lines.push(format!("cd {}", self.quote(&dir.to_string_lossy())));
+                }
+                for (name, value) in &record.env {
+                    match value {
+                        Some(value) => lines.push(format!("export {name}={}", self.quote(value))),
+                        None => lines.push(format!("unset {name}")),
+                    }
+                }
+                lines.push(invocation);
+                format!("(\n{})\n", lines.iter().map(|line| format!("    {line}\n")).join(""))
+            }
+            ScriptKind::PowerShell => {
+                // Environment changes are process-wide, so they need to be reverted by hand.
+                lines.push("$previousEnv = @{}".into());
+                for (name, value) in &record.env {
+                    let name = self.quote(name);
+                    lines.push(format!(
+                        "$previousEnv[{name}] = [Environment]::GetEnvironmentVariable({name})"
+                    ));
+                    let value = value.as_ref().map_or("$null".into(), |value| self.quote(value));
+                    lines.push(format!("[Environment]::SetEnvironmentVariable({name}, {value})"));
+                }
+                let dir = record
+                    .current_dir
+                    .as_ref()
+                    .map_or(".".into(), |dir| self.quote(&dir.to_string_lossy()));
+                lines.push(format!("Push-Location {dir}"));
+                lines.push("try {".into());
+                lines.push(format!("    & {invocation}"));
+                lines.push(
+                    "    if ($LASTEXITCODE -ne 0) { throw \"Command failed with exit code \
+                     $LASTEXITCODE.\" }"
+                        .into(),
+                );
+                lines.push("} finally {".into());
+                lines.push("    Pop-Location".into());
+                lines.push(
+                    "    foreach ($name in $previousEnv.Keys) { \
+                     [Environment]::SetEnvironmentVariable($name, $previousEnv[$name]) }"
+                        .into(),
+                );
+                lines.push("}".into());
+                format!("& {{\n{}}}\n", lines.iter().map(|line| format!("    {line}\n")).join(""))
+            }
+        }
+    }
+}
+
+/// Write the script replaying all the commands recorded so far.
+#[context("Failed to write the replay script to {}.", path.as_ref().display())]
+pub fn write_script(path: impl AsRef<Path>, kind: ScriptKind) -> Result {
+    let records = records();
+    crate::fs::write(&path, kind.render(&records))?;
+    info!("Wrote script replaying {} commands to {}.", records.len(), path.as_ref().display());
+    Ok(())
+}
+
+/// Write the commands recorded so far as a JSON array.
+#[context("Failed to write the command log to {}.", path.as_ref().display())]
+pub fn write_json(path: impl AsRef<Path>) -> Result {
+    crate::fs::write(&path, serde_json::to_string_pretty(&records())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_command() {
+        let mut command = std::process::Command::new("cargo");
+        command.args(["build", "--release"]).current_dir("/repo").env("RUSTFLAGS", "-Dwarnings");
+        command.env_remove("CARGO_TARGET_DIR");
+        let record = Record::new(&command);
+        assert_eq!(record.program, "cargo");
+        assert_eq!(record.args, vec!["build", "--release"]);
+        assert_eq!(record.current_dir, Some(PathBuf::from("/repo")));
+        assert!(record.env.contains(&("RUSTFLAGS".into(), Some("-Dwarnings".into()))));
+        assert!(record.env.contains(&("CARGO_TARGET_DIR".into(), None)));
+        assert!(!record.skipped);
+    }
+
+    #[test]
+    fn redacting_secrets() {
+        let mut command = std::process::Command::new("notarytool");
+        command.args(["submit", "--password", "hunter2", "--api-key=abc", "/keys/cert.p12"]);
+        command.env("GITHUB_TOKEN", "ghp_123").env("AWS_REGION", "eu-west-1");
+        command.env("RUSTFLAGS", "-Dwarnings").env_remove("AWS_SESSION_TOKEN");
+        let record = Record::new(&command);
+        assert_eq!(record.args, [
+            "submit",
+            "--password",
+            REDACTED,
+            "--api-key=<redacted>",
+            "/keys/cert.p12"
+        ]);
+        assert!(record.env.contains(&("GITHUB_TOKEN".into(), Some(REDACTED.into()))));
+        assert!(record.env.contains(&("AWS_REGION".into(), Some("eu-west-1".into()))));
+        assert!(record.env.contains(&("RUSTFLAGS".into(), Some("-Dwarnings".into()))));
+        assert!(record.env.contains(&("AWS_SESSION_TOKEN".into(), None)));
+        assert!(!is_sensitive_name("RUSTFLAGS"));
+        assert!(is_sensitive_name("AWS_SECRET_ACCESS_KEY"));
+        assert!(is_sensitive_name("AWS_ACCESS_KEY_ID"));
+
+        register_secret("pfx-password-for-test");
+        let mut command = std::process::Command::new("signtool");
+        command.args(["sign", "/p", "pfx-password-for-test"]);
+        assert_eq!(Record::new(&command).args, ["sign", "/p", REDACTED]);
+    }
+
+    #[test]
+    fn json_record() -> Result {
+        let mut command = std::process::Command::new("docker");
+        command.args(["push", "enso:latest"]);
+        let record = Record { skipped: true, ..Record::new(&command) };
+        let json = serde_json::to_value(&record)?;
+        assert_eq!(json["program"], "docker");
+        assert_eq!(json["args"][1], "enso:latest");
+        assert_eq!(json["skipped"], true);
+        assert_eq!(ScriptKind::Bash.render_invocation(&record), "'docker' 'push' 'enso:latest'");
+        Ok(())
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(ScriptKind::Bash.quote("it's"), r#"'it'\''s'"#);
+        assert_eq!(ScriptKind::PowerShell.quote("it's"), "'it''s'");
+        assert_eq!(ScriptKind::for_path("replay.PS1"), ScriptKind::PowerShell);
+        assert_eq!(ScriptKind::for_path("replay.sh"), ScriptKind::Bash);
+    }
+
+    #[test]
+    fn render_line() {
+        let record = Record {
+            program:     "cargo".into(),
+            args:        vec!["build".into(), "--features".into(), "a b".into()],
+            current_dir: Some("/repo".into()),
+            env:         vec![
+                ("RUSTFLAGS".into(), Some("-D warnings".into())),
+                ("CARGO_TARGET_DIR".into(), None),
+            ],
+            skipped:     false,
+        };
+        assert_eq!(
+            ScriptKind::Bash.render_line(&record),
+            "cd /repo && env -u CARGO_TARGET_DIR RUSTFLAGS='-D warnings' cargo build --features 'a b'"
+        );
+        assert_eq!(
+            ScriptKind::PowerShell.render_line(&record),
+            "Set-Location '/repo'; $env:RUSTFLAGS = '-D warnings'; Remove-Item Env:CARGO_TARGET_DIR; \
+             & cargo build --features 'a b'"
+        );
+    }
+
+    #[test]
+    fn render_bash() {
+        let record = Record {
+            program:     "sbt".into(),
+            args:        vec!["compile".into()],
+            current_dir: Some("/repo".into()),
+            env:         vec![("JAVA_OPTS".into(), Some("-Xss16M".into()))],
+            skipped:     false,
+        };
+        let script = ScriptKind::Bash.render(&[record]);
+        assert!(script.starts_with("#!/usr/bin/env bash\n"));
+        assert!(script
+            .contains("    cd '/repo'\n    export JAVA_OPTS='-Xss16M'\n    'sbt' 'compile'\n"));
+    }
+}
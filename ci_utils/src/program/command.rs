@@ -1,19 +1,30 @@
 use crate::prelude::*;
 use anyhow::Context;
 
+use crate::actions::workflow::LogGroup;
 use crate::env::new::TypedVariable;
+use crate::program::process_tree::ProcessTree;
+use crate::program::retry::AttemptFailure;
+use crate::program::retry::RetryPolicy;
+use crate::program::watchdog::Activity;
+use crate::program::watchdog::Watchdog;
 use std::borrow::BorrowMut;
+use std::collections::VecDeque;
 use std::fmt::Debug;
 use std::fmt::Formatter;
 use std::process::ExitStatus;
 use std::process::Output;
 use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
 use tokio::io::AsyncBufReadExt;
 use tokio::io::AsyncRead;
 use tokio::io::BufReader;
 use tokio::process::Child;
 use tokio::task::JoinHandle;
 use tracing::field;
+use tracing::Level;
 
 #[macro_export]
 macro_rules! new_command_type {
@@ -239,9 +250,122 @@ pub trait CommandOption {
     }
 }
 
+/// The most recent lines of the process output, kept for the error reports.
+#[derive(Clone, Debug, Default)]
+pub struct OutputTail {
+    lines: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl OutputTail {
+    /// How many lines are kept.
+    pub const CAPACITY: usize = 50;
+
+    pub fn push(&self, line: impl Into<String>) {
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == Self::CAPACITY {
+            lines.pop_front();
+        }
+        lines.push_back(line.into());
+    }
+
+    pub fn contents(&self) -> String {
+        self.lines.lock().unwrap().iter().join("\n")
+    }
+}
+
+/// Callback invoked with each output line of a process, see [`Command::on_output_line`].
+pub type LineHook = Arc<dyn Fn(&str) + Send + Sync>;
+
 pub struct Command {
     pub inner:          tokio::process::Command,
     pub status_checker: Arc<dyn Fn(ExitStatus) -> Result + Send + Sync>,
+    /// If set, the process will be killed after being inactive for too long.
+    pub watchdog:       Option<Watchdog>,
+    /// If set, the process will be killed after running for too long.
+    pub timeout:        Option<Duration>,
+    /// Whether the output captured by [`Command::output_ok`] is also logged line by line.
+    pub log_captured:   bool,
+    /// How the output of processes run with [`Command::run_ok`] is logged.
+    pub log_streaming:  LogStreaming,
+    /// If set, the failed process is run again, as long as the policy allows.
+    pub retry:          Option<RetryPolicy>,
+    /// Tag of the logged output lines. If not set, the program name is used.
+    pub log_prefix:     Option<String>,
+    /// Whether the descendants of the process are killed together with it, see
+    /// [`Command::kill_process_tree`].
+    pub kill_tree:      bool,
+    /// What to do if the working directory does not exist when the process is spawned.
+    pub missing_cwd:    MissingCurrentDir,
+    /// If set, each run happens in a new temporary working directory.
+    pub fresh_tempdir:  Option<FreshTempDir>,
+    /// Called with the output lines of processes run with [`Command::run_ok`].
+    pub line_hooks:     Vec<LineHook>,
+}
+
+/// Future of a single attempt to run a process, see [`RetryPolicy`].
+type Attempt<T> = BoxFuture<'static, std::result::Result<T, AttemptFailure>>;
+
+/// Settings of the temporary working directory, see [`Command::in_fresh_tempdir`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FreshTempDir {
+    /// Keep the directory if the command fails, so its contents can be inspected.
+    pub retain_on_failure: bool,
+}
+
+/// What to do if the working directory of a command does not exist when it is spawned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MissingCurrentDir {
+    /// Fail with an error naming the directory and the program.
+    Fail,
+    /// Create the directory, including the missing parents.
+    Create,
+}
+
+impl Default for MissingCurrentDir {
+    fn default() -> Self {
+        MissingCurrentDir::Fail
+    }
+}
+
+/// How the output lines of a process are forwarded to the log.
+///
+/// Each line is tagged with the program name and logged at the level of its stream.
+#[derive(Clone, Copy, Debug)]
+pub struct LogStreaming {
+    pub stdout_level: Level,
+    pub stderr_level: Level,
+    /// Whether to put the whole output into a collapsible group when running on GitHub Actions.
+    /// The group is titled with the command line.
+    pub group:        bool,
+}
+
+impl Default for LogStreaming {
+    fn default() -> Self {
+        Self { stdout_level: Level::INFO, stderr_level: Level::INFO, group: false }
+    }
+}
+
+impl LogStreaming {
+    /// Settings for the verbose, long-running processes, whose output should be collapsed.
+    pub fn grouped() -> Self {
+        Self { group: true, ..default() }
+    }
+}
+
+/// Output of a finished process, as returned by [`Command::output_ok`].
+#[derive(Clone, Debug)]
+pub struct CapturedOutput {
+    pub stdout:   Vec<u8>,
+    pub stderr:   Vec<u8>,
+    pub status:   ExitStatus,
+    /// Time since the process was spawned until it exited.
+    pub duration: Duration,
+}
+
+impl From<CapturedOutput> for Output {
+    fn from(output: CapturedOutput) -> Self {
+        Output { status: output.status, stdout: output.stdout, stderr: output.stderr }
+    }
 }
 
 impl Borrow<tokio::process::Command> for Command {
@@ -263,33 +387,297 @@ impl Debug for Command {
 }
 
 impl Command {
+    /// Create a new command.
+    ///
+    /// The spawned process is killed when its handle is dropped, e.g. when the future waiting for
+    /// it is cancelled. Use [`IsCommandWrapper::kill_on_drop`] to opt out.
     pub fn new<S: AsRef<OsStr>>(program: S) -> Command {
         let inner = tokio::process::Command::new(program);
         let status_checker = Arc::new(|status: ExitStatus| status.exit_ok().anyhow_err());
-        Self { inner, status_checker }
+        Self::with_status_checker(inner, status_checker)
     }
 
     pub fn new_over<P: Program + 'static>(inner: tokio::process::Command) -> Self {
-        Command { inner, status_checker: Arc::new(P::handle_exit_status) }
+        Self::with_status_checker(inner, Arc::new(P::handle_exit_status))
+    }
+
+    fn with_status_checker(
+        mut inner: tokio::process::Command,
+        status_checker: Arc<dyn Fn(ExitStatus) -> Result + Send + Sync>,
+    ) -> Self {
+        inner.kill_on_drop(true);
+        Command {
+            inner,
+            status_checker,
+            watchdog: None,
+            timeout: None,
+            log_captured: false,
+            log_streaming: default(),
+            retry: None,
+            log_prefix: None,
+            kill_tree: TARGET_OS == OS::Windows || crate::actions::workflow::is_in_env(),
+            missing_cwd: default(),
+            fresh_tempdir: None,
+            line_hooks: default(),
+        }
+    }
+
+    /// Kill the process if it does not finish within the given time.
+    ///
+    /// Applies to processes run with [`Command::run_ok`], [`Command::output_ok`] and
+    /// [`Command::run_stdout`]. The error reports the elapsed time and the recent output.
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Kill the process if it produces no output for the watchdog's inactivity limit.
+    ///
+    /// Applies only to processes run with [`Command::run_ok`].
+    pub fn set_watchdog(&mut self, watchdog: Watchdog) -> &mut Self {
+        self.watchdog = Some(watchdog);
+        self
+    }
+
+    /// Log the output captured by [`Command::output_ok`] as it comes, rather than only returning
+    /// it when the process exits.
+    pub fn log_captured_output(&mut self, enabled: bool) -> &mut Self {
+        self.log_captured = enabled;
+        self
+    }
+
+    /// Run the process again if it fails, as long as the policy allows.
+    ///
+    /// Applies to processes run with [`Command::run_ok`], [`Command::output_ok`] and the methods
+    /// based on them. The retries run the same program with the same arguments, environment and
+    /// working directory. Other settings, like the standard input, are not preserved.
+    pub fn retry(&mut self, policy: RetryPolicy) -> &mut Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Kill all the descendants of the process when it is killed on timeout, by the watchdog or
+    /// because the future running it was cancelled. The processes left behind by a process that
+    /// exited on its own are not affected.
+    ///
+    /// Applies to processes run with [`Command::run_ok`], [`Command::output_ok`] and the methods
+    /// based on them. Enabled by default on Windows and on CI. On Unix the process is put in its
+    /// own process group, so it is not interrupted by Ctrl+C in the terminal; that's why it is
+    /// disabled by default for local runs.
+    pub fn kill_process_tree(&mut self, enabled: bool) -> &mut Self {
+        self.kill_tree = enabled;
+        self
+    }
+
+    /// Set what to do if the working directory does not exist when the process is spawned.
+    ///
+    /// By default spawning fails, with an error naming the directory rather than the OS one
+    /// (which does not tell whether the program or the directory is missing).
+    pub fn on_missing_current_dir(&mut self, action: MissingCurrentDir) -> &mut Self {
+        self.missing_cwd = action;
+        self
+    }
+
+    /// Run the process in a new temporary directory, removed once the process finishes.
+    ///
+    /// Applies to processes run with [`Command::run_ok`], [`Command::output_ok`] and the methods
+    /// based on them, overriding the working directory set otherwise. Each retry gets a new
+    /// directory.
+    pub fn in_fresh_tempdir(&mut self) -> &mut Self {
+        self.fresh_tempdir.get_or_insert_with(default);
+        self
+    }
+
+    /// Like [`Command::in_fresh_tempdir`], but keep the directory if the process fails, so its
+    /// contents can be inspected.
+    pub fn retain_tempdir_on_failure(&mut self) -> &mut Self {
+        self.fresh_tempdir = Some(FreshTempDir { retain_on_failure: true });
+        self
+    }
+
+    /// Run the attempt in a fresh temporary directory, if requested.
+    fn in_tempdir<T: Send + 'static>(
+        &mut self,
+        attempt: impl FnOnce(&mut Self) -> Attempt<T>,
+    ) -> Attempt<T> {
+        let settings = match self.fresh_tempdir {
+            Some(settings) => settings,
+            None => return attempt(self),
+        };
+        let dir = match tempfile::tempdir() {
+            Ok(dir) => dir,
+            Err(e) => return ready(Err(e.into())).boxed(),
+        };
+        self.current_dir(dir.path());
+        let attempt = attempt(self);
+        async move {
+            let result = attempt.await;
+            if result.is_err() && settings.retain_on_failure {
+                let path = dir.into_path();
+                warn!("Keeping the working directory of the failed command: {}", path.display());
+            }
+            // Otherwise, the directory is removed when dropped.
+            result
+        }
+        .boxed()
+    }
+
+    /// Check the working directory before spawning, see [`Command::on_missing_current_dir`].
+    fn prepare_current_dir(&self) -> Result {
+        let command = self.inner.as_std();
+        let dir = match command.get_current_dir() {
+            Some(dir) if !dir.is_dir() => dir,
+            _ => return Ok(()),
+        };
+        match self.missing_cwd {
+            MissingCurrentDir::Fail => bail!(
+                "Cannot run `{}`: the working directory {} does not exist.",
+                command.get_program().to_string_lossy(),
+                dir.display()
+            ),
+            MissingCurrentDir::Create => {
+                debug!("Creating the missing working directory {}.", dir.display());
+                crate::fs::create_dir_if_missing(dir)
+            }
+        }
+    }
+
+    /// Create a new command that runs the same program with the same arguments, environment and
+    /// working directory.
+    fn duplicate(&self) -> Command {
+        let original = self.inner.as_std();
+        let mut inner = tokio::process::Command::new(original.get_program());
+        inner.args(original.get_args());
+        for (key, value) in original.get_envs() {
+            match value {
+                Some(value) => inner.env(key, value),
+                None => inner.env_remove(key),
+            };
+        }
+        if let Some(dir) = original.get_current_dir() {
+            inner.current_dir(dir);
+        }
+        inner.kill_on_drop(true);
+        Command {
+            inner,
+            status_checker: self.status_checker.clone(),
+            watchdog: self.watchdog.clone(),
+            timeout: self.timeout,
+            log_captured: self.log_captured,
+            log_streaming: self.log_streaming,
+            retry: None,
+            log_prefix: self.log_prefix.clone(),
+            kill_tree: self.kill_tree,
+            missing_cwd: self.missing_cwd,
+            fresh_tempdir: self.fresh_tempdir,
+            line_hooks: self.line_hooks.clone(),
+        }
+    }
+
+    /// Set how the output of the process run with [`Command::run_ok`] is logged.
+    pub fn stream_logs(&mut self, log_streaming: LogStreaming) -> &mut Self {
+        self.log_streaming = log_streaming;
+        self
+    }
+
+    /// Call the hook with each line of the standard output and error of the process run with
+    /// [`Command::run_ok`], e.g. to collect the reported errors.
+    ///
+    /// The lines are still logged. All the lines are processed before `run_ok` returns, unless
+    /// the process leaves descendants holding its output open.
+    pub fn on_output_line(&mut self, hook: impl Fn(&str) + Send + Sync + 'static) -> &mut Self {
+        self.line_hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// Tag the logged output lines with the given prefix instead of the program name.
+    ///
+    /// Useful to tell apart the output of several processes running the same program at once.
+    pub fn log_prefix(&mut self, prefix: impl Into<String>) -> &mut Self {
+        self.log_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Name of the program, used to prefix its output lines in the log.
+    fn program_name(&self) -> String {
+        if let Some(prefix) = &self.log_prefix {
+            return prefix.clone();
+        }
+        let program = self.inner.as_std().get_program();
+        let program = Path::new(program).file_stem().unwrap_or_default();
+        program.to_string_lossy().into_owned()
     }
 
     pub fn spawn_intercepting(&mut self) -> Result<Child> {
+        self.spawn_logged(default(), default()).map(|(child, _)| child)
+    }
+
+    /// Spawn the process with its output forwarded to the log. Returns the child and the tasks
+    /// processing its standard output and error.
+    ///
+    /// The output lines are also recorded in the `tail`, the standard error lines in the
+    /// `stderr_tail`.
+    fn spawn_logged(
+        &mut self,
+        tail: Option<OutputTail>,
+        stderr_tail: Option<OutputTail>,
+    ) -> Result<(Child, [JoinHandle<Result>; 2])> {
         self.stdout(Stdio::piped());
         self.stderr(Stdio::piped());
-
-        let program = self.inner.as_std().get_program();
-        let program = Path::new(program).file_stem().unwrap_or_default().to_os_string();
-        let program = program.to_string_lossy();
+        let program = self.program_name();
 
         let mut child = self.spawn()?;
 
+        let activity = self.watchdog.as_ref().map(|watchdog| watchdog.activity.clone());
+        if let Some(activity) = &activity {
+            // Inactivity is measured since the process start.
+            activity.touch();
+        }
         // FIXME unwraps
-        spawn_log_processor(format!("{program}ℹ️"), child.stdout.take().unwrap());
-        spawn_log_processor(format!("{program}⚠️"), child.stderr.take().unwrap());
-        Ok(child)
+        let LogStreaming { stdout_level, stderr_level, .. } = self.log_streaming;
+        let stdout = spawn_line_processor(
+            format!("{program}ℹ️"),
+            child.stdout.take().unwrap(),
+            stdout_level,
+            activity.clone(),
+            Vec::from_iter(tail.clone()),
+            self.line_hooks.clone(),
+        );
+        let stderr = spawn_line_processor(
+            format!("{program}⚠️"),
+            child.stderr.take().unwrap(),
+            stderr_level,
+            activity,
+            tail.into_iter().chain(stderr_tail).collect(),
+            self.line_hooks.clone(),
+        );
+        Ok((child, [stdout, stderr]))
     }
 
+    /// Run the process to completion, failing if the exit status is not accepted.
+    ///
+    /// In the [dry-run mode](crate::program::audit::set_dry_run) the process is only recorded.
     pub fn run_ok(&mut self) -> BoxFuture<'static, Result<()>> {
+        if crate::program::audit::is_dry_run() {
+            let result = crate::env::overlay::apply_scoped(self)
+                .map(|()| crate::program::audit::record_skipped(self.inner.as_std()));
+            return ready(result).boxed();
+        }
+        match self.retry.clone() {
+            Some(policy) => {
+                let mut retried = self.duplicate();
+                let first = self.in_tempdir(Self::run_ok_attempt);
+                let next = move || {
+                    crate::metrics::record_retry(retried.program_name());
+                    retried.in_tempdir(Self::run_ok_attempt)
+                };
+                async move { policy.run(first, next).await }.boxed()
+            }
+            None => self.in_tempdir(Self::run_ok_attempt).map_err(|failure| failure.error).boxed(),
+        }
+    }
+
+    fn run_ok_attempt(&mut self) -> BoxFuture<'static, std::result::Result<(), AttemptFailure>> {
         let pretty = self.describe();
         let span = info_span!(
             "Running process.",
@@ -298,23 +686,87 @@ impl Command {
             command = tracing::field::Empty,
         )
         .entered();
-        let child = self.spawn_intercepting();
+        let tail = OutputTail::default();
+        let stderr_tail = OutputTail::default();
+        let group = self.log_streaming.group.then(|| LogGroup::new(&pretty));
+        let has_line_hooks = !self.line_hooks.is_empty();
+        let kill_tree = self.kill_tree;
+        let spawned = self.spawn_logged(Some(tail.clone()), Some(stderr_tail.clone())).map(
+            |(child, processors)| {
+                let tree = kill_tree.then(|| ProcessTree::attach(&child));
+                (child, processors, tree)
+            },
+        );
         let status_checker = self.status_checker.clone();
+        let watchdog = self.watchdog.clone();
+        let timeout = self.timeout;
         async move {
-            let mut child = child?;
-            let status = child
-                .wait()
-                .inspect_ok(|exit_status| {
-                    tracing::Span::current().record("status", &exit_status.code());
-                })
-                .await?;
-            status_checker(status).context(format!("Command failed: {}", pretty))
+            // Unless released, the tree is killed when this future is dropped or returns early.
+            let (mut child, processors, tree) = spawned?;
+            let started = Instant::now();
+            let status = match timeout {
+                Some(limit) => {
+                    let result =
+                        tokio::time::timeout(limit, wait(&mut child, watchdog.as_ref())).await;
+                    match result {
+                        Ok(status) => status,
+                        Err(_) => {
+                            child.kill().await?;
+                            let error = timed_out(limit, started.elapsed(), &tail.contents())
+                                .context(format!("Command failed: {}", pretty));
+                            return Err(AttemptFailure {
+                                error,
+                                status: None,
+                                stderr: stderr_tail.contents(),
+                            });
+                        }
+                    }
+                }
+                None => wait(&mut child, watchdog.as_ref()).await,
+            }
+            .inspect(|exit_status| {
+                tracing::Span::current().record("status", &exit_status.code());
+            })?;
+            if let Some(tree) = tree {
+                tree.release();
+            }
+            if group.is_some() || has_line_hooks {
+                // Let the remaining output land in the group and reach the hooks. Descendant
+                // processes might still keep the pipes open, so don't wait for it indefinitely.
+                let grace = Duration::from_secs(1);
+                let _ = tokio::time::timeout(grace, futures::future::join_all(processors)).await;
+            }
+            drop(group);
+            status_checker(status).context(format!("Command failed: {}", pretty)).map_err(|error| {
+                AttemptFailure { error, status: Some(status), stderr: stderr_tail.contents() }
+            })
         }
         .instrument(span.exit())
         .boxed()
     }
 
-    pub fn output_ok(&mut self) -> BoxFuture<'static, Result<Output>> {
+    /// Run the process to completion, capturing its standard output and error.
+    ///
+    /// Fails if the exit status is not accepted, the error then contains the captured output.
+    pub fn output_ok(&mut self) -> BoxFuture<'static, Result<CapturedOutput>> {
+        match self.retry.clone() {
+            Some(policy) => {
+                let mut retried = self.duplicate();
+                let first = self.in_tempdir(Self::output_ok_attempt);
+                let next = move || {
+                    crate::metrics::record_retry(retried.program_name());
+                    retried.in_tempdir(Self::output_ok_attempt)
+                };
+                async move { policy.run(first, next).await }.boxed()
+            }
+            None =>
+                self.in_tempdir(Self::output_ok_attempt).map_err(|failure| failure.error).boxed(),
+        }
+    }
+
+    fn output_ok_attempt(
+        &mut self,
+    ) -> BoxFuture<'static, std::result::Result<CapturedOutput, AttemptFailure>> {
         let pretty = self.describe();
         let span = info_span!(
             "Running process for the output.",
@@ -326,23 +778,73 @@ impl Command {
 
         self.stdout(Stdio::piped());
         self.stderr(Stdio::piped());
-        let child = self.spawn();
+        let kill_tree = self.kill_tree;
+        let spawned = self.spawn().map(|child| {
+            let tree = kill_tree.then(|| ProcessTree::attach(&child));
+            (child, tree)
+        });
         let status_checker = self.status_checker.clone();
+        let timeout = self.timeout;
+        let (stdout_prefix, stderr_prefix) = if self.log_captured {
+            let program = self.program_name();
+            (Some(format!("{program}ℹ️")), Some(format!("{program}⚠️")))
+        } else {
+            (None, None)
+        };
         async move {
-            let child = child?;
-            let output =
-                child.wait_with_output().await.context("Failed while waiting for output.")?;
+            let (mut child, tree) = spawned?;
+            let started = Instant::now();
+            let stdout = spawn_capture(child.stdout.take(), stdout_prefix);
+            let stderr = spawn_capture(child.stderr.take(), stderr_prefix);
+            let status = match timeout {
+                Some(limit) => match tokio::time::timeout(limit, child.wait()).await {
+                    Ok(status) => status?,
+                    Err(_) => {
+                        child.kill().await?;
+                        // Dropping the tree kills the descendants. Still, the ones that escaped
+                        // it might keep the pipes open, so don't wait for the output indefinitely.
+                        drop(tree);
+                        let stdout = finish_capture(stdout).await;
+                        let stderr = finish_capture(stderr).await;
+                        let output = match (stdout, stderr) {
+                            (Ok(stdout), Ok(stderr)) => format!(
+                                "Stdout:\n{}\n\nStderr:\n{}",
+                                String::from_utf8_lossy(&stdout),
+                                String::from_utf8_lossy(&stderr),
+                            ),
+                            _ => "<unavailable>".into(),
+                        };
+                        return Err(timed_out(limit, started.elapsed(), &output).into());
+                    }
+                },
+                None => child.wait().await?,
+            };
+            if let Some(tree) = tree {
+                tree.release();
+            }
+            let duration = started.elapsed();
+            // As in the timeout case, the descendants might keep the pipes open.
+            let stdout = finish_capture(stdout).await?;
+            let stderr = finish_capture(stderr).await?;
+            let output = CapturedOutput { status, stdout, stderr, duration };
             tracing::Span::current().record("status", &output.status.code());
-            status_checker(output.status).with_context(|| {
-                format!(
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if let Err(error) = status_checker(output.status) {
+                let error = error.context(format!(
                     "Stdout:\n{}\n\nStderr:\n{}\n",
                     String::from_utf8_lossy(&output.stdout),
-                    String::from_utf8_lossy(&output.stderr),
-                )
-            })?;
-            Result::Ok(output)
+                    stderr,
+                ));
+                let stderr = stderr.into_owned();
+                return Err(AttemptFailure { error, status: Some(output.status), stderr });
+            }
+            Ok(output)
         }
-        .map_err(move |e| e.context(format!("Failed to get output of the command: {}", pretty)))
+        .map_err(move |mut failure: AttemptFailure| {
+            let context = format!("Failed to get output of the command: {}", pretty);
+            failure.error = failure.error.context(context);
+            failure
+        })
         .instrument(span.exit())
         .boxed()
     }
@@ -358,6 +860,25 @@ impl Command {
         .boxed()
     }
 
+    /// Like [`Command::run_stdout`], but with the surrounding whitespace (like the trailing
+    /// newline) removed.
+    pub fn run_stdout_string(&mut self) -> BoxFuture<'static, Result<String>> {
+        let output = self.run_stdout();
+        async move { Ok(output.await?.trim().to_string()) }.boxed()
+    }
+
+    /// Run the process and parse its standard output as JSON.
+    pub fn run_stdout_json<T: DeserializeOwned>(&mut self) -> BoxFuture<'static, Result<T>> {
+        let pretty = self.describe();
+        let output = self.output_ok();
+        async move {
+            let output = output.await?;
+            serde_json::from_slice(&output.stdout)
+                .with_context(|| format!("Failed to parse the JSON output of: {pretty}"))
+        }
+        .boxed()
+    }
+
     pub fn spawn(&mut self) -> Result<Child> {
         let pretty = self.describe();
 
@@ -369,6 +890,25 @@ impl Command {
             debug!("Spawning {}.", pretty);
         }
 
+        self.prepare_current_dir()?;
+        crate::env::overlay::apply_scoped(self)?;
+        // Deep working directories (e.g. within `node_modules`) exceed the `MAX_PATH` limit. The
+        // `cmd.exe` interpreter does not support the verbatim paths, so it keeps the original one.
+        let program = self.inner.as_std().get_program();
+        if TARGET_OS == OS::Windows && !crate::os::windows::runs_in_cmd(program) {
+            let long_dir = self
+                .inner
+                .as_std()
+                .get_current_dir()
+                .map(|dir| crate::os::windows::long_path(dir).into_owned());
+            if let Some(long_dir) = long_dir {
+                self.inner.current_dir(long_dir);
+            }
+        }
+        crate::program::audit::record(self.inner.as_std());
+        if self.kill_tree {
+            crate::program::process_tree::isolate(&mut self.inner);
+        }
         self.inner.spawn().context(format!("Failed to spawn: {}", pretty)).inspect(|child| {
             if let Some(pid) = child.id() {
                 current_span.record("pid", &pid);
@@ -387,9 +927,137 @@ impl Command {
     // }
 }
 
+/// Wait for the process to finish, using the watchdog if given.
+async fn wait(child: &mut Child, watchdog: Option<&Watchdog>) -> Result<ExitStatus> {
+    match watchdog {
+        Some(watchdog) => wait_watched(child, watchdog).await,
+        None => child.wait().await.anyhow_err(),
+    }
+}
+
+/// Output of the process captured so far, shared with the task reading it.
+type CaptureBuffer = Arc<Mutex<Vec<u8>>>;
+
+/// Read the whole output of the process into the buffer. If the prefix is given, the lines are also
+/// logged.
+async fn capture(
+    pipe: Option<impl AsyncRead + Unpin>,
+    log_prefix: Option<String>,
+    buffer: CaptureBuffer,
+) -> Result {
+    let mut reader = match pipe {
+        Some(pipe) => BufReader::new(pipe),
+        None => return Ok(()),
+    };
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line).await? == 0 {
+            break;
+        }
+        if let Some(prefix) = &log_prefix {
+            debug!("{prefix} {}", String::from_utf8_lossy(&line).trim_end());
+        }
+        buffer.lock().unwrap().extend_from_slice(&line);
+    }
+    Ok(())
+}
+
+/// Start capturing the output of the process in the background.
+fn spawn_capture(
+    pipe: Option<impl AsyncRead + Send + Unpin + 'static>,
+    log_prefix: Option<String>,
+) -> (JoinHandle<Result>, CaptureBuffer) {
+    let buffer = CaptureBuffer::default();
+    (tokio::spawn(capture(pipe, log_prefix, buffer.clone())), buffer)
+}
+
+/// Get the output captured from the process that has already exited.
+///
+/// The descendants that outlived the process might keep the pipe open. The reader is given a short
+/// grace period to reach the end of the output, then it is aborted and the output captured so far
+/// is returned.
+async fn finish_capture(
+    (mut task, buffer): (JoinHandle<Result>, CaptureBuffer),
+) -> Result<Vec<u8>> {
+    let grace = Duration::from_secs(1);
+    match tokio::time::timeout(grace, &mut task).await {
+        Ok(result) => result??,
+        Err(_) => {
+            warn!("The output pipe is still open after the process has exited, aborting reading.");
+            task.abort();
+        }
+    }
+    let output = std::mem::take(&mut *buffer.lock().unwrap());
+    Ok(output)
+}
+
+fn timed_out(limit: Duration, elapsed: Duration, output: &str) -> anyhow::Error {
+    anyhow!(
+        "Process was killed after running for {elapsed:.1?}, exceeding the timeout of {limit:?}. \
+        Recent output:\n{output}"
+    )
+}
+
+/// Wait for the process to finish, killing it if the watchdog considers it hung.
+async fn wait_watched(child: &mut Child, watchdog: &Watchdog) -> Result<ExitStatus> {
+    loop {
+        match tokio::time::timeout(watchdog.time_left(), child.wait()).await {
+            Ok(status) => return status.anyhow_err(),
+            Err(_) if watchdog.is_hung() => {
+                if let Some(pid) = child.id() {
+                    watchdog.diagnose(pid).await;
+                }
+                child.kill().await?;
+                bail!(
+                    "Process was killed after being inactive for {:?}.",
+                    watchdog.inactivity_limit
+                );
+            }
+            // Some activity was recorded in the meantime, keep waiting.
+            Err(_) => continue,
+        }
+    }
+}
+
 pub fn spawn_log_processor(
     prefix: String,
     out: impl AsyncRead + Send + Unpin + 'static,
+) -> JoinHandle<Result> {
+    spawn_log_processor_with_activity(prefix, out, None)
+}
+
+/// Log the line at the given level.
+pub fn log_line(level: Level, prefix: &str, line: &str) {
+    if level == Level::ERROR {
+        error!("{prefix} {line}")
+    } else if level == Level::WARN {
+        warn!("{prefix} {line}")
+    } else if level == Level::INFO {
+        info!("{prefix} {line}")
+    } else if level == Level::DEBUG {
+        debug!("{prefix} {line}")
+    } else {
+        trace!("{prefix} {line}")
+    }
+}
+
+/// Like [`spawn_log_processor`] but also records each output line in the given activity tracker.
+pub fn spawn_log_processor_with_activity(
+    prefix: String,
+    out: impl AsyncRead + Send + Unpin + 'static,
+    activity: Option<Activity>,
+) -> JoinHandle<Result> {
+    spawn_line_processor(prefix, out, Level::INFO, activity, default(), default())
+}
+
+fn spawn_line_processor(
+    prefix: String,
+    out: impl AsyncRead + Send + Unpin + 'static,
+    level: Level,
+    activity: Option<Activity>,
+    tails: Vec<OutputTail>,
+    hooks: Vec<LineHook>,
 ) -> JoinHandle<Result> {
     tokio::task::spawn(
         async move {
@@ -397,10 +1065,19 @@ pub fn spawn_log_processor(
             let bufread = BufReader::new(out);
             let mut lines = bufread.split(b'\n');
             while let Some(line_bytes) = lines.next_segment().await? {
+                if let Some(activity) = &activity {
+                    activity.touch();
+                }
                 match String::from_utf8(line_bytes) {
                     Ok(line) => {
                         let line = line.trim_end_matches('\r');
-                        info!("{prefix} {line}");
+                        log_line(level, &prefix, line);
+                        for tail in &tails {
+                            tail.push(line);
+                        }
+                        for hook in &hooks {
+                            hook(line);
+                        }
                     }
                     Err(e) => {
                         error!("{prefix} Failed to decode a line from output: {e}");
@@ -430,7 +1107,85 @@ pub trait FallibleManipulator {
 
 #[cfg(test)]
 mod tests {
-    // use super::*;
+    use super::*;
+
+    #[test]
+    fn output_tail_is_bounded() {
+        let tail = OutputTail::default();
+        for i in 0..OutputTail::CAPACITY + 2 {
+            tail.push(i.to_string());
+        }
+        let contents = tail.contents();
+        assert!(contents.starts_with("2\n"));
+        assert!(contents.ends_with(&(OutputTail::CAPACITY + 1).to_string()));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn captured_output() -> Result {
+        let script = r#"echo '{"answer": 42}'; echo 'warning' >&2"#;
+        let mut command = Command::new("sh");
+        command.args(["-c", script]).log_captured_output(true);
+        let output = command.output_ok().await?;
+        assert_eq!(output.stdout, b"{\"answer\": 42}\n");
+        assert_eq!(output.stderr, b"warning\n");
+
+        #[derive(Deserialize)]
+        struct Answer {
+            answer: u32,
+        }
+        let answer = Command::new("sh").args(["-c", script]).run_stdout_json::<Answer>().await?;
+        assert_eq!(answer.answer, 42);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn output_of_process_with_lingering_descendant() -> Result {
+        // The background process inherits the pipes and keeps them open after the shell exits.
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo done; sleep 30 &"]);
+        let output = tokio::time::timeout(Duration::from_secs(10), command.output_ok()).await??;
+        assert_eq!(output.stdout, b"done\n");
+        Ok(())
+    }
+
+    #[test]
+    fn missing_current_dir() -> Result {
+        let temp = tempfile::tempdir()?;
+        let dir = temp.path().join("missing");
+        let mut command = Command::new("cargo");
+        command.arg("--version").current_dir(&dir);
+        let message = format!("{:#}", command.spawn().unwrap_err());
+        assert!(message.contains("`cargo`"), "{message}");
+        assert!(message.contains(&dir.display().to_string()), "{message}");
+
+        command.on_missing_current_dir(MissingCurrentDir::Create).prepare_current_dir()?;
+        assert!(dir.is_dir());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn fresh_tempdir_is_removed() -> Result {
+        let mut command = Command::new("sh");
+        command.args(["-c", "touch marker && pwd"]).in_fresh_tempdir();
+        let dir = PathBuf::from(command.run_stdout_string().await?);
+        assert!(!dir.as_os_str().is_empty());
+        assert!(!dir.exists());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn timeout_kills_process() {
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo started; sleep 30"]).timeout(Duration::from_millis(500));
+        let error = command.run_ok().await.unwrap_err();
+        let message = format!("{error:#}");
+        assert!(message.contains("exceeding the timeout"), "{message}");
+        assert!(message.contains("started"), "{message}");
+    }
+
     // use crate::global::new_spinner;
     // // use crate::global::println;
     // use tokio::io::AsyncBufReadExt;
@@ -0,0 +1,81 @@
+//! Interpretation of the exit status of the external programs.
+
+use crate::prelude::*;
+
+use snafu::Snafu;
+use std::process::ExitStatus;
+
+
+/// Program-specific error, like the ones described by the exit codes of
+/// [`SevenZip`](crate::programs::SevenZip).
+pub type Source = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// Failure of a program, as interpreted from its exit status by
+/// [`Program::interpret_exit_code`].
+#[derive(Debug, Snafu)]
+pub enum ProgramError {
+    /// The exit code has a known program-specific meaning.
+    #[snafu(display("Process exited with code {code}: {source}"))]
+    Domain { code: i32, source: Source },
+    /// The exit code has no known meaning, other than a failure.
+    #[snafu(display("Process exited with code {code}."))]
+    ExitCode { code: i32 },
+    /// The process did not exit on its own, e.g. it was killed by a signal.
+    #[snafu(display("Process was terminated: {status}."))]
+    Terminated { status: ExitStatus },
+}
+
+impl ProgramError {
+    /// Generic interpretation: only the zero exit code denotes success.
+    pub fn check(status: ExitStatus) -> std::result::Result<(), Self> {
+        match status.code() {
+            Some(0) => Ok(()),
+            Some(code) => Err(ProgramError::ExitCode { code }),
+            None => Err(ProgramError::Terminated { status }),
+        }
+    }
+
+    /// Error with a program-specific meaning of the exit code.
+    pub fn domain(code: i32, error: impl Into<Source>) -> Self {
+        ProgramError::Domain { code, source: error.into() }
+    }
+
+    /// The exit code, if the process exited on its own.
+    pub fn code(&self) -> Option<i32> {
+        match self {
+            ProgramError::Domain { code, .. } | ProgramError::ExitCode { code } => Some(*code),
+            ProgramError::Terminated { status } => status.code(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::programs::robocopy::Robocopy;
+    use crate::programs::SevenZip;
+
+    #[cfg(unix)]
+    fn exit_status(code: i32) -> ExitStatus {
+        std::os::unix::process::ExitStatusExt::from_raw(code << 8)
+    }
+
+    #[cfg(windows)]
+    fn exit_status(code: i32) -> ExitStatus {
+        std::os::windows::process::ExitStatusExt::from_raw(code as u32)
+    }
+
+    #[test]
+    fn interpreting_exit_codes() {
+        assert!(ProgramError::check(exit_status(0)).is_ok());
+        assert_eq!(ProgramError::check(exit_status(3)).unwrap_err().code(), Some(3));
+
+        // Robocopy reports the successful copies with the codes below 8.
+        assert!(Robocopy::interpret_exit_code(exit_status(3)).is_ok());
+        let error = Robocopy::interpret_exit_code(exit_status(8)).unwrap_err();
+        assert!(matches!(error, ProgramError::Domain { code: 8, .. }), "{error:?}");
+
+        let error = SevenZip::interpret_exit_code(exit_status(8)).unwrap_err();
+        assert!(error.to_string().contains("Not enough memory"), "{error}");
+    }
+}
@@ -0,0 +1,135 @@
+//! Running a set of prepared commands concurrently, with a limit on the number of processes
+//! running at once.
+
+use crate::prelude::*;
+
+
+/// What to do when one of the commands in a [`CommandGroup`] fails.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailureMode {
+    /// Stop on the first failure. The processes that are still running are killed and the
+    /// pending ones are not started.
+    FailFast,
+    /// Run all the commands and report all the failures together.
+    CollectAll,
+}
+
+impl Default for FailureMode {
+    fn default() -> Self {
+        FailureMode::FailFast
+    }
+}
+
+/// Set of named commands that are run concurrently with [`Command::run_ok`].
+///
+/// The output lines of each command are tagged with the name of its job, so the interleaved logs
+/// can be told apart. The commands are started in the order they were added.
+#[derive(Debug)]
+pub struct CommandGroup {
+    jobs:         Vec<(String, Command)>,
+    max_parallel: usize,
+    failure_mode: FailureMode,
+}
+
+impl CommandGroup {
+    /// Create an empty group running at most `max_parallel` processes at once.
+    pub fn new(max_parallel: usize) -> Self {
+        Self { jobs: default(), max_parallel: max_parallel.max(1), failure_mode: default() }
+    }
+
+    /// Create an empty group running as many processes at once as there are CPUs.
+    pub fn per_cpu() -> Self {
+        Self::new(std::thread::available_parallelism().map_or(1, |count| count.get()))
+    }
+
+    pub fn failure_mode(mut self, failure_mode: FailureMode) -> Self {
+        self.failure_mode = failure_mode;
+        self
+    }
+
+    /// Add the command as a job with the given name.
+    pub fn add(&mut self, name: impl Into<String>, command: impl Into<Command>) -> &mut Self {
+        self.jobs.push((name.into(), command.into()));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    /// Run all the jobs. Fails if any of them fails.
+    pub async fn run(self) -> Result {
+        let CommandGroup { jobs, max_parallel, failure_mode } = self;
+        let total = jobs.len();
+        let mut results = futures::stream::iter(jobs)
+            .map(|(name, mut command)| {
+                command.log_prefix(name.as_str());
+                command.run_ok().map(move |result| (name, result))
+            })
+            .buffer_unordered(max_parallel);
+
+        let mut failures = vec![];
+        while let Some((name, result)) = results.next().await {
+            match result {
+                Ok(()) => debug!("Job {name} finished."),
+                Err(error) => match failure_mode {
+                    // Dropping the stream kills the running processes.
+                    FailureMode::FailFast =>
+                        return Err(error.context(format!("Job {name} failed."))),
+                    FailureMode::CollectAll => {
+                        warn!("Job {name} failed: {error:?}");
+                        failures.push((name, error));
+                    }
+                },
+            }
+        }
+        match failures.as_slice() {
+            [] => Ok(()),
+            failures => {
+                let details =
+                    failures.iter().map(|(name, error)| format!("{name}: {error:#}")).join("\n");
+                bail!("{} of {total} jobs failed:\n{details}", failures.len())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    fn sh(script: &str) -> Command {
+        let mut command = Command::new("sh");
+        command.args(["-c", script]);
+        command
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn collects_all_failures() {
+        let mut group = CommandGroup::new(2).failure_mode(FailureMode::CollectAll);
+        group.add("ok", sh("true")).add("first", sh("exit 1")).add("second", sh("exit 2"));
+        let message = format!("{:#}", group.run().await.unwrap_err());
+        assert!(message.starts_with("2 of 3 jobs failed"), "{message}");
+        assert!(message.contains("first: ") && message.contains("second: "), "{message}");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn fails_fast() -> Result {
+        let marker = tempfile::NamedTempFile::new()?.into_temp_path();
+        let mut group = CommandGroup::new(1);
+        group
+            .add("failing", sh("exit 1"))
+            .add("pending", sh(&format!("rm '{}'", marker.display())));
+        assert!(group.run().await.is_err());
+        // The pending job should never be started.
+        assert!(marker.exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+//! Installing the missing programs on demand, so the CI runners can bootstrap themselves.
+//!
+//! A program opts in by returning an installer from [`Program::installer`]. The installation is
+//! then triggered by [`Program::lookup_or_install`].
+
+use crate::prelude::*;
+
+use crate::download::Download;
+use crate::programs::Cargo;
+use crate::programs::Npm;
+
+
+/// Way of installing a program.
+#[async_trait]
+pub trait Installer: Debug + Send + Sync {
+    /// Install the program.
+    ///
+    /// Returns the directory with the installed executable, unless it is put in a directory that
+    /// is already on `PATH`.
+    async fn install(&self) -> Result<Option<PathBuf>>;
+}
+
+/// Install a Rust binary crate with `cargo install`.
+#[derive(Clone, Debug)]
+pub struct CargoInstall {
+    pub package: String,
+}
+
+impl CargoInstall {
+    pub fn new(package: impl Into<String>) -> Self {
+        Self { package: package.into() }
+    }
+}
+
+#[async_trait]
+impl Installer for CargoInstall {
+    async fn install(&self) -> Result<Option<PathBuf>> {
+        // Run in a temporary directory, so the system-wide default toolchain is used, rather than
+        // the override of the current directory (which is likely under our repository root).
+        Cargo.cmd()?.arg("install").arg(&self.package).in_fresh_tempdir().run_ok().await?;
+        let cargo_home = match std::env::var_os("CARGO_HOME") {
+            Some(cargo_home) => PathBuf::from(cargo_home),
+            None => dirs::home_dir().context("Cannot figure out home directory.")?.join(".cargo"),
+        };
+        Ok(Some(cargo_home.join("bin")))
+    }
+}
+
+/// Install a Node package globally with `npm install --global`.
+#[derive(Clone, Debug)]
+pub struct NpmGlobal {
+    pub package: String,
+}
+
+impl NpmGlobal {
+    pub fn new(package: impl Into<String>) -> Self {
+        Self { package: package.into() }
+    }
+}
+
+#[async_trait]
+impl Installer for NpmGlobal {
+    async fn install(&self) -> Result<Option<PathBuf>> {
+        Npm.cmd()?.args(["install", "--global"]).arg(&self.package).run_ok().await?;
+        let prefix =
+            PathBuf::from(Npm.cmd()?.args(["prefix", "--global"]).run_stdout_string().await?);
+        // On Windows the executables are put directly in the prefix directory.
+        Ok(Some(match TARGET_OS {
+            OS::Windows => prefix,
+            _ => prefix.join("bin"),
+        }))
+    }
+}
+
+/// Download a release archive and extract it into a tools directory.
+///
+/// The archive is kept in the cache, so it is not downloaded again if the tools directory is
+/// removed.
+#[derive(Clone, Debug)]
+pub struct DownloadArchive {
+    pub url:         Url,
+    /// Expected SHA-256 digest of the archive.
+    pub sha256:      Option<String>,
+    /// Directory where the archive is extracted.
+    pub directory:   PathBuf,
+    /// Path to the directory with executables, relative to the extracted archive root.
+    pub bin_subpath: PathBuf,
+}
+
+#[async_trait]
+impl Installer for DownloadArchive {
+    async fn install(&self) -> Result<Option<PathBuf>> {
+        let mut download = Download::new(self.url.clone())?;
+        if let Some(sha256) = &self.sha256 {
+            download = download.sha256(sha256);
+        }
+        let archive = download.fetch().await?;
+        crate::archive::extract_to(&archive, &self.directory).await?;
+        Ok(Some(self.directory.join(&self.bin_subpath)))
+    }
+}
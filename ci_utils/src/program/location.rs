@@ -1,6 +1,23 @@
 use crate::prelude::*;
 
 use crate::program::command::MyCommand;
+use std::any::TypeId;
+use std::lazy::SyncLazy;
+use std::sync::Mutex;
+
+
+/// Executables explicitly set with [`Location::set_global`], keyed by the program type.
+static OVERRIDES: SyncLazy<Mutex<HashMap<TypeId, PathBuf>>> = SyncLazy::new(default);
+
+/// Name of the environment variable that overrides the location of the program with the given
+/// executable name, e.g. `ENSO_PROGRAM_WASM_OPT` for `wasm-opt`.
+pub fn override_variable_name(executable_name: &str) -> String {
+    let name = executable_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect::<String>();
+    format!("ENSO_PROGRAM_{name}")
+}
 
 #[derive(Clone, Debug)]
 pub struct Location<P> {
@@ -28,4 +45,57 @@ impl<P: Program> Location<P> {
     pub fn cmd(&self) -> P::Command {
         P::Command::new_program(self)
     }
+    /// Make all the lookups of the program yield the given executable, regardless of `PATH`.
+    ///
+    /// This has priority over the `ENSO_PROGRAM_<NAME>` environment variable.
+    pub fn set_global(path: impl Into<PathBuf>) {
+        OVERRIDES.lock().unwrap().insert(TypeId::of::<P>(), path.into());
+    }
+
+    /// Remove the override set with [`Location::set_global`].
+    pub fn clear_global() {
+        OVERRIDES.lock().unwrap().remove(&TypeId::of::<P>());
+    }
+
+    /// The explicitly overridden location of the program, if any.
+    ///
+    /// Checks the [global override](Location::set_global) first, then the environment variable
+    /// named by [`override_variable_name`].
+    pub fn overridden(program: &P) -> Option<Self> {
+        if let Some(path) = OVERRIDES.lock().unwrap().get(&TypeId::of::<P>()) {
+            return Some(Self::new(path));
+        }
+        let variable = override_variable_name(program.executable_name());
+        let path = std::env::var_os(&variable).filter(|path| !path.is_empty())?;
+        trace!("Using {} from {variable}.", program.pretty_name());
+        Some(Self::new(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program::Unknown;
+
+    #[test]
+    fn variable_names() {
+        assert_eq!(override_variable_name("wasm-opt"), "ENSO_PROGRAM_WASM_OPT");
+        assert_eq!(override_variable_name("7z"), "ENSO_PROGRAM_7Z");
+    }
+
+    #[test]
+    fn global_override() {
+        struct Overridden;
+        impl Program for Overridden {
+            fn executable_name(&self) -> &str {
+                "enso-test-overridden"
+            }
+        }
+        assert!(Overridden.lookup().is_err());
+        Location::<Overridden>::set_global("/opt/test/bin/tool");
+        assert_eq!(Overridden.lookup().unwrap().executable_path, Path::new("/opt/test/bin/tool"));
+        Location::<Overridden>::clear_global();
+        assert!(Location::overridden(&Overridden).is_none());
+        assert!(Location::overridden(&Unknown("enso-test-unknown".into())).is_none());
+    }
 }
@@ -0,0 +1,216 @@
+//! Termination of the whole tree of processes spawned by a command.
+//!
+//! Killing just the spawned process is not enough, as its descendants (like Gradle daemons or
+//! Node subprocesses) survive and keep holding file locks. Thus, on Unix the process is spawned
+//! as the leader of a new process group, and on Windows it is assigned to a new job object. Either
+//! can be used to terminate all the processes at once.
+
+use crate::prelude::*;
+
+use tokio::process::Child;
+
+
+/// Make the spawned process the root of a tree that can be terminated with [`ProcessTree`].
+///
+/// On Unix the process gets its own process group. Because of that, it no longer receives the
+/// signals (like `SIGINT` on Ctrl+C) sent by the terminal to the foreground process group.
+pub fn isolate(command: &mut tokio::process::Command) {
+    #[cfg(unix)]
+    {
+        use nix::unistd::setpgid;
+        use nix::unistd::Pid;
+        // SAFETY: `setpgid` is async-signal-safe and the closure does not allocate.
+        unsafe {
+            command.pre_exec(|| {
+                setpgid(Pid::from_raw(0), Pid::from_raw(0)).map_err(std::io::Error::from)
+            });
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = command;
+}
+
+/// Handle to the tree of processes rooted in the spawned child.
+///
+/// Unless [released](ProcessTree::release), the whole tree is killed when the handle is dropped,
+/// e.g. when the future waiting for the process is cancelled or fails on timeout.
+#[derive(Debug)]
+pub struct ProcessTree {
+    #[cfg(unix)]
+    group: Option<nix::unistd::Pid>,
+    #[cfg(windows)]
+    job:   Option<job::Job>,
+}
+
+impl ProcessTree {
+    /// Track the tree of the child spawned from an [isolated](isolate) command.
+    #[cfg(unix)]
+    pub fn attach(child: &Child) -> Self {
+        let group = child.id().and_then(|pid| i32::try_from(pid).ok());
+        Self { group: group.map(nix::unistd::Pid::from_raw) }
+    }
+
+    /// Track the tree of the child spawned from an [isolated](isolate) command.
+    ///
+    /// Failures are only logged, the child is then killed alone as a fallback.
+    #[cfg(windows)]
+    pub fn attach(child: &Child) -> Self {
+        let job = job::Job::new().and_then(|job| job.assign(child).map(|()| job));
+        let job = job.inspect_err(|e| warn!("Failed to track the process tree: {e:?}")).ok();
+        Self { job }
+    }
+
+    /// Kill all the processes in the tree.
+    pub fn kill(&mut self) {
+        #[cfg(unix)]
+        {
+            use nix::sys::signal::killpg;
+            use nix::sys::signal::Signal;
+            if let Some(group) = self.group.take() {
+                debug!("Killing the process group {group}.");
+                // The group might be already gone if all its processes have exited.
+                let _ = killpg(group, Signal::SIGKILL);
+            }
+        }
+        #[cfg(windows)]
+        {
+            if let Some(job) = self.job.take() {
+                debug!("Terminating the job object of the process tree.");
+                job.terminate();
+            }
+        }
+    }
+
+    /// Stop tracking the tree, leaving the remaining processes running.
+    ///
+    /// Used once the root process has exited on its own, as the processes it intentionally left
+    /// behind (like daemons) should not be killed.
+    pub fn release(mut self) {
+        #[cfg(unix)]
+        {
+            self.group = None;
+        }
+        #[cfg(windows)]
+        {
+            if let Some(job) = self.job.take() {
+                job.release();
+            }
+        }
+    }
+}
+
+impl Drop for ProcessTree {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+#[cfg(windows)]
+mod job {
+    use super::*;
+
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::Foundation::HANDLE;
+    use windows_sys::Win32::System::JobObjects::AssignProcessToJobObject;
+    use windows_sys::Win32::System::JobObjects::CreateJobObjectW;
+    use windows_sys::Win32::System::JobObjects::JobObjectExtendedLimitInformation;
+    use windows_sys::Win32::System::JobObjects::SetInformationJobObject;
+    use windows_sys::Win32::System::JobObjects::TerminateJobObject;
+    use windows_sys::Win32::System::JobObjects::JOBOBJECT_EXTENDED_LIMIT_INFORMATION;
+    use windows_sys::Win32::System::JobObjects::JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+
+    /// Owned handle to a job object. The processes in the job are killed when it is closed,
+    /// even if this process crashes.
+    #[derive(Debug)]
+    pub struct Job(HANDLE);
+
+    // SAFETY: Job object handles can be used from any thread.
+    unsafe impl Send for Job {}
+    unsafe impl Sync for Job {}
+
+    impl Job {
+        pub fn new() -> Result<Self> {
+            // SAFETY: Null attributes and name are allowed, the handle is checked before use.
+            let handle = unsafe { CreateJobObjectW(std::ptr::null(), std::ptr::null()) };
+            ensure!(handle != 0, "Failed to create a job object: {}", last_error());
+            let job = Job(handle);
+            job.set_limit_flags(JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE)?;
+            Ok(job)
+        }
+
+        fn set_limit_flags(&self, flags: u32) -> Result {
+            // SAFETY: The structure is plain data, for which all zeroes is a valid value.
+            let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = unsafe { std::mem::zeroed() };
+            info.BasicLimitInformation.LimitFlags = flags;
+            // SAFETY: The pointer and size describe a valid structure of the declared class.
+            let ok = unsafe {
+                SetInformationJobObject(
+                    self.0,
+                    JobObjectExtendedLimitInformation,
+                    &info as *const _ as *const std::ffi::c_void,
+                    std::mem::size_of_val(&info) as u32,
+                )
+            };
+            ensure!(ok != 0, "Failed to set the job object limits: {}", last_error());
+            Ok(())
+        }
+
+        pub fn assign(&self, child: &Child) -> Result {
+            let process = child.raw_handle().context("The process has already exited.")?;
+            // SAFETY: Both handles are valid for the duration of the call.
+            let ok = unsafe { AssignProcessToJobObject(self.0, process as HANDLE) };
+            ensure!(ok != 0, "Failed to assign the process to a job object: {}", last_error());
+            Ok(())
+        }
+
+        pub fn terminate(self) {
+            // SAFETY: The handle is valid, it is closed by the drop afterwards.
+            unsafe { TerminateJobObject(self.0, 1) };
+        }
+
+        /// Close the job without killing the processes in it.
+        pub fn release(self) {
+            if let Err(e) = self.set_limit_flags(0) {
+                warn!("Failed to release the process tree: {e:?}");
+            }
+        }
+    }
+
+    impl Drop for Job {
+        fn drop(&mut self) {
+            // SAFETY: The handle is owned and closed only here.
+            unsafe { CloseHandle(self.0) };
+        }
+    }
+
+    fn last_error() -> std::io::Error {
+        std::io::Error::last_os_error()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn kills_descendants() -> Result {
+        let mut command = tokio::process::Command::new("sh");
+        // The grandchild prints its PID and outlives its parent, unless the tree is killed.
+        command.args(["-c", "sleep 30 & echo $!; wait"]).stdout(std::process::Stdio::piped());
+        isolate(&mut command);
+        let mut child = command.spawn()?;
+        let mut tree = ProcessTree::attach(&child);
+        let mut stdout = tokio::io::BufReader::new(child.stdout.take().unwrap());
+        let mut line = String::new();
+        tokio::io::AsyncBufReadExt::read_line(&mut stdout, &mut line).await?;
+        let grandchild = nix::unistd::Pid::from_raw(line.trim().parse()?);
+
+        tree.kill();
+        child.wait().await?;
+        // Give the system a moment to reap the killed grandchild.
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        assert!(nix::sys::signal::kill(grandchild, None).is_err());
+        Ok(())
+    }
+}
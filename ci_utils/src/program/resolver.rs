@@ -1,5 +1,21 @@
 use crate::prelude::*;
 
+use std::lazy::SyncLazy;
+use std::sync::Mutex;
+
+
+/// The inputs of a lookup: the executable names, the searched directories and the working
+/// directory.
+type CacheKey = (Vec<OsString>, OsString, PathBuf);
+
+/// Results of the lookups done so far in this process.
+static CACHE: SyncLazy<Mutex<HashMap<CacheKey, PathBuf>>> = SyncLazy::new(default);
+
+/// Forget the results of the previous lookups, e.g. after installing a program.
+pub fn clear_cache() {
+    CACHE.lock().unwrap().clear();
+}
+
 pub struct Resolver<P> {
     pub cwd:          PathBuf,
     pub names:        Vec<OsString>,
@@ -9,7 +25,7 @@ pub struct Resolver<P> {
 
 impl<P> Resolver<P> {
     pub fn new(names: Vec<&str>, fallback_dirs: Vec<PathBuf>) -> Result<Self> {
-        let path = std::env::var_os("PATH").unwrap_or_default();
+        let path = crate::env::overlay::var_os("PATH").unwrap_or_default();
         let env_path_dirs = std::env::split_paths(&path);
         let lookup_dirs = std::env::join_paths(env_path_dirs.chain(fallback_dirs.clone()))?;
         let names = names.into_iter().map(OsString::from).collect();
@@ -29,14 +45,52 @@ impl<P> Resolver<P> {
             .flatten()
     }
 
+    /// Like [`Resolver::lookup`], but reuses the result of the same lookup done earlier.
+    ///
+    /// The cache is keyed by all the lookup inputs, including `PATH`, so changing the environment
+    /// is respected. A cached executable that has been removed is looked up again.
+    pub fn lookup_cached(self) -> Result<PathBuf> {
+        let key = (self.names.clone(), self.lookup_dirs.clone(), self.cwd.clone());
+        if let Some(path) = CACHE.lock().unwrap().get(&key) && path.exists() {
+            return Ok(path.clone());
+        }
+        let path = self.lookup()?;
+        CACHE.lock().unwrap().insert(key, path.clone());
+        Ok(path)
+    }
+
+    /// Find the first executable matching any of the names, trying the names in order.
+    ///
+    /// On failure, the error lists all the attempted names and locations.
     pub fn lookup(self) -> Result<PathBuf> {
         let empty = Cow::from("<MISSING NAME>");
         let names = self.names.iter().map(|name| name.to_string_lossy()).collect_vec();
         let name = names.first().unwrap_or(&empty).to_string();
-        let names = names.join(", ");
+        let names = names.iter().map(|name| format!("`{name}`")).join(", ");
         let locations = self.lookup_dirs.clone();
+        let variable = super::location::override_variable_name(&name);
         self.lookup_all().next().ok_or_else(|| {
-            anyhow!("Failed to find a program `{}`. Recognized executable names: {}. Tested locations: {}", name, names, locations.to_string_lossy())
+            anyhow!(
+                "Failed to find a program `{name}`. Tried executable names: {names}. Tested \
+                locations: {}. The location can be also given explicitly with the {variable} \
+                environment variable.",
+                locations.to_string_lossy()
+            )
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_all_names() {
+        let names = vec!["enso-test-missing", "enso-test-missing2"];
+        let error = Resolver::<()>::new(names, vec![]).unwrap().lookup().unwrap_err().to_string();
+        assert!(
+            error.contains("Tried executable names: `enso-test-missing`, `enso-test-missing2`.")
+        );
+        assert!(error.contains("ENSO_PROGRAM_ENSO_TEST_MISSING"));
+    }
+}
@@ -0,0 +1,170 @@
+//! Retrying commands that are known to fail for transient reasons, like network hiccups.
+
+use crate::prelude::*;
+
+use std::process::ExitStatus;
+use std::time::Duration;
+
+
+/// Failed attempt to run a command.
+#[derive(Debug)]
+pub struct AttemptFailure {
+    pub error:  anyhow::Error,
+    /// Exit status of the process, if it ran to completion.
+    pub status: Option<ExitStatus>,
+    /// Standard error of the process. If the output was not captured, only its recent lines.
+    pub stderr: String,
+}
+
+impl From<anyhow::Error> for AttemptFailure {
+    fn from(error: anyhow::Error) -> Self {
+        Self { error, status: None, stderr: default() }
+    }
+}
+
+impl From<std::io::Error> for AttemptFailure {
+    fn from(error: std::io::Error) -> Self {
+        anyhow::Error::from(error).into()
+    }
+}
+
+impl From<tokio::task::JoinError> for AttemptFailure {
+    fn from(error: tokio::task::JoinError) -> Self {
+        anyhow::Error::from(error).into()
+    }
+}
+
+/// Decides whether the failed attempt should be retried.
+pub type Condition = Arc<dyn Fn(&AttemptFailure) -> bool + Send + Sync>;
+
+/// How many times and when a failing command is retried.
+///
+/// The delay before the first retry is `initial_delay`, then it is multiplied by the
+/// `backoff_factor` for each subsequent retry, up to `max_delay`.
+#[derive(Clone, Derivative)]
+#[derivative(Debug)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one.
+    pub max_attempts:   usize,
+    pub initial_delay:  Duration,
+    pub backoff_factor: u32,
+    pub max_delay:      Duration,
+    #[derivative(Debug = "ignore")]
+    pub condition:      Condition,
+}
+
+impl RetryPolicy {
+    /// Retry any failure, up to the given total number of attempts.
+    pub fn new(max_attempts: usize) -> Self {
+        Self {
+            max_attempts,
+            initial_delay: Duration::from_secs(2),
+            backoff_factor: 2,
+            max_delay: Duration::from_secs(60),
+            condition: Arc::new(|_| true),
+        }
+    }
+
+    pub fn backoff(mut self, initial_delay: Duration, backoff_factor: u32) -> Self {
+        self.initial_delay = initial_delay;
+        self.backoff_factor = backoff_factor;
+        self
+    }
+
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Retry only the failures satisfying the condition.
+    pub fn retry_if(
+        mut self,
+        condition: impl Fn(&AttemptFailure) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.condition = Arc::new(condition);
+        self
+    }
+
+    /// Retry only the failures whose standard error contains any of the given patterns.
+    pub fn retry_if_stderr_contains(self, patterns: impl IntoIterator<Item: Into<String>>) -> Self {
+        let patterns = patterns.into_iter().map(Into::into).collect_vec();
+        self.retry_if(move |failure| patterns.iter().any(|p| failure.stderr.contains(p.as_str())))
+    }
+
+    /// Delay before the given retry, counted from 1.
+    pub fn delay_before(&self, retry: usize) -> Duration {
+        let exponent = u32::try_from(retry.saturating_sub(1)).unwrap_or(u32::MAX);
+        let factor = self.backoff_factor.saturating_pow(exponent);
+        self.initial_delay.saturating_mul(factor).min(self.max_delay)
+    }
+
+    /// Run the first attempt and, while it is allowed, the subsequent ones.
+    pub async fn run<T, F>(&self, first: F, mut next: impl FnMut() -> F) -> Result<T>
+    where F: Future<Output = std::result::Result<T, AttemptFailure>> {
+        let mut attempt = first;
+        let mut attempts_made = 1;
+        loop {
+            match attempt.await {
+                Ok(value) => return Ok(value),
+                Err(failure) if attempts_made < self.max_attempts && (self.condition)(&failure) => {
+                    let delay = self.delay_before(attempts_made);
+                    warn!(
+                        "Attempt {attempts_made}/{} failed, retrying in {delay:?}: {:?}",
+                        self.max_attempts, failure.error
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempts_made += 1;
+                    attempt = next();
+                }
+                Err(failure) if attempts_made > 1 =>
+                    return Err(failure.error.context(format!("Failed {attempts_made} times."))),
+                Err(failure) => return Err(failure.error),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn delays() {
+        let policy = RetryPolicy::new(10)
+            .backoff(Duration::from_secs(1), 3)
+            .max_delay(Duration::from_secs(20));
+        let delays = (1..=4).map(|retry| policy.delay_before(retry).as_secs()).collect_vec();
+        assert_eq!(delays, [1, 3, 9, 20]);
+    }
+
+    #[tokio::test]
+    async fn retries_matching_failures() -> Result {
+        let failure = |stderr: &str| AttemptFailure {
+            error:  anyhow!("Failed."),
+            status: None,
+            stderr: stderr.into(),
+        };
+        let policy = RetryPolicy::new(3)
+            .backoff(Duration::ZERO, 1)
+            .retry_if_stderr_contains(["toomanyrequests"]);
+
+        let attempts = AtomicUsize::new(0);
+        let attempt = || {
+            let count = attempts.fetch_add(1, Ordering::SeqCst) + 1;
+            let result = if count < 3 { Err(failure("toomanyrequests")) } else { Ok(count) };
+            ready(result)
+        };
+        assert_eq!(policy.run(attempt(), attempt).await?, 3);
+
+        let attempts = AtomicUsize::new(0);
+        let attempt = || {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            ready(Err::<(), _>(failure("no such image")))
+        };
+        assert!(policy.run(attempt(), attempt).await.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
+}
@@ -1,11 +1,41 @@
 use crate::prelude::*;
 
+use std::process::Stdio;
+
+
 pub trait Shell: Program {
     fn run_command(&self) -> Result<Command>;
     fn run_script(&self, script_path: impl AsRef<Path>) -> Result<Command>;
     fn run_shell(&self) -> Result<Command>;
 }
 
+/// Result of a script run, successful or not.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScriptOutput {
+    /// Exit code of the shell. `None` if it was terminated by a signal.
+    pub exit_code: Option<i32>,
+    pub stdout:    String,
+    pub stderr:    String,
+}
+
+impl ScriptOutput {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// Run the command, capturing its output. Unlike [`Command::output_ok`], a non-zero exit code is
+/// not an error, so the caller can handle it.
+pub async fn capture(command: &mut Command) -> Result<ScriptOutput> {
+    command.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+    let output = command.spawn()?.wait_with_output().await?;
+    Ok(ScriptOutput {
+        exit_code: output.status.code(),
+        stdout:    String::from_utf8_lossy(&output.stdout).into(),
+        stderr:    String::from_utf8_lossy(&output.stderr).into(),
+    })
+}
+
 // Deduces shell from file extension.
 pub fn run_script(script_path: impl AsRef<Path>) -> Result<Command> {
     let shell_kind = match script_path.as_ref().extension() {
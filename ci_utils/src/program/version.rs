@@ -1,5 +1,8 @@
 use crate::prelude::*;
 use regex::Regex;
+use semver::VersionReq;
+use std::lazy::SyncLazy;
+use std::sync::Mutex;
 
 // Taken from the official semver description:
 // https://semver.org/#is-there-a-suggested-regular-expression-regex-to-check-a-semver-string
@@ -20,6 +23,53 @@ pub fn find_in_text(text: &str) -> anyhow::Result<Version> {
     Version::from_str(version_text)
 }
 
+/// Like [`find_in_text`], but also accepts versions with only major and minor components (like
+/// `tar (GNU tar) 1.26`). The missing patch component is assumed to be zero.
+pub fn find_in_text_lenient(text: &str) -> anyhow::Result<Version> {
+    if let Ok(version) = find_in_text(text) {
+        return Ok(version);
+    }
+    // unwrap safe, as the regex is a constant.
+    let regex = Regex::new(r"(\d+)\.(\d+)(?:\.(\d+))?").unwrap();
+    let captures = regex
+        .captures(text)
+        .ok_or_else(|| anyhow!("Failed to find version string within the following: {}", text))?;
+    let component = |index| captures.get(index).map_or(Ok(0), |m| m.as_str().parse::<u64>());
+    Ok(Version::new(component(1)?, component(2)?, component(3)?))
+}
+
+/// Versions of the programs already probed in this process, keyed by the executable path.
+static PROBED: SyncLazy<Mutex<HashMap<PathBuf, Version>>> = SyncLazy::new(default);
+
+/// Version of the given executable, if it was already probed.
+pub fn cached(executable: &Path) -> Option<Version> {
+    PROBED.lock().unwrap().get(executable).cloned()
+}
+
+/// Remember the version of the given executable, so it does not need to be probed again.
+pub fn remember(executable: impl Into<PathBuf>, version: Version) {
+    PROBED.lock().unwrap().insert(executable.into(), version);
+}
+
+/// Fail with an actionable message if the found version does not satisfy the requirement.
+///
+/// The `purpose` describes what needs the required version, e.g. the used option.
+pub fn check_requirement(
+    program_name: &str,
+    found: &Version,
+    requirement: &VersionReq,
+    purpose: Option<&str>,
+) -> Result {
+    if !requirement.matches(found) {
+        match purpose {
+            Some(purpose) =>
+                bail!("Found {program_name} {found}, need {requirement} for {purpose}."),
+            None => bail!("Found {program_name} {found}, need {requirement}."),
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -40,4 +90,22 @@ mod tests {
         assert_eq!(version.build, <_>::default());
         Ok(())
     }
+
+    #[test]
+    fn lenient_parsing() -> Result {
+        assert_eq!(find_in_text_lenient("tar (GNU tar) 1.26")?, Version::new(1, 26, 0));
+        assert_eq!(find_in_text_lenient("bsdtar 3.5.1 - libarchive 3.5.1")?, Version::new(3, 5, 1));
+        assert!(find_in_text_lenient("tar (busybox)").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn requirement_message() -> Result {
+        let found = Version::new(1, 26, 0);
+        let requirement = VersionReq::parse(">=1.30")?;
+        let error = check_requirement("tar", &found, &requirement, Some("--sort")).unwrap_err();
+        assert_eq!(error.to_string(), "Found tar 1.26.0, need >=1.30 for --sort.");
+        check_requirement("tar", &Version::new(1, 34, 0), &requirement, None)?;
+        Ok(())
+    }
 }
@@ -0,0 +1,210 @@
+//! Detection of hung processes.
+//!
+//! A process that produces no output and reports no progress for a configured time is considered
+//! hung. Before it gets killed, the registered diagnostics are run, so the logs contain information
+//! on what the process was doing (e.g. thread stack dumps).
+
+use crate::prelude::*;
+
+use crate::ok_ready_boxed;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+use sysinfo::PidExt;
+use sysinfo::ProcessExt;
+use sysinfo::SystemExt;
+
+
+crate::define_env_var!(
+    /// Minutes without output after which a watched build process is considered hung.
+    ENSO_BUILD_WATCHDOG_MINUTES,
+    u64
+);
+
+/// Inactivity limit used by [`Watchdog::from_env`], unless overridden by
+/// [`ENSO_BUILD_WATCHDOG_MINUTES`].
+pub const DEFAULT_INACTIVITY_LIMIT: Duration = Duration::from_secs(30 * 60);
+
+/// Timestamp of the last observed activity of a process.
+///
+/// The process output is recorded automatically. Other events (like progress bar updates) can be
+/// recorded by calling [`Activity::touch`] on a clone of this handle.
+#[derive(Clone, Debug)]
+pub struct Activity {
+    last: Arc<Mutex<Instant>>,
+}
+
+impl Default for Activity {
+    fn default() -> Self {
+        Self { last: Arc::new(Mutex::new(Instant::now())) }
+    }
+}
+
+impl Activity {
+    /// Record that the process is alive.
+    pub fn touch(&self) {
+        *self.last.lock().unwrap() = Instant::now();
+    }
+
+    /// Time elapsed since the last recorded activity.
+    pub fn idle_time(&self) -> Duration {
+        self.last.lock().unwrap().elapsed()
+    }
+}
+
+/// Action to be run when the process is found to be hung.
+pub trait Diagnostic: Debug + Send + Sync {
+    /// Run the diagnostic for the process with a given PID.
+    ///
+    /// Failures are reported, but do not prevent other diagnostics from being run.
+    fn run(&self, pid: u32) -> BoxFuture<'static, Result>;
+}
+
+/// Dumps the native thread stacks of the process using the platform's tooling.
+///
+/// Uses `gdb` on Linux and `sample` on macOS. On Windows no tool is available out of the box, so
+/// this is a no-op.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StackDump;
+
+impl Diagnostic for StackDump {
+    fn run(&self, pid: u32) -> BoxFuture<'static, Result> {
+        let mut cmd = match TARGET_OS {
+            OS::Linux => {
+                let mut cmd = Command::new("gdb");
+                cmd.args(["-batch", "-ex", "thread apply all bt", "-p"]).arg(pid.to_string());
+                cmd
+            }
+            OS::MacOS => {
+                let mut cmd = Command::new("sample");
+                cmd.arg(pid.to_string()).arg("1");
+                cmd
+            }
+            _ => {
+                warn!("Native stack dumps are not supported on {TARGET_OS}.");
+                return ok_ready_boxed(());
+            }
+        };
+        cmd.run_ok()
+    }
+}
+
+/// Dumps the thread stacks of all the JVM processes in the process tree using `jstack`.
+///
+/// This is the most useful diagnostic for the `sbt` and `gradle` builds.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JvmThreadDump;
+
+impl Diagnostic for JvmThreadDump {
+    fn run(&self, pid: u32) -> BoxFuture<'static, Result> {
+        let jvm_pids = process_tree(pid)
+            .into_iter()
+            .filter(|(_, name)| name.to_lowercase().starts_with("java"))
+            .map(|(pid, _)| pid)
+            .collect_vec();
+        async move {
+            for jvm_pid in jvm_pids {
+                Command::new("jstack").arg("-l").arg(jvm_pid.to_string()).run_ok().await?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// PIDs and names of the process and all its descendants.
+pub fn process_tree(root: u32) -> Vec<(u32, String)> {
+    let mut system = sysinfo::System::new();
+    system.refresh_processes();
+    let mut ret = vec![];
+    let mut to_visit = vec![root];
+    while let Some(pid) = to_visit.pop() {
+        if let Some(process) = system.process(sysinfo::Pid::from_u32(pid)) {
+            ret.push((pid, process.name().to_string()));
+        }
+        to_visit.extend(
+            system
+                .processes()
+                .values()
+                .filter(|process| process.parent() == Some(sysinfo::Pid::from_u32(pid)))
+                .map(|process| process.pid().as_u32()),
+        );
+    }
+    ret
+}
+
+/// Configuration of the hung process detection.
+#[derive(Clone, Debug)]
+pub struct Watchdog {
+    /// How long the process may be inactive before it is considered hung.
+    pub inactivity_limit: Duration,
+    /// Diagnostics to run on the hung process before it is killed.
+    pub diagnostics:      Vec<Arc<dyn Diagnostic>>,
+    /// Activity tracker of the watched process.
+    pub activity:         Activity,
+}
+
+impl Watchdog {
+    /// Create a watchdog with the default diagnostics (native and JVM stack dumps).
+    pub fn new(inactivity_limit: Duration) -> Self {
+        Self {
+            inactivity_limit,
+            diagnostics: vec![Arc::new(StackDump), Arc::new(JvmThreadDump)],
+            activity: default(),
+        }
+    }
+
+    /// Create a watchdog with the default diagnostics and the inactivity limit set by
+    /// [`ENSO_BUILD_WATCHDOG_MINUTES`], or [`DEFAULT_INACTIVITY_LIMIT`] if it is not set.
+    pub fn from_env() -> Self {
+        let limit = ENSO_BUILD_WATCHDOG_MINUTES
+            .get()
+            .map_or(DEFAULT_INACTIVITY_LIMIT, |minutes| Duration::from_secs(minutes * 60));
+        Self::new(limit)
+    }
+
+    /// Replace the diagnostics to be run on the hung process.
+    pub fn with_diagnostics(mut self, diagnostics: Vec<Arc<dyn Diagnostic>>) -> Self {
+        self.diagnostics = diagnostics;
+        self
+    }
+
+    /// Whether the process has been inactive for too long.
+    pub fn is_hung(&self) -> bool {
+        self.activity.idle_time() >= self.inactivity_limit
+    }
+
+    /// Time left until the process will be considered hung, unless some activity is recorded.
+    pub fn time_left(&self) -> Duration {
+        self.inactivity_limit.saturating_sub(self.activity.idle_time())
+    }
+
+    /// Run all the diagnostics on the process.
+    pub async fn diagnose(&self, pid: u32) {
+        warn!(
+            "Process {pid} has been inactive for {:?}. Running {} diagnostics.",
+            self.inactivity_limit,
+            self.diagnostics.len()
+        );
+        for diagnostic in &self.diagnostics {
+            if let Err(e) = diagnostic.run(pid).await {
+                warn!("Diagnostic {diagnostic:?} failed: {e:?}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inactivity() {
+        let watchdog = Watchdog::new(Duration::from_millis(50));
+        assert!(!watchdog.is_hung());
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(watchdog.is_hung());
+        watchdog.activity.touch();
+        assert!(!watchdog.is_hung());
+    }
+}
@@ -1,16 +1,22 @@
 use crate::prelude::*;
 
+pub mod appimagetool;
 pub mod cargo;
 pub mod cmd;
 pub mod conda;
 pub mod docker;
+pub mod dpkg;
+pub mod electron_builder;
 pub mod flatc;
+pub mod gh;
 pub mod git;
 pub mod go;
 pub mod graal;
+pub mod hdiutil;
 pub mod java;
 pub mod javac;
 pub mod node;
+#[deprecated(note = "Use `ide_ci::programs::node::Npx` instead.")]
 pub mod npx;
 pub mod pwsh;
 pub mod robocopy;
@@ -20,23 +26,28 @@ pub mod rustup;
 pub mod sbt;
 pub mod seven_zip;
 pub mod sh;
+pub mod signing;
 pub mod tar;
 pub mod vs;
 pub mod vswhere;
 pub mod wasm_opt;
 pub mod wasm_pack;
+pub mod wix;
 
 pub use cargo::Cargo;
 pub use cmd::Cmd;
 pub use conda::Conda;
 pub use docker::Docker;
+pub use electron_builder::ElectronBuilder;
 pub use flatc::Flatc;
+pub use gh::Gh;
 pub use git::Git;
 pub use go::Go;
 pub use java::Java;
 pub use javac::Javac;
 pub use node::Node;
 pub use node::Npm;
+pub use node::Npx;
 pub use pwsh::PwSh;
 pub use sbt::Sbt;
 pub use seven_zip::SevenZip;
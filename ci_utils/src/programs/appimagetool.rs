@@ -0,0 +1,37 @@
+//! Wrapper over `appimagetool`, building AppImages out of the `AppDir` directories.
+
+use crate::prelude::*;
+
+
+/// The `appimagetool` program.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AppImageTool;
+
+impl Program for AppImageTool {
+    fn executable_name(&self) -> &'static str {
+        "appimagetool"
+    }
+
+    fn alternative_names(&self) -> Vec<&str> {
+        vec!["appimagetool-x86_64.AppImage", "appimagetool-aarch64.AppImage"]
+    }
+}
+
+impl AppImageTool {
+    /// Build the AppImage for the given architecture out of the `AppDir`.
+    pub async fn build(
+        &self,
+        app_dir: impl AsRef<Path>,
+        arch: Arch,
+        output: impl AsRef<Path>,
+    ) -> Result {
+        self.cmd()?
+            .env("ARCH", arch.as_str())
+            // The tool is usually an AppImage itself, and the CI runners often lack FUSE.
+            .env("APPIMAGE_EXTRACT_AND_RUN", "1")
+            .arg(app_dir.as_ref())
+            .arg(output.as_ref())
+            .run_ok()
+            .await
+    }
+}
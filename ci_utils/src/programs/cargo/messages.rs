@@ -0,0 +1,156 @@
+//! Machine-readable output of Cargo, as enabled by `--message-format=json`.
+//!
+//! See: <https://doc.rust-lang.org/cargo/reference/external-tools.html#json-messages>
+
+use crate::prelude::*;
+
+use crate::actions::annotations;
+use crate::actions::workflow;
+use crate::actions::workflow::Location;
+use crate::actions::workflow::MessageLevel;
+use crate::program::command::spawn_log_processor;
+use cargo_metadata::diagnostic::Diagnostic;
+use cargo_metadata::diagnostic::DiagnosticLevel;
+use cargo_metadata::Artifact;
+use cargo_metadata::Message;
+use std::process::Stdio;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::BufReader;
+use tokio::io::Lines;
+use tokio::process::Child;
+use tokio::process::ChildStdout;
+
+
+pub const MESSAGE_FORMAT_JSON: &str = "--message-format=json";
+
+/// Parse a single line of Cargo's standard output.
+///
+/// Lines that are not Cargo messages (e.g. the output of the run program or test harness) are
+/// returned as [`Message::TextLine`].
+pub fn parse_line(line: String) -> Message {
+    serde_json::from_str(&line).unwrap_or(Message::TextLine(line))
+}
+
+/// Running Cargo process, whose standard output is parsed into messages.
+///
+/// The standard error, with the human-readable progress, is logged as usual.
+pub struct MessageStream {
+    child: Child,
+    lines: Lines<BufReader<ChildStdout>>,
+}
+
+impl MessageStream {
+    /// Spawn the `cargo build`, `cargo test` or `cargo run` command with JSON message output.
+    pub fn spawn(command: &mut crate::program::Command) -> Result<Self> {
+        command.arg(MESSAGE_FORMAT_JSON);
+        command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        let mut child = command.spawn()?;
+        let stderr = child.stderr.take().context("Failed to get cargo stderr handle.")?;
+        spawn_log_processor("cargo⚠️".into(), stderr);
+        let stdout = child.stdout.take().context("Failed to get cargo stdout handle.")?;
+        Ok(Self { child, lines: BufReader::new(stdout).lines() })
+    }
+
+    /// Get the next message. Returns `None` once Cargo closes its output.
+    pub async fn next(&mut self) -> Result<Option<Message>> {
+        Ok(self.lines.next_line().await?.map(parse_line))
+    }
+
+    /// Convert into a stream of messages. The exit status of the process is not checked.
+    pub fn into_stream(self) -> BoxStream<'static, Result<Message>> {
+        futures::stream::unfold(self, |mut this| async move {
+            this.next().await.transpose().map(|message| (message, this))
+        })
+        .boxed()
+    }
+
+    /// Skip the remaining messages and wait for the process to finish successfully.
+    pub async fn wait_ok(mut self) -> Result {
+        while self.next().await?.is_some() {}
+        self.child.wait().await?.exit_ok()?;
+        Ok(())
+    }
+}
+
+/// Run the command, reporting compiler diagnostics. Returns the artifacts that were built.
+///
+/// Diagnostics are logged and, when running on GitHub Actions, also reported as annotations of
+/// the relevant source lines. Other output is logged.
+pub async fn run_reporting(command: &mut crate::program::Command) -> Result<Vec<Artifact>> {
+    let mut messages = MessageStream::spawn(command)?;
+    let mut artifacts = Vec::new();
+    while let Some(message) = messages.next().await? {
+        match message {
+            Message::CompilerArtifact(artifact) => artifacts.push(artifact),
+            Message::CompilerMessage(message) => report_diagnostic(&message.message),
+            Message::TextLine(line) => info!("cargoℹ️ {line}"),
+            _ => {}
+        }
+    }
+    messages.wait_ok().await?;
+    Ok(artifacts)
+}
+
+/// Paths of the executables (binaries, tests, examples) among the artifacts.
+pub fn executables(artifacts: &[Artifact]) -> Vec<PathBuf> {
+    artifacts
+        .iter()
+        .filter_map(|artifact| artifact.executable.as_ref())
+        .map(|path| path.clone().into_std_path_buf())
+        .collect()
+}
+
+/// Convert the compiler diagnostic into a GitHub Actions annotation.
+///
+/// Returns `None` for diagnostics that are not worth annotating, like notes.
+pub fn annotation(diagnostic: &Diagnostic) -> Option<workflow::Message> {
+    let level = match diagnostic.level {
+        DiagnosticLevel::Ice | DiagnosticLevel::Error => MessageLevel::Error,
+        DiagnosticLevel::Warning => MessageLevel::Warning,
+        _ => return None,
+    };
+    let location = diagnostic.spans.iter().find(|span| span.is_primary).map(|span| Location {
+        file:       span.file_name.clone().into(),
+        line:       Some(span.line_start),
+        end_line:   Some(span.line_end),
+        column:     Some(span.column_start),
+        end_column: Some(span.column_end),
+    });
+    let text = diagnostic.rendered.clone().unwrap_or_else(|| diagnostic.message.clone());
+    Some(workflow::Message { level, text, title: Some(diagnostic.message.clone()), location })
+}
+
+fn report_diagnostic(diagnostic: &Diagnostic) {
+    let rendered = diagnostic.rendered.as_deref().unwrap_or(&diagnostic.message);
+    match diagnostic.level {
+        // The annotation is reported below, with the location.
+        DiagnosticLevel::Ice | DiagnosticLevel::Error => error!(annotated = true, "{rendered}"),
+        DiagnosticLevel::Warning => warn!(annotated = true, "{rendered}"),
+        _ => info!("{rendered}"),
+    }
+    if let Some(annotation) = annotation(diagnostic) {
+        annotations::report(annotation);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_messages() {
+        let line = r#"{"reason":"compiler-message","package_id":"enso-build 0.1.0 (path+file:///repo/build)","manifest_path":"/repo/build/Cargo.toml","target":{"kind":["lib"],"crate_types":["lib"],"name":"enso-build","src_path":"/repo/build/src/lib.rs","edition":"2021","doc":true,"doctest":true,"test":true},"message":{"rendered":"warning: unused variable: `x`\n","children":[],"code":{"code":"unused_variables","explanation":null},"level":"warning","message":"unused variable: `x`","spans":[{"byte_end":10,"byte_start":9,"column_end":10,"column_start":9,"expansion":null,"file_name":"build/src/lib.rs","is_primary":true,"label":null,"line_end":3,"line_start":3,"suggested_replacement":null,"suggestion_applicability":null,"text":[]}]}}"#;
+        let diagnostic = match parse_line(line.into()) {
+            Message::CompilerMessage(message) => message.message,
+            other => panic!("Unexpected message: {other:?}"),
+        };
+        let annotation = annotation(&diagnostic).unwrap();
+        assert_eq!(
+            annotation.parameters(),
+            "title=unused variable%3A `x`,file=build/src/lib.rs,line=3,endLine=3,col=9,endColumn=10"
+        );
+
+        let text = "test tests::parse_messages ... ok";
+        assert_eq!(parse_line(text.into()), Message::TextLine(text.into()));
+    }
+}
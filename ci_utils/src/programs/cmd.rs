@@ -1,6 +1,8 @@
 use crate::env;
 use crate::env::Modification;
 use crate::prelude::*;
+use crate::program::shell::capture;
+use crate::program::shell::ScriptOutput;
 use crate::programs::cmd::args::RUN_COMMAND;
 use std::process::Stdio;
 use unicase::UniCase;
@@ -44,6 +46,58 @@ impl Shell for Cmd {
     }
 }
 
+/// Quote the argument for use in a batch script.
+///
+/// The argument is quoted as expected by the invoked program (see
+/// [`quote_argument`](crate::os::windows::quote_argument)) and then escaped from cmd. Fails for
+/// arguments that cannot be passed through cmd, i.e. the ones containing line breaks.
+pub fn quote(arg: &str) -> Result<String> {
+    ensure!(
+        !arg.contains(['\n', '\r']),
+        "Argument cannot be passed through cmd, as it contains line breaks: {arg}"
+    );
+    let quoted = crate::os::windows::quote_argument(arg);
+    if !arg.contains('"') {
+        // Within double quotes, the other special characters are not interpreted, except for
+        // `%` that marks variable expansion.
+        return Ok(quoted.replace('%', "%%"));
+    }
+    // The escaped quotes inside would toggle the cmd's own quoting, exposing the special
+    // characters. Thus, all of them (including the quotes) are escaped with carets instead.
+    Ok(quoted
+        .chars()
+        .flat_map(|c| match c {
+            '%' => vec!['%', '%'],
+            '^' | '&' | '|' | '<' | '>' | '(' | ')' | '"' => vec!['^', c],
+            _ => vec![c],
+        })
+        .collect())
+}
+
+/// Generate the batch script line invoking the program with the given arguments.
+pub fn invocation(
+    program: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item: AsRef<OsStr>>,
+) -> Result<String> {
+    let quoted = |arg: &OsStr| quote(&arg.to_string_lossy());
+    let program = quoted(program.as_ref())?;
+    let args = args.into_iter().map(|arg| quoted(arg.as_ref())).collect_result()?;
+    Ok(once(program).chain(args).join(" "))
+}
+
+/// Run the batch script with given contents and capture its output and exit code.
+///
+/// The script is written to a temporary file, so it is not subject to the command line parsing.
+pub async fn capture_script_text(script: &str) -> Result<ScriptOutput> {
+    let temp = crate::fs::temp::dir("script")?;
+    let script_path = temp.path().join("script.cmd");
+    crate::fs::write(&script_path, format!("@echo off\r\n{}\r\n", script.replace('\n', "\r\n")))?;
+    let output = capture(&mut Cmd.run_script(&script_path)?).await?;
+    // The script must exist until it finishes.
+    drop(temp);
+    Ok(output)
+}
+
 pub fn run_commands<'a, Cmds, Arg>(commands: Cmds) -> anyhow::Result<Command>
 where
     Cmds: IntoIterator<Item: IntoIterator<Item = Arg>>,
@@ -150,6 +204,17 @@ pub fn parse_dumped_env(output: &str) -> Result<BTreeMap<UniCase<String>, String
 mod tests {
     use super::*;
 
+    #[test]
+    fn quoting() -> Result {
+        assert_eq!(quote("C:\\Program Files\\Enso")?, r#""C:\Program Files\Enso""#);
+        assert_eq!(quote("100% & more")?, r#""100%% & more""#);
+        assert_eq!(quote(r#"say "hi" & exit"#)?, r#"^"say \^"hi\^" ^& exit^""#);
+        assert_eq!(quote(r"C:\dist\")?, r#""C:\dist\\""#);
+        assert!(quote("two\nlines").is_err());
+        assert_eq!(invocation("signtool", ["sign", "/a"])?, r#""signtool" "sign" "/a""#);
+        Ok(())
+    }
+
     #[test]
     fn path_like() {
         assert!(is_path_like("Path"));
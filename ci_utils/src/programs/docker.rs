@@ -1,11 +1,14 @@
 use crate::prelude::*;
 
 use crate::env::new::TypedVariable;
+use crate::program::retry::RetryPolicy;
 use shrinkwraprs::Shrinkwrap;
 use std::collections::HashMap;
 use std::fmt::Formatter;
 use std::process::Stdio;
 use std::str::FromStr;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
 
 #[derive(Clone, Debug, PartialEq, Ord, PartialOrd, Eq, Hash)]
 pub enum NetworkDriver {
@@ -76,6 +79,14 @@ impl Program for Docker {
 
 impl Docker {
     pub async fn build(&self, options: BuildOptions) -> Result<ImageId> {
+        if options.buildx.is_some() {
+            return self.buildx_build(&options).await;
+        }
+        ensure!(
+            options.platforms.len() <= 1,
+            "Building for multiple platforms ({}) requires buildx.",
+            options.platforms.join(", ")
+        );
         let mut command = self.cmd()?;
         command.arg("build").args(options.args());
         debug!("{:?}", command);
@@ -93,6 +104,57 @@ impl Docker {
         Ok(ImageId(built_image_id.into()))
     }
 
+    /// Build the image with the BuildKit `buildx` plugin.
+    ///
+    /// If the image is pushed rather than loaded, the returned ID is the digest of the manifest.
+    async fn buildx_build(&self, options: &BuildOptions) -> Result<ImageId> {
+        let iid_file = crate::fs::temp::file("docker-iid", ".txt")?;
+        let mut command = self.cmd()?;
+        command.args(["buildx", "build"]).args(options.args());
+        command.arg("--iidfile").arg(iid_file.path());
+        command.run_ok().await?;
+        let built_image_id = crate::fs::read_to_string(iid_file.path())?.trim().to_string();
+        ensure!(!built_image_id.is_empty(), "Docker buildx did not report the image ID.");
+        debug!("Image {} successfully built!", built_image_id);
+        Ok(ImageId(built_image_id))
+    }
+
+    /// Create a new tag for the image.
+    pub async fn tag(&self, source: &ImageId, target: impl AsRef<str>) -> Result {
+        self.cmd()?.arg("tag").arg(&source.0).arg(target.as_ref()).run_ok().await
+    }
+
+    /// Pull the image (given by its name and tag) from the registry.
+    ///
+    /// Docker Hub limits the pull rate, so the pull is retried if the limit is hit.
+    pub async fn pull(&self, image: impl AsRef<str>) -> Result {
+        let retry = RetryPolicy::new(4)
+            .backoff(Duration::from_secs(15), 2)
+            .retry_if_stderr_contains(["toomanyrequests", "TLS handshake timeout"]);
+        self.cmd()?.arg("pull").arg(image.as_ref()).retry(retry).run_ok().await
+    }
+
+    /// Push the image (given by its name and tag) to the registry.
+    pub async fn push(&self, image: impl AsRef<str>) -> Result {
+        self.cmd()?.arg("push").arg(image.as_ref()).run_ok().await
+    }
+
+    /// Log in to the registry. The password is passed through the standard input, so it does not
+    /// appear in the process list nor in the logs.
+    pub async fn login(&self, credentials: &Credentials) -> Result {
+        let mut cmd = self.cmd()?;
+        cmd.arg("login").arg("--username").arg(&credentials.username).arg("--password-stdin");
+        if let Some(registry) = &credentials.registry {
+            cmd.arg(registry);
+        }
+        let mut child = cmd.stdin(Stdio::piped()).spawn()?;
+        let mut stdin = child.stdin.take().context("Failed to get docker stdin handle.")?;
+        stdin.write_all(credentials.password.as_bytes()).await?;
+        drop(stdin);
+        child.wait().await?.exit_ok()?;
+        Ok(())
+    }
+
     pub fn run_cmd(&self, options: &RunOptions) -> Result<Command> {
         let mut cmd = self.cmd()?;
         cmd.arg("run").args(options.args());
@@ -140,6 +202,36 @@ impl Docker {
             .await
     }
 
+    /// Copy the file or directory out of the container.
+    pub async fn download(
+        &self,
+        container: &ContainerId,
+        from: impl AsRef<Path>,
+        to: impl AsRef<Path>,
+    ) -> Result {
+        self.cmd()?
+            .arg("cp")
+            .arg(format!("{}:{}", container.as_str(), from.as_ref().display()))
+            .arg(to.as_ref())
+            .run_ok()
+            .await
+    }
+
+    /// Copy the file or directory out of the image, without running it.
+    ///
+    /// A temporary container is created for that purpose and removed afterwards.
+    pub async fn extract_from_image(
+        &self,
+        image: &ImageId,
+        from: impl AsRef<Path>,
+        to: impl AsRef<Path>,
+    ) -> Result {
+        let container = self.create(&RunOptions::new(image.clone())).await?;
+        let result = self.download(&container, from, to).await;
+        self.remove_container(&container, true).await?;
+        result
+    }
+
     pub async fn start(&self, container: &ContainerId) -> Result {
         self.cmd()?.arg("start").arg(container.as_str()).run_ok().await
     }
@@ -206,6 +298,64 @@ impl Docker {
     }
 }
 
+/// Credentials for a container registry.
+#[derive(Clone)]
+pub struct Credentials {
+    /// Registry server. If not set, Docker Hub is used.
+    pub registry: Option<String>,
+    pub username: String,
+    pub password: String,
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials")
+            .field("registry", &self.registry)
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Options specific to the BuildKit `buildx` builder.
+#[derive(Clone, Debug, Default)]
+pub struct BuildxOptions {
+    /// Name of the builder instance. If not set, the current one is used.
+    pub builder:    Option<String>,
+    /// Push the built image to the registry.
+    pub push:       bool,
+    /// Load the built image into the local image store. Not supported for multi-platform builds.
+    pub load:       bool,
+    /// External cache sources, e.g. `type=registry,ref=user/app:cache`.
+    pub cache_from: Vec<String>,
+    /// Cache export destinations, e.g. `type=registry,ref=user/app:cache,mode=max`.
+    pub cache_to:   Vec<String>,
+}
+
+impl BuildxOptions {
+    pub fn args(&self) -> Vec<OsString> {
+        let mut ret = Vec::new();
+        if let Some(builder) = self.builder.as_ref() {
+            ret.push("--builder".into());
+            ret.push(builder.into());
+        }
+        if self.push {
+            ret.push("--push".into());
+        }
+        if self.load {
+            ret.push("--load".into());
+        }
+        for cache_from in &self.cache_from {
+            ret.push("--cache-from".into());
+            ret.push(cache_from.into());
+        }
+        for cache_to in &self.cache_to {
+            ret.push("--cache-to".into());
+            ret.push(cache_to.into());
+        }
+        ret
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct BuildOptions {
     pub context:    PathBuf,
@@ -213,6 +363,10 @@ pub struct BuildOptions {
     pub tags:       Vec<String>,
     pub build_args: HashMap<String, Option<String>>,
     pub file:       Option<PathBuf>,
+    /// Target platforms, e.g. `linux/amd64`. More than one platform requires `buildx`.
+    pub platforms:  Vec<String>,
+    /// If set, the image is built with `docker buildx build` rather than `docker build`.
+    pub buildx:     Option<BuildxOptions>,
 }
 
 impl BuildOptions {
@@ -223,9 +377,21 @@ impl BuildOptions {
             tags:       default(),
             build_args: default(),
             file:       default(),
+            platforms:  default(),
+            buildx:     default(),
         }
     }
 
+    pub fn platform(&mut self, platform: impl Into<String>) -> &mut Self {
+        self.platforms.push(platform.into());
+        self
+    }
+
+    pub fn buildx(&mut self, options: BuildxOptions) -> &mut Self {
+        self.buildx = Some(options);
+        self
+    }
+
     pub fn add_build_arg_from_env_or<R>(
         &mut self,
         name: impl AsRef<str>,
@@ -269,6 +435,13 @@ impl BuildOptions {
             // C:\Users\mwu\AppData\Local\Temp\2\.tmpOykTop`
             ret.push(file.without_verbatim_prefix().into());
         }
+        if !self.platforms.is_empty() {
+            ret.push("--platform".into());
+            ret.push(self.platforms.join(",").into());
+        }
+        if let Some(buildx) = self.buildx.as_ref() {
+            ret.extend(buildx.args());
+        }
         ret
     }
 }
@@ -346,6 +519,8 @@ pub struct RunOptions {
     pub storage_size_gb:   Option<usize>,
     /// Proxy all received signals to the process (non-TTY mode only).
     pub sig_proxy:         Option<bool>,
+    /// Platform of the image to use, e.g. `linux/arm64`, if the image supports many.
+    pub platform:          Option<String>,
 }
 
 impl RunOptions {
@@ -362,6 +537,7 @@ impl RunOptions {
             network: default(),
             storage_size_gb: default(),
             sig_proxy: default(),
+            platform: default(),
         }
     }
 
@@ -448,6 +624,11 @@ impl RunOptions {
             ret.push(arg.into());
         }
 
+        if let Some(platform) = self.platform.as_ref() {
+            ret.push("--platform".into());
+            ret.push(platform.into());
+        }
+
         ret.push(OsString::from(&self.image.0));
 
         ret.extend(self.command.clone());
@@ -473,6 +654,28 @@ impl FromStr for ContainerId {
 mod tests {
     use super::*;
 
+    #[test]
+    fn buildx_args() {
+        let mut opts = BuildOptions::new("image");
+        opts.platform("linux/amd64").platform("linux/arm64").buildx(BuildxOptions {
+            push: true,
+            cache_from: vec!["type=registry,ref=enso/runtime:cache".into()],
+            ..default()
+        });
+        assert_eq!(opts.args(), vec![
+            "image",
+            "--platform",
+            "linux/amd64,linux/arm64",
+            "--push",
+            "--cache-from",
+            "type=registry,ref=enso/runtime:cache"
+        ]);
+
+        let mut run = RunOptions::new(ImageId("enso/runtime".into()));
+        run.platform = Some("linux/arm64".into());
+        assert_eq!(run.args(), vec!["--platform", "linux/arm64", "enso/runtime"]);
+    }
+
     #[tokio::test]
     #[ignore]
     async fn network() -> Result {
@@ -0,0 +1,38 @@
+//! Wrapper over `dpkg-deb`, building the Debian packages.
+
+use crate::prelude::*;
+
+
+/// The `dpkg-deb` program.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DpkgDeb;
+
+impl Program for DpkgDeb {
+    fn executable_name(&self) -> &'static str {
+        "dpkg-deb"
+    }
+}
+
+impl DpkgDeb {
+    /// Build the package out of the directory tree, which must contain the `DEBIAN/control` file.
+    ///
+    /// The files are owned by root in the package, regardless of who owns them on disk.
+    pub async fn build(&self, root: impl AsRef<Path>, output: impl AsRef<Path>) -> Result {
+        self.cmd()?
+            .args(["--build", "--root-owner-group"])
+            .arg(root.as_ref())
+            .arg(output.as_ref())
+            .run_ok()
+            .await
+    }
+}
+
+/// Name of the architecture, as used by Debian.
+pub fn arch_name(arch: Arch) -> Result<&'static str> {
+    match arch {
+        Arch::X86_64 => Ok("amd64"),
+        Arch::X86 => Ok("i386"),
+        Arch::AArch64 => Ok("arm64"),
+        other => bail!("Unsupported architecture for Debian packages: {other}."),
+    }
+}
@@ -0,0 +1,179 @@
+//! Wrapper over `electron-builder`, packaging the Electron applications into the installers.
+//!
+//! See https://www.electron.build/cli
+
+use crate::prelude::*;
+
+
+/// The `electron-builder` CLI. It is usually installed as a project's dependency, so
+/// [`build`](ElectronBuilder::build) runs it through [`Npx`](crate::programs::Npx).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ElectronBuilder;
+
+impl Program for ElectronBuilder {
+    fn executable_name(&self) -> &'static str {
+        "electron-builder"
+    }
+}
+
+/// Package formats, as named by `electron-builder`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, strum::AsRefStr, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Target {
+    Nsis,
+    Msi,
+    Dmg,
+    Zip,
+    #[strum(serialize = "AppImage")]
+    AppImage,
+    Deb,
+    Rpm,
+    #[strum(serialize = "tar.gz")]
+    TarGz,
+}
+
+impl Target {
+    /// The platform for which the format is built.
+    pub fn os(self) -> Option<OS> {
+        match self {
+            Target::Nsis | Target::Msi => Some(OS::Windows),
+            Target::Dmg => Some(OS::MacOS),
+            Target::AppImage | Target::Deb | Target::Rpm => Some(OS::Linux),
+            Target::Zip | Target::TarGz => None,
+        }
+    }
+}
+
+/// Code signing of the packages. The `electron-builder` reads the certificate from the
+/// environment.
+#[derive(Clone, Derivative)]
+#[derivative(Debug)]
+pub enum Signing {
+    /// Do not sign, even if a certificate is found in the keychain.
+    Disabled,
+    /// Sign using the certificate file (`.p12` or `.pfx`), given as a path, URL or base64 data.
+    Certificate {
+        link:     String,
+        #[derivative(Debug = "ignore")]
+        password: Option<String>,
+    },
+}
+
+impl Signing {
+    pub fn env(&self) -> Vec<(&'static str, String)> {
+        match self {
+            Signing::Disabled => vec![("CSC_IDENTITY_AUTO_DISCOVERY", "false".into())],
+            Signing::Certificate { link, password } => {
+                let mut ret = vec![("CSC_LINK", link.clone())];
+                ret.extend(password.iter().map(|password| ("CSC_KEY_PASSWORD", password.clone())));
+                ret
+            }
+        }
+    }
+}
+
+/// Options of the `electron-builder` run.
+#[derive(Clone, Debug)]
+pub struct BuildOptions {
+    pub os:         OS,
+    pub arch:       Option<Arch>,
+    /// Package formats. If empty, the ones from the project's configuration are built.
+    pub targets:    Vec<Target>,
+    /// Configuration file, used instead of the `build` section of `package.json`.
+    pub config:     Option<PathBuf>,
+    pub output_dir: Option<PathBuf>,
+    /// If not set, the signing is up to the project's configuration and the environment.
+    pub signing:    Option<Signing>,
+}
+
+impl BuildOptions {
+    pub fn new(os: OS) -> Self {
+        Self { os, arch: None, targets: default(), config: None, output_dir: None, signing: None }
+    }
+
+    pub fn target(mut self, target: Target) -> Self {
+        self.targets.push(target);
+        self
+    }
+
+    pub fn args(&self) -> Result<Vec<OsString>> {
+        let platform = match self.os {
+            OS::Windows => "--win",
+            OS::MacOS => "--mac",
+            OS::Linux => "--linux",
+            other => bail!("electron-builder does not support {other}."),
+        };
+        let mut ret: Vec<OsString> = vec![platform.into()];
+        for target in &self.targets {
+            if let Some(os) = target.os() {
+                ensure!(os == self.os, "Cannot build {target} packages for {}.", self.os);
+            }
+            ret.push(target.as_ref().into());
+        }
+        if let Some(arch) = self.arch {
+            ret.push(
+                match arch {
+                    Arch::X86_64 => "--x64",
+                    Arch::AArch64 => "--arm64",
+                    other => bail!("electron-builder does not support the {other} architecture."),
+                }
+                .into(),
+            );
+        }
+        if let Some(config) = &self.config {
+            ret.extend(["--config".into(), config.into()]);
+        }
+        if let Some(output_dir) = &self.output_dir {
+            ret.push(format!("--config.directories.output={}", output_dir.display()).into());
+        }
+        // Publishing is done by our release jobs.
+        ret.extend(["--publish".into(), "never".into()]);
+        Ok(ret)
+    }
+}
+
+impl ElectronBuilder {
+    /// Package the Electron project in the given directory.
+    pub async fn build(&self, project_dir: impl AsRef<Path>, options: &BuildOptions) -> Result {
+        let mut cmd = crate::programs::Npx.cmd()?;
+        cmd.arg(self.executable_name());
+        cmd.current_dir(project_dir).args(options.args()?);
+        if let Some(signing) = &options.signing {
+            cmd.envs(signing.env());
+        }
+        cmd.run_ok().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_args() -> Result {
+        let options = BuildOptions {
+            arch: Some(Arch::X86_64),
+            output_dir: Some("dist".into()),
+            ..BuildOptions::new(OS::Linux).target(Target::AppImage).target(Target::TarGz)
+        };
+        assert_eq!(options.args()?, [
+            "--linux",
+            "AppImage",
+            "tar.gz",
+            "--x64",
+            "--config.directories.output=dist",
+            "--publish",
+            "never"
+        ]);
+        assert!(BuildOptions::new(OS::Windows).target(Target::Dmg).args().is_err());
+
+        let signing =
+            Signing::Certificate { link: "cert.p12".into(), password: Some("x".into()) };
+        assert!(!format!("{signing:?}").contains("\"x\""));
+        assert_eq!(signing.env(), [
+            ("CSC_LINK", "cert.p12".into()),
+            ("CSC_KEY_PASSWORD", "x".into())
+        ]);
+        Ok(())
+    }
+}
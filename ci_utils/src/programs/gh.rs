@@ -0,0 +1,184 @@
+//! Wrapper over the GitHub CLI.
+//!
+//! It is an escape hatch for the GitHub features that are not (yet) covered by the REST models of
+//! this crate. The commands output JSON (as requested through `--json` fields), which is parsed
+//! into the structures below.
+
+use crate::prelude::*;
+
+use chrono::DateTime;
+use chrono::Utc;
+
+
+/// The GitHub CLI.
+///
+/// Unless a token is given explicitly, `gh` authenticates using the ambient `GH_TOKEN` or
+/// `GITHUB_TOKEN` environment variable (or its own stored credentials).
+#[derive(Clone, Debug, Default)]
+pub struct Gh {
+    pub token: Option<String>,
+}
+
+impl Program for Gh {
+    fn init_command<'a>(&self, cmd: &'a mut Self::Command) -> &'a mut Self::Command {
+        // Never wait for user input, we are not running interactively.
+        cmd.env("GH_PROMPT_DISABLED", "1");
+        if let Some(token) = &self.token {
+            cmd.env("GH_TOKEN", token);
+        }
+        cmd
+    }
+
+    fn executable_name(&self) -> &'static str {
+        "gh"
+    }
+}
+
+impl Gh {
+    pub fn with_token(token: impl Into<String>) -> Self {
+        Self { token: Some(token.into()) }
+    }
+
+    /// Run the command with the `--json` option requesting the given fields and parse its output.
+    pub async fn json<T: DeserializeOwned>(
+        &self,
+        args: impl IntoIterator<Item: AsRef<OsStr>>,
+        fields: &[&str],
+    ) -> Result<T> {
+        let mut cmd = self.cmd()?;
+        cmd.args(args).arg("--json").arg(fields.join(","));
+        cmd.run_stdout_json().await
+    }
+
+    /// Call the GitHub API endpoint (e.g. `repos/enso-org/enso/actions/caches`) with the `GET`
+    /// method.
+    pub async fn api<T: DeserializeOwned>(&self, endpoint: &str) -> Result<T> {
+        self.api_with_method(endpoint, "GET", &[]).await
+    }
+
+    /// Call the GitHub API endpoint. Fields are given as `key=value` pairs and are sent as
+    /// parameters of the request.
+    pub async fn api_with_method<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        method: &str,
+        fields: &[(&str, &str)],
+    ) -> Result<T> {
+        let mut cmd = self.cmd()?;
+        cmd.args(["api", "--method", method, endpoint]);
+        for (name, value) in fields {
+            cmd.arg("--raw-field").arg(format!("{name}={value}"));
+        }
+        cmd.run_stdout_json().await
+    }
+
+    /// Describe the release with the given tag.
+    pub async fn release_view(&self, repo: &impl RepoPointer, tag: &str) -> Result<ReleaseInfo> {
+        let args = ["release", "view", tag, "--repo", &repo.to_string()];
+        self.json(args, ReleaseInfo::FIELDS).await
+    }
+
+    /// List the most recent workflow runs, optionally only of the given workflow.
+    pub async fn run_list(
+        &self,
+        repo: &impl RepoPointer,
+        workflow: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<RunInfo>> {
+        let mut args = vec!["run".to_string(), "list".into(), "--repo".into(), repo.to_string()];
+        args.extend(["--limit".into(), limit.to_string()]);
+        if let Some(workflow) = workflow {
+            args.extend(["--workflow".into(), workflow.into()]);
+        }
+        self.json(args, RunInfo::FIELDS).await
+    }
+
+    /// Describe the workflow run with the given ID.
+    pub async fn run_view(&self, repo: &impl RepoPointer, run_id: u64) -> Result<RunInfo> {
+        let args =
+            ["run".into(), "view".into(), run_id.to_string(), "--repo".into(), repo.to_string()];
+        self.json(args, RunInfo::FIELDS).await
+    }
+}
+
+/// Release asset, as described by `gh release view --json assets`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetInfo {
+    pub name: String,
+    pub size: u64,
+    pub url:  String,
+}
+
+/// Release, as described by `gh release view --json`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReleaseInfo {
+    pub tag_name:      String,
+    pub name:          String,
+    pub is_draft:      bool,
+    pub is_prerelease: bool,
+    pub published_at:  Option<DateTime<Utc>>,
+    pub url:           String,
+    pub assets:        Vec<AssetInfo>,
+}
+
+impl ReleaseInfo {
+    pub const FIELDS: &'static [&'static str] =
+        &["tagName", "name", "isDraft", "isPrerelease", "publishedAt", "url", "assets"];
+}
+
+/// Workflow run, as described by `gh run list --json` or `gh run view --json`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunInfo {
+    pub database_id:   u64,
+    pub workflow_name: String,
+    pub head_branch:   String,
+    pub head_sha:      String,
+    pub event:         String,
+    /// E.g. `queued`, `in_progress` or `completed`.
+    pub status:        String,
+    /// E.g. `success` or `failure`. Empty if the run has not completed yet.
+    pub conclusion:    String,
+    pub created_at:    DateTime<Utc>,
+    pub url:           String,
+}
+
+impl RunInfo {
+    pub const FIELDS: &'static [&'static str] = &[
+        "databaseId",
+        "workflowName",
+        "headBranch",
+        "headSha",
+        "event",
+        "status",
+        "conclusion",
+        "createdAt",
+        "url",
+    ];
+
+    pub fn is_completed(&self) -> bool {
+        self.status == "completed"
+    }
+
+    pub fn is_successful(&self) -> bool {
+        self.is_completed() && self.conclusion == "success"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_run_list() -> Result {
+        let output = r#"[{"conclusion":"","createdAt":"2022-06-01T12:00:00Z","databaseId":2421,
+            "event":"push","headBranch":"develop","headSha":"1a2b3c","status":"in_progress",
+            "url":"https://github.com/enso-org/enso/actions/runs/2421","workflowName":"Nightly"}]"#;
+        let runs = serde_json::from_str::<Vec<RunInfo>>(output)?;
+        assert_eq!(runs[0].database_id, 2421);
+        assert!(!runs[0].is_completed());
+        Ok(())
+    }
+}
@@ -2,6 +2,7 @@ use crate::new_command_type;
 use crate::prelude::*;
 
 use crate::program::command::Manipulator;
+use std::str::FromStr;
 
 #[derive(Clone, Debug, Default)]
 pub struct Git {
@@ -26,10 +27,280 @@ impl Git {
     }
 
     pub async fn head_hash(&self) -> Result<String> {
-        self.cmd()?.args(["rev-parse", "--verify", "HEAD"]).output_ok().await?.single_line_stdout()
+        self.rev_parse("HEAD").await
+    }
+
+    /// Clone the repository into the given directory. Returns the wrapper for the new clone.
+    ///
+    /// If `depth` is given, a shallow clone is made.
+    pub async fn clone_repository(
+        url: &str,
+        target: impl AsRef<Path>,
+        depth: Option<u32>,
+    ) -> Result<Self> {
+        let target = target.as_ref();
+        let mut cmd = Git::default().cmd()?;
+        cmd.arg("clone");
+        if let Some(depth) = depth {
+            cmd.arg(format!("--depth={depth}"));
+        }
+        cmd.arg("--").arg(url).arg(target).run_ok().await?;
+        Ok(Git::new(target))
+    }
+
+    /// Fetch the given refspecs from the remote (name or URL).
+    pub async fn fetch(
+        &self,
+        remote: &str,
+        refspecs: impl IntoIterator<Item: AsRef<str>>,
+    ) -> Result {
+        let mut cmd = self.cmd()?;
+        cmd.args(["fetch", remote]);
+        cmd.args(refspecs.into_iter().map(|refspec| refspec.as_ref().to_string()));
+        cmd.run_ok().await
+    }
+
+    /// Check out the given branch, tag or commit.
+    pub async fn checkout(&self, git_ref: &str) -> Result {
+        self.cmd()?.args(["checkout", git_ref, "--"]).run_ok().await
+    }
+
+    /// Get the full hash of the commit the revision points to.
+    pub async fn rev_parse(&self, revision: &str) -> Result<String> {
+        let revision = format!("{revision}^{{commit}}");
+        self.cmd()?
+            .args(["rev-parse", "--verify", &revision])
+            .output_ok()
+            .await?
+            .single_line_stdout()
+    }
+
+    /// Describe the working tree relative to the most recent tag reachable from `HEAD`.
+    ///
+    /// Fails if there are no tags reachable.
+    pub async fn describe(&self) -> Result<Describe> {
+        let output =
+            self.cmd()?.args(["describe", "--tags", "--long", "--dirty"]).run_stdout().await?;
+        output.trim().parse()
+    }
+
+    /// Get the status of the working tree.
+    pub async fn status(&self) -> Result<Status> {
+        let output = self.cmd()?.args(["status", "--porcelain=v1", "-z"]).run_stdout().await?;
+        Status::from_porcelain(&output)
+    }
+
+    /// Whether there are any changes in the working tree, including untracked files.
+    pub async fn is_dirty(&self) -> Result<bool> {
+        Ok(!self.status().await?.is_clean())
+    }
+
+    /// List the files tracked in the index, relative to the repository path.
+    ///
+    /// Additional arguments (e.g. pathspecs) can be passed to filter the files.
+    pub async fn ls_files(
+        &self,
+        args: impl IntoIterator<Item: AsRef<OsStr>>,
+    ) -> Result<Vec<PathBuf>> {
+        let output = self.cmd()?.args(["ls-files", "-z"]).args(args).run_stdout().await?;
+        Ok(split_nul_separated(&output).map(PathBuf::from).collect())
+    }
+
+    /// List all the tags in the repository.
+    pub async fn tags(&self) -> Result<Vec<String>> {
+        let output = self.cmd()?.args(["tag", "--list"]).run_stdout().await?;
+        Ok(output.lines().map(ToString::to_string).collect())
+    }
+
+    /// List the tags pointing at the given revision.
+    pub async fn tags_pointing_at(&self, revision: &str) -> Result<Vec<String>> {
+        let output = self.cmd()?.args(["tag", "--points-at", revision]).run_stdout().await?;
+        Ok(output.lines().map(ToString::to_string).collect())
+    }
+}
+
+fn split_nul_separated(text: &str) -> impl Iterator<Item = &str> {
+    text.split('\0').filter(|entry| !entry.is_empty())
+}
+
+/// Parsed output of `git describe --tags --long --dirty`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Describe {
+    /// The most recent tag reachable from `HEAD`.
+    pub tag:               String,
+    /// Number of commits on top of the tag.
+    pub commits_since_tag: u32,
+    /// Abbreviated hash of the `HEAD` commit.
+    pub abbreviated_hash:  String,
+    /// Whether the working tree has uncommitted changes to tracked files.
+    pub dirty:             bool,
+}
+
+impl Describe {
+    /// Whether `HEAD` is exactly at the tag.
+    pub fn is_exact(&self) -> bool {
+        self.commits_since_tag == 0
     }
 }
 
+impl FromStr for Describe {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (rest, dirty) = match s.strip_suffix("-dirty") {
+            Some(rest) => (rest, true),
+            None => (s, false),
+        };
+        // Tags may contain dashes, so the string is split from the end.
+        match rest.rsplitn(3, '-').collect_vec().as_slice() {
+            [hash, count, tag] if hash.starts_with('g') && !tag.is_empty() => Ok(Self {
+                tag: tag.to_string(),
+                commits_since_tag: count.parse()?,
+                abbreviated_hash: hash[1..].to_string(),
+                dirty,
+            }),
+            _ => bail!("Failed to parse `git describe` output: {s}"),
+        }
+    }
+}
+
+/// Entry of the `git status --porcelain` output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StatusEntry {
+    /// Status of the file in the index, e.g. `M` for modified or `?` for untracked.
+    pub index:         char,
+    /// Status of the file in the working tree.
+    pub worktree:      char,
+    pub path:          PathBuf,
+    /// The path the file was renamed or copied from.
+    pub original_path: Option<PathBuf>,
+}
+
+impl StatusEntry {
+    pub fn is_untracked(&self) -> bool {
+        self.index == '?' && self.worktree == '?'
+    }
+}
+
+/// Parsed output of `git status --porcelain`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Status {
+    pub entries: Vec<StatusEntry>,
+}
+
+impl Status {
+    /// Parse the output of `git status --porcelain=v1 -z`.
+    pub fn from_porcelain(text: &str) -> Result<Self> {
+        let mut entries = Vec::new();
+        let mut fields = split_nul_separated(text);
+        while let Some(field) = fields.next() {
+            let mut chars = field.chars();
+            let (index, worktree, separator) = (chars.next(), chars.next(), chars.next());
+            let (index, worktree) = match (index, worktree, separator) {
+                (Some(index), Some(worktree), Some(' ')) => (index, worktree),
+                _ => bail!("Failed to parse `git status` entry: {field}"),
+            };
+            // With `-z`, the original path of a rename or copy follows as a separate field.
+            let original_path = if index == 'R' || index == 'C' {
+                let original = fields.next().context("Missing original path of a renamed file.")?;
+                Some(PathBuf::from(original))
+            } else {
+                None
+            };
+            entries.push(StatusEntry {
+                index,
+                worktree,
+                path: PathBuf::from(&field[3..]),
+                original_path,
+            });
+        }
+        Ok(Self { entries })
+    }
+
+    /// Whether there are no changes, including untracked files.
+    pub fn is_clean(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Whether any tracked file has been changed.
+    pub fn has_tracked_changes(&self) -> bool {
+        self.entries.iter().any(|entry| !entry.is_untracked())
+    }
+
+    pub fn untracked(&self) -> impl Iterator<Item = &Path> {
+        self.entries.iter().filter(|entry| entry.is_untracked()).map(|entry| entry.path.as_path())
+    }
+}
+
+/// What [`ensure_checkout`] had to do to bring the directory to the requested state.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CheckoutOutcome {
+    /// The repository was not present and has been cloned.
+    Cloned { commit: String },
+    /// The repository was present, but at a different commit.
+    Updated { from: Option<String>, to: String },
+    /// The repository was already at the requested commit.
+    UpToDate { commit: String },
+}
+
+impl Display for CheckoutOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckoutOutcome::Cloned { commit } => write!(f, "cloned at {commit}"),
+            CheckoutOutcome::Updated { from: Some(from), to } =>
+                write!(f, "updated from {from} to {to}"),
+            CheckoutOutcome::Updated { from: None, to } => write!(f, "updated to {to}"),
+            CheckoutOutcome::UpToDate { commit } => write!(f, "already at {commit}"),
+        }
+    }
+}
+
+/// Ensure that the directory contains a clean checkout of the given repository at the given ref.
+///
+/// If the directory is not a git repository, it is initialized. Then the ref (a branch, tag or
+/// commit hash) is fetched from the `remote` URL and the working tree is hard-reset to it. Local
+/// changes in the directory are discarded. If `depth` is given, a shallow fetch is performed.
+///
+/// Fails if the working tree is not clean after the reset (e.g. due to untracked files).
+#[context("Failed to check out `{git_ref}` of {remote} in {}.", dir.as_ref().display())]
+pub async fn ensure_checkout(
+    dir: impl AsRef<Path>,
+    remote: &str,
+    git_ref: &str,
+    depth: Option<u32>,
+) -> Result<CheckoutOutcome> {
+    let dir = dir.as_ref();
+    let git = Git::new(dir);
+    let existed = dir.join(".git").exists();
+    let previous = if existed {
+        git.head_hash().await.ok()
+    } else {
+        crate::fs::tokio::create_dir_if_missing(dir).await?;
+        git.cmd()?.arg("init").run_ok().await?;
+        None
+    };
+
+    let mut fetch = git.cmd()?;
+    fetch.args(["fetch", "--force"]);
+    if let Some(depth) = depth {
+        fetch.arg(format!("--depth={depth}"));
+    }
+    fetch.args([remote, git_ref]).run_ok().await?;
+    git.cmd()?.args(["reset", "--hard", "FETCH_HEAD"]).run_ok().await?;
+
+    let status = git.status().await?;
+    ensure!(status.is_clean(), "The working tree is not clean after reset: {status:?}");
+
+    let commit = git.head_hash().await?;
+    let outcome = match previous {
+        _ if !existed => CheckoutOutcome::Cloned { commit },
+        Some(previous) if previous == commit => CheckoutOutcome::UpToDate { commit },
+        from => CheckoutOutcome::Updated { from, to: commit },
+    };
+    info!("Repository {remote} in {}: {outcome}.", dir.display());
+    Ok(outcome)
+}
+
 
 new_command_type!(Git, GitCommand);
 
@@ -106,3 +377,63 @@ impl Manipulator for Clean {
         command.args(args);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_describe() -> Result {
+        let describe = Describe::from_str("2022.1.1-nightly.2022-04-01-12-g1a2b3c4-dirty")?;
+        assert_eq!(describe, Describe {
+            tag:               "2022.1.1-nightly.2022-04-01".into(),
+            commits_since_tag: 12,
+            abbreviated_hash:  "1a2b3c4".into(),
+            dirty:             true,
+        });
+        assert!(Describe::from_str("v1.0-0-gabcdef0")?.is_exact());
+        assert!(Describe::from_str("abcdef0").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_status() -> Result {
+        let status =
+            Status::from_porcelain(" M build/src/lib.rs\0R  new.rs\0old.rs\0?? notes.txt\0")?;
+        assert_eq!(status.entries.len(), 3);
+        assert_eq!(status.entries[1].path, PathBuf::from("new.rs"));
+        assert_eq!(status.entries[1].original_path, Some(PathBuf::from("old.rs")));
+        assert_eq!(status.untracked().collect_vec(), vec![Path::new("notes.txt")]);
+        assert!(status.has_tracked_changes());
+        assert!(Status::from_porcelain("")?.is_clean());
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn checkout_local_repository() -> Result {
+        let temp = tempfile::tempdir()?;
+        let origin = temp.path().join("origin");
+        let checkout = temp.path().join("checkout");
+        crate::fs::tokio::create_dir_if_missing(&origin).await?;
+        let git = Git::new(&origin);
+        git.cmd()?.arg("init").run_ok().await?;
+        crate::fs::write(origin.join("README.md"), "Hello")?;
+        git.cmd()?.args(["add", "."]).run_ok().await?;
+        git.cmd()?
+            .args(["-c", "user.name=CI", "-c", "user.email=ci@example.com"])
+            .args(["commit", "-m", "Initial commit"])
+            .run_ok()
+            .await?;
+        let head = git.head_hash().await?;
+        let remote = origin.to_str().unwrap();
+
+        let outcome = ensure_checkout(&checkout, remote, "HEAD", Some(1)).await?;
+        assert_eq!(outcome, CheckoutOutcome::Cloned { commit: head.clone() });
+        crate::fs::write(checkout.join("README.md"), "Modified")?;
+        let outcome = ensure_checkout(&checkout, remote, "HEAD", Some(1)).await?;
+        assert_eq!(outcome, CheckoutOutcome::UpToDate { commit: head });
+        assert_eq!(std::fs::read_to_string(checkout.join("README.md"))?, "Hello");
+        Ok(())
+    }
+}
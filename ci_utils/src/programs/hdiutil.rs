@@ -0,0 +1,105 @@
+//! Wrapper over the macOS `hdiutil`, managing the disk images.
+
+use crate::prelude::*;
+
+
+/// The `hdiutil` program.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Hdiutil;
+
+impl Program for Hdiutil {
+    fn executable_name(&self) -> &'static str {
+        "hdiutil"
+    }
+}
+
+/// Format of the created image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, strum::AsRefStr)]
+pub enum ImageFormat {
+    /// Read-only, zlib-compressed. Opens on every macOS version.
+    #[strum(serialize = "UDZO")]
+    Zlib,
+    /// Read-only, bzip2-compressed.
+    #[strum(serialize = "UDBZ")]
+    Bzip2,
+    /// Read-only, LZFSE-compressed. Smaller and faster than zlib, requires macOS 10.11.
+    #[strum(serialize = "ULFO")]
+    Lzfse,
+}
+
+impl Default for ImageFormat {
+    fn default() -> Self {
+        Self::Zlib
+    }
+}
+
+/// Options of the `hdiutil create` invocation.
+#[derive(Clone, Debug)]
+pub struct CreateOptions {
+    /// Name of the mounted volume, shown by Finder.
+    pub volume_name: String,
+    /// Directory whose contents become the image's contents.
+    pub source:      PathBuf,
+    pub format:      ImageFormat,
+    /// File system, like `HFS+` or `APFS`.
+    pub filesystem:  String,
+}
+
+impl CreateOptions {
+    pub fn new(volume_name: impl Into<String>, source: impl Into<PathBuf>) -> Self {
+        Self {
+            volume_name: volume_name.into(),
+            source:      source.into(),
+            format:      default(),
+            filesystem:  "HFS+".into(),
+        }
+    }
+
+    pub fn args(&self) -> Vec<OsString> {
+        vec![
+            "-volname".into(),
+            self.volume_name.as_str().into(),
+            "-srcfolder".into(),
+            (&self.source).into(),
+            "-format".into(),
+            self.format.as_ref().into(),
+            "-fs".into(),
+            self.filesystem.as_str().into(),
+            // Overwrite the existing image.
+            "-ov".into(),
+        ]
+    }
+}
+
+impl Hdiutil {
+    /// Create the disk image with the source directory's contents.
+    pub async fn create(&self, options: &CreateOptions, image: impl AsRef<Path>) -> Result {
+        self.cmd()?.arg("create").args(options.args()).arg(image.as_ref()).run_ok().await
+    }
+
+    /// Verify the image's checksum.
+    pub async fn verify(&self, image: impl AsRef<Path>) -> Result {
+        self.cmd()?.arg("verify").arg(image.as_ref()).run_ok().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_args() {
+        let options = CreateOptions::new("Enso", "staging");
+        assert_eq!(options.args(), [
+            "-volname",
+            "Enso",
+            "-srcfolder",
+            "staging",
+            "-format",
+            "UDZO",
+            "-fs",
+            "HFS+",
+            "-ov"
+        ]);
+    }
+}
@@ -1,7 +1,17 @@
 use crate::prelude::*;
 
+use crate::env::new::TypedVariable;
+use crate::env::EnvironmentOverlay;
+use crate::program::command::FallibleManipulator;
 use crate::program::command::Manipulator;
 
+
+crate::define_env_var!(
+    /// Root directory of the JDK used by the build tools, like sbt.
+    JAVA_HOME,
+    PathBuf
+);
+
 #[derive(Clone, Debug, derive_more::Deref, derive_more::DerefMut)]
 pub struct Classpath(pub Vec<PathBuf>);
 
@@ -47,10 +57,171 @@ impl Program for Java {
     }
 }
 
+/// An installed JDK.
+#[derive(Clone, Debug)]
+pub struct Jdk {
+    /// The `JAVA_HOME` directory, i.e. the parent of the `bin` directory.
+    pub home:    PathBuf,
+    /// Version as declared in the `release` file, e.g. `11.0.11` or `1.8.0_292`.
+    pub version: String,
+    pub major:   LanguageVersion,
+}
+
+impl Jdk {
+    /// Describe the JDK installed in the given directory.
+    #[context("Failed to recognize a JDK in {}.", home.as_ref().display())]
+    pub fn from_home(home: impl AsRef<Path>) -> Result<Self> {
+        let home = home.as_ref().to_path_buf();
+        let java = home.join("bin").join(format!("java{}", std::env::consts::EXE_SUFFIX));
+        ensure!(java.exists(), "There is no Java executable at {}.", java.display());
+        let release = crate::fs::read_to_string(home.join("release"))?;
+        let version = parse_release_version(&release)?;
+        let major = major_version(&version)?;
+        Ok(Self { home, version, major })
+    }
+
+    /// Find all the JDKs in `JAVA_HOME` and in the platform's default installation directories.
+    pub fn discover() -> Vec<Self> {
+        let mut candidates = Vec::new();
+        if let Ok(home) = JAVA_HOME.get() {
+            candidates.push(home);
+        }
+        for root in default_install_roots() {
+            if let Ok(entries) = std::fs::read_dir(root) {
+                for entry in entries.flatten() {
+                    let path = entry.path();
+                    candidates.push(match TARGET_OS {
+                        OS::MacOS => path.join_iter(["Contents", "Home"]),
+                        _ => path,
+                    });
+                }
+            }
+        }
+        candidates
+            .into_iter()
+            .unique()
+            .filter_map(|home| {
+                Self::from_home(&home).inspect_err(|e| trace!("Skipping JDK candidate: {e:?}")).ok()
+            })
+            .collect()
+    }
+
+    /// Find an installed JDK with the given major Java version.
+    pub fn find(major: LanguageVersion) -> Result<Self> {
+        let found = Self::discover();
+        let versions = found.iter().map(|jdk| jdk.version.as_str()).join(", ");
+        found.iter().find(|jdk| jdk.major.0 == major.0).cloned().with_context(|| {
+            format!("Failed to find JDK for {major}. Found JDKs in versions: [{versions}].")
+        })
+    }
+
+    /// Fail if this JDK is not of the given major Java version.
+    pub fn require_major(&self, major: LanguageVersion) -> Result {
+        ensure!(
+            self.major.0 == major.0,
+            "JDK in {} is {}, while {major} is required.",
+            self.home.display(),
+            self.major
+        );
+        Ok(())
+    }
+
+    pub fn bin_dir(&self) -> PathBuf {
+        self.home.join("bin")
+    }
+
+    /// Command running the `java` executable of this JDK.
+    pub fn java(&self) -> Result<Command> {
+        self.tool_command(Java.executable_name())
+    }
+
+    /// Command running the `javac` executable of this JDK.
+    pub fn javac(&self) -> Result<Command> {
+        self.tool_command(crate::programs::Javac.executable_name())
+    }
+
+    fn tool_command(&self, name: &str) -> Result<Command> {
+        let executable = self.bin_dir().join(format!("{name}{}", std::env::consts::EXE_SUFFIX));
+        let mut command = Command::new(executable);
+        command.try_applying(self)?;
+        Ok(command)
+    }
+
+    /// Environment that makes this JDK the default one: `JAVA_HOME` pointing to it and its
+    /// binaries first in `PATH`.
+    pub fn environment(&self) -> Result<EnvironmentOverlay> {
+        Ok(EnvironmentOverlay::new().set(JAVA_HOME, &self.home)?.prepend_to_path(self.bin_dir()))
+    }
+}
+
+/// Makes the spawned process use this JDK: sets `JAVA_HOME` and puts its `bin` first on `PATH`.
+impl FallibleManipulator for Jdk {
+    fn try_applying<C: IsCommandWrapper + ?Sized>(&self, command: &mut C) -> Result {
+        self.environment()?.try_applying(command)
+    }
+}
+
+/// Directories, where JDKs are typically installed in subdirectories.
+pub fn default_install_roots() -> Vec<PathBuf> {
+    match TARGET_OS {
+        OS::Linux => vec!["/usr/lib/jvm".into(), "/usr/java".into()],
+        OS::MacOS => vec!["/Library/Java/JavaVirtualMachines".into()],
+        OS::Windows => vec![
+            r"C:\Program Files\Java".into(),
+            r"C:\Program Files\Eclipse Adoptium".into(),
+            r"C:\Program Files\Microsoft".into(),
+        ],
+        _ => vec![],
+    }
+}
+
+/// Get the `JAVA_VERSION` value from the contents of the JDK's `release` file.
+pub fn parse_release_version(release_file: &str) -> Result<String> {
+    release_file
+        .lines()
+        .find_map(|line| line.strip_prefix("JAVA_VERSION="))
+        .map(|value| value.trim().trim_matches('"').to_string())
+        .context("The release file does not declare JAVA_VERSION.")
+}
+
+/// Get the major Java version, handling both the legacy (`1.8.0_292`) and the current (`11.0.11`)
+/// version schemes.
+pub fn major_version(version: &str) -> Result<LanguageVersion> {
+    let mut parts = version.split(|c: char| !c.is_ascii_digit());
+    let major = match parts.next() {
+        Some("1") => parts.next(),
+        first => first,
+    };
+    major.context(format!("Invalid Java version: {version}"))?.parse()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn parse_release_file() -> Result {
+        let release = "IMPLEMENTOR=\"GraalVM Community\"\nJAVA_VERSION=\"11.0.11\"\n";
+        assert_eq!(parse_release_version(release)?, "11.0.11");
+        assert_eq!(major_version("11.0.11")?.0, 11);
+        assert_eq!(major_version("1.8.0_292")?.0, 8);
+        assert_eq!(major_version("17")?.0, 17);
+        Ok(())
+    }
+
+    #[test]
+    fn jdk_from_home() -> Result {
+        let home = tempfile::tempdir()?;
+        assert!(Jdk::from_home(home.path()).is_err());
+        let java = format!("java{}", std::env::consts::EXE_SUFFIX);
+        crate::fs::write(home.path().join("bin").join(java), "")?;
+        crate::fs::write(home.path().join("release"), "JAVA_VERSION=\"17.0.2\"")?;
+        let jdk = Jdk::from_home(home.path())?;
+        assert_eq!(jdk.major.0, 17);
+        assert!(jdk.require_major(LanguageVersion(11)).is_err());
+        Ok(())
+    }
+
     #[test]
     fn parse_version() {
         let contents = "openjdk 11.0.11 2021-04-20\nOpenJDK Runtime Environment GraalVM CE 21.1.0 (build 11.0.11+8-jvmci-21.1-b05)\nOpenJDK 64-Bit Server VM GraalVM CE 21.1.0 (build 11.0.11+8-jvmci-21.1-b05, mixed mode, sharing)";
@@ -1,6 +1,10 @@
 use crate::new_command_type;
 use crate::prelude::*;
 
+use crate::program::command::LogStreaming;
+use crate::program::retry::RetryPolicy;
+use semver::VersionReq;
+
 #[derive(Clone, Copy, Debug, Default)]
 pub struct Node;
 
@@ -22,13 +26,28 @@ impl NpmCommand {
         // and // revert this workaround. See also:
         // // https://www.ibm.com/support/pages/disableunccheck-registry-key-created-during-rational-synergy-installation
         // let path = dbg!(path.as_ref().strip_prefix(r"\\?\")).unwrap_or(path.as_ref());
-        self.arg("install");
+        self.arg("install").retry(registry_retry_policy());
+        self
+    }
+    /// Install the exact dependencies from the lockfile, removing the existing `node_modules`.
+    pub fn ci(&mut self) -> &mut Self {
+        self.arg("ci").retry(registry_retry_policy());
         self
     }
     pub fn workspace(&mut self, workspace: impl AsRef<OsStr>) -> &mut Self {
         self.arg("--workspace").arg(workspace);
         self
     }
+    /// Run the command in the context of all the configured workspaces.
+    pub fn workspaces(&mut self) -> &mut Self {
+        self.arg("--workspaces");
+        self
+    }
+    /// Run the command as if the package was in the given directory.
+    pub fn prefix(&mut self, package_dir: impl AsRef<Path>) -> &mut Self {
+        self.arg("--prefix").arg(package_dir.as_ref());
+        self
+    }
     pub fn run(
         &mut self,
         script_name: impl AsRef<OsStr>,
@@ -39,6 +58,16 @@ impl NpmCommand {
     }
 }
 
+/// Retry the npm invocations that failed due to the registry connection problems.
+pub fn registry_retry_policy() -> RetryPolicy {
+    RetryPolicy::new(3).retry_if_stderr_contains([
+        "ECONNRESET",
+        "ETIMEDOUT",
+        "EAI_AGAIN",
+        "503 Service Unavailable",
+    ])
+}
+
 #[derive(Clone, Copy, Debug, Default)]
 pub struct Npm;
 
@@ -48,4 +77,88 @@ impl Program for Npm {
     fn executable_name(&self) -> &'static str {
         "npm"
     }
+
+    fn init_command<'a>(&self, cmd: &'a mut Self::Command) -> &'a mut Self::Command {
+        cmd.stream_logs(LogStreaming::grouped());
+        cmd
+    }
+}
+
+new_command_type! {Npx, NpxCommand}
+
+impl NpxCommand {
+    /// Do not ask for confirmation before installing a missing package.
+    pub fn yes(&mut self) -> &mut Self {
+        self.arg("--yes");
+        self
+    }
+    /// The package to install (if missing) before running the command.
+    pub fn package(&mut self, package: impl AsRef<OsStr>) -> &mut Self {
+        self.arg("--package").arg(package);
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Npx;
+
+impl Program for Npx {
+    type Command = NpxCommand;
+
+    fn executable_name(&self) -> &'static str {
+        "npx"
+    }
+}
+
+/// Versions of Node.js and npm required by the package, as declared in its `package.json`.
+///
+/// See: <https://docs.npmjs.com/cli/configuring-npm/package-json#engines>
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Engines {
+    pub node: Option<VersionReq>,
+    pub npm:  Option<VersionReq>,
+}
+
+impl Engines {
+    /// Read the requirements from the `package.json` file in the given package directory.
+    #[context("Failed to read the engines of the package in {}.", package_dir.as_ref().display())]
+    pub fn from_package(package_dir: impl AsRef<Path>) -> Result<Self> {
+        #[derive(Deserialize)]
+        struct PackageJson {
+            #[serde(default)]
+            engines: Engines,
+        }
+        let text = crate::fs::read_to_string(package_dir.as_ref().join("package.json"))?;
+        Ok(serde_json::from_str::<PackageJson>(&text)?.engines)
+    }
+
+    /// Fail if the installed Node.js or npm does not satisfy the requirements.
+    pub async fn check(&self) -> Result {
+        if let Some(node) = &self.node {
+            Node.require_version_for(node, "the package's engines").await?;
+        }
+        if let Some(npm) = &self.npm {
+            Npm.require_version_for(npm, "the package's engines").await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn engines_from_package() -> Result {
+        let dir = tempfile::tempdir()?;
+        let package_json = r#"{"name":"enso","engines":{"node":">=16.15.0","npm":"^8.11.0"}}"#;
+        crate::fs::write(dir.path().join("package.json"), package_json)?;
+        let engines = Engines::from_package(dir.path())?;
+        assert!(engines.node.unwrap().matches(&Version::new(16, 17, 1)));
+        assert!(!engines.npm.unwrap().matches(&Version::new(9, 0, 0)));
+
+        crate::fs::write(dir.path().join("package.json"), r#"{"name":"content"}"#)?;
+        assert!(Engines::from_package(dir.path())?.node.is_none());
+        Ok(())
+    }
 }
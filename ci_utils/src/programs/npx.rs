@@ -1,10 +1,3 @@
-use crate::prelude::*;
+//! The `npx` wrapper now lives in the [`node`](crate::programs::node) module, along with `npm`.
 
-#[derive(Clone, Copy, Debug, Default)]
-pub struct Npx;
-
-impl Program for Npx {
-    fn executable_name(&self) -> &'static str {
-        "npx"
-    }
-}
+pub use crate::programs::node::Npx;
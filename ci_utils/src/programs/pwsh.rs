@@ -1,17 +1,87 @@
 use crate::prelude::*;
 
+use crate::program::shell::capture;
+use crate::program::shell::ScriptOutput;
+
 pub struct PwSh;
 
 pub mod arg {
     pub const RUN_COMMAND: &str = "-Command";
+    pub const RUN_ENCODED_COMMAND: &str = "-EncodedCommand";
     pub const RUN_FILE: &str = "-File";
+    pub const NO_PROFILE: &str = "-NoProfile";
+    pub const NON_INTERACTIVE: &str = "-NonInteractive";
+}
+
+/// Quote the text as a PowerShell string literal, so it is taken verbatim.
+pub fn quote(text: &str) -> String {
+    // In single-quoted strings, only the single quote itself needs escaping (by doubling).
+    // PowerShell also treats the typographic quotes as single quotes.
+    let escaped = text
+        .chars()
+        .flat_map(|c| match c {
+            '\'' | '‘' | '’' | '‚' | '‛' => vec![c, c],
+            _ => vec![c],
+        })
+        .collect::<String>();
+    format!("'{escaped}'")
+}
+
+/// Generate the statement invoking the program with the given arguments, quoting all of them.
+///
+/// The call operator is needed, as otherwise the quoted program name would be parsed as a string
+/// expression rather than a command. Note that Windows PowerShell (and PowerShell before 7.3)
+/// does not escape the double quotes inside the arguments passed to native programs.
+pub fn invocation(
+    program: impl AsRef<OsStr>,
+    args: impl IntoIterator<Item: AsRef<OsStr>>,
+) -> String {
+    let quoted = |arg: &OsStr| quote(&arg.to_string_lossy());
+    let words = once(quoted(program.as_ref())).chain(args.into_iter().map(|a| quoted(a.as_ref())));
+    once("&".to_string()).chain(words).join(" ")
+}
+
+/// Wrap the script block, so the errors stop the execution and the exit code of the last native
+/// command becomes the exit code of PowerShell.
+pub fn wrap_script_block(script: &str) -> String {
+    let prologue = "$ErrorActionPreference = 'Stop'";
+    let epilogue = "if ($LASTEXITCODE) { exit $LASTEXITCODE }";
+    format!("{prologue}\n& {{\n{script}\n}}\n{epilogue}\n")
+}
+
+/// Encode the script as expected by the `-EncodedCommand` argument: Base64 of the UTF-16LE text.
+///
+/// Passing the script this way avoids any issues with quoting it on the command line.
+pub fn encode_command(script: &str) -> String {
+    let bytes = script.encode_utf16().flat_map(u16::to_le_bytes).collect_vec();
+    data_encoding::BASE64.encode(&bytes)
+}
+
+impl PwSh {
+    /// Command running the given script block.
+    pub fn script_block_cmd(&self, script: &str) -> Result<Command> {
+        let mut command = self.cmd()?;
+        command.args([arg::NO_PROFILE, arg::NON_INTERACTIVE]);
+        command.arg(arg::RUN_ENCODED_COMMAND).arg(encode_command(&wrap_script_block(script)));
+        Ok(command)
+    }
+
+    /// Run the script block, failing if it fails.
+    pub async fn run_script_block(&self, script: &str) -> Result {
+        self.script_block_cmd(script)?.run_ok().await
+    }
+
+    /// Run the script block and capture its output and exit code.
+    pub async fn capture_script_block(&self, script: &str) -> Result<ScriptOutput> {
+        capture(&mut self.script_block_cmd(script)?).await
+    }
 }
 
 impl Program for PwSh {
     fn executable_name(&self) -> &'static str {
         "pwsh"
     }
-    fn executable_name_fallback() -> Vec<&'static str> {
+    fn alternative_names(&self) -> Vec<&str> {
         vec!["powershell"]
     }
 }
@@ -34,3 +104,25 @@ impl Shell for PwSh {
         self.cmd()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoting() {
+        assert_eq!(quote("C:\\Program Files\\Enso"), "'C:\\Program Files\\Enso'");
+        assert_eq!(quote("it's $HOME"), "'it''s $HOME'");
+        assert_eq!(
+            invocation("signtool", ["sign", "/f", "cert.pfx"]),
+            "& 'signtool' 'sign' '/f' 'cert.pfx'"
+        );
+    }
+
+    #[test]
+    fn encoding() {
+        // Reference value obtained with:
+        // [Convert]::ToBase64String([Text.Encoding]::Unicode.GetBytes("echo 1"))
+        assert_eq!(encode_command("echo 1"), "ZQBjAGgAbwAgADEA");
+    }
+}
@@ -3,6 +3,11 @@
 /// See https://docs.microsoft.com/en-us/windows-server/administration/windows-commands/robocopy
 use crate::prelude::*;
 
+use crate::fs::mirror::FastCopy;
+use crate::fs::mirror::SymlinkPolicy;
+use crate::fs::MirrorOptions;
+use crate::program::ProgramError;
+
 pub struct Robocopy;
 
 impl Program for Robocopy {
@@ -10,24 +15,50 @@ impl Program for Robocopy {
         "robocopy"
     }
 
-    fn handle_exit_status(status: std::process::ExitStatus) -> Result {
+    /// The exit code is a bit mask. The codes below 8 denote success, whether anything was
+    /// copied or not.
+    fn interpret_exit_code(
+        status: std::process::ExitStatus,
+    ) -> std::result::Result<(), ProgramError> {
         match status.code() {
-            None => status.exit_ok().anyhow_err(),
-            Some(code) if code >= 8 => bail!("Exit with code {}.", code),
-            Some(_) => Ok(()),
+            Some(code) if code < 8 => Ok(()),
+            Some(code) if code & 16 != 0 =>
+                Err(ProgramError::domain(code, "Serious error, no files were copied.")),
+            Some(code) => Err(ProgramError::domain(code, "Some files could not be copied.")),
+            None => ProgramError::check(status),
         }
     }
 }
 
-impl Robocopy {}
+impl FastCopy for Robocopy {
+    /// Robocopy matches the exclusions by the file names, not by the relative paths, so it is used
+    /// only when nothing is filtered.
+    fn supports(&self, options: &MirrorOptions) -> bool {
+        options.include.is_empty()
+            && options.exclude.is_empty()
+            && options.symlinks != SymlinkPolicy::Skip
+            && options.progress.is_none()
+    }
+
+    fn mirror_cmd(
+        &self,
+        source: &Path,
+        destination: &Path,
+        options: &MirrorOptions,
+    ) -> Result<Command> {
+        let mut cmd = self.cmd()?;
+        cmd.arg(source).arg(destination);
+        // `/mir` is `/e` (copy subdirectories, including the empty ones) with `/purge`.
+        cmd.arg(if options.delete_extraneous { "/mir" } else { "/e" });
+        if options.symlinks == SymlinkPolicy::Preserve {
+            cmd.arg("/sl");
+        }
+        // Multithreaded copying, without the per-file progress percentages cluttering the log.
+        cmd.args(["/mt", "/np"]);
+        Ok(cmd)
+    }
+}
 
 pub async fn mirror_directory(source: impl AsRef<Path>, destination: impl AsRef<Path>) -> Result {
-    Robocopy
-        .cmd()?
-        .arg(source.as_ref())
-        .arg(destination.as_ref())
-        .arg("/mir")
-        .arg("/sl")
-        .run_ok()
-        .await
+    Robocopy.mirror_cmd(source.as_ref(), destination.as_ref(), &default())?.run_ok().await
 }
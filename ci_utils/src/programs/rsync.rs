@@ -1,5 +1,9 @@
 use crate::prelude::*;
 
+use crate::fs::mirror::FastCopy;
+use crate::fs::mirror::SymlinkPolicy;
+use crate::fs::MirrorOptions;
+
 pub struct Rsync;
 
 impl Program for Rsync {
@@ -14,6 +18,12 @@ pub enum Option {
     Archive,
     /// delete extraneous files from dest dirs
     Delete,
+    /// transform symlink into referent file/dir
+    CopyLinks,
+    /// don't copy symlinks
+    NoLinks,
+    /// clear the executable bits of the files
+    NoExecutable,
 }
 
 impl AsRef<OsStr> for Option {
@@ -21,20 +31,86 @@ impl AsRef<OsStr> for Option {
         OsStr::new(match self {
             Self::Archive => "--archive",
             Self::Delete => "--delete",
+            Self::CopyLinks => "--copy-links",
+            Self::NoLinks => "--no-links",
+            Self::NoExecutable => "--chmod=F-x",
         })
     }
 }
 
+impl FastCopy for Rsync {
+    /// The glob patterns of the exclusions are understood by rsync the same way, as long as they
+    /// are anchored at the source root. Inclusions would need the parent directories included
+    /// as well, so they are not supported.
+    fn supports(&self, options: &MirrorOptions) -> bool {
+        options.include.is_empty() && options.progress.is_none()
+    }
+
+    fn mirror_cmd(
+        &self,
+        source: &Path,
+        destination: &Path,
+        options: &MirrorOptions,
+    ) -> Result<Command> {
+        // rsync treats "path/to/dir" and "path/to/dir/" differently.
+        // We want the latter (otherwise `source` would be placed inside `destination`), so we
+        // append an empty path segment.
+        let source = source.join("");
+        let mut cmd = self.cmd()?;
+        cmd.args([Option::Archive]);
+        if options.delete_extraneous {
+            cmd.args([Option::Delete]);
+        }
+        match options.symlinks {
+            SymlinkPolicy::Preserve => {}
+            SymlinkPolicy::Follow => {
+                cmd.args([Option::CopyLinks]);
+            }
+            SymlinkPolicy::Skip => {
+                cmd.args([Option::NoLinks]);
+            }
+        }
+        if !options.preserve_executable {
+            cmd.args([Option::NoExecutable]);
+        }
+        for pattern in &options.exclude {
+            cmd.arg("--exclude").arg(format!("/{}", pattern.as_str()));
+        }
+        cmd.arg(&source).arg(destination);
+        Ok(cmd)
+    }
+}
+
 pub async fn mirror_directory(source: impl AsRef<Path>, destination: impl AsRef<Path>) -> Result {
-    // rsync treats "path/to/dir" and "path/to/dir/" differently.
-    // We want the latter (otherwise `source` would be placed inside `destination`), so we append an
-    // empty path segment.
-    let source = source.as_ref().join("");
-    Rsync
-        .cmd()?
-        .args([Option::Archive, Option::Delete])
-        .arg(&source)
-        .arg(destination.as_ref())
-        .run_ok()
-        .await
+    Rsync.mirror_cmd(source.as_ref(), destination.as_ref(), &default())?.run_ok().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mirroring_arguments() -> Result {
+        if Rsync.lookup().is_err() {
+            return Ok(());
+        }
+        let options = MirrorOptions::default()
+            .exclude("lib/cache")?
+            .symlinks(SymlinkPolicy::Follow)
+            .delete_extraneous(false);
+        assert!(Rsync.supports(&options));
+        let cmd = Rsync.mirror_cmd(Path::new("source"), Path::new("dest"), &options)?;
+        let args = cmd.as_std().get_args().map(|arg| arg.to_string_lossy()).collect_vec();
+        let expected_source = Path::new("source").join("");
+        assert_eq!(args, [
+            "--archive",
+            "--copy-links",
+            "--exclude",
+            "/lib/cache",
+            expected_source.as_str(),
+            "dest"
+        ]);
+        assert!(!Rsync.supports(&options.include("bin/**")?));
+        Ok(())
+    }
 }
@@ -14,6 +14,40 @@ pub mod env {
     }
 }
 
+/// Contents of the `rust-toolchain.toml` file.
+///
+/// See: <https://rust-lang.github.io/rustup/overrides.html#the-toolchain-file>
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ToolchainFile {
+    pub toolchain: ToolchainSpec,
+}
+
+impl ToolchainFile {
+    pub const NAME: &'static str = "rust-toolchain.toml";
+
+    /// Read the toolchain file from the given directory.
+    #[context("Failed to read the toolchain file in {}.", directory.as_ref().display())]
+    pub fn read(directory: impl AsRef<Path>) -> Result<Self> {
+        let text = crate::fs::read_to_string(directory.as_ref().join(Self::NAME))?;
+        Ok(toml::from_str(&text)?)
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ToolchainSpec {
+    /// Toolchain name, like `stable` or `nightly-2022-04-07`.
+    pub channel:    String,
+    #[serde(default)]
+    pub components: Vec<String>,
+    #[serde(default)]
+    pub targets:    Vec<String>,
+    /// Installation profile, like `minimal` or `default`.
+    pub profile:    Option<String>,
+}
+
+/// The target of the WASM modules built by us.
+pub const WASM_TARGET: &str = "wasm32-unknown-unknown";
+
 pub struct Rustup;
 
 impl Program for Rustup {
@@ -21,3 +55,102 @@ impl Program for Rustup {
         "rustup"
     }
 }
+
+impl Rustup {
+    /// Install the toolchain with all the components and targets it requires.
+    pub async fn install_toolchain(&self, spec: &ToolchainSpec) -> Result {
+        let mut cmd = self.cmd()?;
+        cmd.args(["toolchain", "install", &spec.channel]);
+        if let Some(profile) = &spec.profile {
+            cmd.args(["--profile", profile]);
+        }
+        for component in &spec.components {
+            cmd.args(["--component", component]);
+        }
+        for target in &spec.targets {
+            cmd.args(["--target", target]);
+        }
+        cmd.run_ok().await
+    }
+
+    /// Install the toolchain declared in the `rust-toolchain.toml` in the given directory.
+    pub async fn install_from_file(&self, directory: impl AsRef<Path>) -> Result<ToolchainSpec> {
+        let spec = ToolchainFile::read(directory)?.toolchain;
+        self.install_toolchain(&spec).await?;
+        Ok(spec)
+    }
+
+    /// Add compilation targets to the toolchain.
+    pub async fn add_targets(
+        &self,
+        toolchain: &str,
+        targets: impl IntoIterator<Item: AsRef<OsStr>>,
+    ) -> Result {
+        self.cmd()?.args(["target", "add", "--toolchain", toolchain]).args(targets).run_ok().await
+    }
+
+    /// Add components (like `clippy`) to the toolchain.
+    pub async fn add_components(
+        &self,
+        toolchain: &str,
+        components: impl IntoIterator<Item: AsRef<OsStr>>,
+    ) -> Result {
+        let mut cmd = self.cmd()?;
+        cmd.args(["component", "add", "--toolchain", toolchain]).args(components).run_ok().await
+    }
+
+    /// Name of the toolchain that is used in the current directory, like
+    /// `nightly-2022-04-07-x86_64-unknown-linux-gnu`.
+    pub async fn active_toolchain(&self) -> Result<String> {
+        let output = self.cmd()?.args(["show", "active-toolchain"]).run_stdout().await?;
+        parse_active_toolchain(&output)
+    }
+
+    /// Targets installed for the toolchain.
+    pub async fn installed_targets(&self, toolchain: &str) -> Result<Vec<String>> {
+        let mut cmd = self.cmd()?;
+        cmd.args(["target", "list", "--installed", "--toolchain", toolchain]);
+        let output = cmd.run_stdout().await?;
+        Ok(output.lines().map(str::trim).filter(|line| !line.is_empty()).map(Into::into).collect())
+    }
+}
+
+/// Get the toolchain name from the `rustup show active-toolchain` output, which also includes
+/// the reason why the toolchain was selected, e.g.:
+/// `nightly-2022-04-07-x86_64-unknown-linux-gnu (overridden by '/repo/rust-toolchain.toml')`.
+pub fn parse_active_toolchain(output: &str) -> Result<String> {
+    output
+        .split_whitespace()
+        .next()
+        .map(ToString::to_string)
+        .context(format!("Failed to parse the active toolchain from: {output}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_toolchain_file() -> Result {
+        let text = r#"
+[toolchain]
+components = [ "rustfmt"]
+channel = "nightly-2022-04-07"
+targets = ["wasm32-unknown-unknown"]
+"#;
+        let spec = toml::from_str::<ToolchainFile>(text)?.toolchain;
+        assert_eq!(spec.channel, "nightly-2022-04-07");
+        assert_eq!(spec.components, vec!["rustfmt"]);
+        assert_eq!(spec.targets, vec![WASM_TARGET]);
+        assert_eq!(spec.profile, None);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_active() -> Result {
+        let output = "nightly-2022-04-07-x86_64-unknown-linux-gnu (overridden by \
+                      '/repo/rust-toolchain.toml')\n";
+        assert_eq!(parse_active_toolchain(output)?, "nightly-2022-04-07-x86_64-unknown-linux-gnu");
+        Ok(())
+    }
+}
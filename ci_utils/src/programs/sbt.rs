@@ -1,5 +1,15 @@
 use crate::prelude::*;
 
+use crate::actions::annotations;
+use crate::actions::workflow::Location;
+use crate::actions::workflow::Message;
+use crate::actions::workflow::MessageLevel;
+use crate::program::command::LogStreaming;
+use crate::program::command::Manipulator;
+use crate::program::watchdog::Watchdog;
+use regex::Regex;
+use std::sync::Mutex;
+
 macro_rules! strong_string {
     ($name:ident($inner_ty:ty)) => {
         paste::paste! {
@@ -27,6 +37,11 @@ impl Program for Sbt {
     fn executable_name(&self) -> &'static str {
         "sbt"
     }
+
+    fn init_command<'a>(&self, cmd: &'a mut Self::Command) -> &'a mut Self::Command {
+        // Builds produce thousands of lines, better to have them collapsed.
+        cmd.stream_logs(LogStreaming::grouped())
+    }
 }
 
 impl Sbt {
@@ -39,6 +54,134 @@ impl Sbt {
         }
         ret
     }
+
+    /// Format a string with a command that will execute all the given tasks one after another.
+    ///
+    /// All the tasks are run in a single JVM, so its startup and the project loading costs are
+    /// paid only once. The execution stops on the first failing task.
+    pub fn sequential_tasks(tasks: impl IntoIterator<Item: AsRef<str>>) -> String {
+        tasks.into_iter().map(|task| task.as_ref().to_string()).join("; ")
+    }
+}
+
+/// Options of the sbt launcher script.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LauncherOption {
+    /// Disable the interactive mode, e.g. the prompts on the project loading failures.
+    Batch,
+    /// Set the JVM memory limits (in megabytes).
+    Memory(u32),
+    /// Set the Java system property.
+    SystemProperty { name: String, value: String },
+}
+
+impl Manipulator for LauncherOption {
+    fn apply<C: IsCommandWrapper + ?Sized>(&self, command: &mut C) {
+        match self {
+            LauncherOption::Batch => command.arg("--batch"),
+            LauncherOption::Memory(megabytes) => command.arg("--mem").arg(megabytes.to_string()),
+            LauncherOption::SystemProperty { name, value } =>
+                command.arg(format!("-D{name}={value}")),
+        };
+    }
+}
+
+/// If the line is an error reported by sbt, returns its text.
+pub fn parse_error_line(line: &str) -> Option<&str> {
+    line.trim_start().strip_prefix("[error]").map(str::trim)
+}
+
+lazy_static! {
+    static ref DIAGNOSTIC: Regex =
+        Regex::new(r"^\[(error|warn)\]\s+(.+\.(?:scala|java)):(\d+):(\d+):\s*(.*)$").unwrap();
+}
+
+/// If the line is a compiler diagnostic reported by sbt, like
+/// `[error] /repo/src/Main.scala:12:5: not found: value x`, convert it into an annotation.
+pub fn parse_diagnostic(line: &str) -> Option<Message> {
+    let captures = DIAGNOSTIC.captures(line.trim())?;
+    let level = match &captures[1] {
+        "error" => MessageLevel::Error,
+        _ => MessageLevel::Warning,
+    };
+    let location = Location {
+        file: captures[2].into(),
+        line: captures[3].parse().ok(),
+        column: captures[4].parse().ok(),
+        ..default()
+    };
+    let mut message = Message::new(level, &captures[5]);
+    message.location = Some(location);
+    Some(message)
+}
+
+lazy_static! {
+    static ref COLOR_CODE: Regex = Regex::new(r"\x1b\[[0-9;]*m").unwrap();
+}
+
+/// Remove the ANSI color codes from the text.
+fn strip_colors(text: &str) -> Cow<str> {
+    COLOR_CODE.replace_all(text, "")
+}
+
+/// The `[error]` lines printed by sbt, to be included in the error of the failed command.
+#[derive(Clone, Debug, Default)]
+struct ReportedErrors {
+    lines:   Vec<String>,
+    /// Number of the errors beyond the [limit](Self::LIMIT).
+    omitted: usize,
+}
+
+impl ReportedErrors {
+    /// How many errors are kept. The first ones are usually the most relevant.
+    const LIMIT: usize = 50;
+
+    fn push(&mut self, error: &str) {
+        if self.lines.len() < Self::LIMIT {
+            self.lines.push(error.to_string());
+        } else {
+            self.omitted += 1;
+        }
+    }
+}
+
+impl Display for ReportedErrors {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.lines.join("\n"))?;
+        if self.omitted > 0 {
+            write!(f, "\n... and {} more.", self.omitted)?;
+        }
+        Ok(())
+    }
+}
+
+/// Run the sbt command, logging its output.
+///
+/// If the command fails, the `[error]` lines that sbt printed are included in the returned error,
+/// so they do not need to be looked up in the (very long) build log. The compiler diagnostics are
+/// reported as annotations.
+///
+/// Builds hanging without any output are diagnosed and killed, see [`Watchdog::from_env`].
+pub async fn run_reporting_errors(command: &mut Command) -> Result {
+    command.set_watchdog(Watchdog::from_env());
+    let errors = Arc::new(Mutex::new(ReportedErrors::default()));
+    let collected = errors.clone();
+    command.on_output_line(move |line| {
+        let line = strip_colors(line);
+        if let Some(error) = parse_error_line(&line) {
+            collected.lock().unwrap().push(error);
+        }
+        if let Some(diagnostic) = parse_diagnostic(&line) {
+            annotations::report(diagnostic);
+        }
+    });
+    let result = command.run_ok().await;
+    let errors = errors.lock().unwrap();
+    if errors.lines.is_empty() {
+        result
+    } else {
+        result.with_context(|| format!("sbt reported errors:\n{errors}"))
+    }
 }
 
 #[cfg(test)]
@@ -50,4 +193,42 @@ mod tests {
         let tasks = ["test", "syntaxJS/fullOptJS"];
         assert_eq!(Sbt::concurrent_tasks(tasks), "all test syntaxJS/fullOptJS");
     }
+
+    #[test]
+    fn format_sequential_tasks() {
+        let tasks = ["compile", "engine-runner/assembly"];
+        assert_eq!(Sbt::sequential_tasks(tasks), "compile; engine-runner/assembly");
+    }
+
+    #[test]
+    fn parse_errors() {
+        let line = "[\x1b[31merror\x1b[0m] Compilation failed";
+        assert_eq!(parse_error_line(&strip_colors(line)), Some("Compilation failed"));
+        assert_eq!(parse_error_line("[info] Compiling 12 Scala sources"), None);
+    }
+
+    #[test]
+    fn limit_reported_errors() {
+        let mut errors = ReportedErrors::default();
+        for i in 0..ReportedErrors::LIMIT + 2 {
+            errors.push(&format!("error {i}"));
+        }
+        assert_eq!(errors.lines.len(), ReportedErrors::LIMIT);
+        assert!(errors.to_string().ends_with("\n... and 2 more."));
+    }
+
+    #[test]
+    fn parse_diagnostics() {
+        let line = "[error] C:\\enso\\engine\\Main.scala:12:5: not found: value x";
+        let message = parse_diagnostic(line).unwrap();
+        assert!(matches!(message.level, MessageLevel::Error));
+        assert_eq!(message.text, "not found: value x");
+        let location = message.location.unwrap();
+        assert_eq!(location.file, Path::new(r"C:\enso\engine\Main.scala"));
+        assert_eq!((location.line, location.column), (Some(12), Some(5)));
+
+        let line = "[warn] /repo/lib/Foo.java:3:1: unchecked call";
+        assert!(matches!(parse_diagnostic(line).unwrap().level, MessageLevel::Warning));
+        assert!(parse_diagnostic("[error] Compilation failed").is_none());
+    }
 }
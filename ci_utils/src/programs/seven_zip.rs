@@ -1,5 +1,12 @@
 use crate::prelude::*;
+
+use crate::archive::CompressionOptions;
+use crate::program::ProgramError;
+
 use snafu::Snafu;
+use std::process::Stdio;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
 
 pub struct SevenZip;
 
@@ -7,7 +14,7 @@ impl Program for SevenZip {
     fn executable_name(&self) -> &'static str {
         "7z"
     }
-    fn executable_name_fallback() -> Vec<&'static str> {
+    fn alternative_names(&self) -> Vec<&str> {
         // 7zz is reportedly used sometimes on macOS
         vec!["7za", "7zz"]
     }
@@ -22,13 +29,13 @@ impl Program for SevenZip {
         vec![]
     }
 
-    fn handle_exit_status(status: std::process::ExitStatus) -> anyhow::Result<()> {
-        if status.success() {
-            Ok(())
-        } else if let Some(code) = status.code() {
-            Err(ExecutionError::from_exit_code(code).into())
-        } else {
-            Err(ExecutionError::Unknown.into())
+    fn interpret_exit_code(
+        status: std::process::ExitStatus,
+    ) -> std::result::Result<(), ProgramError> {
+        match status.code() {
+            Some(0) => Ok(()),
+            Some(code) => Err(ProgramError::domain(code, ExecutionError::from_exit_code(code))),
+            None => ProgramError::check(status),
         }
     }
 }
@@ -66,6 +73,64 @@ impl ExecutionError {
     }
 }
 
+/// Archive entry, as described by the technical listing (`7z l -slt`).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Entry {
+    pub path:        PathBuf,
+    pub is_dir:      bool,
+    /// Uncompressed size in bytes.
+    pub size:        u64,
+    /// Compressed size in bytes. Not known for the entries of solid archives, except the first.
+    pub packed_size: Option<u64>,
+    /// Checksum, as hexadecimal digits.
+    pub crc:         Option<String>,
+}
+
+impl Entry {
+    fn from_properties(properties: &BTreeMap<&str, &str>) -> Result<Self> {
+        let path = properties.get("Path").context("Missing the entry path.")?;
+        let number = |key: &str| -> Result<Option<u64>> {
+            let value = properties.get(key).filter(|value| !value.is_empty());
+            value
+                .map(|value| value.parse())
+                .transpose()
+                .with_context(|| format!("Invalid {key} of the entry {path}."))
+        };
+        let is_dir = properties.get("Folder") == Some(&"+")
+            || properties.get("Attributes").map_or(false, |attributes| attributes.starts_with('D'));
+        Ok(Self {
+            path: PathBuf::from(path),
+            is_dir,
+            size: number("Size")?.unwrap_or_default(),
+            packed_size: number("Packed Size")?,
+            crc: properties.get("CRC").filter(|crc| !crc.is_empty()).map(|crc| crc.to_string()),
+        })
+    }
+}
+
+/// Parse the technical listing printed by `7z l -slt`.
+///
+/// The archive's own properties come first, then after a line of dashes, the entries follow, each
+/// as a block of `Key = Value` lines.
+pub fn parse_technical_listing(listing: &str) -> Result<Vec<Entry>> {
+    let (_, entries) = listing
+        .split_once("\n----------")
+        .context("Missing the separator before the entries in the 7-Zip listing.")?;
+    let mut ret = Vec::new();
+    let mut properties = BTreeMap::new();
+    for line in entries.lines().map(str::trim).chain(once("")) {
+        if line.is_empty() {
+            if !properties.is_empty() {
+                ret.push(Entry::from_properties(&properties)?);
+                properties.clear();
+            }
+        } else if let Some((key, value)) = line.split_once('=') {
+            properties.insert(key.trim(), value.trim());
+        }
+    }
+    Ok(ret)
+}
+
 impl SevenZip {
     pub fn add_cmd<P: AsRef<Path>>(
         &self,
@@ -81,31 +146,51 @@ impl SevenZip {
         Ok(cmd)
     }
 
+    /// Switches that apply the compression options.
+    pub fn compression_switches(options: &CompressionOptions) -> Result<Vec<Switch>> {
+        options.validate()?;
+        let mut ret = vec![];
+        if let Some(level) = options.level {
+            ret.push(Switch::CompressionLevel(level));
+        }
+        if let Some(threads) = options.threads {
+            ret.push(Switch::Multithreading(threads));
+        }
+        Ok(ret)
+    }
+
     /// Removes the old archive under output path if it was present.
     pub async fn pack<P: AsRef<Path>>(
         &self,
         output_archive: impl AsRef<Path>,
         paths_to_pack: impl IntoIterator<Item = P>,
+        options: CompressionOptions,
     ) -> Result {
         crate::fs::remove_if_exists(output_archive.as_ref())?;
-        self.add(output_archive, paths_to_pack).await
+        self.add(output_archive, paths_to_pack, options).await
     }
 
     pub async fn pack_directory_contents(
         self,
         output_archive: impl AsRef<Path>,
         root_directory: impl AsRef<Path>,
+        options: CompressionOptions,
     ) -> Result {
         // See: https://superuser.com/a/418708
-        self.pack(output_archive, [root_directory.as_ref().join("*")]).await
+        self.pack(output_archive, [root_directory.as_ref().join("*")], options).await
     }
 
     pub async fn add<P: AsRef<Path>>(
         &self,
         output_archive: impl AsRef<Path>,
         paths_to_pack: impl IntoIterator<Item = P>,
+        options: CompressionOptions,
     ) -> Result {
-        self.add_cmd(output_archive, paths_to_pack)?.run_ok().await
+        let mut cmd = self.add_cmd(output_archive, paths_to_pack)?;
+        for switch in Self::compression_switches(&options)? {
+            cmd.args(switch);
+        }
+        cmd.run_ok().await
     }
 
     pub fn unpack_cmd(
@@ -122,21 +207,111 @@ impl SevenZip {
         Ok(cmd)
     }
 
-    pub fn unpack_from_stdin_cmd(&self, output_directory: impl AsRef<Path>) -> Result<Command> {
+    /// Command printing the technical listing of the archive entries. See
+    /// [`parse_technical_listing`].
+    pub fn list_cmd(&self, archive: impl AsRef<Path>) -> Result<Command> {
+        let mut cmd = self.cmd()?;
+        cmd.arg(ArchiveCommand::List).args(Switch::TechnicalListing).arg(archive.as_ref());
+        Ok(cmd)
+    }
+
+    /// Command checking the integrity of the archive, without extracting it.
+    pub fn test_cmd(&self, archive: impl AsRef<Path>) -> Result<Command> {
+        let mut cmd = self.cmd()?;
+        cmd.arg(ArchiveCommand::Test).arg(archive.as_ref());
+        Ok(cmd)
+    }
+
+    /// List the archive entries.
+    pub async fn list(&self, archive: impl AsRef<Path>) -> Result<Vec<Entry>> {
+        let listing = self.list_cmd(archive)?.run_stdout().await?;
+        parse_technical_listing(&listing)
+    }
+
+    /// Check that all the archive entries can be extracted and match their checksums.
+    pub async fn test(&self, archive: impl AsRef<Path>) -> Result {
+        self.test_cmd(archive)?.run_ok().await
+    }
+
+    /// Command that extracts the archive passed through the standard input.
+    pub fn unpack_from_stdin_cmd(
+        &self,
+        archive_type: ArchiveType,
+        output_directory: impl AsRef<Path>,
+    ) -> Result<Command> {
+        ensure!(
+            archive_type.supports_streaming(),
+            "7-Zip cannot read {archive_type:?} archives from the standard input."
+        );
         let out_switch = Switch::OutputDirectory(output_directory.as_ref().into());
         let mut cmd = self.cmd()?;
         cmd.arg(ArchiveCommand::ExtractWithFullPaths)
             .args(Switch::AssumeYes)
             .args(out_switch)
+            .args(Switch::ArchiveType(archive_type))
             .args(Switch::ReadFromStdin);
         Ok(cmd)
     }
+
+    /// Command that writes the archive with the given paths to the standard output.
+    pub fn pack_to_stdout_cmd<P: AsRef<Path>>(
+        &self,
+        archive_type: ArchiveType,
+        paths_to_pack: impl IntoIterator<Item = P>,
+    ) -> Result<Command> {
+        ensure!(
+            archive_type.supports_streaming(),
+            "7-Zip cannot write {archive_type:?} archives to the standard output."
+        );
+        let mut cmd = self.cmd()?;
+        cmd.arg(ArchiveCommand::Add)
+            .args(Switch::ArchiveType(archive_type))
+            .args(Switch::WriteToStdout)
+            .args(Switch::DisableArchiveName);
+        for path in paths_to_pack {
+            cmd.arg(path.as_ref());
+        }
+        Ok(cmd)
+    }
+
+    /// Extract the archive read from the given stream, e.g. a network download.
+    pub async fn unpack_from_reader(
+        &self,
+        archive_type: ArchiveType,
+        mut reader: impl AsyncRead + Unpin,
+        output_directory: impl AsRef<Path>,
+    ) -> Result {
+        crate::fs::tokio::create_dir_if_missing(&output_directory).await?;
+        let mut cmd = self.unpack_from_stdin_cmd(archive_type, output_directory)?;
+        let mut child = cmd.stdin(Stdio::piped()).spawn()?;
+        let mut stdin = child.stdin.take().context("Failed to get 7z stdin handle.")?;
+        tokio::io::copy(&mut reader, &mut stdin).await?;
+        // Closing the stream signals the end of the archive.
+        drop(stdin);
+        Self::handle_exit_status(child.wait().await?)
+    }
+
+    /// Pack the paths and write the archive into the given stream, e.g. an upload.
+    pub async fn pack_to_writer<P: AsRef<Path>>(
+        &self,
+        archive_type: ArchiveType,
+        paths_to_pack: impl IntoIterator<Item = P>,
+        mut writer: impl AsyncWrite + Unpin,
+    ) -> Result {
+        let mut cmd = self.pack_to_stdout_cmd(archive_type, paths_to_pack)?;
+        let mut child = cmd.stdout(Stdio::piped()).spawn()?;
+        let mut stdout = child.stdout.take().context("Failed to get 7z stdout handle.")?;
+        tokio::io::copy(&mut stdout, &mut writer).await?;
+        Self::handle_exit_status(child.wait().await?)
+    }
 }
 
 #[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
 pub enum ArchiveCommand {
     Add,
     ExtractWithFullPaths,
+    List,
+    Test,
 }
 
 impl AsRef<OsStr> for ArchiveCommand {
@@ -144,11 +319,48 @@ impl AsRef<OsStr> for ArchiveCommand {
         match self {
             Self::Add => "a",
             Self::ExtractWithFullPaths => "x",
+            Self::List => "l",
+            Self::Test => "t",
         }
         .as_ref()
     }
 }
 
+/// Archive type, as given to the `-t` switch.
+#[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
+pub enum ArchiveType {
+    SevenZip,
+    Zip,
+    Tar,
+    Gzip,
+    Bzip2,
+    Xz,
+}
+
+impl ArchiveType {
+    /// Whether the archive can be processed sequentially, i.e. read from the standard input or
+    /// written to the standard output.
+    ///
+    /// The 7z and zip formats need random access to the archive file.
+    pub fn supports_streaming(self) -> bool {
+        !matches!(self, ArchiveType::SevenZip | ArchiveType::Zip)
+    }
+}
+
+impl From<ArchiveType> for OsString {
+    fn from(value: ArchiveType) -> Self {
+        match value {
+            ArchiveType::SevenZip => "7z",
+            ArchiveType::Zip => "zip",
+            ArchiveType::Tar => "tar",
+            ArchiveType::Gzip => "gzip",
+            ArchiveType::Bzip2 => "bzip2",
+            ArchiveType::Xz => "xz",
+        }
+        .into()
+    }
+}
+
 // https://sevenzip.osdn.jp/chm/cmdline/switches/index.htm
 #[derive(Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
 pub enum Switch {
@@ -159,6 +371,17 @@ pub enum Switch {
     SetCharset(Charset),
     /// Read data from standard input, rather than from a file.
     ReadFromStdin,
+    /// Write data to standard output, rather than to a file.
+    WriteToStdout,
+    /// Do not expect the archive name on the command line.
+    DisableArchiveName,
+    ArchiveType(ArchiveType),
+    /// Compression level, from 0 (store only) to 9 (ultra).
+    CompressionLevel(u32),
+    /// Number of compression threads. Zero enables multithreading with the default count.
+    Multithreading(usize),
+    /// Show the technical information when listing the archive.
+    TechnicalListing,
 }
 
 #[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
@@ -239,7 +462,135 @@ impl IntoIterator for Switch {
             Self::RedirectStream(str, dest) => vec!["-bs".into(), str.into(), dest.into()],
             Self::SetCharset(charset) => vec!["-scc".into(), charset.into()],
             Self::ReadFromStdin => vec!["-si".into()],
+            Self::WriteToStdout => vec!["-so".into()],
+            Self::DisableArchiveName => vec!["-an".into()],
+            Self::CompressionLevel(level) => vec![format!("-mx{level}").into()],
+            Self::Multithreading(0) => vec!["-mmt=on".into()],
+            Self::Multithreading(threads) => vec![format!("-mmt{threads}").into()],
+            Self::TechnicalListing => vec!["-slt".into()],
+            Self::ArchiveType(archive_type) => {
+                let mut switch = OsString::from("-t");
+                switch.push(OsString::from(archive_type));
+                vec![switch]
+            }
         }
         .into_iter()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::expect;
+
+    #[test]
+    fn streaming_switches() {
+        let args = |switch: Switch| switch.into_iter().collect_vec();
+        assert_eq!(args(Switch::ArchiveType(ArchiveType::Tar)), vec![OsString::from("-ttar")]);
+        assert_eq!(args(Switch::WriteToStdout), vec![OsString::from("-so")]);
+        assert!(ArchiveType::Xz.supports_streaming());
+        assert!(!ArchiveType::SevenZip.supports_streaming());
+    }
+
+    #[test]
+    fn compression_switches() -> Result {
+        let args = |options: CompressionOptions| -> Result<Vec<OsString>> {
+            Ok(SevenZip::compression_switches(&options)?.into_iter().flatten().collect())
+        };
+        assert_eq!(args(CompressionOptions::best())?, vec![
+            OsString::from("-mx9"),
+            OsString::from("-mmt4")
+        ]);
+        assert!(args(default())?.is_empty());
+        assert!(args(CompressionOptions { level: Some(10), ..default() }).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn technical_listing() -> Result {
+        let listing = r"
+7-Zip [64] 16.02 : Copyright (c) 1999-2016 Igor Pavlov : 2016-05-21
+
+Listing archive: dist.7z
+
+--
+Path = dist.7z
+Type = 7z
+Physical Size = 1024
+Solid = +
+
+----------
+Path = bin
+Size = 0
+Packed Size = 0
+Modified = 2022-05-10 12:00:00
+Attributes = D_ drwxr-xr-x
+CRC =
+Encrypted = -
+
+Path = bin/enso
+Size = 2048
+Packed Size = 900
+Modified = 2022-05-10 12:00:00
+Attributes = A_ -rwxr-xr-x
+CRC = 1A2B3C4D
+Encrypted = -
+
+Path = README.md
+Size = 10
+Packed Size = 
+Attributes = A_ -rw-r--r--
+CRC = 0F0F0F0F
+";
+        let entries = parse_technical_listing(listing)?;
+        assert_eq!(entries, vec![
+            Entry {
+                path:        PathBuf::from("bin"),
+                is_dir:      true,
+                size:        0,
+                packed_size: Some(0),
+                crc:         None,
+            },
+            Entry {
+                path:        PathBuf::from("bin/enso"),
+                is_dir:      false,
+                size:        2048,
+                packed_size: Some(900),
+                crc:         Some("1A2B3C4D".into()),
+            },
+            Entry {
+                path:        PathBuf::from("README.md"),
+                is_dir:      false,
+                size:        10,
+                packed_size: None,
+                crc:         Some("0F0F0F0F".into()),
+            },
+        ]);
+        assert!(parse_technical_listing("Error: not an archive").is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn archive_commands() -> Result {
+        let temp = tempfile::tempdir()?;
+        let dist = temp.path().join("dist");
+        crate::fs::write(dist.join("bin").join("enso"), "enso")?;
+        let archive = temp.path().join("dist.7z");
+        SevenZip.pack_directory_contents(&archive, &dist, default()).await?;
+
+        expect(SevenZip.test_cmd(&archive)?).stdout_contains("Everything is Ok").run().await?;
+        let output =
+            expect(SevenZip.list_cmd(&archive)?).stdout_contains("Path = bin").run().await?;
+        let entries = parse_technical_listing(&String::from_utf8_lossy(&output.stdout))?;
+        assert!(entries.iter().any(|entry| entry.path == Path::new("bin").join("enso")));
+
+        let out = temp.path().join("out");
+        expect(SevenZip.unpack_cmd(&archive, &out)?).success().run().await?;
+        assert_eq!(crate::fs::read_to_string(out.join("bin").join("enso"))?, "enso");
+
+        let broken = temp.path().join("broken.7z");
+        crate::fs::write(&broken, "not an archive")?;
+        expect(SevenZip.test_cmd(&broken)?).code(2).run().await?;
+        Ok(())
+    }
+}
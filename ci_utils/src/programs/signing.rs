@@ -0,0 +1,58 @@
+//! Code signing of the release artifacts.
+//!
+//! Each platform has its own tooling: `signtool` on Windows, `codesign` (followed by the
+//! notarization) on macOS. [`sign_and_verify`] picks the right one for the current OS.
+
+use crate::prelude::*;
+
+pub mod codesign;
+pub mod notarytool;
+pub mod signtool;
+
+pub use codesign::Codesign;
+pub use notarytool::Xcrun;
+pub use signtool::SignTool;
+
+
+/// Signing settings for macOS.
+#[derive(Clone, Debug)]
+pub struct MacOsSigning {
+    pub codesign:     codesign::SignOptions,
+    /// If set, the signed artifact is also notarized and the ticket is stapled to it.
+    pub notarization: Option<notarytool::Credentials>,
+}
+
+/// Signing settings for all the platforms. Only the one for the current OS is used.
+#[derive(Clone, Debug, Default)]
+pub struct SigningConfig {
+    pub windows: Option<signtool::SignOptions>,
+    pub macos:   Option<MacOsSigning>,
+}
+
+/// Sign the artifact with the current platform's tooling and verify the signature.
+///
+/// Fails if the configuration for the current platform is missing. On Linux there is nothing to
+/// do, as we do not sign Linux artifacts.
+pub async fn sign_and_verify(artifact: impl AsRef<Path>, config: &SigningConfig) -> Result {
+    let artifact = artifact.as_ref();
+    info!("Signing {}.", artifact.display());
+    match TARGET_OS {
+        OS::Windows => {
+            let options = config.windows.as_ref().context("Missing Windows signing config.")?;
+            SignTool.sign(options, [artifact]).await?;
+            SignTool.verify([artifact]).await?;
+        }
+        OS::MacOS => {
+            let macos = config.macos.as_ref().context("Missing macOS signing config.")?;
+            Codesign.sign(&macos.codesign, artifact).await?;
+            Codesign.verify(artifact).await?;
+            if let Some(credentials) = &macos.notarization {
+                Xcrun.notarize(artifact, credentials).await?;
+                Xcrun.staple(artifact).await?;
+            }
+        }
+        OS::Linux => debug!("Artifacts are not signed on Linux."),
+        other => bail!("Code signing is not supported on {other}."),
+    }
+    Ok(())
+}
@@ -0,0 +1,104 @@
+//! Wrapper over the macOS `codesign`.
+
+use crate::prelude::*;
+
+
+/// The `codesign` program, part of the Xcode command line tools.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Codesign;
+
+impl Program for Codesign {
+    fn executable_name(&self) -> &'static str {
+        "codesign"
+    }
+}
+
+/// Options of the `codesign --sign` invocation.
+#[derive(Clone, Debug)]
+pub struct SignOptions {
+    /// Name (or hash) of the signing identity, e.g. `Developer ID Application: New Byte Order`.
+    pub identity:         String,
+    /// Keychain to look the identity up in. If not set, the default search list is used.
+    pub keychain:         Option<PathBuf>,
+    /// Entitlements property list embedded into the signature.
+    pub entitlements:     Option<PathBuf>,
+    /// Enable the hardened runtime. Required for notarization.
+    pub hardened_runtime: bool,
+    /// Include a secure timestamp. Required for notarization.
+    pub timestamp:        bool,
+    /// Also sign the nested code, like frameworks and helpers inside the app bundle.
+    pub deep:             bool,
+}
+
+impl SignOptions {
+    /// Options fit for the artifacts that are going to be notarized.
+    pub fn new(identity: impl Into<String>) -> Self {
+        Self {
+            identity:         identity.into(),
+            keychain:         None,
+            entitlements:     None,
+            hardened_runtime: true,
+            timestamp:        true,
+            deep:             true,
+        }
+    }
+
+    pub fn args(&self) -> Vec<OsString> {
+        let mut ret: Vec<OsString> =
+            vec!["--sign".into(), self.identity.as_str().into(), "--force".into()];
+        if let Some(keychain) = &self.keychain {
+            ret.extend(["--keychain".into(), keychain.into()]);
+        }
+        if let Some(entitlements) = &self.entitlements {
+            ret.extend(["--entitlements".into(), entitlements.into()]);
+        }
+        if self.hardened_runtime {
+            ret.extend(["--options".into(), "runtime".into()]);
+        }
+        if self.timestamp {
+            ret.push("--timestamp".into());
+        }
+        if self.deep {
+            ret.push("--deep".into());
+        }
+        ret
+    }
+}
+
+impl Codesign {
+    /// Sign the given file or bundle, replacing any existing signature.
+    pub async fn sign(&self, options: &SignOptions, path: impl AsRef<Path>) -> Result {
+        self.cmd()?.args(options.args()).arg(path.as_ref()).run_ok().await
+    }
+
+    /// Verify the signature, including all the nested code.
+    pub async fn verify(&self, path: impl AsRef<Path>) -> Result {
+        self.cmd()?
+            .args(["--verify", "--deep", "--strict", "--verbose=2"])
+            .arg(path.as_ref())
+            .run_ok()
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_args() {
+        let mut options = SignOptions::new("Developer ID Application: Enso");
+        options.entitlements = Some("entitlements.plist".into());
+        options.deep = false;
+        assert_eq!(options.args(), [
+            "--sign",
+            "Developer ID Application: Enso",
+            "--force",
+            "--entitlements",
+            "entitlements.plist",
+            "--options",
+            "runtime",
+            "--timestamp"
+        ]);
+    }
+}
@@ -0,0 +1,138 @@
+//! Apple notarization service, used through `xcrun notarytool` and `xcrun stapler`.
+
+use crate::prelude::*;
+
+
+/// The `xcrun` launcher of the Xcode developer tools.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Xcrun;
+
+impl Program for Xcrun {
+    fn executable_name(&self) -> &'static str {
+        "xcrun"
+    }
+}
+
+/// The `ditto` program, used to pack app bundles for submission.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Ditto;
+
+impl Program for Ditto {
+    fn executable_name(&self) -> &'static str {
+        "ditto"
+    }
+}
+
+/// Credentials for the notarization service.
+#[derive(Clone, Derivative)]
+#[derivative(Debug)]
+pub enum Credentials {
+    /// App Store Connect API key.
+    ApiKey { key_path: PathBuf, key_id: String, issuer: String },
+    /// Apple ID with an app-specific password.
+    AppleId {
+        apple_id: String,
+        #[derivative(Debug = "ignore")]
+        password: String,
+        team_id:  String,
+    },
+    /// Credentials stored in the keychain with `notarytool store-credentials`.
+    KeychainProfile(String),
+}
+
+impl Credentials {
+    /// The `notarytool` arguments with the credentials.
+    ///
+    /// The password is [registered as a secret](crate::program::audit::register_secret) to keep it
+    /// out of the logs.
+    pub fn args(&self) -> Vec<OsString> {
+        match self {
+            Credentials::ApiKey { key_path, key_id, issuer } => vec![
+                "--key".into(),
+                key_path.into(),
+                "--key-id".into(),
+                key_id.into(),
+                "--issuer".into(),
+                issuer.into(),
+            ],
+            Credentials::AppleId { apple_id, password, team_id } => {
+                crate::program::audit::register_secret(password);
+                vec![
+                    "--apple-id".into(),
+                    apple_id.into(),
+                    "--password".into(),
+                    password.into(),
+                    "--team-id".into(),
+                    team_id.into(),
+                ]
+            }
+            Credentials::KeychainProfile(profile) =>
+                vec!["--keychain-profile".into(), profile.into()],
+        }
+    }
+}
+
+impl Xcrun {
+    /// Submit the artifact for notarization and wait for the verdict.
+    ///
+    /// The service accepts only `zip`, `dmg` and `pkg` files. Other artifacts (like app bundles)
+    /// are zipped before the submission.
+    pub async fn notarize(&self, artifact: impl AsRef<Path>, credentials: &Credentials) -> Result {
+        let artifact = artifact.as_ref();
+        let is_submittable = artifact
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map_or(false, |extension| ["zip", "dmg", "pkg"].contains(&extension));
+        let temp_dir = crate::fs::temp::dir("notarization")?;
+        let submitted = if is_submittable {
+            artifact.to_owned()
+        } else {
+            let archive = temp_dir.path().join(artifact.file_name().unwrap_or_default());
+            let archive = archive.with_extension("zip");
+            Ditto
+                .cmd()?
+                .args(["-c", "-k", "--keepParent"])
+                .arg(artifact)
+                .arg(&archive)
+                .run_ok()
+                .await?;
+            archive
+        };
+        self.cmd()?
+            .args(["notarytool", "submit", "--wait"])
+            .args(credentials.args())
+            .arg(&submitted)
+            .run_ok()
+            .await
+            .with_context(|| format!("Failed to notarize {}.", artifact.display()))
+    }
+
+    /// Attach the notarization ticket to the artifact, so it can be verified offline.
+    pub async fn staple(&self, artifact: impl AsRef<Path>) -> Result {
+        self.cmd()?.args(["stapler", "staple"]).arg(artifact.as_ref()).run_ok().await
+    }
+
+    /// Check that the artifact has a valid notarization ticket stapled.
+    pub async fn validate_staple(&self, artifact: impl AsRef<Path>) -> Result {
+        self.cmd()?.args(["stapler", "validate"]).arg(artifact.as_ref()).run_ok().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn credentials_args() {
+        let credentials = Credentials::AppleId {
+            apple_id: "ci@enso.org".into(),
+            password: "notarytool-test-password".into(),
+            team_id:  "TEAM".into(),
+        };
+        assert!(!format!("{credentials:?}").contains("notarytool-test-password"));
+        assert_eq!(credentials.args()[..2], ["--apple-id", "ci@enso.org"]);
+        let mut cmd = Command::new("xcrun");
+        cmd.args(credentials.args());
+        assert!(!cmd.describe().contains("notarytool-test-password"));
+    }
+}
@@ -0,0 +1,156 @@
+//! Wrapper over the Windows SDK `signtool`.
+
+use crate::prelude::*;
+
+
+/// The default timestamping server, used unless the configuration says otherwise.
+pub const DEFAULT_TIMESTAMP_URL: &str = "http://timestamp.digicert.com";
+
+/// The `signtool` program from the Windows SDK.
+///
+/// It is usually not in `PATH`, so the newest SDK's `bin` directory is searched as well.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SignTool;
+
+impl Program for SignTool {
+    fn executable_name(&self) -> &'static str {
+        "signtool"
+    }
+
+    fn default_locations(&self) -> Vec<PathBuf> {
+        let sdk_bin = crate::platform::win::program_files_x86()
+            .map(|program_files| program_files.join("Windows Kits").join("10").join("bin"));
+        let sdk_bin = match sdk_bin {
+            Ok(sdk_bin) => sdk_bin,
+            Err(_) => return default(),
+        };
+        let mut versions = std::fs::read_dir(&sdk_bin)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.join("x64").is_dir())
+            .collect_vec();
+        // Directories are named after SDK versions, like `10.0.19041.0`. Newest go first.
+        versions.sort_by(|a, b| b.cmp(a));
+        versions.into_iter().map(|version| version.join("x64")).collect()
+    }
+}
+
+/// Source of the certificate used for signing.
+#[derive(Clone, Derivative)]
+#[derivative(Debug)]
+pub enum Certificate {
+    /// PFX file, optionally protected by a password.
+    File {
+        path:     PathBuf,
+        #[derivative(Debug = "ignore")]
+        password: Option<String>,
+    },
+    /// Certificate from the Windows certificate store, identified by its SHA-1 thumbprint.
+    Store { thumbprint: String },
+}
+
+impl Certificate {
+    /// The `signtool` arguments selecting the certificate.
+    ///
+    /// The tool accepts the password only as an argument, so it is
+    /// [registered as a secret](crate::program::audit::register_secret) to keep it out of the logs.
+    pub fn args(&self) -> Vec<OsString> {
+        match self {
+            Certificate::File { path, password } => {
+                let mut ret = vec!["/f".into(), path.into()];
+                if let Some(password) = password {
+                    crate::program::audit::register_secret(password);
+                    ret.extend(["/p".into(), password.into()]);
+                }
+                ret
+            }
+            Certificate::Store { thumbprint } => vec!["/sha1".into(), thumbprint.into()],
+        }
+    }
+}
+
+/// Options of the `signtool sign` invocation.
+#[derive(Clone, Debug)]
+pub struct SignOptions {
+    pub certificate:   Certificate,
+    /// RFC 3161 timestamping server. Without timestamp the signature expires with the
+    /// certificate.
+    pub timestamp_url: Option<String>,
+    /// Description of the signed content, shown by the UAC prompt.
+    pub description:   Option<String>,
+}
+
+impl SignOptions {
+    pub fn new(certificate: Certificate) -> Self {
+        Self { certificate, timestamp_url: Some(DEFAULT_TIMESTAMP_URL.into()), description: None }
+    }
+
+    pub fn args(&self) -> Vec<OsString> {
+        let mut ret: Vec<OsString> = vec!["sign".into(), "/fd".into(), "sha256".into()];
+        ret.extend(self.certificate.args());
+        if let Some(url) = &self.timestamp_url {
+            ret.extend(["/tr".into(), url.into(), "/td".into(), "sha256".into()]);
+        }
+        if let Some(description) = &self.description {
+            ret.extend(["/d".into(), description.into()]);
+        }
+        ret
+    }
+}
+
+impl SignTool {
+    /// Sign the given files.
+    pub async fn sign(
+        &self,
+        options: &SignOptions,
+        files: impl IntoIterator<Item: AsRef<Path>>,
+    ) -> Result {
+        self.cmd()?
+            .args(options.args())
+            .args(files.into_iter().map(|f| f.as_ref().to_owned()))
+            .run_ok()
+            .await
+    }
+
+    /// Verify the signatures using the default Authenticode verification policy.
+    pub async fn verify(&self, files: impl IntoIterator<Item: AsRef<Path>>) -> Result {
+        self.cmd()?
+            .args(["verify", "/pa", "/v"])
+            .args(files.into_iter().map(|f| f.as_ref().to_owned()))
+            .run_ok()
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_args() {
+        let certificate = Certificate::File {
+            path:     "cert.pfx".into(),
+            password: Some("signtool-test-password".into()),
+        };
+        assert!(!format!("{certificate:?}").contains("signtool-test-password"));
+        let options = SignOptions::new(certificate);
+        assert_eq!(options.args(), [
+            "sign",
+            "/fd",
+            "sha256",
+            "/f",
+            "cert.pfx",
+            "/p",
+            "signtool-test-password",
+            "/tr",
+            DEFAULT_TIMESTAMP_URL,
+            "/td",
+            "sha256"
+        ]);
+        let mut cmd = Command::new("signtool");
+        cmd.args(options.args());
+        assert!(!cmd.describe().contains("signtool-test-password"));
+    }
+}
@@ -1,8 +1,9 @@
-use std::vec::IntoIter;
-// use std::vec::IntoIter;
 use crate::prelude::*;
 
+use crate::archive::CompressionOptions;
+use crate::archive::ExtractOptions;
 use crate::archive::Format;
+use std::lazy::SyncLazy;
 
 
 #[derive(Clone, Copy, Debug, Ord, PartialOrd, Eq, PartialEq)]
@@ -11,6 +12,7 @@ pub enum Compression {
     Gzip,
     Lzma,
     Xz,
+    Zstd,
 }
 
 impl Compression {
@@ -24,6 +26,8 @@ impl Compression {
             Ok(Compression::Lzma)
         } else if extension == "xz" {
             Ok(Compression::Xz)
+        } else if extension == "zst" {
+            Ok(Compression::Zstd)
         } else {
             bail!("The extension `{}` does not denote a supported compression algorithm for TAR archives.", extension)
         }
@@ -38,6 +42,7 @@ impl Display for Compression {
             Gzip => "gzip",
             Lzma => "lzma",
             Xz => "xz",
+            Zstd => "zstd",
         })
     }
 }
@@ -49,6 +54,7 @@ impl AsRef<str> for Compression {
             Compression::Gzip => "-z",
             Compression::Lzma => "--lzma",
             Compression::Xz => "-J",
+            Compression::Zstd => "--zstd",
         }
     }
 }
@@ -60,55 +66,347 @@ impl AsRef<OsStr> for Compression {
     }
 }
 
+/// Implementation of tar, as they differ in the supported options.
 #[derive(Clone, Copy, Debug, Ord, PartialOrd, Eq, PartialEq)]
-pub enum Switch<'a> {
-    TargetFile(&'a Path),
-    Verbose,
-    UseFormat(Compression),
-    WorkingDir(&'a Path),
+pub enum Flavor {
+    /// GNU tar, the default on most Linux distributions.
+    Gnu,
+    /// libarchive's bsdtar, shipped with macOS and Windows.
+    Bsd,
+    /// Some other implementation (e.g. BusyBox). Only the common options are assumed to work.
+    Other,
 }
 
-impl<'a> IntoIterator for &'a Switch<'a> {
-    type Item = &'a OsStr;
-    type IntoIter = IntoIter<&'a OsStr>;
+impl Flavor {
+    /// Identify the implementation from the output of `tar --version`.
+    pub fn from_version_text(text: &str) -> Self {
+        if text.contains("GNU tar") {
+            Flavor::Gnu
+        } else if text.contains("bsdtar") {
+            Flavor::Bsd
+        } else {
+            Flavor::Other
+        }
+    }
 
-    fn into_iter(self) -> Self::IntoIter {
-        match self {
-            Switch::TargetFile(tgt) => vec!["-f".as_ref(), tgt.as_ref()],
-            Switch::Verbose => vec!["--verbose".as_ref()],
-            Switch::UseFormat(compression) => vec![compression.as_ref()],
-            Switch::WorkingDir(dir) => vec!["--directory".as_ref(), dir.as_ref()],
+    /// Whether the compression can be explicitly requested with a command line flag.
+    ///
+    /// The less common compressions are known to be supported only by GNU tar and bsdtar.
+    pub fn supports_compression(self, compression: Compression) -> bool {
+        match compression {
+            Compression::Lzma | Compression::Zstd => matches!(self, Flavor::Gnu | Flavor::Bsd),
+            Compression::Bzip2 | Compression::Gzip | Compression::Xz => true,
         }
-        .into_iter()
+    }
+
+    /// Whether `--sort` is supported.
+    pub fn supports_sort(self) -> bool {
+        self == Flavor::Gnu
+    }
+
+    /// Whether `--transform` is supported.
+    pub fn supports_transform(self) -> bool {
+        self == Flavor::Gnu
     }
 }
 
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq)]
-pub enum Command {
+/// Operation mode of tar.
+#[derive(Clone, Copy, Debug, Ord, PartialOrd, Eq, PartialEq)]
+pub enum Mode {
     Append,
     Create,
     Extract,
     List,
 }
 
-impl AsRef<str> for Command {
+impl AsRef<str> for Mode {
     fn as_ref(&self) -> &str {
         match self {
-            Command::Append => "-r",
-            Command::Create => "-c",
-            Command::Extract => "-x",
-            Command::List => "-t",
+            Mode::Append => "-r",
+            Mode::Create => "-c",
+            Mode::Extract => "-x",
+            Mode::List => "-t",
         }
     }
 }
 
-impl AsRef<OsStr> for Command {
+impl AsRef<OsStr> for Mode {
     fn as_ref(&self) -> &OsStr {
         let str: &str = self.as_ref();
         str.as_ref()
     }
 }
 
+/// Description of a tar invocation.
+///
+/// Incompatible combinations of options are reported when the arguments are rendered, so the
+/// command is never run with an inconsistent set of flags.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TarCommandBuilder {
+    pub mode:                Mode,
+    pub archive:             PathBuf,
+    /// Compression of the archive. If not set when reading the archive, tar will detect it.
+    pub compression:         Option<Compression>,
+    /// Directory to change into before performing any operation.
+    pub working_dir:         Option<PathBuf>,
+    /// Patterns of files to be skipped.
+    pub excludes:            Vec<String>,
+    /// Number of leading components to strip from file names on extraction.
+    pub strip_components:    Option<usize>,
+    /// Whether the owners recorded in the archive are restored on extraction. If not set, tar
+    /// restores them only when run by root.
+    pub same_owner:          Option<bool>,
+    /// `sed`-like replace expressions applied to file names, e.g. `s,^foo,bar,`.
+    ///
+    /// Supported only by GNU tar.
+    pub transforms:          Vec<String>,
+    pub verbose:             bool,
+    /// Sort the directory entries by name, so the archive contents are reproducible.
+    ///
+    /// Supported only by GNU tar.
+    pub sort_by_name:        bool,
+    /// Compression tuning, passed to the compressor through the arguments or the environment.
+    pub compression_options: CompressionOptions,
+    /// Files to be packed or, when reading the archive, the members to be processed.
+    pub paths:               Vec<PathBuf>,
+}
+
+impl TarCommandBuilder {
+    pub fn new(mode: Mode, archive: impl Into<PathBuf>) -> Self {
+        Self {
+            mode,
+            archive: archive.into(),
+            compression: default(),
+            working_dir: default(),
+            excludes: default(),
+            strip_components: default(),
+            same_owner: default(),
+            transforms: default(),
+            verbose: default(),
+            sort_by_name: default(),
+            compression_options: default(),
+            paths: default(),
+        }
+    }
+
+    pub fn create(archive: impl Into<PathBuf>) -> Self {
+        Self::new(Mode::Create, archive)
+    }
+
+    pub fn extract(archive: impl Into<PathBuf>) -> Self {
+        Self::new(Mode::Extract, archive)
+    }
+
+    pub fn list(archive: impl Into<PathBuf>) -> Self {
+        Self::new(Mode::List, archive)
+    }
+
+    pub fn compression(&mut self, compression: impl Into<Option<Compression>>) -> &mut Self {
+        self.compression = compression.into();
+        self
+    }
+
+    /// Use the compression denoted by the archive's file extension, if there is any.
+    pub fn compression_from_extension(&mut self) -> &mut Self {
+        if let Ok(Format::Tar(compression)) = Format::from_filename(&self.archive) {
+            self.compression = compression;
+        }
+        self
+    }
+
+    pub fn working_dir(&mut self, dir: impl Into<PathBuf>) -> &mut Self {
+        self.working_dir = Some(dir.into());
+        self
+    }
+
+    pub fn exclude(&mut self, pattern: impl Into<String>) -> &mut Self {
+        self.excludes.push(pattern.into());
+        self
+    }
+
+    pub fn strip_components(&mut self, count: usize) -> &mut Self {
+        self.strip_components = Some(count);
+        self
+    }
+
+    pub fn same_owner(&mut self, same_owner: bool) -> &mut Self {
+        self.same_owner = Some(same_owner);
+        self
+    }
+
+    pub fn transform(&mut self, expression: impl Into<String>) -> &mut Self {
+        self.transforms.push(expression.into());
+        self
+    }
+
+    pub fn verbose(&mut self, verbose: bool) -> &mut Self {
+        self.verbose = verbose;
+        self
+    }
+
+    pub fn sort_by_name(&mut self, sort: bool) -> &mut Self {
+        self.sort_by_name = sort;
+        self
+    }
+
+    pub fn compression_options(&mut self, options: CompressionOptions) -> &mut Self {
+        self.compression_options = options;
+        self
+    }
+
+    /// Compressor program invocation to be used instead of the compression switch, if the
+    /// compression options need to be passed as its arguments.
+    ///
+    /// This is the case for gzip, which deprecated reading the options from the environment.
+    pub fn compress_program(&self) -> Option<String> {
+        match self.compression {
+            Some(Compression::Gzip) =>
+                self.compression_options.level.map(|level| format!("gzip -{}", level.max(1))),
+            _ => None,
+        }
+    }
+
+    /// Environment variables that pass the compression options to the compressor program.
+    ///
+    /// Tar does not have switches for tuning the compression, but the compressors it invokes read
+    /// their default options from the environment. Threads are ignored by the single-threaded
+    /// compressors. Gzip options are passed through [`Self::compress_program`] instead.
+    pub fn compression_env(&self) -> Vec<(&'static str, String)> {
+        let CompressionOptions { level, extreme, .. } = self.compression_options;
+        let threads = self.compression_options.resolved_threads();
+        let mut ret = vec![];
+        match self.compression {
+            Some(Compression::Bzip2) =>
+                ret.extend(level.map(|level| ("BZIP2", format!("-{}", level.max(1))))),
+            Some(compression @ (Compression::Xz | Compression::Lzma)) => {
+                let preset_modifier = if extreme { "e" } else { "" };
+                let mut options = level
+                    .map(|level| format!("-{level}{preset_modifier}"))
+                    .into_iter()
+                    .collect_vec();
+                if compression == Compression::Xz && let Some(threads) = threads {
+                    options.push(format!("-T{threads}"));
+                }
+                if !options.is_empty() {
+                    ret.push(("XZ_OPT", options.join(" ")));
+                }
+            }
+            Some(Compression::Zstd) => {
+                ret.extend(level.map(|level| ("ZSTD_CLEVEL", level.max(1).to_string())));
+                ret.extend(threads.map(|threads| ("ZSTD_NBTHREADS", threads.to_string())));
+            }
+            Some(Compression::Gzip) | None => {}
+        }
+        ret
+    }
+
+    /// Make the invocation compatible with the given tar implementation.
+    ///
+    /// Options that only affect the archive's reproducibility are dropped. If an essential option
+    /// is not supported, an error is returned.
+    pub fn adapt_to(&mut self, flavor: Flavor) -> Result<&mut Self> {
+        const HINT: &str = "Please install GNU tar and make it available in PATH as `tar`, e.g. \
+        on macOS `brew install gnu-tar` and add its `gnubin` directory to PATH.";
+        if self.sort_by_name && !flavor.supports_sort() {
+            debug!("{flavor:?} tar cannot sort entries, the archive might not be reproducible.");
+            self.sort_by_name = false;
+        }
+        if let Some(compression) = self.compression {
+            ensure!(
+                flavor.supports_compression(compression),
+                "{flavor:?} tar does not support {compression} compression. {HINT}"
+            );
+        }
+        ensure!(
+            self.transforms.is_empty() || flavor.supports_transform(),
+            "{flavor:?} tar does not support transforming file names. {HINT}"
+        );
+        Ok(self)
+    }
+
+    pub fn path(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.paths.push(path.into());
+        self
+    }
+
+    pub fn paths<P: Into<PathBuf>>(&mut self, paths: impl IntoIterator<Item = P>) -> &mut Self {
+        self.paths.extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    /// Check that the options can be used together.
+    pub fn validate(&self) -> Result {
+        self.compression_options.validate()?;
+        match self.mode {
+            Mode::Create | Mode::Append =>
+                ensure!(!self.paths.is_empty(), "No files were given to be put into the archive."),
+            Mode::Extract | Mode::List => {}
+        }
+        if self.mode == Mode::Append {
+            ensure!(
+                self.compression.is_none(),
+                "Cannot append to a compressed archive (compression: {:?}).",
+                self.compression
+            );
+        }
+        if self.mode != Mode::Extract {
+            ensure!(
+                self.strip_components.is_none(),
+                "Stripping path components is supported only when extracting."
+            );
+            ensure!(
+                self.same_owner.is_none(),
+                "Restoring the ownership is supported only when extracting."
+            );
+        }
+        Ok(())
+    }
+
+    /// Render the command line arguments.
+    pub fn args(&self) -> Result<Vec<OsString>> {
+        self.validate()?;
+        let mut ret: Vec<OsString> = vec![AsRef::<OsStr>::as_ref(&self.mode).into()];
+        if let Some(program) = self.compress_program() {
+            ret.push(format!("--use-compress-program={program}").into());
+        } else if let Some(compression) = self.compression {
+            ret.push(AsRef::<OsStr>::as_ref(&compression).into());
+        }
+        if self.verbose {
+            ret.push("--verbose".into());
+        }
+        if self.sort_by_name {
+            ret.push("--sort=name".into());
+        }
+        ret.push("-f".into());
+        ret.push(self.archive.clone().into());
+        if let Some(dir) = &self.working_dir {
+            ret.push("--directory".into());
+            ret.push(dir.into());
+        }
+        if let Some(count) = self.strip_components {
+            ret.push(format!("--strip-components={count}").into());
+        }
+        if let Some(same_owner) = self.same_owner {
+            ret.push(if same_owner { "--same-owner" } else { "--no-same-owner" }.into());
+        }
+        ret.extend(self.excludes.iter().map(|pattern| format!("--exclude={pattern}").into()));
+        ret.extend(self.transforms.iter().map(|expr| format!("--transform={expr}").into()));
+        if !self.paths.is_empty() {
+            // Paths could begin with a dash, they must not be mistaken for options.
+            ret.push("--".into());
+            ret.extend(self.paths.iter().map(|path| path.into()));
+        }
+        Ok(ret)
+    }
+
+    /// Create the `tar` command with the rendered arguments.
+    pub fn build(&self) -> Result<crate::prelude::Command> {
+        let mut cmd = Tar.cmd()?;
+        cmd.args(self.args()?).envs(self.compression_env());
+        Ok(cmd)
+    }
+}
+
 pub struct Tar;
 
 impl Program for Tar {
@@ -118,20 +416,47 @@ impl Program for Tar {
 }
 
 impl Tar {
+    /// Implementation of the `tar` found in PATH.
+    ///
+    /// The probe is run only once per process. If it fails, [`Flavor::Other`] is assumed.
+    pub fn flavor(&self) -> Flavor {
+        static FLAVOR: SyncLazy<Flavor> = SyncLazy::new(|| match Tar.probe_flavor() {
+            Ok(flavor) => {
+                debug!("Detected {flavor:?} tar.");
+                flavor
+            }
+            Err(e) => {
+                warn!("Failed to identify the tar implementation: {e:?}");
+                Flavor::Other
+            }
+        });
+        *FLAVOR
+    }
+
+    /// Run `tar --version` and identify the implementation from its output.
+    ///
+    /// This is a blocking call, as the result is needed when constructing commands.
+    pub fn probe_flavor(&self) -> Result<Flavor> {
+        let location = self.lookup()?;
+        let output =
+            std::process::Command::new(&location.executable_path).arg("--version").output()?;
+        output.status.exit_ok()?;
+        Ok(Flavor::from_version_text(&String::from_utf8_lossy(&output.stdout)))
+    }
+
     #[context("Failed to crate an archive {}.", output_archive.as_ref().display())]
     pub fn pack_cmd<P: AsRef<Path>>(
         &self,
         output_archive: impl AsRef<Path>,
         paths_to_pack: impl IntoIterator<Item = P>,
+        options: CompressionOptions,
     ) -> Result<crate::prelude::Command> {
-        let mut cmd = self.cmd()?;
-        cmd.arg(Command::Create);
-
-        if let Ok(Format::Tar(Some(compression))) = Format::from_filename(&output_archive) {
-            cmd.args(&Switch::UseFormat(compression));
-        }
-
-        cmd.args(&Switch::TargetFile(output_archive.as_ref()));
+        let mut builder = TarCommandBuilder::create(output_archive.as_ref());
+        builder
+            .compression_from_extension()
+            .compression_options(options)
+            .sort_by_name(true)
+            .adapt_to(self.flavor())?;
 
         let paths: Vec<PathBuf> =
             paths_to_pack.into_iter().map(|path| path.as_ref().to_owned()).collect();
@@ -139,13 +464,12 @@ impl Tar {
         match paths.as_slice() {
             [item] =>
                 if let Some(parent) = crate::fs::canonicalize(item)?.parent() {
-                    cmd.args(&Switch::WorkingDir(parent));
-                    cmd.arg(item.file_name().unwrap()); // None can happen only when path ends with
-                                                        // ".." - that's why we canonicalize
+                    builder.working_dir(parent);
+                    // None can happen only when path ends with ".." - that's why we canonicalize.
+                    builder.path(item.file_name().unwrap());
                 },
             // [dir] if dir.is_dir() => {
-            //     cmd.args(&Switch::WorkingDir(dir.to_owned()));
-            //     cmd.arg(".");
+            //     builder.working_dir(dir).path(".");
             // }
             _ => {
                 todo!("")
@@ -163,18 +487,16 @@ impl Tar {
                * } */
         }
 
-
-        Ok(cmd)
-        // cmd_from_args![Command::Create, val [switches], output_archive.as_ref(), ref
-        // [paths_to_pack]]
+        builder.build()
     }
 
     pub async fn pack<P: AsRef<Path>>(
         self,
         output_archive: impl AsRef<Path>,
         paths_to_pack: impl IntoIterator<Item = P>,
+        options: CompressionOptions,
     ) -> Result {
-        self.pack_cmd(output_archive, paths_to_pack)?.run_ok().await
+        self.pack_cmd(output_archive, paths_to_pack, options)?.run_ok().await
     }
 
     pub async fn pack_directory_contents(
@@ -182,30 +504,85 @@ impl Tar {
         compression: Option<Compression>,
         output_archive: impl AsRef<Path>,
         root_directory: impl AsRef<Path>,
+        options: CompressionOptions,
     ) -> Result {
         // See: https://stackoverflow.com/a/3035446
-        self.cmd()?
-            .arg(Command::Create)
-            .args(compression)
-            .args(&Switch::TargetFile(output_archive.as_ref()))
-            .args(&Switch::WorkingDir(root_directory.as_ref()))
-            .arg(".")
+        TarCommandBuilder::create(output_archive.as_ref())
+            .compression(compression)
+            .compression_options(options)
+            .working_dir(root_directory.as_ref())
+            .path(".")
+            .adapt_to(self.flavor())?
+            .build()?
             .run_ok()
             .await
     }
 
+    /// Command that extracts the whole archive into the output directory.
+    ///
+    /// The output directory must exist. For additional options (like stripping path components)
+    /// use [`TarCommandBuilder`] directly.
+    pub fn unpack_cmd(
+        &self,
+        archive: impl AsRef<Path>,
+        output_directory: impl AsRef<Path>,
+    ) -> Result<crate::prelude::Command> {
+        self.unpack_cmd_with(archive, output_directory, default())
+    }
+
+    pub fn unpack_cmd_with(
+        &self,
+        archive: impl AsRef<Path>,
+        output_directory: impl AsRef<Path>,
+        options: ExtractOptions,
+    ) -> Result<crate::prelude::Command> {
+        TarCommandBuilder::extract(archive.as_ref())
+            .working_dir(output_directory.as_ref())
+            .same_owner(options.preserve_ownership)
+            .adapt_to(self.flavor())?
+            .build()
+    }
+
+    /// Command that prints the paths of all the archive entries, one per line.
+    pub fn list_cmd(&self, archive: impl AsRef<Path>) -> Result<crate::prelude::Command> {
+        TarCommandBuilder::list(archive.as_ref()).build()
+    }
+
+    /// Command that extracts only the given files (or directories) from the archive.
+    pub fn extract_files_cmd<P: AsRef<Path>>(
+        &self,
+        archive: impl AsRef<Path>,
+        output_directory: impl AsRef<Path>,
+        files: impl IntoIterator<Item = P>,
+    ) -> Result<crate::prelude::Command> {
+        TarCommandBuilder::extract(archive.as_ref())
+            .working_dir(output_directory.as_ref())
+            .paths(files.into_iter().map(|file| file.as_ref().to_owned()))
+            .build()
+    }
+
     pub async fn unpack(
         &self,
         archive: impl AsRef<Path>,
         output_directory: impl AsRef<Path>,
+    ) -> Result {
+        self.unpack_with(archive, output_directory, default()).await
+    }
+
+    pub async fn unpack_with(
+        &self,
+        archive: impl AsRef<Path>,
+        output_directory: impl AsRef<Path>,
+        options: ExtractOptions,
     ) -> Result {
         crate::fs::tokio::create_dir_if_missing(&output_directory).await?;
-        self.cmd()?
-            .arg(Command::Extract)
-            .args(&Switch::TargetFile(archive.as_ref()))
-            .args(&Switch::WorkingDir(output_directory.as_ref()))
-            .run_ok()
-            .await
+        self.unpack_cmd_with(archive, output_directory, options)?.run_ok().await
+    }
+
+    /// List the paths of all the archive entries.
+    pub async fn list(&self, archive: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+        let stdout = self.list_cmd(archive)?.run_stdout().await?;
+        Ok(stdout.lines().filter(|line| !line.is_empty()).map(PathBuf::from).collect())
     }
 }
 
@@ -213,6 +590,8 @@ impl Tar {
 #[cfg(test)]
 pub mod tests {
     use super::*;
+    use crate::testing::expect;
+    use crate::testing::Matcher;
 
     #[test]
     fn deduce_format_from_extension() {
@@ -224,12 +603,189 @@ pub mod tests {
         expect_ok("gz", Compression::Gzip);
         expect_ok("lzma", Compression::Lzma);
         expect_ok("xz", Compression::Xz);
+        expect_ok("zst", Compression::Zstd);
+    }
+
+    fn args_of(cmd: &crate::prelude::Command) -> Vec<String> {
+        cmd.as_std().get_args().map(|arg| arg.to_string_lossy().into_owned()).collect()
+    }
+
+    fn args_of_builder(builder: &TarCommandBuilder) -> Vec<String> {
+        builder.args().unwrap().iter().map(|arg| arg.to_string_lossy().into_owned()).collect()
+    }
+
+    #[test]
+    fn builder_arguments() {
+        let mut builder = TarCommandBuilder::extract("archive.tar.gz");
+        builder.working_dir("out").strip_components(1).exclude("*.pdb").transform("s,^enso,dist,");
+        assert_eq!(args_of_builder(&builder), vec![
+            "-x",
+            "-f",
+            "archive.tar.gz",
+            "--directory",
+            "out",
+            "--strip-components=1",
+            "--exclude=*.pdb",
+            "--transform=s,^enso,dist,"
+        ]);
+
+        let mut builder = TarCommandBuilder::create("archive.tar.zst");
+        builder.compression_from_extension().verbose(true).path("dist");
+        assert_eq!(args_of_builder(&builder), vec![
+            "-c",
+            "--zstd",
+            "--verbose",
+            "-f",
+            "archive.tar.zst",
+            "--",
+            "dist"
+        ]);
+    }
+
+    #[test]
+    fn identify_flavor() {
+        let gnu = "tar (GNU tar) 1.34\nCopyright (C) 2021 Free Software Foundation, Inc.";
+        let bsd = "bsdtar 3.5.1 - libarchive 3.5.1 zlib/1.2.11 liblzma/5.0.5 bz2lib/1.0.8";
+        assert_eq!(Flavor::from_version_text(gnu), Flavor::Gnu);
+        assert_eq!(Flavor::from_version_text(bsd), Flavor::Bsd);
+        assert_eq!(Flavor::from_version_text("tar (busybox) 1.36.1"), Flavor::Other);
+    }
+
+    #[test]
+    fn adapt_to_bsd_tar() -> Result {
+        let mut builder = TarCommandBuilder::create("archive.tar.gz");
+        builder.compression_from_extension().sort_by_name(true).path("dist");
+        builder.clone().adapt_to(Flavor::Gnu)?;
+        assert!(builder.sort_by_name);
+        builder.adapt_to(Flavor::Bsd)?;
+        assert!(!builder.sort_by_name);
+
+        for archive in ["archive.tar.lzma", "archive.tar.zst"] {
+            let mut builder = TarCommandBuilder::create(archive);
+            builder.compression_from_extension().path("dist");
+            builder.clone().adapt_to(Flavor::Gnu)?;
+            builder.clone().adapt_to(Flavor::Bsd)?;
+            assert!(builder.adapt_to(Flavor::Other).is_err());
+        }
+        let mut builder = TarCommandBuilder::create("archive.tar.gz");
+        builder.compression_from_extension().path("dist");
+        builder.adapt_to(Flavor::Other)?;
+        assert!(TarCommandBuilder::extract("a.tar")
+            .transform("s,a,b,")
+            .adapt_to(Flavor::Bsd)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn compression_environment() {
+        let mut builder = TarCommandBuilder::create("archive.tar.xz");
+        builder.compression_from_extension().path("dist");
+        assert!(builder.compression_env().is_empty());
+        builder.compression_options(CompressionOptions::best());
+        assert_eq!(builder.compression_env(), vec![("XZ_OPT", "-9e -T4".to_string())]);
+        builder.compression(Compression::Gzip);
+        assert!(builder.compression_env().is_empty());
+        assert_eq!(builder.compress_program().as_deref(), Some("gzip -9"));
+        let args = builder.args().unwrap();
+        assert!(args.contains(&"--use-compress-program=gzip -9".into()));
+        assert!(!args.contains(&"-z".into()));
     }
 
     #[test]
-    fn pack_command_test() {
-        let cmd = Tar.pack_cmd("output.tar.gz", &["target.bmp"]).unwrap();
+    fn builder_rejects_incompatible_options() {
+        assert!(TarCommandBuilder::create("archive.tar").args().is_err());
+        assert!(TarCommandBuilder::list("archive.tar").strip_components(1).args().is_err());
+        let mut append = TarCommandBuilder::new(Mode::Append, "archive.tar.gz");
+        append.compression(Compression::Gzip).path("file");
+        assert!(append.args().is_err());
+    }
+
+    #[test]
+    fn ownership_arguments() -> Result {
+        let cmd = Tar.unpack_cmd("archive.tar", "out")?;
+        assert_eq!(args_of(&cmd), vec![
+            "-x",
+            "-f",
+            "archive.tar",
+            "--directory",
+            "out",
+            "--no-same-owner"
+        ]);
+        let options = ExtractOptions { preserve_ownership: true };
+        let cmd = Tar.unpack_cmd_with("archive.tar", "out", options)?;
+        assert!(args_of(&cmd).contains(&"--same-owner".to_string()));
+        assert!(TarCommandBuilder::create("archive.tar")
+            .same_owner(true)
+            .path("a")
+            .args()
+            .is_err());
+        Ok(())
+    }
+
+    /// Directory with the `dist` tree packed into `dist.tar.gz`.
+    async fn packed_dist() -> Result<tempfile::TempDir> {
+        let temp = tempfile::tempdir()?;
+        let dist = temp.path().join("dist");
+        crate::fs::write(dist.join("bin").join("enso"), "enso")?;
+        crate::fs::write(dist.join("lib").join("runtime.jar"), "runtime")?;
+        let cmd = Tar.pack_cmd(temp.path().join("dist.tar.gz"), [&dist], default())?;
+        expect(cmd).success().run().await?;
+        Ok(temp)
+    }
+
+    #[tokio::test]
+    async fn list_command_test() -> Result {
+        let cmd = Tar.list_cmd("archive.tar")?;
+        assert_eq!(args_of(&cmd), vec!["-t", "-f", "archive.tar"]);
+
+        let temp = packed_dist().await?;
+        expect(Tar.list_cmd(temp.path().join("dist.tar.gz"))?)
+            .stdout_matches("(?m)^dist/bin/enso$")?
+            .stdout_contains("dist/lib/runtime.jar")
+            .run()
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn extract_files_command_test() -> Result {
+        let cmd = Tar.extract_files_cmd("archive.tar", "out", ["bin/enso", "lib"])?;
+        assert_eq!(args_of(&cmd), vec![
+            "-x",
+            "-f",
+            "archive.tar",
+            "--directory",
+            "out",
+            "--",
+            "bin/enso",
+            "lib"
+        ]);
+
+        let temp = packed_dist().await?;
+        let output = temp.path().join("out");
+        crate::fs::create_dir_if_missing(&output)?;
+        let cmd = Tar.extract_files_cmd(temp.path().join("dist.tar.gz"), &output, ["dist/bin"])?;
+        expect(cmd).success().stdout(Matcher::Empty).run().await?;
+        assert_eq!(
+            crate::fs::read_to_string(output.join("dist").join("bin").join("enso"))?,
+            "enso"
+        );
+        assert!(!output.join("dist").join("lib").exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pack_command_test() -> Result {
+        let temp = packed_dist().await?;
+        let cmd =
+            Tar.pack_cmd(temp.path().join("output.tar.gz"), [temp.path().join("dist")], default())?;
         debug!("{:?}", cmd);
-        dbg!(cmd);
+        expect(cmd).success().run().await?;
+        expect(Tar.list_cmd(temp.path().join("output.tar.gz"))?)
+            .stdout_contains("dist/bin/enso")
+            .run()
+            .await?;
+        Ok(())
     }
 }
@@ -22,8 +22,7 @@ impl VsWhere {
             .args(Option::Required(vec![component]).format_arguments())
             .args(Option::ForceUTF8.format_arguments());
 
-        let stdout = command.run_stdout().await?;
-        serde_json::from_str(&stdout).anyhow_err()
+        command.run_stdout_json().await
     }
 
     pub async fn find_with(component: Component) -> Result<InstanceInfo> {
@@ -34,8 +33,7 @@ impl VsWhere {
             .args(Option::ForceUTF8.format_arguments())
             .args(["-products", "*"]); // FIXME add types
 
-        let stdout = command.run_stdout().await?;
-        let instances = serde_json::from_str::<Vec<InstanceInfo>>(&stdout)?;
+        let instances = command.run_stdout_json::<Vec<InstanceInfo>>().await?;
         Ok(instances.into_iter().next().ok_or(NoMsvcInstallation)?)
     }
 
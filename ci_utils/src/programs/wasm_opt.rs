@@ -1,7 +1,18 @@
 use crate::prelude::*;
 use crate::program::command::Manipulator;
 
-#[derive(Clone, Copy, Debug, strum::Display, strum::EnumString)]
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    strum::Display,
+    strum::EnumString,
+)]
 pub enum OptimizationLevel {
     /// execute default optimization passes (equivalent to -Os)
     O,
@@ -2,9 +2,9 @@ use crate::prelude::*;
 
 use crate::new_command_type;
 use crate::program::command::Manipulator;
-use tempfile::TempDir;
+use crate::program::installer::CargoInstall;
+use crate::program::Installer;
 
-use crate::programs::Cargo;
 
 /// What kind of Cargo build profile should be used.
 ///
@@ -70,6 +70,10 @@ impl Program for WasmPack {
     fn executable_name(&self) -> &'static str {
         "wasm-pack"
     }
+
+    fn installer(&self) -> Option<Box<dyn Installer>> {
+        Some(Box::new(CargoInstall::new("wasm-pack")))
+    }
 }
 
 
@@ -102,17 +106,5 @@ impl WasmPackCommand {
 // new_command_type! {WasmPack, WasmPackBuildCommand}
 
 pub async fn install_if_missing() -> Result {
-    let temp = TempDir::new()?;
-    // We want to run this command in a temporary directory, as to install wasm-pack using a
-    // system-wide default toolchain, rather than overrides for the current folder (which is likely
-    // under our repository root).
-    //
-    // Note that this will install the tool to the default system-wide location, not temp.
-    if WasmPack.lookup().is_err() {
-        Cargo.cmd()?.args(["install", "wasm-pack"]).current_dir(&temp.path()).run_ok().await?;
-        // TODO
-        //  this kind of function likely could use some generalization, that should also cover how
-        //  PATH updates are handled
-    }
-    Ok(())
+    WasmPack.lookup_or_install().await.map(drop)
 }
@@ -0,0 +1,240 @@
+//! Wrappers over the WiX Toolset v3, used to build the Windows Installer (MSI) packages.
+//!
+//! The sources (`.wxs`) are first compiled by [`Candle`] into objects (`.wixobj`), which are then
+//! linked by [`Light`] into the installer. See [`build_msi`] for the whole flow.
+
+use crate::prelude::*;
+
+use crate::programs::signing::signtool;
+use crate::programs::signing::SignTool;
+
+
+/// Find the WiX `bin` directory, using the `WIX` variable set by the toolset's installer.
+fn wix_locations() -> Vec<PathBuf> {
+    std::env::var_os("WIX").map(|wix| PathBuf::from(wix).join("bin")).into_iter().collect()
+}
+
+/// The WiX compiler.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Candle;
+
+impl Program for Candle {
+    fn executable_name(&self) -> &'static str {
+        "candle"
+    }
+
+    fn default_locations(&self) -> Vec<PathBuf> {
+        wix_locations()
+    }
+}
+
+/// The WiX linker.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Light;
+
+impl Program for Light {
+    fn executable_name(&self) -> &'static str {
+        "light"
+    }
+
+    fn default_locations(&self) -> Vec<PathBuf> {
+        wix_locations()
+    }
+}
+
+/// Name of the architecture, as understood by the `-arch` switch.
+pub fn arch_name(arch: Arch) -> Result<&'static str> {
+    match arch {
+        Arch::X86_64 => Ok("x64"),
+        Arch::X86 => Ok("x86"),
+        Arch::AArch64 => Ok("arm64"),
+        other => bail!("WiX does not support the {other} architecture."),
+    }
+}
+
+/// Options of the [`Candle`] compilation.
+#[derive(Clone, Debug)]
+pub struct CompileOptions {
+    /// Directory where the `.wixobj` files are written.
+    pub output_dir: PathBuf,
+    pub arch:       Arch,
+    /// Preprocessor variables, available in the sources as `$(var.Name)`.
+    pub defines:    BTreeMap<String, String>,
+    /// Extensions, like `WixUtilExtension`.
+    pub extensions: Vec<String>,
+}
+
+impl CompileOptions {
+    pub fn new(output_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            output_dir: output_dir.into(),
+            arch:       TARGET_ARCH,
+            defines:    default(),
+            extensions: default(),
+        }
+    }
+
+    pub fn define(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.defines.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn extension(mut self, extension: impl Into<String>) -> Self {
+        self.extensions.push(extension.into());
+        self
+    }
+
+    pub fn args(&self) -> Result<Vec<OsString>> {
+        // The trailing separator tells Candle that the output is a directory.
+        let mut ret: Vec<OsString> =
+            vec!["-nologo".into(), "-out".into(), self.output_dir.join("").into()];
+        ret.extend(["-arch".into(), arch_name(self.arch)?.into()]);
+        for (name, value) in &self.defines {
+            ret.push(format!("-d{name}={value}").into());
+        }
+        for extension in &self.extensions {
+            ret.extend(["-ext".into(), extension.into()]);
+        }
+        Ok(ret)
+    }
+}
+
+/// Options of the [`Light`] linking.
+#[derive(Clone, Debug)]
+pub struct LinkOptions {
+    /// Path of the built installer.
+    pub output:              PathBuf,
+    /// Extensions, like `WixUIExtension`.
+    pub extensions:          Vec<String>,
+    /// Cultures of the localized strings, like `en-us`.
+    pub cultures:            Vec<String>,
+    /// Skip the ICE validation, which requires administrator rights on some CI runners.
+    pub suppress_validation: bool,
+}
+
+impl LinkOptions {
+    pub fn new(output: impl Into<PathBuf>) -> Self {
+        Self {
+            output:              output.into(),
+            extensions:          default(),
+            cultures:            default(),
+            suppress_validation: false,
+        }
+    }
+
+    pub fn extension(mut self, extension: impl Into<String>) -> Self {
+        self.extensions.push(extension.into());
+        self
+    }
+
+    pub fn args(&self) -> Vec<OsString> {
+        let mut ret: Vec<OsString> = vec!["-nologo".into(), "-out".into(), (&self.output).into()];
+        for extension in &self.extensions {
+            ret.extend(["-ext".into(), extension.into()]);
+        }
+        if !self.cultures.is_empty() {
+            ret.push(format!("-cultures:{}", self.cultures.join(";")).into());
+        }
+        if self.suppress_validation {
+            ret.push("-sval".into());
+        }
+        ret
+    }
+}
+
+impl Candle {
+    /// Compile the sources, returning the paths of the objects.
+    pub async fn compile(
+        &self,
+        sources: impl IntoIterator<Item: AsRef<Path>>,
+        options: &CompileOptions,
+    ) -> Result<Vec<PathBuf>> {
+        let sources = sources.into_iter().map(|source| source.as_ref().to_owned()).collect_vec();
+        crate::fs::tokio::create_dir_if_missing(&options.output_dir).await?;
+        self.cmd()?.args(options.args()?).args(&sources).run_ok().await?;
+        sources
+            .iter()
+            .map(|source| {
+                let name = source.file_stem().context("Source path has no file name.")?;
+                Ok(options.output_dir.join(name).with_extension("wixobj"))
+            })
+            .collect()
+    }
+}
+
+impl Light {
+    pub async fn link(
+        &self,
+        objects: impl IntoIterator<Item: AsRef<Path>>,
+        options: &LinkOptions,
+    ) -> Result {
+        crate::fs::tokio::create_parent_dir_if_missing(&options.output).await?;
+        self.cmd()?
+            .args(options.args())
+            .args(objects.into_iter().map(|object| object.as_ref().to_owned()))
+            .run_ok()
+            .await
+    }
+}
+
+/// Options of [`build_msi`].
+#[derive(Clone, Debug)]
+pub struct MsiOptions {
+    pub compile: CompileOptions,
+    pub link:    LinkOptions,
+    /// If set, the built installer is signed and the signature is verified.
+    pub signing: Option<signtool::SignOptions>,
+}
+
+/// Build the installer from the WiX sources. Returns the installer's path.
+#[tracing::instrument(skip_all, fields(output = %options.link.output.display()), err)]
+pub async fn build_msi(
+    sources: impl IntoIterator<Item: AsRef<Path>>,
+    options: &MsiOptions,
+) -> Result<PathBuf> {
+    let objects = Candle.compile(sources, &options.compile).await?;
+    Light.link(&objects, &options.link).await?;
+    let msi = &options.link.output;
+    if let Some(signing) = &options.signing {
+        SignTool.sign(signing, [msi]).await?;
+        SignTool.verify([msi]).await?;
+    }
+    Ok(msi.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wix_args() -> Result {
+        let compile = CompileOptions { arch: Arch::X86_64, ..CompileOptions::new("obj") }
+            .define("Version", "2022.1.1")
+            .extension("WixUtilExtension");
+        let output_dir = Path::new("obj").join("");
+        assert_eq!(compile.args()?, [
+            OsStr::new("-nologo"),
+            OsStr::new("-out"),
+            output_dir.as_os_str(),
+            OsStr::new("-arch"),
+            OsStr::new("x64"),
+            OsStr::new("-dVersion=2022.1.1"),
+            OsStr::new("-ext"),
+            OsStr::new("WixUtilExtension"),
+        ]);
+
+        let mut link = LinkOptions::new("enso.msi").extension("WixUIExtension");
+        link.cultures.push("en-us".into());
+        link.suppress_validation = true;
+        assert_eq!(link.args(), [
+            "-nologo",
+            "-out",
+            "enso.msi",
+            "-ext",
+            "WixUIExtension",
+            "-cultures:en-us",
+            "-sval"
+        ]);
+        Ok(())
+    }
+}
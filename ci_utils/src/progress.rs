@@ -0,0 +1,256 @@
+//! Progress reporting shared by all the concurrently running steps.
+//!
+//! When attached to a terminal, the progress is drawn as bars, all managed by a single
+//! [`MultiProgress`], so they do not clobber each other. The bars are placed below the bars of the
+//! tracing spans they were created in, forming a hierarchy. The spans get their own spinners if
+//! they have a `progress` field, e.g. `info_span!("build", progress = true)`.
+//!
+//! Otherwise (e.g. on CI, where the output goes to the log), the bars are hidden and the progress
+//! is reported as plain log lines, at most once per [`PLAIN_REPORT_INTERVAL`].
+
+use crate::prelude::*;
+
+use indicatif::MultiProgress;
+use indicatif::ProgressBar;
+use indicatif::ProgressDrawTarget;
+use std::lazy::SyncLazy;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+use tracing::span::Attributes;
+use tracing::Id;
+use tracing::Subscriber;
+use tracing_subscriber::registry::LookupSpan;
+
+
+/// How often the progress is reported when not attached to a terminal.
+pub const PLAIN_REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often the spinners are redrawn.
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Name of the span field requesting a spinner for the span.
+pub const SPAN_FIELD: &str = "progress";
+
+/// Whether the progress bars should be drawn.
+pub fn is_interactive() -> bool {
+    !crate::ci::run_in_ci() && !ProgressDrawTarget::stderr().is_hidden()
+}
+
+/// Place of a span in the bar hierarchy.
+#[derive(Clone, Debug, Default)]
+struct Anchor {
+    /// The bar of the span or of its nearest ancestor that has one.
+    bar:   Option<ProgressBar>,
+    /// Number of the ancestor bars.
+    depth: usize,
+    /// Whether the bar belongs to the span, rather than to its ancestor.
+    own:   bool,
+}
+
+#[derive(Debug)]
+struct Manager {
+    multi:       MultiProgress,
+    interactive: bool,
+    /// Anchors of the open spans, by the span ids.
+    anchors:     Mutex<HashMap<u64, Anchor>>,
+}
+
+impl Manager {
+    fn new() -> Self {
+        let interactive = is_interactive();
+        let multi = if interactive {
+            MultiProgress::new()
+        } else {
+            MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+        };
+        Self { multi, interactive, anchors: default() }
+    }
+
+    fn anchor(&self, span: Option<&Id>) -> Anchor {
+        let anchors = self.anchors.lock().unwrap();
+        span.and_then(|id| anchors.get(&id.into_u64())).cloned().unwrap_or_default()
+    }
+
+    /// Add the bar below the bar of the given anchor.
+    fn add(&self, bar: ProgressBar, anchor: &Anchor) -> ProgressBar {
+        match &anchor.bar {
+            Some(parent) => self.multi.insert_after(parent, bar),
+            None => self.multi.add(bar),
+        }
+    }
+}
+
+static MANAGER: SyncLazy<Manager> = SyncLazy::new(Manager::new);
+
+/// Print the line above the bars, so they are not broken.
+pub fn println(message: impl AsRef<str>) {
+    if MANAGER.interactive && MANAGER.multi.println(message.as_ref()).is_ok() {
+        return;
+    }
+    eprintln!("{}", message.as_ref());
+}
+
+/// Plain-line reporting state of a bar.
+#[derive(Debug)]
+struct PlainReporter {
+    started:     Instant,
+    last_report: Instant,
+}
+
+/// Handle to the reported progress. The bar is removed when the handle is dropped.
+#[derive(Debug)]
+pub struct Progress {
+    bar:     ProgressBar,
+    /// Indentation reflecting the depth in the hierarchy.
+    indent:  String,
+    message: String,
+    plain:   Option<Mutex<PlainReporter>>,
+}
+
+impl Progress {
+    fn new(bar: ProgressBar, message: String) -> Self {
+        let anchor = MANAGER.anchor(tracing::Span::current().id().as_ref());
+        let bar = MANAGER.add(bar, &anchor);
+        let indent = "  ".repeat(anchor.depth);
+        let plain = (!MANAGER.interactive).then(|| {
+            info!("{message}");
+            let now = Instant::now();
+            Mutex::new(PlainReporter { started: now, last_report: now })
+        });
+        bar.set_message(format!("{indent}{message}"));
+        Self { bar, indent, message, plain }
+    }
+
+    /// Report the current position as a plain line, if it was not reported for a while.
+    fn report_plain(&self) {
+        if let Some(plain) = &self.plain {
+            let mut plain = plain.lock().unwrap();
+            if plain.last_report.elapsed() >= PLAIN_REPORT_INTERVAL {
+                plain.last_report = Instant::now();
+                match self.bar.length() {
+                    Some(length) => info!("{}: {}/{length}", self.message, self.bar.position()),
+                    None => info!("{}: still in progress", self.message),
+                }
+            }
+        }
+    }
+
+    pub fn inc(&self, delta: u64) {
+        self.bar.inc(delta);
+        self.report_plain();
+    }
+
+    pub fn set_position(&self, position: u64) {
+        self.bar.set_position(position);
+        self.report_plain();
+    }
+
+    pub fn set_length(&self, length: u64) {
+        self.bar.set_length(length);
+    }
+
+    pub fn set_message(&mut self, message: impl Into<String>) {
+        self.message = message.into();
+        self.bar.set_message(format!("{}{}", self.indent, self.message));
+        self.report_plain();
+    }
+
+    /// Remove the bar, reporting the completion.
+    pub fn finish(self) {
+        // Handled by the drop.
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if let Some(plain) = &self.plain {
+            let elapsed = plain.lock().unwrap().started.elapsed();
+            info!("{}: done in {elapsed:.1?}.", self.message);
+        }
+        self.bar.finish_and_clear();
+    }
+}
+
+/// Progress of a work with the known amount of units, like bytes to download.
+pub fn bar(message: impl Into<String>, length: u64) -> Progress {
+    Progress::new(ProgressBar::new(length), message.into())
+}
+
+/// Progress of a work with the unknown amount of units.
+pub fn spinner(message: impl Into<String>) -> Progress {
+    let bar = ProgressBar::new_spinner();
+    bar.enable_steady_tick(TICK_INTERVAL);
+    Progress::new(bar, message.into())
+}
+
+/// Add the bar to the global set of bars, below the current span's one.
+///
+/// Prefer [`bar`] and [`spinner`], which also report the progress when not attached to a terminal.
+pub fn add(bar: ProgressBar) -> ProgressBar {
+    let anchor = MANAGER.anchor(tracing::Span::current().id().as_ref());
+    MANAGER.add(bar, &anchor)
+}
+
+/// Tracing layer maintaining the bar hierarchy and drawing spinners for the `progress` spans.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Layer;
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> tracing_subscriber::Layer<S> for Layer {
+    fn on_new_span(
+        &self,
+        attrs: &Attributes<'_>,
+        id: &Id,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let parent = match attrs.parent() {
+            Some(parent) => Some(parent.clone()),
+            None if attrs.is_contextual() => ctx.current_span().id().cloned(),
+            None => None,
+        };
+        let parent_anchor = MANAGER.anchor(parent.as_ref());
+        let anchor = if attrs.metadata().fields().field(SPAN_FIELD).is_some() {
+            let bar = MANAGER.add(ProgressBar::new_spinner(), &parent_anchor);
+            let indent = "  ".repeat(parent_anchor.depth);
+            bar.set_message(format!("{indent}{}", attrs.metadata().name()));
+            if MANAGER.interactive {
+                bar.enable_steady_tick(TICK_INTERVAL);
+            }
+            Anchor { bar: Some(bar), depth: parent_anchor.depth + 1, own: true }
+        } else {
+            Anchor { own: false, ..parent_anchor }
+        };
+        MANAGER.anchors.lock().unwrap().insert(id.into_u64(), anchor);
+    }
+
+    fn on_close(&self, id: Id, _ctx: tracing_subscriber::layer::Context<'_, S>) {
+        let anchor = MANAGER.anchors.lock().unwrap().remove(&id.into_u64());
+        if let Some(Anchor { bar: Some(bar), own: true, .. }) = anchor {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::prelude::*;
+    use tracing_subscriber::Registry;
+
+    #[test]
+    fn span_hierarchy() {
+        let subscriber = Registry::default().with(Layer);
+        tracing::subscriber::with_default(subscriber, || {
+            let current = || MANAGER.anchor(tracing::Span::current().id().as_ref());
+            let outer = info_span!("build", progress = true).entered();
+            assert_eq!(current().depth, 1);
+            let plain = info_span!("helper").entered();
+            assert_eq!(current().depth, 1);
+            assert!(!current().own);
+            let inner = info_span!("test", progress = true).entered();
+            assert_eq!(current().depth, 2);
+            drop((inner, plain, outer));
+            assert_eq!(current().depth, 0);
+        });
+    }
+}
@@ -70,6 +70,17 @@ where
     WithShorthand::de(de)
 }
 
+/// Function to be used as `#[serde(default, deserialize_with="null_as_default")]`.
+///
+/// Treats both a missing field and an explicit `null` as the type's default value. This is useful
+/// for third-party payloads, where fields tend to be either omitted or nulled out without notice.
+pub fn null_as_default<'de, D, T>(de: D) -> std::result::Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default, {
+    Option::<T>::deserialize(de).map(Option::unwrap_or_default)
+}
+
 /// Module to be used as `#[serde(with="regex_vec")]`
 ///
 /// It supports serialization of `Vec<Regex>` through either a single `String` or `String` sequence.
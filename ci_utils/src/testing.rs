@@ -0,0 +1,201 @@
+//! Checking the results of the processes in tests.
+//!
+//! ```no_run
+//! # use ide_ci::prelude::*;
+//! # use ide_ci::testing::expect;
+//! # async fn example() -> Result {
+//! let mut cmd = ide_ci::programs::Git.cmd()?;
+//! cmd.arg("--version");
+//! expect(cmd).success().stdout_matches(r"^git version \d+")?.run().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The golden files are compared with the output, after normalizing the line endings. If
+//! [`ENSO_BUILD_UPDATE_GOLDEN`] is set, they are overwritten with the actual output instead.
+
+use crate::prelude::*;
+
+use crate::program::command::CapturedOutput;
+use regex::Regex;
+
+
+crate::define_env_var! {
+    /// If set, the golden files are updated with the actual output rather than compared with it.
+    ENSO_BUILD_UPDATE_GOLDEN, bool
+}
+
+/// Expectation about the text output of a process.
+#[derive(Clone, Debug)]
+pub enum Matcher {
+    Exact(String),
+    Contains(String),
+    Regex(Regex),
+    /// The output must be the same as the contents of the file.
+    Golden(PathBuf),
+    Empty,
+}
+
+impl Matcher {
+    /// Check the output, describing the mismatch in the error.
+    pub fn check(&self, actual: &str) -> Result {
+        let actual = normalize_newlines(actual);
+        match self {
+            Matcher::Exact(expected) => ensure!(
+                actual == normalize_newlines(expected),
+                "Expected:\n{expected}\nActual:\n{actual}"
+            ),
+            Matcher::Contains(expected) =>
+                ensure!(actual.contains(expected.as_str()), "Expected `{expected}` in:\n{actual}"),
+            Matcher::Regex(regex) =>
+                ensure!(regex.is_match(&actual), "Expected a match of `{regex}` in:\n{actual}"),
+            Matcher::Empty => ensure!(actual.is_empty(), "Expected no output, got:\n{actual}"),
+            Matcher::Golden(path) =>
+                if ENSO_BUILD_UPDATE_GOLDEN.is_set() {
+                    info!("Updating the golden file {}.", path.display());
+                    crate::fs::write(path, actual.as_bytes())?;
+                } else {
+                    let expected = crate::fs::read_to_string(path)?;
+                    ensure!(
+                        actual == normalize_newlines(&expected),
+                        "Output differs from the golden file {}. Set {} to update it.\nExpected:\n\
+                        {expected}\nActual:\n{actual}",
+                        path.display(),
+                        ENSO_BUILD_UPDATE_GOLDEN.name()
+                    );
+                },
+        }
+        Ok(())
+    }
+}
+
+fn normalize_newlines(text: &str) -> String {
+    text.replace("\r\n", "\n")
+}
+
+/// Expectations about the process run, see [`expect`].
+#[derive(Debug)]
+pub struct CommandExpectation {
+    command: Command,
+    code:    Option<i32>,
+    stdout:  Vec<Matcher>,
+    stderr:  Vec<Matcher>,
+}
+
+/// Start describing what the command should do when run.
+///
+/// Without any expectations set, only the successful exit is required.
+pub fn expect(command: Command) -> CommandExpectation {
+    CommandExpectation { command, code: None, stdout: default(), stderr: default() }
+}
+
+impl CommandExpectation {
+    pub fn code(mut self, code: i32) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn success(self) -> Self {
+        self.code(0)
+    }
+
+    pub fn stdout(mut self, matcher: Matcher) -> Self {
+        self.stdout.push(matcher);
+        self
+    }
+
+    pub fn stderr(mut self, matcher: Matcher) -> Self {
+        self.stderr.push(matcher);
+        self
+    }
+
+    pub fn stdout_is(self, expected: impl Into<String>) -> Self {
+        self.stdout(Matcher::Exact(expected.into()))
+    }
+
+    pub fn stdout_contains(self, expected: impl Into<String>) -> Self {
+        self.stdout(Matcher::Contains(expected.into()))
+    }
+
+    pub fn stdout_matches(self, regex: &str) -> Result<Self> {
+        Ok(self.stdout(Matcher::Regex(Regex::new(regex)?)))
+    }
+
+    pub fn stdout_golden(self, path: impl Into<PathBuf>) -> Self {
+        self.stdout(Matcher::Golden(path.into()))
+    }
+
+    pub fn stderr_contains(self, expected: impl Into<String>) -> Self {
+        self.stderr(Matcher::Contains(expected.into()))
+    }
+
+    pub fn stderr_golden(self, path: impl Into<PathBuf>) -> Self {
+        self.stderr(Matcher::Golden(path.into()))
+    }
+
+    /// Check the captured output against the expectations.
+    pub fn check(&self, output: &CapturedOutput) -> Result {
+        let code = output.status.code();
+        match self.code {
+            Some(expected) =>
+                ensure!(code == Some(expected), "Expected exit code {expected}, got {code:?}."),
+            None => ensure!(output.status.success(), "The process failed: {}.", output.status),
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        for matcher in &self.stdout {
+            matcher.check(&stdout).context("Unexpected standard output.")?;
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        for matcher in &self.stderr {
+            matcher.check(&stderr).context("Unexpected standard error.")?;
+        }
+        Ok(())
+    }
+
+    /// Run the command and check its output. The output is returned for further inspection.
+    pub async fn run(mut self) -> Result<CapturedOutput> {
+        let pretty = self.command.describe();
+        // The exit status is checked against the expectations, not by the program's rules.
+        self.command.status_checker = Arc::new(|_| Ok(()));
+        let output = self.command.output_ok().await?;
+        self.check(&output).with_context(|| format!("Unexpected result of: {pretty}"))?;
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matching_output() -> Result {
+        assert!(Matcher::Exact("a\nb\n".into()).check("a\r\nb\r\n").is_ok());
+        assert!(Matcher::Contains("version".into()).check("git version 2.36").is_ok());
+        assert!(Matcher::Regex(Regex::new(r"\d+\.\d+")?).check("no digits").is_err());
+        assert!(Matcher::Empty.check("").is_ok());
+
+        let golden = tempfile::NamedTempFile::new()?;
+        crate::fs::write(golden.path(), "expected\n")?;
+        let matcher = Matcher::Golden(golden.path().into());
+        assert!(matcher.check("expected\r\n").is_ok());
+        let error = matcher.check("actual\n").unwrap_err();
+        assert!(format!("{error:?}").contains(ENSO_BUILD_UPDATE_GOLDEN.name()), "{error:?}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn expecting_failure() -> Result {
+        let mut cmd = crate::programs::Bash.cmd()?;
+        cmd.args(["-c", "echo partial; echo broken >&2; exit 3"]);
+        let output = expect(cmd)
+            .code(3)
+            .stdout_is("partial\n")
+            .stderr_contains("broken")
+            .stderr(Matcher::Regex(Regex::new("(?m)^broken$")?))
+            .run()
+            .await?;
+        assert_eq!(output.status.code(), Some(3));
+        Ok(())
+    }
+}
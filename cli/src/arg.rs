@@ -17,6 +17,8 @@ use clap::Subcommand;
 use derivative::Derivative;
 use ide_ci::cache;
 use ide_ci::extensions::path::display_fmt;
+use ide_ci::github::permissions::Permission;
+use ide_ci::github::permissions::Requirement;
 use ide_ci::models::config::RepoContext;
 use octocrab::models::RunId;
 
@@ -124,6 +126,60 @@ pub enum Target {
     JavaGen(java_gen::Target),
 }
 
+impl Target {
+    /// GitHub token permissions that are needed to handle this target.
+    ///
+    /// These are checked upfront, so we can fail early with an explanation, rather than with an
+    /// obscure error in the middle of the job.
+    pub fn required_permissions(&self) -> Vec<Requirement> {
+        match self {
+            Target::Wasm(target) => match &target.command {
+                wasm::Command::Get(source) => source.required_permissions(),
+                _ => vec![],
+            },
+            Target::Gui(target) => match &target.command {
+                gui::Command::Build(job) => job.input.required_permissions(),
+                gui::Command::Get(source) => gui::source_permissions(source),
+                gui::Command::Watch(job) => job.build.input.required_permissions(),
+            },
+            Target::Backend(target) => match &target.command {
+                backend::Command::Get { source } => source.required_permissions(),
+                backend::Command::Upload { .. } => vec![Requirement::write(Permission::Contents)],
+                _ => vec![],
+            },
+            Target::Ide(target) => match &target.command {
+                ide::Command::Build { params } | ide::Command::Start { params, .. } =>
+                    params.required_permissions(),
+                ide::Command::Upload { params, .. } => {
+                    let mut ret = params.required_permissions();
+                    ret.push(Requirement::write(Permission::Contents));
+                    ret
+                }
+                ide::Command::Watch { gui, project_manager } => {
+                    let mut ret = gui.build.input.required_permissions();
+                    ret.extend(project_manager.required_permissions());
+                    ret
+                }
+                ide::Command::IntegrationTest { project_manager, .. } =>
+                    project_manager.required_permissions(),
+            },
+            Target::Release(_) => vec![Requirement::write(Permission::Contents)],
+            _ => vec![],
+        }
+    }
+}
+
+impl Cli {
+    /// GitHub token permissions that are needed to handle this invocation.
+    pub fn required_permissions(&self) -> Vec<Requirement> {
+        let mut ret = self.target.required_permissions();
+        if self.resume_from.is_some() {
+            ret.push(Requirement::read(Permission::Actions));
+        }
+        ret
+    }
+}
+
 /// Build, test and package Enso Engine.
 #[derive(Clone, Debug, Parser)]
 #[clap(author, version, about, long_about = None)]
@@ -161,6 +217,30 @@ pub struct Cli {
     #[clap(long, hide = !ide_ci::actions::workflow::is_in_env(), parse(try_from_str), default_value_t = true, enso_env())]
     pub upload_artifacts: bool,
 
+    /// ID of a previous CI run to resume from. Targets that are to be built locally will be
+    /// instead retrieved from that run's artifacts, if the run has uploaded them. Only the targets
+    /// that failed (or were not reached) in that run will be actually built.
+    ///
+    /// `GITHUB_TOKEN` environment variable with "repo" access is required to download CI run
+    /// artifacts.
+    #[clap(long, enso_env())]
+    pub resume_from: Option<RunId>,
+
+    /// Write a script reproducing all the external commands run by this invocation. The script
+    /// is written even if the run fails. PowerShell is used if the path has `.ps1` extension,
+    /// otherwise a Bash script is generated.
+    #[clap(long, enso_env())]
+    pub replay_script: Option<PathBuf>,
+
+    /// Do not execute the commands that perform the build steps, only log what would be run.
+    /// Commands queried for their output (like program versions) are still executed.
+    #[clap(long, enso_env())]
+    pub dry_run: bool,
+
+    /// Where to write the JSON log of the commands skipped in the dry-run mode.
+    #[clap(long, enso_env(), requires = "dry_run")]
+    pub dry_run_log: Option<PathBuf>,
+
     #[clap(subcommand)]
     pub target: Target,
 }
@@ -219,6 +299,27 @@ pub enum SourceKind {
     Release,
 }
 
+impl SourceKind {
+    /// GitHub token permissions that are needed to acquire the target this way.
+    pub fn required_permissions(self) -> Vec<Requirement> {
+        match self {
+            SourceKind::CiRun => vec![Requirement::read(Permission::Actions)],
+            SourceKind::Release => vec![Requirement::read(Permission::Contents)],
+            // The artifacts of the current run are accessed using the Actions runtime token.
+            SourceKind::Build | SourceKind::Local | SourceKind::CurrentCiRun => vec![],
+        }
+    }
+}
+
+impl<Target: IsTargetSource> Source<Target> {
+    /// GitHub token permissions that are needed to acquire the target from this source.
+    ///
+    /// The inputs needed to build the target locally are not covered.
+    pub fn required_permissions(&self) -> Vec<Requirement> {
+        self.source.required_permissions()
+    }
+}
+
 /// Strongly typed argument for an output directory of a given build target.
 #[derive(Args, Clone, Derivative)]
 #[derivative(Debug, PartialEq)]
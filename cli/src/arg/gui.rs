@@ -2,6 +2,7 @@ use enso_build::prelude::*;
 
 use crate::arg::BuildJob;
 use crate::arg::Source;
+use crate::arg::SourceKind;
 use crate::arg::WatchJob;
 use crate::source_args_hlp;
 use crate::IsWatchableSource;
@@ -10,6 +11,7 @@ use enso_build::project::wasm::Wasm;
 
 use clap::Args;
 use clap::Subcommand;
+use ide_ci::github::permissions::Requirement;
 
 source_args_hlp!(Gui, "gui", BuildInput);
 
@@ -23,6 +25,23 @@ pub struct BuildInput {
     pub wasm: Source<Wasm>,
 }
 
+impl BuildInput {
+    /// GitHub token permissions that are needed to acquire the inputs of the GUI build.
+    pub fn required_permissions(&self) -> Vec<Requirement> {
+        self.wasm.required_permissions()
+    }
+}
+
+/// GitHub token permissions that are needed to acquire the GUI from the given source, including
+/// its build inputs if it is to be built.
+pub fn source_permissions(source: &Source<Gui>) -> Vec<Requirement> {
+    let mut ret = source.required_permissions();
+    if source.source == SourceKind::Build {
+        ret.extend(source.build_args.required_permissions());
+    }
+    ret
+}
+
 #[derive(Args, Clone, Debug, PartialEq)]
 pub struct WatchInput {
     #[clap(flatten)]
@@ -10,6 +10,7 @@ use enso_build::project::wasm::DEFAULT_INTEGRATION_TESTS_WASM_TIMEOUT;
 
 use clap::Args;
 use clap::Subcommand;
+use ide_ci::github::permissions::Requirement;
 use octocrab::models::ReleaseId;
 
 source_args_hlp!(Target, "ide", BuildInput);
@@ -24,6 +25,15 @@ pub struct BuildInput {
     pub output_path:     OutputPath<Target>,
 }
 
+impl BuildInput {
+    /// GitHub token permissions that are needed to acquire the GUI and the Project Manager.
+    pub fn required_permissions(&self) -> Vec<Requirement> {
+        let mut ret = crate::arg::gui::source_permissions(&self.gui);
+        ret.extend(self.project_manager.required_permissions());
+        ret
+    }
+}
+
 #[derive(Subcommand, Clone, Debug)]
 pub enum Command {
     /// Builds both Project Manager and GUI, puts them together into a single, client Electron
@@ -1,6 +1,6 @@
 use enso_build::setup_octocrab;
 use enso_build_cli::prelude::*;
-use ide_ci::io::web::handle_error_response;
+use ide_ci::github::Client;
 use ide_ci::log::setup_logging;
 use ide_ci::models::config::RepoContext;
 
@@ -15,10 +15,9 @@ async fn main() -> Result {
     for release in draft_releases {
         let id = release.id;
 
-        let route = format!("{}repos/{repo}/releases/{id}", octo.base_url);
+        let route = format!("/repos/{repo}/releases/{id}");
         println!("Will delete {}: {route}.", release.name.unwrap_or_default());
-        let response = octo._delete(route, Option::<&()>::None).await?;
-        handle_error_response(response).await?;
+        Client::new(octo.clone()).delete(route).await?;
     }
 
 
@@ -0,0 +1,18 @@
+use enso_build::setup_octocrab;
+use enso_build_cli::prelude::*;
+use ide_ci::actions::artifacts::gc;
+use ide_ci::log::setup_logging;
+use ide_ci::models::config::RepoContext;
+
+/// How many pages (of 100 artifacts each) of the most recent artifacts are checked.
+const PAGES: u32 = 10;
+
+#[tokio::main]
+async fn main() -> Result {
+    setup_logging()?;
+    let repo = RepoContext::from_str("enso-org/enso")?;
+    let dry_run = std::env::args().any(|arg| arg == "--dry-run");
+    let octo = setup_octocrab().await?;
+    gc::collect_orphaned_artifacts(&octo, &repo, PAGES, gc::default_threshold(), dry_run).await?;
+    Ok(())
+}
@@ -0,0 +1,27 @@
+use enso_build::setup_octocrab;
+use enso_build_cli::prelude::*;
+use ide_ci::actions::artifacts::retention;
+use ide_ci::actions::artifacts::retention::LongTermStorage;
+use ide_ci::log::setup_logging;
+use ide_ci::models::config::RepoContext;
+use octocrab::models::ReleaseId;
+
+/// Usage: `republish-expiring-artifacts <release-id> <artifact-name>... [--dry-run]`
+///
+/// The critical artifacts that are about to expire are uploaded as assets of the release.
+#[tokio::main]
+async fn main() -> Result {
+    setup_logging()?;
+    let repo = RepoContext::from_str("enso-org/enso")?;
+    let (flags, mut args): (Vec<_>, Vec<_>) =
+        std::env::args().skip(1).partition(|arg| arg.starts_with("--"));
+    let dry_run = flags.iter().any(|arg| arg == "--dry-run");
+    ensure!(args.len() >= 2, "Expected the release ID and the names of the artifacts.");
+    let release = ReleaseId(args.remove(0).parse()?);
+    let client = ide_ci::github::create_client(ide_ci::github::client::token_from_env()?)?;
+    let storage = LongTermStorage::Release { repo: repo.clone(), release, client };
+    let octo = setup_octocrab().await?;
+    let margin = retention::default_margin();
+    retention::republish_expiring(&octo, &repo, &args, margin, &storage, dry_run).await?;
+    Ok(())
+}
@@ -75,16 +75,19 @@ use enso_build::source::WithDestination;
 use ide_ci::actions::workflow::is_in_env;
 use ide_ci::cache::Cache;
 use ide_ci::fs::remove_if_exists;
+use ide_ci::github::permissions::TokenPermissions;
 use ide_ci::github::release::upload_asset;
 use ide_ci::global;
 use ide_ci::log::setup_logging;
 use ide_ci::ok_ready_boxed;
+use ide_ci::program::audit;
+use ide_ci::program::audit::ScriptKind;
 use ide_ci::programs::cargo;
 use ide_ci::programs::rustc;
 use ide_ci::programs::Cargo;
 use ide_ci::programs::Git;
+use octocrab::models::RunId;
 use std::time::Duration;
-use tempfile::tempdir;
 use tokio::process::Child;
 use tokio::runtime::Runtime;
 
@@ -96,7 +99,9 @@ fn resolve_artifact_name(input: Option<String>, project: &impl IsTarget) -> Stri
 #[derive(Clone, Derivative)]
 #[derivative(Debug)]
 pub struct Processor {
-    pub context: BuildContext,
+    pub context:     BuildContext,
+    /// If set, the targets to be built will be first looked up in this run's artifacts.
+    pub resume_from: Option<RunId>,
 }
 
 impl Deref for Processor {
@@ -137,7 +142,7 @@ impl Processor {
             source_root: absolute_repo_path.into(),
             remote_repo: cli.repo_remote.clone(),
         };
-        Ok(Self { context })
+        Ok(Self { context, resume_from: cli.resume_from })
     }
 
     pub fn context(&self) -> project::Context {
@@ -157,7 +162,15 @@ impl Processor {
         let source = match source.source {
             arg::SourceKind::Build => {
                 let resolved = T::resolve(self, source.build_args);
-                async move { Ok(Source::BuildLocally(resolved.await?)) }.boxed()
+                let resumed = self.resume_source(&target, source.artifact_name.clone());
+                async move {
+                    if let Some(ci_run) = resumed.await? {
+                        Ok(Source::External(ExternalSource::CiRun(ci_run)))
+                    } else {
+                        Ok(Source::BuildLocally(resolved.await?))
+                    }
+                }
+                .boxed()
             }
             arg::SourceKind::Local =>
                 ready(Ok(Source::External(ExternalSource::LocalFile(source.path.clone())))).boxed(),
@@ -195,6 +208,39 @@ impl Processor {
             .boxed()
     }
 
+    /// Check if the target's artifact can be reused from the run that we resume from.
+    ///
+    /// Returns `None` if we are not resuming or the run did not upload the artifact (e.g. because
+    /// the job building it failed).
+    pub fn resume_source<T: IsTarget>(
+        &self,
+        target: &T,
+        artifact_name: Option<String>,
+    ) -> BoxFuture<'static, Result<Option<CiRunSource>>> {
+        let run_id = match self.resume_from {
+            Some(run_id) => run_id,
+            None => return ok_ready_boxed(None),
+        };
+        let artifact_name = resolve_artifact_name(artifact_name, target);
+        let repository = self.remote_repo.clone();
+        let octocrab = self.octocrab.clone();
+        async move {
+            // Only the missing artifact means rebuilding, other failures (like the invalid token)
+            // are reported.
+            match repository.try_find_artifact_by_name(&octocrab, run_id, &artifact_name).await? {
+                Some(_) => {
+                    info!("Reusing artifact {artifact_name} from the run {run_id}.");
+                    Ok(Some(CiRunSource { run_id, repository, artifact_name }))
+                }
+                None => {
+                    info!("Artifact {artifact_name} is not available in the run {run_id}.");
+                    Ok(None)
+                }
+            }
+        }
+        .boxed()
+    }
+
     #[tracing::instrument]
     pub fn resolve_release_source<T: IsTarget>(
         &self,
@@ -506,7 +552,7 @@ impl Processor {
                 headless,
                 wasm_timeout,
             } => {
-                let custom_root = tempdir();
+                let custom_root = ide_ci::fs::temp::dir("project-root");
                 let (custom_root, project_manager) = match custom_root {
                     Ok(tempdir) => {
                         let custom_root = Some(tempdir.path().into());
@@ -726,7 +772,30 @@ pub async fn main_internal(config: enso_build::config::Config) -> Result {
     let cli = Cli::parse();
 
     debug!("Parsed CLI arguments: {cli:#?}");
+    trace!("Known environment variables:\n{}", ide_ci::env::registry::dump());
+
+    let replay_script = cli.replay_script.clone();
+    let dry_run_log = cli.dry_run_log.clone();
+    audit::set_enabled(replay_script.is_some());
+    audit::set_dry_run(cli.dry_run);
+    let result = run(config, cli).await;
+    if let Some(dry_run_log) = dry_run_log {
+        if let Err(e) = audit::write_json(&dry_run_log) {
+            warn!("Failed to write the dry-run command log: {e:?}");
+        }
+    }
+    if let Some(replay_script) = replay_script {
+        // The script is most useful when the run has failed, so we write it in any case.
+        let kind = ScriptKind::for_path(&replay_script);
+        if let Err(e) = audit::write_script(&replay_script, kind) {
+            warn!("Failed to write the replay script: {e:?}");
+        }
+    }
+    result
+}
 
+/// Handle the parsed command line invocation.
+pub async fn run(config: enso_build::config::Config, cli: Cli) -> Result {
     if !cli.skip_version_check {
         config.check_programs().await?;
     }
@@ -738,6 +807,13 @@ pub async fn main_internal(config: enso_build::config::Config) -> Result {
         remove_if_exists(cli.repo_path.join("ci-build"))?;
     }
 
+    let required_permissions = cli.required_permissions();
+    if !required_permissions.is_empty() {
+        let token = ide_ci::github::client::token_from_env()
+            .context("GitHub access token is required for this target.")?;
+        TokenPermissions::query(&token).await?.check(&required_permissions)?;
+    }
+
     let ctx: Processor = Processor::new(&cli).instrument(info_span!("Building context.")).await?;
     match cli.target {
         Target::Wasm(wasm) => ctx.handle_wasm(wasm).await?,
@@ -812,7 +888,20 @@ pub async fn main_internal(config: enso_build::config::Config) -> Result {
 
 pub fn lib_main(config: enso_build::config::Config) -> Result {
     let rt = Runtime::new()?;
-    rt.block_on(async { main_internal(config).await })?;
+    rt.block_on(async {
+        let result = main_internal(config).await;
+        // Diagnostics are most useful when the build fails, so they are published regardless.
+        if let Err(e) = ide_ci::actions::annotations::publish_reported("Build diagnostics").await {
+            warn!("Failed to publish the annotations: {e:?}");
+        }
+        if let Err(e) = ide_ci::log::timer::report() {
+            warn!("Failed to report the step timings: {e:?}");
+        }
+        if let Err(e) = ide_ci::metrics::report().await {
+            warn!("Failed to report the build metrics: {e:?}");
+        }
+        result
+    })?;
     rt.shutdown_timeout(Duration::from_secs(60 * 30));
     info!("Successfully ending.");
     Ok(())